@@ -159,6 +159,7 @@ use crate::description_util::TextEditor;
 use crate::diff_util;
 use crate::diff_util::DiffFormat;
 use crate::diff_util::DiffFormatArgs;
+use crate::diff_util::DiffNormalizer;
 use crate::diff_util::DiffRenderer;
 use crate::formatter::FormatRecorder;
 use crate::formatter::Formatter;
@@ -917,6 +918,7 @@ impl WorkspaceCommandEnvironment {
             repo,
             self.command.revset_extensions().clone(),
             &id_prefix_context,
+            None,
             self.immutable_expression(),
         );
         expression.intersect_with(&to_rewrite_revset);
@@ -1242,6 +1244,32 @@ impl WorkspaceCommandHelper {
         Ok((locked_ws, wc_commit))
     }
 
+    /// Builds a tree from the files currently on disk without recording a
+    /// snapshot.
+    ///
+    /// File contents are written to the store, but neither the working-copy
+    /// state nor the working-copy commit is updated. Untracked files matching
+    /// `start_tracking_matcher` are included as if they were tracked.
+    ///
+    /// Ignored files that aren't tracked are excluded like in a snapshot, even
+    /// if they match `start_tracking_matcher`. The working copy reads the
+    /// `.gitignore` file of each directory while snapshotting, so clearing the
+    /// base ignores wouldn't be enough to include them.
+    // TODO: Include ignored files once the snapshot options can disable the
+    // `.gitignore` files.
+    pub fn working_copy_tree_on_disk(
+        &mut self,
+        start_tracking_matcher: &dyn Matcher,
+    ) -> Result<MergedTree, CommandError> {
+        let options = self.snapshot_options_with_start_tracking_matcher(start_tracking_matcher)?;
+        let mut locked_ws = self.workspace.start_working_copy_mutation()?;
+        let (tree_id, _stats) = locked_ws.locked_wc().snapshot(&options)?;
+        // Drop the lock without finishing the mutation so the working-copy
+        // state on disk is left untouched.
+        drop(locked_ws);
+        Ok(self.repo().store().get_root_tree(&tree_id)?)
+    }
+
     fn create_and_check_out_recovery_commit(
         &mut self,
         ui: &Ui,
@@ -1347,6 +1375,26 @@ to the current parents may contain changes from multiple commits.
         Ok(expression.to_matcher())
     }
 
+    /// Loads the paths to leave out of diffs from `ui.diff.exclude`. Returns
+    /// `None` if no paths are excluded.
+    pub fn diff_excluded_paths(&self, ui: &Ui) -> Result<Option<Box<dyn Matcher>>, CommandError> {
+        let patterns = self.settings().get::<Vec<String>>("ui.diff.exclude")?;
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+        let mut diagnostics = FilesetDiagnostics::new();
+        let path_converter = RepoPathUiConverter::Fs {
+            cwd: "".into(),
+            base: "".into(),
+        };
+        let excluded: Vec<_> = patterns
+            .iter()
+            .map(|pattern| fileset::parse(&mut diagnostics, pattern, &path_converter))
+            .try_collect()?;
+        print_parse_diagnostics(ui, "In `ui.diff.exclude`", &diagnostics)?;
+        Ok(Some(FilesetExpression::union_all(excluded).to_matcher()))
+    }
+
     pub fn snapshot_options_with_start_tracking_matcher<'a>(
         &self,
         start_tracking_matcher: &'a dyn Matcher,
@@ -1435,10 +1483,19 @@ to the current parents may contain changes from multiple commits.
     /// Loads textual diff renderer from the settings and command arguments.
     pub fn diff_renderer_for(
         &self,
+        ui: &Ui,
         args: &DiffFormatArgs,
     ) -> Result<DiffRenderer<'_>, CommandError> {
-        let formats = diff_util::diff_formats_for(self.settings(), args)?;
-        Ok(self.diff_renderer(formats))
+        let formats = diff_util::diff_formats_for(ui, self.settings(), args)?;
+        let grouping = diff_util::diff_grouping_for(self.settings(), args)?;
+        let normalizer = DiffNormalizer::from_settings(self.settings())?;
+        let excluded_paths = self.diff_excluded_paths(ui)?;
+        Ok(self
+            .diff_renderer(formats)
+            .with_grouping(grouping)
+            .with_normalizer(normalizer)
+            .with_reversed(args.reverse)
+            .with_excluded_paths(excluded_paths))
     }
 
     /// Loads textual diff renderer from the settings and log-like command
@@ -1446,11 +1503,24 @@ to the current parents may contain changes from multiple commits.
     /// enable patch output.
     pub fn diff_renderer_for_log(
         &self,
+        ui: &Ui,
         args: &DiffFormatArgs,
         patch: bool,
     ) -> Result<Option<DiffRenderer<'_>>, CommandError> {
-        let formats = diff_util::diff_formats_for_log(self.settings(), args, patch)?;
-        Ok((!formats.is_empty()).then(|| self.diff_renderer(formats)))
+        let formats = diff_util::diff_formats_for_log(ui, self.settings(), args, patch)?;
+        if formats.is_empty() {
+            return Ok(None);
+        }
+        let grouping = diff_util::diff_grouping_for(self.settings(), args)?;
+        let normalizer = DiffNormalizer::from_settings(self.settings())?;
+        let excluded_paths = self.diff_excluded_paths(ui)?;
+        Ok(Some(
+            self.diff_renderer(formats)
+                .with_grouping(grouping)
+                .with_normalizer(normalizer)
+                .with_reversed(args.reverse)
+                .with_excluded_paths(excluded_paths),
+        ))
     }
 
     /// Loads diff editor from the settings.
@@ -1645,6 +1715,7 @@ to the current parents may contain changes from multiple commits.
             self.repo().as_ref(),
             self.env.command.revset_extensions().clone(),
             self.id_prefix_context(),
+            Some(self.workspace_id()),
             expression,
         )
     }
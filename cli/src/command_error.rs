@@ -61,6 +61,7 @@ use crate::config::ConfigEnvError;
 use crate::description_util::ParseBulkEditMessageError;
 use crate::description_util::TempTextEditError;
 use crate::description_util::TextEditError;
+use crate::diff_util::DiffNormalizeError;
 use crate::diff_util::DiffRenderError;
 use crate::formatter::FormatRecorder;
 use crate::formatter::Formatter;
@@ -414,10 +415,19 @@ impl From<DiffRenderError> for CommandError {
     fn from(err: DiffRenderError) -> Self {
         match err {
             DiffRenderError::DiffGenerate(_) => user_error(err),
+            DiffRenderError::Backend(BackendError::Other(err))
+                if err.is::<DiffNormalizeError>() =>
+            {
+                user_error(err)
+            }
             DiffRenderError::Backend(err) => err.into(),
             DiffRenderError::AccessDenied { .. } => user_error(err),
             DiffRenderError::InvalidRepoPath(_) => user_error(err),
             DiffRenderError::Io(err) => err.into(),
+            DiffRenderError::UnsupportedContentFormat => user_error(err),
+            DiffRenderError::BinaryFile { .. } => user_error(err),
+            DiffRenderError::UnsupportedIntroducedLinesFormat => user_error(err),
+            DiffRenderError::UnsupportedToolFormat => user_error(err),
         }
     }
 }
@@ -664,6 +674,9 @@ impl From<UserRevsetEvaluationError> for CommandError {
     fn from(err: UserRevsetEvaluationError) -> Self {
         match err {
             UserRevsetEvaluationError::Resolution(err) => err.into(),
+            UserRevsetEvaluationError::UnknownWorkspace(err) => {
+                user_error(err).hinted("Use `jj workspace list` to see the existing workspaces.")
+            }
             UserRevsetEvaluationError::Evaluation(err) => err.into(),
         }
     }
@@ -35,6 +35,10 @@ pub struct DebugTreeArgs {
     id: Option<String>,
     #[arg(long, requires = "id")]
     dir: Option<String>,
+    /// Show the files currently on disk without snapshotting them, except for
+    /// ignored files
+    #[arg(long, conflicts_with_all = ["revision", "id"])]
+    working_copy: bool,
     #[arg(value_name = "FILESETS")]
     paths: Vec<String>,
     // TODO: Add an option to include trees that are ancestors of the matched paths
@@ -45,8 +49,15 @@ pub fn cmd_debug_tree(
     command: &CommandHelper,
     args: &DebugTreeArgs,
 ) -> Result<(), CommandError> {
-    let workspace_command = command.workspace_helper(ui)?;
-    let tree = if let Some(tree_id_hex) = &args.id {
+    let mut workspace_command = if args.working_copy {
+        command.workspace_helper_no_snapshot(ui)?
+    } else {
+        command.workspace_helper(ui)?
+    };
+    let tree = if args.working_copy {
+        let start_tracking_matcher = workspace_command.auto_tracking_matcher(ui)?;
+        workspace_command.working_copy_tree_on_disk(start_tracking_matcher.as_ref())?
+    } else if let Some(tree_id_hex) = &args.id {
         let tree_id =
             TreeId::try_from_hex(tree_id_hex).map_err(|_| user_error("Invalid tree id"))?;
         let dir = if let Some(dir_str) = &args.dir {
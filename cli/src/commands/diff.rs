@@ -12,21 +12,42 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fs::File;
+use std::io::Read;
+use std::io::Write as _;
+use std::path::PathBuf;
+
 use clap_complete::ArgValueCandidates;
 use clap_complete::ArgValueCompleter;
 use itertools::Itertools;
+use jj_lib::backend::CommitId;
+use jj_lib::backend::FileId;
+use jj_lib::commit::Commit;
 use jj_lib::copies::CopyRecords;
+use jj_lib::fileset::FilesetExpression;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::matchers::FilesMatcher;
+use jj_lib::matchers::IntersectionMatcher;
+use jj_lib::matchers::Matcher;
+use jj_lib::merged_tree::MergedTree;
 use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
 use jj_lib::rewrite::merge_commit_trees;
+use pollster::FutureExt as _;
 use tracing::instrument;
 
+use super::fix::get_tools_matcher;
 use crate::cli_util::print_unmatched_explicit_paths;
 use crate::cli_util::CommandHelper;
 use crate::cli_util::RevisionArg;
+use crate::cli_util::WorkspaceCommandHelper;
+use crate::command_error::user_error;
+use crate::command_error::user_error_with_message;
 use crate::command_error::CommandError;
 use crate::complete;
 use crate::diff_util::get_copy_records;
 use crate::diff_util::DiffFormatArgs;
+use crate::diff_util::IntroducedLines;
 use crate::ui::Ui;
 
 /// Compare file contents between two revisions
@@ -40,6 +61,12 @@ use crate::ui::Ui;
 /// given revisions. If either is left out, it defaults to the working-copy
 /// commit. For example, `jj diff --from main` shows the changes from "main"
 /// (perhaps a bookmark name) to the working-copy commit.
+///
+/// To compare the working copies of two workspaces, use workspace-qualified
+/// revisions like `jj diff --from default@ --to other@`. The workspaces share
+/// the same repo, so this doesn't copy any files. Note that the working-copy
+/// commit of another workspace reflects its files as of the last time a
+/// command was run in that workspace.
 #[derive(clap::Args, Clone, Debug)]
 #[command(mut_arg("ignore_all_space", |a| a.short('w')))]
 #[command(mut_arg("ignore_space_change", |a| a.short('b')))]
@@ -49,18 +76,23 @@ pub(crate) struct DiffArgs {
     /// If the revision is a merge commit, this shows changes *from* the
     /// automatic merge of the contents of all of its parents *to* the contents
     /// of the revision itself.
+    ///
+    /// If several revisions are specified, e.g. `-r A -r B -r C`, this instead
+    /// shows the changes from each revision to the next one (`A` to `B`, then
+    /// `B` to `C`), each preceded by the two revisions. A file that is absent
+    /// in one of the revisions is shown as added or removed.
     #[arg(
-        long,
+        long = "revision",
         short,
         value_name = "REVSET",
         add = ArgValueCandidates::new(complete::all_revisions)
     )]
-    revision: Option<RevisionArg>,
+    revisions: Vec<RevisionArg>,
     /// Show changes from this revision
     #[arg(
         long,
         short,
-        conflicts_with = "revision",
+        conflicts_with = "revisions",
         value_name = "REVSET",
         add = ArgValueCandidates::new(complete::all_revisions)
     )]
@@ -69,11 +101,48 @@ pub(crate) struct DiffArgs {
     #[arg(
         long,
         short,
-        conflicts_with = "revision",
+        conflicts_with = "revisions",
         value_name = "REVSET",
         add = ArgValueCandidates::new(complete::all_revisions)
     )]
     to: Option<RevisionArg>,
+    /// Show the commit metadata before the diff, like `jj show`
+    ///
+    /// The header is rendered by the `templates.show` template. It's only
+    /// available when showing changes in a single revision.
+    #[arg(long, conflicts_with_all = ["from", "to"])]
+    header: bool,
+    /// Also show renamed files whose source or destination path matches
+    ///
+    /// Renames are detected between the two diffed trees, so a file that was
+    /// renamed several times in between is shown as a single rename from its
+    /// original path to its final path.
+    #[arg(long)]
+    follow_renames: bool,
+    /// Show only files that would be passed to a tool by `jj fix`
+    ///
+    /// Without a value, files matching any of the enabled `fix.tools` are
+    /// shown. With a tool name, e.g. `--fixable=rustfmt`, only files matching
+    /// that tool are shown.
+    #[arg(long, value_name = "TOOL", num_args = 0..=1, require_equals = true)]
+    fixable: Option<Option<String>>,
+    /// Show only hunks that add lines introduced by this revision
+    ///
+    /// The lines added by the revision compared to its parent(s) are looked
+    /// up by their exact content in the diff, so this shows what became of a
+    /// revision's changes over a longer range, e.g. `jj diff --from main@origin
+    /// --introduced-by X`. Lines that were modified after the revision no
+    /// longer match, and blank lines are ignored. Only the `--git` format can
+    /// be used.
+    #[arg(
+        long,
+        value_name = "REVSET",
+        add = ArgValueCandidates::new(complete::all_revisions)
+    )]
+    introduced_by: Option<RevisionArg>,
+    /// Don't exclude the files matching `ui.diff.exclude`
+    #[arg(long)]
+    no_exclude: bool,
     /// Restrict the diff to these paths
     #[arg(
         value_name = "FILESETS",
@@ -81,6 +150,28 @@ pub(crate) struct DiffArgs {
         add = ArgValueCompleter::new(complete::modified_revision_or_range_files),
     )]
     paths: Vec<String>,
+    /// Compare two files instead of revisions
+    ///
+    /// The files don't have to be in the workspace. Only the `--git` and
+    /// `--color-words` formats can be used.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["LEFT", "RIGHT"],
+        value_hint = clap::ValueHint::FilePath,
+        conflicts_with_all = ["revisions", "from", "to", "header", "follow_renames", "fixable", "introduced_by", "paths"],
+    )]
+    file: Vec<PathBuf>,
+    /// Compare the contents of two files in the repo by their ids
+    ///
+    /// Only the `--git` and `--color-words` formats can be used.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["LEFT", "RIGHT"],
+        conflicts_with_all = ["revisions", "from", "to", "header", "follow_renames", "fixable", "introduced_by", "paths", "file"],
+    )]
+    blob: Vec<String>,
     #[command(flatten)]
     format: DiffFormatArgs,
 }
@@ -92,52 +183,257 @@ pub(crate) fn cmd_diff(
     args: &DiffArgs,
 ) -> Result<(), CommandError> {
     let workspace_command = command.workspace_helper(ui)?;
+    if !args.file.is_empty() || !args.blob.is_empty() {
+        return show_content_diff(ui, command, &workspace_command, args);
+    }
     let repo = workspace_command.repo();
     let fileset_expression = workspace_command.parse_file_patterns(ui, &args.paths)?;
-    let matcher = fileset_expression.to_matcher();
     let resolve_revision = |r: &Option<RevisionArg>| {
         workspace_command.resolve_single_rev(ui, r.as_ref().unwrap_or(&RevisionArg::AT))
     };
 
-    let from_tree;
-    let to_tree;
-    let mut copy_records = CopyRecords::default();
+    // Each step is a pair of trees to diff, and the commits to find copy
+    // records between. There are several steps only if multiple revisions are
+    // specified.
+    let mut steps = vec![];
+    let mut header_commit = None;
     if args.from.is_some() || args.to.is_some() {
         let from = resolve_revision(&args.from)?;
         let to = resolve_revision(&args.to)?;
-        from_tree = from.tree()?;
-        to_tree = to.tree()?;
-
-        let records = get_copy_records(repo.store(), from.id(), to.id(), &matcher)?;
-        copy_records.add_records(records)?;
+        let copy_endpoints = vec![(from.id().clone(), to.id().clone())];
+        steps.push(DiffStep {
+            from_tree: from.tree()?,
+            to_tree: to.tree()?,
+            copy_endpoints,
+            commits: None,
+        });
+    } else if args.revisions.len() > 1 {
+        if args.header {
+            return Err(user_error(
+                "--header can only be used when showing a single revision",
+            ));
+        }
+        let commits: Vec<_> = args
+            .revisions
+            .iter()
+            .map(|revision| workspace_command.resolve_single_rev(ui, revision))
+            .try_collect()?;
+        for (from, to) in commits.into_iter().tuple_windows() {
+            steps.push(DiffStep {
+                from_tree: from.tree()?,
+                to_tree: to.tree()?,
+                copy_endpoints: vec![(from.id().clone(), to.id().clone())],
+                commits: Some((from, to)),
+            });
+        }
     } else {
-        let to = resolve_revision(&args.revision)?;
+        let to = resolve_revision(&args.revisions.first().cloned())?;
         let parents: Vec<_> = to.parents().try_collect()?;
-        from_tree = merge_commit_trees(repo.as_ref(), &parents)?;
-        to_tree = to.tree()?;
-
-        for p in &parents {
-            let records = get_copy_records(repo.store(), p.id(), to.id(), &matcher)?;
-            copy_records.add_records(records)?;
+        let copy_endpoints = parents
+            .iter()
+            .map(|p| (p.id().clone(), to.id().clone()))
+            .collect();
+        steps.push(DiffStep {
+            from_tree: merge_commit_trees(repo.as_ref(), &parents)?,
+            to_tree: to.tree()?,
+            copy_endpoints,
+            commits: None,
+        });
+        if args.header {
+            header_commit = Some(to);
         }
     }
 
-    let diff_renderer = workspace_command.diff_renderer_for(&args.format)?;
+    let header = if let Some(commit) = header_commit {
+        let template_string = workspace_command.settings().get_string("templates.show")?;
+        let template = workspace_command.parse_commit_template(ui, &template_string)?;
+        Some((template, commit))
+    } else {
+        None
+    };
+    let tools_matcher = if let Some(tool_name) = &args.fixable {
+        Some(get_tools_matcher(
+            ui,
+            &workspace_command,
+            tool_name.as_deref(),
+        )?)
+    } else {
+        None
+    };
+    let introduced_lines = if let Some(revision) = &args.introduced_by {
+        let commit = workspace_command.resolve_single_rev(ui, revision)?;
+        let parents: Vec<_> = commit.parents().try_collect()?;
+        let from_tree = merge_commit_trees(repo.as_ref(), &parents)?;
+        let introduced_lines = IntroducedLines::from_trees(
+            repo.as_ref(),
+            &from_tree,
+            &commit.tree()?,
+            workspace_command.env().conflict_marker_style(),
+        )
+        .block_on()?;
+        Some(introduced_lines)
+    } else {
+        None
+    };
+    let introduced_paths_matcher = introduced_lines
+        .as_ref()
+        .map(|lines| FilesMatcher::new(lines.paths()));
+    let mut diff_renderer = workspace_command
+        .diff_renderer_for(ui, &args.format)?
+        .with_introduced_lines(introduced_lines);
+    if args.no_exclude {
+        diff_renderer = diff_renderer.with_excluded_paths(None);
+    }
     ui.request_pager();
-    diff_renderer.show_diff(
-        ui,
-        ui.stdout_formatter().as_mut(),
-        &from_tree,
-        &to_tree,
-        &matcher,
-        &copy_records,
-        ui.term_width(),
-    )?;
+    let mut formatter = ui.stdout_formatter();
+    if let Some((template, commit)) = &header {
+        template.format(commit, formatter.as_mut())?;
+    }
+    for (i, step) in steps.iter().enumerate() {
+        let (matcher, copy_records) = step_matcher_and_copy_records(
+            repo.as_ref(),
+            &fileset_expression,
+            &step.copy_endpoints,
+            args.follow_renames,
+        )?;
+        let matcher = if let Some(tools_matcher) = &tools_matcher {
+            Box::new(IntersectionMatcher::new(matcher, tools_matcher))
+        } else {
+            matcher
+        };
+        let matcher = if let Some(paths_matcher) = &introduced_paths_matcher {
+            Box::new(IntersectionMatcher::new(matcher, paths_matcher))
+        } else {
+            matcher
+        };
+        if let Some((from, to)) = &step.commits {
+            if i > 0 {
+                writeln!(formatter)?;
+            }
+            write!(formatter, "From: ")?;
+            workspace_command.write_commit_summary(formatter.as_mut(), from)?;
+            write!(formatter, "\nTo:   ")?;
+            workspace_command.write_commit_summary(formatter.as_mut(), to)?;
+            writeln!(formatter)?;
+        }
+        diff_renderer.show_diff(
+            ui,
+            formatter.as_mut(),
+            &step.from_tree,
+            &step.to_tree,
+            &matcher,
+            &copy_records,
+            ui.term_width(),
+        )?;
+    }
     print_unmatched_explicit_paths(
         ui,
         &workspace_command,
         &fileset_expression,
-        [&from_tree, &to_tree],
+        steps
+            .iter()
+            .flat_map(|step| [&step.from_tree, &step.to_tree]),
+    )?;
+    Ok(())
+}
+
+/// Shows the diff between the two files or blobs specified by `--file` or
+/// `--blob`.
+fn show_content_diff(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    workspace_command: &WorkspaceCommandHelper,
+    args: &DiffArgs,
+) -> Result<(), CommandError> {
+    let contents: Vec<(String, Box<dyn Read>)> = if !args.file.is_empty() {
+        args.file
+            .iter()
+            .map(|path| {
+                let file = File::open(command.cwd().join(path)).map_err(|err| {
+                    user_error_with_message(format!("Failed to read {}", path.display()), err)
+                })?;
+                let reader: Box<dyn Read> = Box::new(file);
+                Ok::<_, CommandError>((path.display().to_string(), reader))
+            })
+            .try_collect()?
+    } else {
+        let store = workspace_command.repo().store();
+        args.blob
+            .iter()
+            .map(|hex| {
+                let id = FileId::try_from_hex(hex)
+                    .map_err(|_| user_error(format!("Invalid blob id: {hex}")))?;
+                let reader = store.read_file(RepoPath::root(), &id).map_err(|err| {
+                    user_error_with_message(format!("Failed to read blob {hex}"), err)
+                })?;
+                Ok::<_, CommandError>((hex.clone(), reader))
+            })
+            .try_collect()?
+    };
+    let ((left_label, mut left), (right_label, mut right)) = contents
+        .into_iter()
+        .collect_tuple()
+        .expect("clap should require exactly two values");
+    let diff_renderer = workspace_command.diff_renderer_for(ui, &args.format)?;
+    ui.request_pager();
+    let mut formatter = ui.stdout_formatter();
+    diff_renderer.show_content_diff(
+        formatter.as_mut(),
+        &left_label,
+        left.as_mut(),
+        &right_label,
+        right.as_mut(),
     )?;
     Ok(())
 }
+
+struct DiffStep {
+    from_tree: MergedTree,
+    to_tree: MergedTree,
+    copy_endpoints: Vec<(CommitId, CommitId)>,
+    /// The commits to print before the diff, if there are several steps.
+    commits: Option<(Commit, Commit)>,
+}
+
+/// Builds the matcher for the paths to diff, and collects the copy records
+/// between the `copy_endpoints`.
+fn step_matcher_and_copy_records(
+    repo: &dyn Repo,
+    fileset_expression: &FilesetExpression,
+    copy_endpoints: &[(CommitId, CommitId)],
+    follow_renames: bool,
+) -> Result<(Box<dyn Matcher>, CopyRecords), CommandError> {
+    let mut copy_records = CopyRecords::default();
+    let matcher = if follow_renames {
+        // Include both sides of renames touching the specified paths.
+        let matcher = fileset_expression.to_matcher();
+        let mut records = Vec::new();
+        for (from_id, to_id) in copy_endpoints {
+            for record in get_copy_records(repo.store(), from_id, to_id, &EverythingMatcher)? {
+                let record = record?;
+                if matcher.matches(&record.source) || matcher.matches(&record.target) {
+                    records.push(record);
+                }
+            }
+        }
+        let expression = FilesetExpression::union_all(
+            [fileset_expression.clone()]
+                .into_iter()
+                .chain(records.iter().flat_map(|record| {
+                    [&record.source, &record.target]
+                        .map(|path| FilesetExpression::file_path(path.clone()))
+                }))
+                .collect(),
+        );
+        copy_records.add_records(records.into_iter().map(Ok))?;
+        expression.to_matcher()
+    } else {
+        let matcher = fileset_expression.to_matcher();
+        for (from_id, to_id) in copy_endpoints {
+            let records = get_copy_records(repo.store(), from_id, to_id, &matcher)?;
+            copy_records.add_records(records)?;
+        }
+        matcher
+    };
+    Ok((matcher, copy_records))
+}
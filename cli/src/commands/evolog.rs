@@ -99,7 +99,8 @@ pub(crate) fn cmd_evolog(
 
     let start_commit = workspace_command.resolve_single_rev(ui, &args.revision)?;
 
-    let diff_renderer = workspace_command.diff_renderer_for_log(&args.diff_format, args.patch)?;
+    let diff_renderer =
+        workspace_command.diff_renderer_for_log(ui, &args.diff_format, args.patch)?;
     let graph_style = GraphStyle::from_settings(workspace_command.settings())?;
     let with_content_format = LogContentFormat::new(ui, workspace_command.settings())?;
 
@@ -12,46 +12,81 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::io::Read as _;
 use std::io::Write;
+use std::iter;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Output;
 use std::process::Stdio;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::mpsc::channel;
 
 use clap_complete::ArgValueCandidates;
 use futures::StreamExt;
 use itertools::Itertools;
 use jj_lib::backend::BackendError;
+use jj_lib::backend::ChangeId;
 use jj_lib::backend::CommitId;
 use jj_lib::backend::FileId;
 use jj_lib::backend::TreeValue;
+use jj_lib::commit::Commit;
+use jj_lib::config::ConfigGetResultExt as _;
+use jj_lib::config::ConfigLayer;
+use jj_lib::config::ConfigLoadError;
+use jj_lib::config::ConfigSource;
+use jj_lib::config::StackedConfig;
 use jj_lib::fileset;
 use jj_lib::fileset::FilesetDiagnostics;
 use jj_lib::fileset::FilesetExpression;
 use jj_lib::matchers::Matcher;
+use jj_lib::matchers::UnionMatcher;
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::merged_tree::MergedTreeBuilder;
 use jj_lib::merged_tree::TreeDiffEntry;
+use jj_lib::object_id::ObjectId as _;
 use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
 use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::repo_path::RepoPathUiConverter;
 use jj_lib::revset::RevsetExpression;
 use jj_lib::revset::RevsetIteratorExt;
-use jj_lib::settings::UserSettings;
+use jj_lib::settings::HumanByteSize;
 use jj_lib::store::Store;
 use jj_lib::tree::Tree;
+use once_cell::sync::OnceCell;
 use pollster::FutureExt;
 use rayon::iter::IntoParallelIterator;
 use rayon::prelude::ParallelIterator;
+use tempfile::TempDir;
 use tracing::instrument;
 
+use crate::cli_util::short_commit_hash;
 use crate::cli_util::CommandHelper;
 use crate::cli_util::RevisionArg;
+use crate::cli_util::WorkspaceCommandHelper;
 use crate::command_error::config_error;
+use crate::command_error::config_error_with_message;
 use crate::command_error::print_parse_diagnostics;
+use crate::command_error::user_error;
+use crate::command_error::user_error_with_hint;
+use crate::command_error::user_error_with_message;
 use crate::command_error::CommandError;
 use crate::complete;
+use crate::config::shell_quote;
 use crate::config::CommandNameAndArgs;
+use crate::config::CommandOrShellScript;
 use crate::ui::Ui;
 
 /// Update files with formatting fixes or other changes
@@ -68,10 +103,11 @@ use crate::ui::Ui;
 /// with existing conflicts will be updated on all sides of the conflict, which
 /// can potentially increase or decrease the number of conflict markers.
 ///
-/// The external tools must accept the current file content on standard input,
-/// and return the updated file content on standard output. A tool's output will
-/// not be used unless it exits with a successful exit code. Output on standard
-/// error will be passed through to the terminal.
+/// Unless they edit files in place, the external tools must accept the current
+/// file content on standard input, and return the updated file content on
+/// standard output. A tool's output will not be used unless it exits with a
+/// successful exit code. Output on standard error will be passed through to
+/// the terminal.
 ///
 /// Tools are defined in a table where the keys are arbitrary identifiers and
 /// the values have the following properties:
@@ -80,14 +116,69 @@ use crate::ui::Ui;
 ///    which will be replaced with the repo-relative path of the file being
 ///    fixed. It is useful to provide the path to tools that include the path in
 ///    error messages, or behave differently based on the directory or file
-///    name.
+///    name. Arguments can also contain `$root`, which will be replaced with
+///    the absolute path of the workspace root, and `$commit_id` and
+///    `$change_id`, which will be replaced with the IDs of the commit the file
+///    is being fixed in. `$commit_id` is the ID of the commit before it's
+///    fixed, so it's stale once the fixes are committed, whereas `$change_id`
+///    stays the same. If any tool uses the commit IDs, files are no longer
+///    deduplicated across commits, so tools run once per commit instead of
+///    once per unique file content. Alternatively, `{ shell = "..." }` runs a
+///    script through the platform shell, which allows pipes and redirections.
+///    Substituted paths are quoted, but the script itself is trusted as is.
+///    On Windows, the script can't contain variables.
 ///  - `patterns`: Determines which files the tool will affect. If this list is
 ///    empty, no files will be affected by the tool. If there are multiple
 ///    patterns, the tool is applied only once to each file in the union of the
 ///    patterns.
+///  - `content-pattern`: A regular expression that the file content must also
+///    match for the tool to run, e.g. `^#!.*sh` for shell scripts without an
+///    extension. It is matched against the content the tool would receive.
 ///  - `enabled`: Enables or disables the tool. If omitted, the tool is enabled.
 ///    This is useful for defining disabled tools in user configuration that can
 ///    be enabled in individual repositories with one config setting.
+///  - `max-concurrency`: The maximum number of invocations of the tool that
+///    may run at the same time. If omitted, the number is not limited. Setting
+///    it to 1 serializes tools that can't run concurrently, at the cost of
+///    fixing files more slowly.
+///  - `stabilize`: If `true`, the tool's output is fed back to the tool until
+///    the output stops changing, and the file is updated only if that happens
+///    within a few runs. This prevents churn from tools that don't produce the
+///    same output for their own output. If omitted, the tool is run once.
+///  - `stdin`: If `false`, the file content is written to a temporary file
+///    with the same name instead of standard input, and `$path` is replaced
+///    with the path of that file. A fix tool still prints the updated content
+///    on standard output. This is for tools that can't read standard input,
+///    and applies to the `"fix"` and `"lint"` modes, since in-place tools
+///    always get a temporary file. If omitted, it's `true`.
+///  - `temp-extension`: The extension of the temporary file that is passed to
+///    tools with `stdin = false` or the `"in-place"` mode, replacing the
+///    extension of the file being fixed. This is for tools that only accept
+///    certain extensions, e.g. `temp-extension = "js"` to format `.mjs` files.
+///    If omitted, the temporary file has the same name as the file.
+///  - `mode`: Either `"fix"` (the default), `"lint"`, or `"in-place"`. A lint
+///    tool only reports problems and never modifies files. Its output is
+///    ignored unless it exits with a non-successful exit code, in which case
+///    its standard output and standard error are printed along with the file
+///    path and the commits the file content was changed in. `jj fix` then
+///    exits with an error after applying the fixes from the other tools. An
+///    in-place tool doesn't use standard input and output. Instead, the file
+///    content is written to a temporary file with the same name, `$path` is
+///    replaced with the path of that file, and the tool is expected to
+///    rewrite it, e.g. `gofmt -w $path`.
+///  - `scope`: Either `"file"` (the default) or `"commit"`. A commit-scoped
+///    tool is run once per commit instead of once per file, which suits tools
+///    that are slow to start. It must use the `"in-place"` mode. The matching
+///    files to fix in the commit are written to a temporary directory at their
+///    repo paths, `$root` is replaced with the path of that directory, which
+///    is also the tool's working directory, and the tool is expected to
+///    rewrite the files there. `$path`, `content-pattern`, and `stabilize`
+///    can't be used. Commit-scoped tools run after the other tools, and don't
+///    get conflicted files or files larger than the maximum file size.
+///  - `priority`: An integer that determines the execution order of tools that
+///    affect the same file. Tools with lower priority run first, and tools
+///    with the same priority run in the order of their names. If omitted, the
+///    priority is 0.
 ///
 /// For example, the following configuration defines how two code formatters
 /// (`clang-format` and `black`) will apply to three different file extensions
@@ -104,10 +195,42 @@ use crate::ui::Ui;
 /// patterns = ["glob:'**/*.py'"]
 /// ```
 ///
-/// Execution order of tools that affect the same file is deterministic, but
-/// currently unspecified, and may change between releases. If two tools affect
-/// the same file, the second tool to run will receive its input from the
-/// output of the first tool.
+/// If two tools affect the same file, the second tool to run will receive its
+/// input from the output of the first tool.
+///
+/// If a tool exits with a non-successful exit code, its output isn't used, and
+/// its standard error is printed along with the exit status, the file path, and
+/// the commits the file content was changed in.
+///
+/// A tool that turns a non-empty file into an empty one has likely failed
+/// without reporting an error. By default, such a file is left unchanged with
+/// a warning. The `fix.empty-output` setting can be set to `"abort"` to fail
+/// the command instead, or to `"allow"` to accept the empty output.
+///
+/// When done, `jj fix` reports how many commits and files were fixed. Files
+/// with the same path and content in several commits are counted once. Files
+/// that weren't passed to the tools because they were too large, or that a tool
+/// emptied, are counted as skipped.
+///
+/// To format the files you're working on before describing the change, run
+/// `jj fix --here`. It fixes only the files changed in the working-copy
+/// commit, and the fixed contents are written to the working copy so that
+/// editors see them. Files with conflicts are left unchanged, so that conflict
+/// markers aren't passed to the tools.
+///
+/// With `--dry-run`, the tools are run, but no files or commits are changed.
+/// Instead, the files that would be changed are listed with the commits they
+/// were changed in.
+///
+/// With `--error-on-failure`, the fixes from the other tools and files are
+/// still applied, but the command exits with an error if any tool failed. The
+/// failed tools and files are listed at the end.
+///
+/// If `fix.verify-command` is set, it is run in the workspace root after the
+/// files have been fixed, e.g. to check that the project still builds. If the
+/// command fails, `jj fix` reports an error. The fix operation is also undone
+/// if `fix.undo-on-verify-failure` is set to `true`; otherwise it can be undone
+/// with `jj undo`.
 #[derive(clap::Args, Clone, Debug)]
 #[command(verbatim_doc_comment)]
 pub(crate) struct FixArgs {
@@ -121,13 +244,52 @@ pub(crate) struct FixArgs {
         add = ArgValueCandidates::new(complete::mutable_revisions)
     )]
     source: Vec<RevisionArg>,
+    /// Fix only the files changed in the working-copy commit, skipping files
+    /// with conflicts
+    #[arg(long, conflicts_with_all = ["source", "include_unchanged_files"])]
+    here: bool,
     /// Fix only these paths
     #[arg(value_name = "FILESETS", value_hint = clap::ValueHint::AnyPath)]
     paths: Vec<String>,
     /// Fix unchanged files in addition to changed ones. If no paths are
     /// specified, all files in the repo will be fixed.
+    ///
+    /// If more files than the `fix.max-unchanged-files` setting would be
+    /// fixed, you are asked to confirm.
     #[arg(long)]
     include_unchanged_files: bool,
+    /// Don't ask for confirmation before fixing many unchanged files
+    #[arg(long, requires = "include_unchanged_files")]
+    yes: bool,
+    /// Print the commands that would be run for each file, without running
+    /// them or modifying any revisions
+    ///
+    /// Each line is a shell-escaped pipeline of the tools that affect the
+    /// file, reading the file content from its path relative to the workspace
+    /// root. The commands read the files on disk, not the content of the
+    /// revisions that would be fixed. Lint, in-place, and commit-scoped tools
+    /// and tools with `stdin = false` are left out with a warning.
+    #[arg(long)]
+    emit_commands: bool,
+    /// Run the tools and list the files they would change, without modifying
+    /// any revisions
+    #[arg(long, conflicts_with = "emit_commands")]
+    dry_run: bool,
+    /// Skip files larger than this size, e.g. `1MiB`
+    ///
+    /// Defaults to the `fix.max-file-size` setting. A size of 0 means no
+    /// limit.
+    #[arg(long, value_name = "SIZE")]
+    max_file_size: Option<HumanByteSize>,
+    /// Exit with an error if any tool failed on any file
+    #[arg(long)]
+    error_on_failure: bool,
+    /// Run only the tool with this name in `fix.tools`
+    ///
+    /// Can be repeated to run several tools. They are still run in the
+    /// configured order.
+    #[arg(long = "tool", value_name = "NAME")]
+    tools: Vec<String>,
 }
 
 #[instrument(skip_all)]
@@ -137,19 +299,53 @@ pub(crate) fn cmd_fix(
     args: &FixArgs,
 ) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
-    let tools_config = get_tools_config(ui, workspace_command.settings())?;
-    let root_commits: Vec<CommitId> = if args.source.is_empty() {
+    let mut tools_config = get_tools_config(ui, &workspace_command)?;
+    if !args.tools.is_empty() {
+        tools_config.select(&args.tools)?;
+    }
+    let root_commits: Vec<CommitId> = if args.here {
+        let wc_commit_id = workspace_command
+            .get_wc_commit_id()
+            .ok_or_else(|| user_error("This command requires a working copy"))?;
+        vec![wc_commit_id.clone()]
+    } else if args.source.is_empty() {
         let revs = workspace_command.settings().get_string("revsets.fix")?;
-        workspace_command.parse_revset(ui, &RevisionArg::from(revs))?
+        workspace_command
+            .parse_revset(ui, &RevisionArg::from(revs))?
+            .evaluate_to_commit_ids()?
+            .try_collect()?
     } else {
-        workspace_command.parse_union_revsets(ui, &args.source)?
-    }
-    .evaluate_to_commit_ids()?
-    .try_collect()?;
+        workspace_command
+            .parse_union_revsets(ui, &args.source)?
+            .evaluate_to_commit_ids()?
+            .try_collect()?
+    };
     workspace_command.check_rewritable(root_commits.iter())?;
     let matcher = workspace_command
         .parse_file_patterns(ui, &args.paths)?
         .to_matcher();
+    let HumanByteSize(mut max_file_size) = match args.max_file_size {
+        Some(size) => size,
+        None => workspace_command
+            .settings()
+            .get_value_with("fix.max-file-size", TryInto::try_into)?,
+    };
+    if max_file_size == 0 {
+        max_file_size = u64::MAX;
+    }
+    let empty_output: EmptyOutputBehavior = workspace_command.settings().get("fix.empty-output")?;
+    let verify_command: Option<CommandNameAndArgs> = workspace_command
+        .settings()
+        .get("fix.verify-command")
+        .optional()?;
+    let undo_on_verify_failure = workspace_command
+        .settings()
+        .get_bool("fix.undo-on-verify-failure")?;
+    let max_unchanged_files = workspace_command
+        .settings()
+        .get::<usize>("fix.max-unchanged-files")?;
+    let old_op_id = workspace_command.repo().op_id().clone();
+    let workspace_root = workspace_command.workspace_root().to_owned();
 
     let mut tx = workspace_command.start_transaction();
 
@@ -157,9 +353,10 @@ pub(crate) fn cmd_fix(
     // deterministic, and should not consider outside information, so it is safe to
     // deduplicate inputs that correspond to multiple files or commits. This is
     // typically more efficient, but it does prevent certain use cases like
-    // providing commit IDs as inputs to be inserted into files. We also need to
-    // record the mapping between tool inputs and paths/commits, to efficiently
-    // rewrite the commits later.
+    // providing commit IDs as inputs to be inserted into files. If a tool asks
+    // for the commit IDs, the inputs are only deduplicated within each commit.
+    // We also need to record the mapping between tool inputs and
+    // paths/commits, to efficiently rewrite the commits later.
     //
     // If a path is being fixed in a particular commit, it must also be fixed in all
     // that commit's descendants. We do this as a way of propagating changes,
@@ -176,8 +373,18 @@ pub(crate) fn cmd_fix(
         .try_collect()?;
     let mut unique_tool_inputs: HashSet<ToolInput> = HashSet::new();
     let mut commit_paths: HashMap<CommitId, HashSet<RepoPathBuf>> = HashMap::new();
-    for commit in commits.iter().rev() {
+    // The commits each tool input was changed in, to tell where lint problems
+    // were found.
+    let mut input_commits: HashMap<ToolInput, Vec<CommitId>> = HashMap::new();
+    let mut conflicted_paths: BTreeSet<RepoPathBuf> = BTreeSet::new();
+    let uses_commit_variables = tools_config.uses_commit_variables();
+    let progress = crate::progress::message_progress(ui);
+    for (i, commit) in commits.iter().rev().enumerate() {
+        if let Some(progress) = &progress {
+            progress(&format!("Checking commit {}/{}", i + 1, commits.len()));
+        }
         let mut paths: HashSet<RepoPathBuf> = HashSet::new();
+        let tool_commit = uses_commit_variables.then(|| ToolCommit::new(commit));
 
         // If --include-unchanged-files, we always fix every matching file in the tree.
         // Otherwise, we fix the matching changed files in this commit, plus any that
@@ -202,6 +409,10 @@ pub(crate) fn cmd_fix(
             }) = diff_stream.next().await
             {
                 let (_before, after) = values?;
+                if args.here && !after.is_resolved() {
+                    conflicted_paths.insert(repo_path);
+                    continue;
+                }
                 // Deleted files have no file content to fix, and they have no terms in `after`,
                 // so we don't add any tool inputs for them. Conflicted files produce one tool
                 // input for each side of the conflict.
@@ -209,13 +420,16 @@ pub(crate) fn cmd_fix(
                     // We currently only support fixing the content of normal files, so we skip
                     // directories and symlinks, and we ignore the executable bit.
                     if let TreeValue::File { id, executable: _ } = term {
-                        // TODO: Skip the file if its content is larger than some configured size,
-                        // preferably without actually reading it yet.
                         let tool_input = ToolInput {
                             file_id: id.clone(),
                             repo_path: repo_path.clone(),
+                            commit: tool_commit.clone(),
                         };
                         unique_tool_inputs.insert(tool_input.clone());
+                        input_commits
+                            .entry(tool_input)
+                            .or_default()
+                            .push(commit.id().clone());
                         paths.insert(repo_path.clone());
                     }
                 }
@@ -226,13 +440,313 @@ pub(crate) fn cmd_fix(
 
         commit_paths.insert(commit.id().clone(), paths);
     }
+    // The progress line would otherwise be left next to the prompt.
+    drop(progress);
+
+    if args.include_unchanged_files && !args.yes {
+        let num_files = unique_tool_inputs
+            .iter()
+            .map(|tool_input| &tool_input.repo_path)
+            .unique()
+            .count();
+        check_unchanged_files_count(ui, num_files, max_unchanged_files)?;
+    }
+
+    if args.emit_commands {
+        let repo_paths: BTreeSet<&RepoPathBuf> = unique_tool_inputs
+            .iter()
+            .map(|tool_input| &tool_input.repo_path)
+            .collect();
+        // Tools that don't filter standard input can't be part of a pipeline.
+        let mut skipped_tools: BTreeSet<&str> = BTreeSet::new();
+        let mut formatter = ui.stdout_formatter();
+        for repo_path in repo_paths {
+            let (emitted_tools, other_tools): (Vec<_>, Vec<_>) = tools_config
+                .tools
+                .iter()
+                .filter(|tool_config| tool_config.matcher.matches(repo_path))
+                .partition(|tool_config| {
+                    tool_config.scope == ToolScope::File
+                        && tool_config.mode == ToolMode::Fix
+                        && tool_config.stdin
+                });
+            skipped_tools.extend(
+                other_tools
+                    .iter()
+                    .map(|tool_config| tool_config.name.as_str()),
+            );
+            let commands = emitted_tools
+                .iter()
+                .map(|tool_config| {
+                    format_command_line(&tool_command(
+                        &tool_config.command,
+                        repo_path.as_internal_file_string(),
+                        &workspace_root,
+                        None,
+                    ))
+                })
+                .collect_vec();
+            if !commands.is_empty() {
+                writeln!(
+                    formatter,
+                    "{} < {}",
+                    commands.join(" | "),
+                    shell_quote(repo_path.as_internal_file_string())
+                )?;
+            }
+        }
+        drop(formatter);
+        if !skipped_tools.is_empty() {
+            writeln!(
+                ui.warning_default(),
+                "Left out tools that don't fix standard input: {}",
+                skipped_tools.iter().join(", ")
+            )?;
+        }
+        return Ok(());
+    }
 
     // Run the configured tool on all of the chosen inputs.
-    let fixed_file_ids = fix_file_ids(
+    let progress = crate::progress::message_progress(ui);
+    let num_tool_inputs = unique_tool_inputs.len();
+    let report_fix_progress = |num_started: usize, num_fixed: usize| {
+        if let Some(progress) = &progress {
+            progress(&format!(
+                "Running tools on file {num_started}/{num_tool_inputs}, fixed {num_fixed}"
+            ));
+        }
+    };
+    let FixedFileIds {
+        fixed_file_ids,
+        mut skipped_paths,
+        mut emptied_paths,
+        unstable_paths,
+        num_checked_files,
+        num_skipped_files,
+        lint_problems,
+        tool_failures,
+        tool_stderrs,
+        unwritten_inputs,
+        mut changed_files_per_tool,
+    } = fix_file_ids(
         tx.repo().store().as_ref(),
         &tools_config,
         &unique_tool_inputs,
+        &workspace_root,
+        max_file_size,
+        empty_output,
+        args.dry_run,
+        &report_fix_progress,
+    )?;
+    let report_commit_progress = |num_started: usize| {
+        if let Some(progress) = &progress {
+            progress(&format!(
+                "Running commit-scoped tools on commit {num_started}/{}",
+                commits.len()
+            ));
+        }
+    };
+    let FixedCommitFiles {
+        fixed_files: fixed_commit_files,
+        checked_inputs: commit_checked_inputs,
+        changed_inputs: commit_changed_inputs,
+        skipped_paths: commit_skipped_paths,
+        emptied_paths: commit_emptied_paths,
+        num_skipped_files: commit_num_skipped_files,
+        tool_failures: commit_tool_failures,
+        tool_stderrs: commit_tool_stderrs,
+        changed_files_per_tool: commit_changed_files_per_tool,
+    } = fix_commit_files(
+        tx.repo().store().as_ref(),
+        &tools_config,
+        &commits,
+        &commit_paths,
+        &fixed_file_ids,
+        uses_commit_variables,
+        max_file_size,
+        empty_output,
+        args.dry_run,
+        &report_commit_progress,
     )?;
+    drop(progress);
+    for stderr in tool_stderrs
+        .iter()
+        .map(|(_, _, stderr)| stderr)
+        .chain(&commit_tool_stderrs)
+    {
+        ui.stderr().write_all(stderr)?;
+    }
+    // Files passed to both file-scoped and commit-scoped tools are counted
+    // once.
+    let num_checked_files = num_checked_files
+        + commit_checked_inputs
+            .iter()
+            .filter(|tool_input| {
+                !tools_config
+                    .file_tools()
+                    .any(|tool_config| tool_config.matcher.matches(&tool_input.repo_path))
+            })
+            .count();
+    let unwritten_inputs = unwritten_inputs
+        .into_iter()
+        .chain(
+            commit_changed_inputs
+                .iter()
+                .filter_map(|tool_input| unique_tool_inputs.get(tool_input)),
+        )
+        .unique()
+        .sorted_by_key(|tool_input| {
+            (
+                &tool_input.repo_path,
+                &tool_input.file_id,
+                &tool_input.commit,
+            )
+        })
+        .collect_vec();
+    changed_files_per_tool.extend(commit_changed_files_per_tool);
+    skipped_paths.extend(commit_skipped_paths);
+    emptied_paths.extend(commit_emptied_paths);
+    let num_skipped_files = num_skipped_files + commit_num_skipped_files;
+
+    for repo_path in &conflicted_paths {
+        let ui_path = tx.base_workspace_helper().format_file_path(repo_path);
+        writeln!(
+            ui.warning_default(),
+            "Skipping {ui_path}: File has conflicts"
+        )?;
+    }
+    for repo_path in skipped_paths {
+        let ui_path = tx.base_workspace_helper().format_file_path(repo_path);
+        writeln!(
+            ui.warning_default(),
+            "Skipping {ui_path}: File is larger than {}",
+            HumanByteSize(max_file_size)
+        )?;
+    }
+    for (repo_path, tool_name) in emptied_paths {
+        let ui_path = tx.base_workspace_helper().format_file_path(repo_path);
+        match empty_output {
+            EmptyOutputBehavior::Allow => unreachable!("empty output should be accepted"),
+            EmptyOutputBehavior::Warn => writeln!(
+                ui.warning_default(),
+                "Skipping {ui_path}: Tool `{tool_name}` produced empty output"
+            )?,
+            EmptyOutputBehavior::Abort => {
+                return Err(user_error_with_hint(
+                    format!("Tool `{tool_name}` produced empty output for {ui_path}"),
+                    "Set `fix.empty-output = \"allow\"` if the tool is expected to empty files.",
+                ));
+            }
+        }
+    }
+    for (repo_path, tool_name) in unstable_paths {
+        let ui_path = tx.base_workspace_helper().format_file_path(repo_path);
+        writeln!(
+            ui.warning_default(),
+            "Skipping tool `{tool_name}` for {ui_path}: Output didn't stabilize after \
+             {MAX_STABILIZE_RUNS} runs"
+        )?;
+    }
+
+    let mut failed_files = vec![];
+    for (tool_input, tool_name, failure) in &tool_failures {
+        let ui_path = tx
+            .base_workspace_helper()
+            .format_file_path(&tool_input.repo_path);
+        let commits = input_commits[*tool_input]
+            .iter()
+            .map(short_commit_hash)
+            .join(", ");
+        match failure.status {
+            Some(status) => writeln!(
+                ui.warning_default(),
+                "Tool `{tool_name}` failed on {ui_path} (changed in {commits}) with {status}:"
+            )?,
+            None => writeln!(
+                ui.warning_default(),
+                "Tool `{tool_name}` failed on {ui_path} (changed in {commits}):"
+            )?,
+        }
+        ui.stderr().write_all(&failure.stderr)?;
+        failed_files.push((ui_path, *tool_name));
+    }
+    // A file can have the same failure in several commits.
+    failed_files.dedup();
+    for (commit, repo_paths, tool_name, failure) in &commit_tool_failures {
+        let commit_hash = short_commit_hash(commit.id());
+        match failure.status {
+            Some(status) => writeln!(
+                ui.warning_default(),
+                "Tool `{tool_name}` failed on commit {commit_hash} with {status}:"
+            )?,
+            None => writeln!(
+                ui.warning_default(),
+                "Tool `{tool_name}` failed on commit {commit_hash}:"
+            )?,
+        }
+        ui.stderr().write_all(&failure.stderr)?;
+        for repo_path in repo_paths {
+            let failed_file = (
+                tx.base_workspace_helper().format_file_path(repo_path),
+                *tool_name,
+            );
+            if !failed_files.contains(&failed_file) {
+                failed_files.push(failed_file);
+            }
+        }
+    }
+
+    for (tool_input, tool_name, output) in &lint_problems {
+        let ui_path = tx
+            .base_workspace_helper()
+            .format_file_path(&tool_input.repo_path);
+        let commits = input_commits[*tool_input]
+            .iter()
+            .map(short_commit_hash)
+            .join(", ");
+        writeln!(
+            ui.warning_default(),
+            "Tool `{tool_name}` found problems in {ui_path} (changed in {commits}):"
+        )?;
+        ui.stderr().write_all(output)?;
+    }
+
+    if args.dry_run {
+        let mut formatter = ui.stdout_formatter();
+        for tool_input in &unwritten_inputs {
+            let ui_path = tx
+                .base_workspace_helper()
+                .format_file_path(&tool_input.repo_path);
+            let commits = input_commits[*tool_input]
+                .iter()
+                .map(short_commit_hash)
+                .join(", ");
+            writeln!(formatter, "{ui_path} (changed in {commits})")?;
+        }
+        drop(formatter);
+        writeln!(
+            ui.status(),
+            "Would fix {} files of {num_checked_files} checked, skipped {num_skipped_files}.",
+            unwritten_inputs.len()
+        )?;
+        if !changed_files_per_tool.is_empty() {
+            writeln!(
+                ui.status(),
+                "Files that would be changed by each tool: {}",
+                format_changed_files_per_tool(&changed_files_per_tool)
+            )?;
+        }
+        if args.error_on_failure {
+            check_tool_failures(ui, &failed_files)?;
+        }
+        return check_lint_problems(&lint_problems);
+    }
+
+    let num_fixed_files = fixed_file_ids.len()
+        + commit_changed_inputs
+            .iter()
+            .filter(|tool_input| !fixed_file_ids.contains_key(tool_input))
+            .count();
 
     // Substitute the fixed file IDs into all of the affected commits. Currently,
     // fixes cannot delete or rename files, change the executable bit, or modify
@@ -245,6 +759,8 @@ pub(crate) fn cmd_fix(
             // TODO: Build the trees in parallel before `transform_descendants()` and only
             // keep the tree IDs in memory, so we can pass them to the rewriter.
             let repo_paths = commit_paths.get(rewriter.old_commit().id()).unwrap();
+            let tool_commit = uses_commit_variables.then(|| ToolCommit::new(rewriter.old_commit()));
+            let commit_fixed_files = fixed_commit_files.get(rewriter.old_commit().id());
             let old_tree = rewriter.old_commit().tree()?;
             let mut tree_builder = MergedTreeBuilder::new(old_tree.id().clone());
             let mut changes = 0;
@@ -255,8 +771,12 @@ pub(crate) fn cmd_fix(
                         let tool_input = ToolInput {
                             file_id: id.clone(),
                             repo_path: repo_path.clone(),
+                            commit: tool_commit.clone(),
                         };
-                        if let Some(new_id) = fixed_file_ids.get(&tool_input) {
+                        let new_id = commit_fixed_files
+                            .and_then(|files| files.get(repo_path))
+                            .or_else(|| fixed_file_ids.get(&tool_input));
+                        if let Some(new_id) = new_id {
                             return Some(TreeValue::File {
                                 id: new_id.clone(),
                                 executable: *executable,
@@ -284,7 +804,123 @@ pub(crate) fn cmd_fix(
         ui.status(),
         "Fixed {num_fixed_commits} commits of {num_checked_commits} checked."
     )?;
-    tx.finish(ui, format!("fixed {num_fixed_commits} commits"))
+    writeln!(
+        ui.status(),
+        "Fixed {num_fixed_files} files of {num_checked_files} checked, skipped \
+         {num_skipped_files}."
+    )?;
+    if !changed_files_per_tool.is_empty() {
+        writeln!(
+            ui.status(),
+            "Files changed by each tool: {}",
+            format_changed_files_per_tool(&changed_files_per_tool)
+        )?;
+    }
+    tx.finish(ui, format!("fixed {num_fixed_commits} commits"))?;
+
+    // The fixes are verified even if some tools failed, since the other fixes
+    // were committed. Nothing to verify if no operation was created.
+    if let Some(verify_command) = &verify_command {
+        if *workspace_command.repo().op_id() != old_op_id {
+            verify_fix(
+                ui,
+                &mut workspace_command,
+                verify_command,
+                undo_on_verify_failure,
+            )?;
+        }
+    }
+    if args.error_on_failure {
+        check_tool_failures(ui, &failed_files)?;
+    }
+    check_lint_problems(&lint_problems)
+}
+
+/// Lists the files that tools failed on, and fails if there were any.
+fn check_tool_failures(ui: &Ui, failed_files: &[(String, &str)]) -> Result<(), CommandError> {
+    if failed_files.is_empty() {
+        return Ok(());
+    }
+    writeln!(ui.warning_default(), "Tools failed on these files:")?;
+    for (ui_path, tool_name) in failed_files {
+        writeln!(ui.stderr(), "  {ui_path}: {tool_name}")?;
+    }
+    let num_files = failed_files
+        .iter()
+        .map(|(ui_path, _)| ui_path)
+        .unique()
+        .count();
+    Err(user_error(format!("Fix tools failed on {num_files} files")))
+}
+
+/// Fails if a lint tool found any problems.
+fn check_lint_problems(lint_problems: &[(&ToolInput, &str, Vec<u8>)]) -> Result<(), CommandError> {
+    if lint_problems.is_empty() {
+        return Ok(());
+    }
+    let num_files = lint_problems
+        .iter()
+        .map(|(tool_input, _, _)| &tool_input.repo_path)
+        .unique()
+        .count();
+    Err(user_error(format!(
+        "Lint tools found problems in {num_files} files"
+    )))
+}
+
+/// Runs the `verify_command` in the workspace root after the fix operation,
+/// and undoes the operation if the command fails and `undo_on_failure` is set.
+fn verify_fix(
+    ui: &mut Ui,
+    workspace_command: &mut WorkspaceCommandHelper,
+    verify_command: &CommandNameAndArgs,
+    undo_on_failure: bool,
+) -> Result<(), CommandError> {
+    writeln!(
+        ui.status(),
+        "Running verification command: {verify_command}"
+    )?;
+    let status = verify_command
+        .to_command()
+        .current_dir(workspace_command.workspace_root())
+        .stdin(Stdio::null())
+        .stdout(ui.stderr_for_child()?)
+        .stderr(ui.stderr_for_child()?)
+        .status()
+        .map_err(|err| {
+            user_error_with_message(
+                format!("Failed to run verification command `{verify_command}`"),
+                err,
+            )
+        })?;
+    if status.success() {
+        writeln!(ui.status(), "Verification succeeded.")?;
+        return Ok(());
+    }
+
+    let message = format!("Verification command exited with {status}");
+    if !undo_on_failure {
+        return Err(user_error_with_hint(
+            message,
+            "Use `jj undo` to undo the fixes.",
+        ));
+    }
+    let fix_op = workspace_command.repo().operation().clone();
+    let parent_op = fix_op
+        .parents()
+        .exactly_one()
+        .ok()
+        .expect("fix operation should have exactly one parent")?;
+    let mut tx = workspace_command.start_transaction();
+    let repo_loader = tx.base_repo().loader();
+    let fix_repo = repo_loader.load_at(&fix_op)?;
+    let parent_repo = repo_loader.load_at(&parent_op)?;
+    tx.repo_mut().merge(&fix_repo, &parent_repo)?;
+    tx.finish(ui, format!("undo operation {}", fix_op.id().hex()))?;
+    Err(user_error_with_hint(
+        message,
+        "The fixes were undone because `fix.undo-on-verify-failure` is set.",
+    ))
 }
 
 /// Represents the API between `jj fix` and the tools it runs.
@@ -304,50 +940,338 @@ struct ToolInput {
     ///  - Update parts of the file's content that should be derived from the
     ///    file's path.
     repo_path: RepoPathBuf,
+
+    /// The commit the file is being fixed in, if any tool uses `$commit_id` or
+    /// `$change_id`. Otherwise, this is `None` so that the same file content
+    /// in different commits is only fixed once.
+    commit: Option<ToolCommit>,
+}
+
+/// IDs of the commit a tool input belongs to.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Debug)]
+struct ToolCommit {
+    commit_id: CommitId,
+    change_id: ChangeId,
+}
+
+impl ToolCommit {
+    fn new(commit: &Commit) -> Self {
+        ToolCommit {
+            commit_id: commit.id().clone(),
+            change_id: commit.change_id().clone(),
+        }
+    }
+}
+
+/// Result of `fix_file_ids()`.
+struct FixedFileIds<'a> {
+    /// The subset of tool inputs that resulted in changed file content.
+    fixed_file_ids: HashMap<&'a ToolInput, FileId>,
+    /// Paths of the files that were not fixed because they were too large.
+    skipped_paths: BTreeSet<&'a RepoPathBuf>,
+    /// Paths of the non-empty files that were not fixed because a tool
+    /// produced empty output, and the names of the tools.
+    emptied_paths: BTreeSet<(&'a RepoPathBuf, &'a str)>,
+    /// Paths of the files that a tool with `stabilize = true` was skipped for
+    /// because its output didn't stabilize, and the names of the tools.
+    unstable_paths: BTreeSet<(&'a RepoPathBuf, &'a str)>,
+    /// Number of tool inputs that matched at least one tool.
+    num_checked_files: usize,
+    /// Number of checked tool inputs that were left unchanged because they
+    /// were too large or a tool produced empty output.
+    num_skipped_files: usize,
+    /// Tool inputs that a lint tool found problems in, the names of the tools,
+    /// and their output, sorted by path and tool name.
+    lint_problems: Vec<(&'a ToolInput, &'a str, Vec<u8>)>,
+    /// Tool inputs that a fix tool failed on, the names of the tools, and the
+    /// failures, sorted by path and tool name.
+    tool_failures: Vec<(&'a ToolInput, &'a str, ToolFailure)>,
+    /// Standard error of the fix tools that succeeded, sorted by path and tool
+    /// name.
+    tool_stderrs: Vec<(&'a ToolInput, &'a str, Vec<u8>)>,
+    /// Tool inputs whose content would have been changed if it weren't a dry
+    /// run, sorted by path.
+    unwritten_inputs: Vec<&'a ToolInput>,
+    /// Number of changed tool inputs each tool contributed changes to, by
+    /// tool name.
+    changed_files_per_tool: BTreeMap<&'a str, usize>,
+}
+
+/// Result of `fix_commit_files()`.
+struct FixedCommitFiles<'a> {
+    /// New file IDs of the files that commit-scoped tools changed, by the ID
+    /// of the original commit and the path.
+    fixed_files: HashMap<CommitId, HashMap<RepoPathBuf, FileId>>,
+    /// Tool inputs that were passed to at least one commit-scoped tool.
+    checked_inputs: HashSet<ToolInput>,
+    /// Tool inputs whose content was changed by commit-scoped tools.
+    changed_inputs: HashSet<ToolInput>,
+    /// Paths of the files that were not passed to the tools because they were
+    /// too large.
+    skipped_paths: BTreeSet<&'a RepoPathBuf>,
+    /// Paths of the non-empty files that were not fixed because a tool
+    /// produced empty output, and the names of the tools.
+    emptied_paths: BTreeSet<(&'a RepoPathBuf, &'a str)>,
+    /// Number of checked tool inputs that were left unchanged because they
+    /// were too large or a tool produced empty output, and that aren't counted
+    /// by `fix_file_ids()`.
+    num_skipped_files: usize,
+    /// Commits that a tool failed on, the paths of the files passed to the
+    /// tool, the names of the tools, and the failures.
+    tool_failures: Vec<(&'a Commit, Vec<RepoPathBuf>, &'a str, ToolFailure)>,
+    /// Standard output and error of the commit-scoped tools that succeeded.
+    tool_stderrs: Vec<Vec<u8>>,
+    /// Number of changed tool inputs each tool contributed changes to, by
+    /// tool name.
+    changed_files_per_tool: BTreeMap<&'a str, usize>,
+}
+
+/// Why a fix tool failed on a file.
+struct ToolFailure {
+    /// Exit status of the tool, or `None` if it couldn't be run.
+    status: Option<ExitStatus>,
+    /// Standard error of the tool, or the error message if it couldn't be run.
+    stderr: Vec<u8>,
+}
+
+/// Result of a fix tool that exited successfully.
+struct ToolOutput {
+    /// The new file content.
+    content: Vec<u8>,
+    /// Standard error of the tool, which is passed through to the user.
+    stderr: Vec<u8>,
+}
+
+impl ToolFailure {
+    fn from_io_error(err: std::io::Error) -> Self {
+        ToolFailure {
+            status: None,
+            stderr: format!("Failed to run tool: {err}\n").into_bytes(),
+        }
+    }
+}
+
+/// How a tool's result is used.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ToolMode {
+    /// Replace the file content with the tool's output.
+    #[default]
+    Fix,
+    /// Report the tool's output as problems if it fails, and leave the file
+    /// content unchanged.
+    Lint,
+    /// Replace the file content with the content of a temporary file that the
+    /// tool rewrote.
+    InPlace,
+}
+
+/// Which files a tool is run on at once.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ToolScope {
+    /// Run the tool once per file content.
+    #[default]
+    File,
+    /// Run the tool once per commit on all of its matching files.
+    Commit,
+}
+
+/// What to do if a tool turns non-empty content into empty content.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum EmptyOutputBehavior {
+    /// Use the empty output.
+    Allow,
+    /// Leave the file unchanged and print a warning.
+    Warn,
+    /// Fail the command.
+    Abort,
 }
 
 /// Applies `run_tool()` to the inputs and stores the resulting file content.
 ///
 /// Returns a map describing the subset of `tool_inputs` that resulted in
-/// changed file content. Failures when handling an input will cause it to be
-/// omitted from the return value, which is indistinguishable from succeeding
-/// with no changes.
-/// TODO: Better error handling so we can tell the user what went wrong with
-/// each failed input.
+/// changed file content. If a tool fails on an input, the tool's changes are
+/// not applied, the remaining tools get the previous content, and the failure
+/// is reported separately. Inputs whose content is larger than `max_file_size` are
+/// not passed to any tool, and their paths are reported separately. Unless
+/// `empty_output` allows it, the remaining tools aren't run once a tool empties
+/// the content, and the input is reported separately instead of being fixed.
+/// Lint tools don't change the content, and their output is reported separately
+/// if they fail. If `dry_run` is set, the changed content isn't written, and
+/// the changed inputs are reported separately. `report_progress` is called with
+/// the number of started and changed inputs before each input is processed.
+#[allow(clippy::too_many_arguments)]
 fn fix_file_ids<'a>(
     store: &Store,
-    tools_config: &ToolsConfig,
+    tools_config: &'a ToolsConfig,
     tool_inputs: &'a HashSet<ToolInput>,
-) -> Result<HashMap<&'a ToolInput, FileId>, CommandError> {
+    workspace_root: &Path,
+    max_file_size: u64,
+    empty_output: EmptyOutputBehavior,
+    dry_run: bool,
+    report_progress: &(dyn Fn(usize, usize) + Sync),
+) -> Result<FixedFileIds<'a>, CommandError> {
     let (updates_tx, updates_rx) = channel();
+    let (skipped_tx, skipped_rx) = channel();
+    let (emptied_tx, emptied_rx) = channel();
+    let (unstable_tx, unstable_rx) = channel();
+    let (lint_tx, lint_rx) = channel();
+    let (failure_tx, failure_rx) = channel();
+    let (stderr_tx, stderr_rx) = channel();
+    let (unwritten_tx, unwritten_rx) = channel();
+    let (changed_tx, changed_rx) = channel();
+    let num_checked_files = AtomicUsize::new(0);
+    let num_started_inputs = AtomicUsize::new(0);
+    let num_changed_inputs = AtomicUsize::new(0);
     // TODO: Switch to futures, or document the decision not to. We don't need
     // threads unless the threads will be doing more than waiting for pipes.
     tool_inputs.into_par_iter().try_for_each_init(
-        || updates_tx.clone(),
-        |updates_tx, tool_input| -> Result<(), CommandError> {
+        || {
+            (
+                updates_tx.clone(),
+                skipped_tx.clone(),
+                emptied_tx.clone(),
+                unstable_tx.clone(),
+                lint_tx.clone(),
+                failure_tx.clone(),
+                stderr_tx.clone(),
+                unwritten_tx.clone(),
+                changed_tx.clone(),
+            )
+        },
+        |(
+            updates_tx,
+            skipped_tx,
+            emptied_tx,
+            unstable_tx,
+            lint_tx,
+            failure_tx,
+            stderr_tx,
+            unwritten_tx,
+            changed_tx,
+        ),
+         tool_input|
+         -> Result<(), CommandError> {
+            report_progress(
+                num_started_inputs.fetch_add(1, Ordering::Relaxed) + 1,
+                num_changed_inputs.load(Ordering::Relaxed),
+            );
             let mut matching_tools = tools_config
-                .tools
-                .iter()
+                .file_tools()
                 .filter(|tool_config| tool_config.matcher.matches(&tool_input.repo_path))
                 .peekable();
             if matching_tools.peek().is_some() {
+                num_checked_files.fetch_add(1, Ordering::Relaxed);
                 // The first matching tool gets its input from the committed file, and any
                 // subsequent matching tool gets its input from the previous matching tool's
                 // output.
-                let mut old_content = vec![];
-                let mut read = store.read_file(&tool_input.repo_path, &tool_input.file_id)?;
-                read.read_to_end(&mut old_content)?;
+                let Some(old_content) = read_file_within_size(
+                    store,
+                    &tool_input.repo_path,
+                    &tool_input.file_id,
+                    max_file_size,
+                )?
+                else {
+                    skipped_tx.send(&tool_input.repo_path).unwrap();
+                    return Ok(());
+                };
+                // The tools whose output differed from their input.
+                let mut changed_by = vec![];
                 let new_content =
-                    matching_tools.fold(old_content.clone(), |prev_content, tool_config| {
-                        match run_tool(&tool_config.command, tool_input, &prev_content) {
-                            Ok(next_content) => next_content,
-                            // TODO: Because the stderr is passed through, this isn't always failing
-                            // silently, but it should do something better will the exit code, tool
-                            // name, etc.
-                            Err(_) => prev_content,
+                    matching_tools.try_fold(old_content.clone(), |prev_content, tool_config| {
+                        if let Some(content_pattern) = &tool_config.content_pattern {
+                            if !content_pattern.is_match(&prev_content) {
+                                return Ok(prev_content);
+                            }
+                        }
+                        if tool_config.mode == ToolMode::Lint {
+                            if let Some(output) = tool_config.run_limited(|| {
+                                run_lint_tool(
+                                    tool_config,
+                                    tool_input,
+                                    workspace_root,
+                                    &prev_content,
+                                )
+                            }) {
+                                lint_tx
+                                    .send((tool_input, tool_config.name.as_str(), output))
+                                    .unwrap();
+                            }
+                            return Ok(prev_content);
+                        }
+                        let result = if tool_config.stabilize {
+                            match tool_config.run_limited(|| {
+                                run_tool_until_stable(
+                                    tool_config,
+                                    tool_input,
+                                    workspace_root,
+                                    &prev_content,
+                                )
+                            }) {
+                                Ok(Some(output)) => Ok(output),
+                                Ok(None) => {
+                                    unstable_tx
+                                        .send((&tool_input.repo_path, tool_config.name.as_str()))
+                                        .unwrap();
+                                    return Ok(prev_content);
+                                }
+                                Err(failure) => Err(failure),
+                            }
+                        } else {
+                            tool_config.run_limited(|| {
+                                run_tool(tool_config, tool_input, workspace_root, &prev_content)
+                            })
+                        };
+                        let result = result.map(|output| {
+                            if !output.stderr.is_empty() {
+                                stderr_tx
+                                    .send((tool_input, tool_config.name.as_str(), output.stderr))
+                                    .unwrap();
+                            }
+                            output.content
+                        });
+                        match result {
+                            // A tool that errored but exited successfully may print
+                            // nothing, which would silently truncate the file.
+                            Ok(next_content)
+                                if next_content.is_empty()
+                                    && !prev_content.is_empty()
+                                    && empty_output != EmptyOutputBehavior::Allow =>
+                            {
+                                Err(tool_config.name.as_str())
+                            }
+                            Ok(next_content) => {
+                                if next_content != prev_content {
+                                    changed_by.push(tool_config.name.as_str());
+                                }
+                                Ok(next_content)
+                            }
+                            Err(failure) => {
+                                failure_tx
+                                    .send((tool_input, tool_config.name.as_str(), failure))
+                                    .unwrap();
+                                Ok(prev_content)
+                            }
                         }
                     });
+                let new_content = match new_content {
+                    Ok(new_content) => new_content,
+                    Err(tool_name) => {
+                        emptied_tx.send((&tool_input.repo_path, tool_name)).unwrap();
+                        return Ok(());
+                    }
+                };
                 if new_content != old_content {
+                    num_changed_inputs.fetch_add(1, Ordering::Relaxed);
+                    for tool_name in changed_by {
+                        changed_tx.send(tool_name).unwrap();
+                    }
+                }
+                if new_content != old_content && dry_run {
+                    unwritten_tx.send(tool_input).unwrap();
+                } else if new_content != old_content {
                     // TODO: send futures back over channel
                     let new_file_id = store
                         .write_file(&tool_input.repo_path, &mut new_content.as_slice())
@@ -359,63 +1283,607 @@ fn fix_file_ids<'a>(
         },
     )?;
     drop(updates_tx);
-    let mut result = HashMap::new();
+    drop(skipped_tx);
+    drop(emptied_tx);
+    drop(unstable_tx);
+    drop(lint_tx);
+    drop(failure_tx);
+    drop(stderr_tx);
+    drop(unwritten_tx);
+    drop(changed_tx);
+    let mut fixed_file_ids = HashMap::new();
     while let Ok((tool_input, new_file_id)) = updates_rx.recv() {
-        result.insert(tool_input, new_file_id);
+        fixed_file_ids.insert(tool_input, new_file_id);
     }
-    Ok(result)
+    // The same path may be skipped for several tool inputs, so count the
+    // inputs before deduplicating the paths.
+    let skipped_paths = skipped_rx.into_iter().collect_vec();
+    let emptied_paths = emptied_rx.into_iter().collect_vec();
+    let num_skipped_files = skipped_paths.len() + emptied_paths.len();
+    let lint_problems = lint_rx
+        .into_iter()
+        .sorted_by_key(|(tool_input, tool_name, _)| {
+            (&tool_input.repo_path, *tool_name, &tool_input.commit)
+        })
+        .collect();
+    let tool_failures = failure_rx
+        .into_iter()
+        .sorted_by_key(|(tool_input, tool_name, _)| {
+            (&tool_input.repo_path, *tool_name, &tool_input.commit)
+        })
+        .collect();
+    let tool_stderrs = stderr_rx
+        .into_iter()
+        .sorted_by_key(|(tool_input, tool_name, _)| {
+            (&tool_input.repo_path, *tool_name, &tool_input.commit)
+        })
+        .collect();
+    Ok(FixedFileIds {
+        fixed_file_ids,
+        skipped_paths: skipped_paths.into_iter().collect(),
+        emptied_paths: emptied_paths.into_iter().collect(),
+        unstable_paths: unstable_rx.into_iter().collect(),
+        num_checked_files: num_checked_files.into_inner(),
+        num_skipped_files,
+        lint_problems,
+        tool_failures,
+        tool_stderrs,
+        unwritten_inputs: unwritten_rx
+            .into_iter()
+            .sorted_by_key(|tool_input| {
+                (
+                    &tool_input.repo_path,
+                    &tool_input.file_id,
+                    &tool_input.commit,
+                )
+            })
+            .collect(),
+        changed_files_per_tool: changed_rx.into_iter().counts().into_iter().collect(),
+    })
 }
 
-/// Runs the `tool_command` to fix the given file content.
+/// Runs the commit-scoped tools once per commit on the files being fixed in
+/// it.
+///
+/// The tools get the content as fixed by the file-scoped tools, which is
+/// looked up in `fixed_file_ids`, and each tool gets the output of the previous
+/// one. Conflicted files and files larger than `max_file_size` are not passed
+/// to the tools, and the large files are reported separately. If a tool fails,
+/// its changes are not applied, and the failure is reported separately. Unless
+/// `empty_output` allows it, a file that a tool empties is left unchanged and
+/// not passed to the remaining tools, and it's reported separately. If
+/// `dry_run` is set, the changed content isn't written. `report_progress` is
+/// called with the number of started commits before each commit is processed.
+#[allow(clippy::too_many_arguments)]
+fn fix_commit_files<'a>(
+    store: &Store,
+    tools_config: &'a ToolsConfig,
+    commits: &'a [Commit],
+    commit_paths: &'a HashMap<CommitId, HashSet<RepoPathBuf>>,
+    fixed_file_ids: &HashMap<&ToolInput, FileId>,
+    uses_commit_variables: bool,
+    max_file_size: u64,
+    empty_output: EmptyOutputBehavior,
+    dry_run: bool,
+    report_progress: &dyn Fn(usize),
+) -> Result<FixedCommitFiles<'a>, CommandError> {
+    let commit_tools = tools_config.commit_tools().collect_vec();
+    let mut fixed_files: HashMap<CommitId, HashMap<RepoPathBuf, FileId>> = HashMap::new();
+    let mut checked_inputs = HashSet::new();
+    let mut changed_inputs = HashSet::new();
+    let mut skipped_paths = BTreeSet::new();
+    let mut emptied_paths = BTreeSet::new();
+    let mut num_skipped_files = 0;
+    let mut tool_failures = vec![];
+    let mut tool_stderrs = vec![];
+    let mut changed_inputs_per_tool: BTreeMap<&str, HashSet<ToolInput>> = BTreeMap::new();
+    let commits = if commit_tools.is_empty() {
+        &[]
+    } else {
+        commits
+    };
+    // Parents are visited first, like when the files to fix are collected.
+    for (i, commit) in commits.iter().rev().enumerate() {
+        report_progress(i + 1);
+        let tool_commit = uses_commit_variables.then(|| ToolCommit::new(commit));
+        let tree = commit.tree()?;
+        let mut files: BTreeMap<&RepoPathBuf, CommitToolFile> = BTreeMap::new();
+        for repo_path in &commit_paths[commit.id()] {
+            if !commit_tools
+                .iter()
+                .any(|tool_config| tool_config.matcher.matches(repo_path))
+            {
+                continue;
+            }
+            let value = tree.path_value(repo_path)?;
+            let Some(Some(TreeValue::File { id, executable: _ })) = value.as_resolved() else {
+                continue;
+            };
+            let tool_input = ToolInput {
+                file_id: id.clone(),
+                repo_path: repo_path.clone(),
+                commit: tool_commit.clone(),
+            };
+            let file_id = fixed_file_ids.get(&tool_input).unwrap_or(id);
+            let Some(content) = read_file_within_size(store, repo_path, file_id, max_file_size)?
+            else {
+                // File-scoped tools have already counted the file as skipped
+                // if they match it.
+                if !tools_config
+                    .file_tools()
+                    .any(|tool_config| tool_config.matcher.matches(repo_path))
+                {
+                    num_skipped_files += 1;
+                }
+                skipped_paths.insert(repo_path);
+                checked_inputs.insert(tool_input);
+                continue;
+            };
+            let file = CommitToolFile {
+                tool_input,
+                old_content: content.clone(),
+                content,
+            };
+            files.insert(repo_path, file);
+        }
+        for tool_config in &commit_tools {
+            let paths = files
+                .keys()
+                .copied()
+                .filter(|repo_path| tool_config.matcher.matches(repo_path))
+                .collect_vec();
+            if paths.is_empty() {
+                continue;
+            }
+            checked_inputs.extend(
+                paths
+                    .iter()
+                    .map(|repo_path| files[repo_path].tool_input.clone()),
+            );
+            let tool_files = paths
+                .iter()
+                .map(|repo_path| (*repo_path, files[repo_path].content.as_slice()))
+                .collect_vec();
+            match tool_config.run_limited(|| {
+                run_commit_tool(&tool_config.command, tool_commit.as_ref(), &tool_files)
+            }) {
+                Ok((new_contents, stderr)) => {
+                    if !stderr.is_empty() {
+                        tool_stderrs.push(stderr);
+                    }
+                    for (repo_path, new_content) in iter::zip(&paths, new_contents) {
+                        let file = files.get_mut(repo_path).unwrap();
+                        // A tool that errored but exited successfully may
+                        // truncate the file.
+                        if new_content.is_empty()
+                            && !file.content.is_empty()
+                            && empty_output != EmptyOutputBehavior::Allow
+                        {
+                            files.remove(repo_path);
+                            emptied_paths.insert((*repo_path, tool_config.name.as_str()));
+                            num_skipped_files += 1;
+                            continue;
+                        }
+                        if new_content != file.content {
+                            changed_inputs_per_tool
+                                .entry(tool_config.name.as_str())
+                                .or_default()
+                                .insert(file.tool_input.clone());
+                            file.content = new_content;
+                        }
+                    }
+                }
+                Err(failure) => {
+                    let paths = paths.iter().map(|&repo_path| repo_path.clone()).collect();
+                    tool_failures.push((commit, paths, tool_config.name.as_str(), failure));
+                }
+            }
+        }
+        for (repo_path, file) in files {
+            if file.content == file.old_content {
+                continue;
+            }
+            changed_inputs.insert(file.tool_input);
+            if !dry_run {
+                let new_file_id = store
+                    .write_file(repo_path, &mut file.content.as_slice())
+                    .block_on()?;
+                fixed_files
+                    .entry(commit.id().clone())
+                    .or_default()
+                    .insert(repo_path.clone(), new_file_id);
+            }
+        }
+    }
+    Ok(FixedCommitFiles {
+        fixed_files,
+        checked_inputs,
+        changed_inputs,
+        skipped_paths,
+        emptied_paths,
+        num_skipped_files,
+        tool_failures,
+        tool_stderrs,
+        changed_files_per_tool: changed_inputs_per_tool
+            .into_iter()
+            .map(|(tool_name, inputs)| (tool_name, inputs.len()))
+            .collect(),
+    })
+}
+
+/// A file passed to the commit-scoped tools.
+struct CommitToolFile {
+    tool_input: ToolInput,
+    /// The content before the commit-scoped tools ran.
+    old_content: Vec<u8>,
+    /// The content as changed by the commit-scoped tools so far.
+    content: Vec<u8>,
+}
+
+/// Reads the content of the file, or returns `None` if it's larger than
+/// `max_file_size`.
+///
+/// The size is checked without reading the content if the backend can tell
+/// it. Otherwise, at most one byte past the limit is read to find out if the
+/// file is too large.
+fn read_file_within_size(
+    store: &Store,
+    repo_path: &RepoPath,
+    file_id: &FileId,
+    max_file_size: u64,
+) -> Result<Option<Vec<u8>>, CommandError> {
+    let known_size = if max_file_size == u64::MAX {
+        None
+    } else {
+        store.file_size(repo_path, file_id)?
+    };
+    if known_size.is_some_and(|size| size > max_file_size) {
+        return Ok(None);
+    }
+    let mut content = vec![];
+    store
+        .read_file(repo_path, file_id)?
+        .take(max_file_size.saturating_add(1))
+        .read_to_end(&mut content)?;
+    if content.len() as u64 > max_file_size {
+        return Ok(None);
+    }
+    Ok(Some(content))
+}
+
+/// Formats the counts of changed files as e.g. `black: 12 files, rustfmt: 3
+/// files`.
+fn format_changed_files_per_tool(changed_files_per_tool: &BTreeMap<&str, usize>) -> String {
+    changed_files_per_tool
+        .iter()
+        .map(|(tool_name, count)| format!("{tool_name}: {count} files"))
+        .join(", ")
+}
+
+/// Runs the tool's command to fix the given file content.
 ///
 /// The `old_content` is assumed to be that of the `tool_input`'s `FileId`, but
 /// this is not verified.
 ///
 /// Returns the new file content, whose value will be the same as `old_content`
-/// unless the command introduced changes. Returns `None` if there were any
-/// failures when starting, stopping, or communicating with the subprocess.
+/// unless the command introduced changes. Returns the exit status and the
+/// standard error of the command if it exited with a non-successful exit code,
+/// or the error if there were any failures when starting, stopping, or
+/// communicating with the subprocess. The standard error of a successful
+/// command is returned along with the new content. In-place tools get the
+/// content in a temporary file written by `write_temp_tool_input()`, and their
+/// standard output is reported along with standard error.
 fn run_tool(
-    tool_command: &CommandNameAndArgs,
+    tool_config: &ToolConfig,
     tool_input: &ToolInput,
+    workspace_root: &Path,
     old_content: &[u8],
-) -> Result<Vec<u8>, ()> {
-    // TODO: Pipe stderr so we can tell the user which commit, file, and tool it is
-    // associated with.
-    let mut vars: HashMap<&str, &str> = HashMap::new();
-    vars.insert("path", tool_input.repo_path.as_internal_file_string());
-    let mut command = tool_command.to_command_with_variables(&vars);
-    tracing::debug!(?command, ?tool_input.repo_path, "spawning fix tool");
-    let mut child = command
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-        .or(Err(()))?;
-    let mut stdin = child.stdin.take().unwrap();
-    let output = std::thread::scope(|s| {
-        s.spawn(move || {
-            stdin.write_all(old_content).ok();
+) -> Result<ToolOutput, ToolFailure> {
+    if tool_config.mode == ToolMode::InPlace {
+        let (_temp_dir, temp_path) = write_temp_tool_input(
+            tool_input,
+            tool_config.temp_extension.as_deref(),
+            old_content,
+        )?;
+        let mut command = self::tool_command(
+            &tool_config.command,
+            &temp_path.to_string_lossy(),
+            workspace_root,
+            tool_input.commit.as_ref(),
+        );
+        let (stdout, stderr) = run_tool_process(&mut command, None, true)?;
+        return Ok(ToolOutput {
+            content: fs::read(&temp_path).map_err(ToolFailure::from_io_error)?,
+            stderr: [stdout, stderr].concat(),
         });
-        Some(child.wait_with_output().or(Err(())))
+    }
+    let (stdout, stderr) =
+        run_file_tool_process(tool_config, tool_input, workspace_root, old_content, false)?;
+    Ok(ToolOutput {
+        content: stdout,
+        stderr,
     })
-    .unwrap()?;
+}
+
+/// Runs the fix or lint tool's command on the given file content.
+///
+/// The content is passed on standard input, or in a temporary file written by
+/// `write_temp_tool_input()` if the tool has `stdin = false`. Returns the
+/// standard output and error of the command like `run_tool_process()`.
+fn run_file_tool_process(
+    tool_config: &ToolConfig,
+    tool_input: &ToolInput,
+    workspace_root: &Path,
+    content: &[u8],
+    report_stdout: bool,
+) -> Result<(Vec<u8>, Vec<u8>), ToolFailure> {
+    if tool_config.stdin {
+        let mut command = self::tool_command(
+            &tool_config.command,
+            tool_input.repo_path.as_internal_file_string(),
+            workspace_root,
+            tool_input.commit.as_ref(),
+        );
+        return run_tool_process(&mut command, Some(content), report_stdout);
+    }
+    let (_temp_dir, temp_path) =
+        write_temp_tool_input(tool_input, tool_config.temp_extension.as_deref(), content)?;
+    let mut command = self::tool_command(
+        &tool_config.command,
+        &temp_path.to_string_lossy(),
+        workspace_root,
+        tool_input.commit.as_ref(),
+    );
+    run_tool_process(&mut command, None, report_stdout)
+}
+
+/// Writes the content to a temporary file with the same name as the file at
+/// `tool_input`'s path, so tools can still tell the file type from its
+/// extension. The extension is replaced with `temp_extension` if specified.
+/// The file is deleted when the returned directory is dropped.
+fn write_temp_tool_input(
+    tool_input: &ToolInput,
+    temp_extension: Option<&str>,
+    content: &[u8],
+) -> Result<(TempDir, PathBuf), ToolFailure> {
+    let temp_dir = tempfile::tempdir().map_err(ToolFailure::from_io_error)?;
+    let (_, file_name) = tool_input
+        .repo_path
+        .split()
+        .expect("fix tool inputs should be files");
+    let mut temp_path = temp_dir.path().join(file_name.as_internal_str());
+    if let Some(extension) = temp_extension {
+        temp_path.set_extension(extension);
+    }
+    fs::write(&temp_path, content).map_err(ToolFailure::from_io_error)?;
+    Ok((temp_dir, temp_path))
+}
+
+/// Runs the commit-scoped in-place `tool_command` in a temporary directory
+/// where the `files` are written at their repo paths.
+///
+/// `$root` is replaced with the path of the temporary directory, which is
+/// also the working directory of the command. Returns the contents of the
+/// files after the command exits in the same order, along with the standard
+/// output and error of the command, and fails like `run_tool_process()`
+/// otherwise. Files that the command deleted are left unchanged.
+fn run_commit_tool(
+    tool_command: &CommandOrShellScript,
+    commit: Option<&ToolCommit>,
+    files: &[(&RepoPathBuf, &[u8])],
+) -> Result<(Vec<Vec<u8>>, Vec<u8>), ToolFailure> {
+    let temp_dir = tempfile::tempdir().map_err(ToolFailure::from_io_error)?;
+    let temp_paths: Vec<PathBuf> = files
+        .iter()
+        .map(|(repo_path, content)| {
+            let temp_path = repo_path
+                .to_fs_path(temp_dir.path())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            fs::create_dir_all(temp_path.parent().unwrap())?;
+            fs::write(&temp_path, content)?;
+            Ok(temp_path)
+        })
+        .try_collect()
+        .map_err(ToolFailure::from_io_error)?;
+    let mut command = self::tool_command(tool_command, "", temp_dir.path(), commit);
+    command.current_dir(temp_dir.path());
+    let (stdout, stderr) = run_tool_process(&mut command, None, true)?;
+    let new_contents = iter::zip(files, &temp_paths)
+        .map(|((_, content), temp_path)| match fs::read(temp_path) {
+            Ok(new_content) => Ok(new_content),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(content.to_vec()),
+            Err(err) => Err(ToolFailure::from_io_error(err)),
+        })
+        .try_collect()?;
+    Ok((new_contents, [stdout, stderr].concat()))
+}
+
+/// Maximum number of times a tool with `stabilize = true` is run on a file.
+const MAX_STABILIZE_RUNS: usize = 5;
+
+/// Runs the `tool_command` on its own output until the output stops changing.
+///
+/// Returns the stable output along with the standard error of all runs, or
+/// `None` if the output still changed after `MAX_STABILIZE_RUNS` runs. Fails if
+/// any of the runs fails.
+fn run_tool_until_stable(
+    tool_config: &ToolConfig,
+    tool_input: &ToolInput,
+    workspace_root: &Path,
+    old_content: &[u8],
+) -> Result<Option<ToolOutput>, ToolFailure> {
+    let mut output = run_tool(tool_config, tool_input, workspace_root, old_content)?;
+    for _ in 1..MAX_STABILIZE_RUNS {
+        let next_output = run_tool(tool_config, tool_input, workspace_root, &output.content)?;
+        output.stderr.extend(next_output.stderr);
+        if next_output.content == output.content {
+            return Ok(Some(output));
+        }
+        output.content = next_output.content;
+    }
+    Ok(None)
+}
+
+/// Runs the lint tool's command on the given file content.
+///
+/// Returns the standard output followed by the standard error of the command
+/// if it exited with a non-successful exit code, or if it couldn't be run.
+fn run_lint_tool(
+    tool_config: &ToolConfig,
+    tool_input: &ToolInput,
+    workspace_root: &Path,
+    content: &[u8],
+) -> Option<Vec<u8>> {
+    run_file_tool_process(tool_config, tool_input, workspace_root, content, true)
+        .err()
+        .map(|failure| failure.stderr)
+}
+
+/// Runs the tool `command`, writing `stdin` to its standard input if
+/// specified.
+///
+/// Returns the standard output and error of the command if it exited
+/// successfully. Returns the exit status and the standard error, preceded by
+/// the standard output if `report_stdout` is set, if the command exited with a
+/// non-successful exit code, or the error if there were any failures when
+/// starting, stopping, or communicating with the subprocess.
+fn run_tool_process(
+    command: &mut Command,
+    stdin: Option<&[u8]>,
+    report_stdout: bool,
+) -> Result<(Vec<u8>, Vec<u8>), ToolFailure> {
+    tracing::debug!(?command, "spawning fix tool");
+    let output = match stdin {
+        Some(content) => command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .and_then(|child| write_stdin_and_wait(child, content)),
+        None => command.stdin(Stdio::null()).output(),
+    }
+    .map_err(ToolFailure::from_io_error)?;
     tracing::debug!(?command, ?output.status, "fix tool exited:");
     if output.status.success() {
-        Ok(output.stdout)
+        Ok((output.stdout, output.stderr))
+    } else if report_stdout {
+        Err(ToolFailure {
+            status: Some(output.status),
+            stderr: [output.stdout, output.stderr].concat(),
+        })
     } else {
-        Err(())
+        Err(ToolFailure {
+            status: Some(output.status),
+            stderr: output.stderr,
+        })
+    }
+}
+
+/// Writes the `content` to the standard input of the `child` while waiting for
+/// it to exit.
+///
+/// The child may exit without reading all of its input, so a broken pipe isn't
+/// an error.
+fn write_stdin_and_wait(mut child: Child, content: &[u8]) -> io::Result<Output> {
+    let mut stdin = child.stdin.take().unwrap();
+    let (write_result, output) = std::thread::scope(|s| {
+        let writer = s.spawn(move || stdin.write_all(content));
+        let output = child.wait_with_output();
+        (writer.join().unwrap(), output)
+    });
+    match write_result {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(err),
+        _ => output,
     }
 }
 
+/// Builds the process to run `tool_command` for the file at `path`.
+///
+/// `$commit_id` and `$change_id` are left as is if `commit` is `None`.
+fn tool_command(
+    tool_command: &CommandOrShellScript,
+    path: &str,
+    workspace_root: &Path,
+    commit: Option<&ToolCommit>,
+) -> Command {
+    let mut vars: HashMap<&str, String> = HashMap::new();
+    vars.insert("path", path.to_owned());
+    vars.insert("root", workspace_root.to_string_lossy().into_owned());
+    if let Some(commit) = commit {
+        vars.insert("commit_id", commit.commit_id.hex());
+        vars.insert("change_id", commit.change_id.reverse_hex());
+    }
+    tool_command.to_command_with_variables(&vars)
+}
+
+/// Formats the `command` as a line that can be pasted into a POSIX shell.
+fn format_command_line(command: &Command) -> String {
+    let envs = command.get_envs().filter_map(|(name, value)| {
+        let value = value?;
+        Some(format!(
+            "{}={}",
+            name.to_string_lossy(),
+            shell_quote(&value.to_string_lossy())
+        ))
+    });
+    let program = shell_quote(&command.get_program().to_string_lossy()).into_owned();
+    let args = command
+        .get_args()
+        .map(|arg| shell_quote(&arg.to_string_lossy()).into_owned());
+    envs.chain([program]).chain(args).join(" ")
+}
+
 /// Represents an entry in the `fix.tools` config table.
 struct ToolConfig {
+    /// The name of the tool, i.e. its key in the config table.
+    name: String,
     /// The command that will be run to fix a matching file.
-    command: CommandNameAndArgs,
+    command: CommandOrShellScript,
     /// The matcher that determines if this tool matches a file.
     matcher: Box<dyn Matcher>,
+    /// If set, the tool only runs on file content that matches this.
+    content_pattern: Option<regex::bytes::Regex>,
     /// Whether the tool is enabled
     enabled: bool,
-    // TODO: Store the `name` field here and print it with the command's stderr, to clearly
-    // associate any errors/warnings with the tool and its configuration entry.
+    /// Limits the number of concurrent invocations of the tool, if set.
+    max_concurrency: Option<NonZeroUsize>,
+    /// Thread pool with `max_concurrency` threads that runs the invocations of
+    /// the tool. It's created on first use.
+    thread_pool: OnceCell<rayon::ThreadPool>,
+    /// Whether to run the tool on its own output until it stops changing.
+    stabilize: bool,
+    /// Whether the tool reads the file content from standard input, or from a
+    /// temporary file passed as `$path`.
+    stdin: bool,
+    /// Replaces the extension of the temporary file passed to the tool, if set.
+    temp_extension: Option<String>,
+    /// Whether the tool fixes files or only reports problems.
+    mode: ToolMode,
+    /// Whether the tool is run per file or per commit.
+    scope: ToolScope,
+    /// Tools with lower priority run first on the same file.
+    priority: i32,
+}
+
+impl ToolConfig {
+    /// Runs `op`, which invokes the tool, in the tool's own thread pool if its
+    /// concurrency is limited.
+    ///
+    /// Unlike blocking on a lock, the calling rayon worker thread keeps
+    /// processing other inputs while waiting for the tool's thread pool.
+    fn run_limited<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        let Some(max_concurrency) = self.max_concurrency else {
+            return op();
+        };
+        let thread_pool = self.thread_pool.get_or_init(|| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(max_concurrency.get())
+                .thread_name(|i| format!("jj-fix-tool-{i}"))
+                .build()
+                .expect("should be able to spawn threads for the fix tool")
+        });
+        thread_pool.install(op)
+    }
 }
 
 /// Represents the `fix.tools` config table.
@@ -425,33 +1893,210 @@ struct ToolsConfig {
     tools: Vec<ToolConfig>,
 }
 
+impl ToolsConfig {
+    /// Keeps only the tools named in `names`. Fails if any of the names isn't
+    /// an enabled tool.
+    fn select(&mut self, names: &[impl AsRef<str>]) -> Result<(), CommandError> {
+        for name in names {
+            let name = name.as_ref();
+            if !self.tools.iter().any(|tool| tool.name == name) {
+                return Err(user_error_with_hint(
+                    format!("No enabled tool named `{name}` in `fix.tools`"),
+                    format!(
+                        "The enabled tools are: {}",
+                        self.tools.iter().map(|tool| &tool.name).join(", ")
+                    ),
+                ));
+            }
+        }
+        self.tools
+            .retain(|tool| names.iter().any(|name| name.as_ref() == tool.name));
+        Ok(())
+    }
+
+    /// Returns the tools that are run once per file content.
+    fn file_tools(&self) -> impl Iterator<Item = &ToolConfig> {
+        self.tools
+            .iter()
+            .filter(|tool_config| tool_config.scope == ToolScope::File)
+    }
+
+    /// Returns the tools that are run once per commit.
+    fn commit_tools(&self) -> impl Iterator<Item = &ToolConfig> {
+        self.tools
+            .iter()
+            .filter(|tool_config| tool_config.scope == ToolScope::Commit)
+    }
+
+    /// Returns true if any tool needs to know the commit it is run for.
+    fn uses_commit_variables(&self) -> bool {
+        self.tools.iter().any(|tool_config| {
+            tool_config.command.uses_variable("commit_id")
+                || tool_config.command.uses_variable("change_id")
+        })
+    }
+}
+
 /// Simplifies deserialization of the config values while building a ToolConfig.
 #[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct RawToolConfig {
-    command: CommandNameAndArgs,
+    command: CommandOrShellScript,
     patterns: Vec<String>,
+    #[serde(default)]
+    content_pattern: Option<String>,
     #[serde(default = "default_tool_enabled")]
     enabled: bool,
+    #[serde(default)]
+    max_concurrency: Option<NonZeroUsize>,
+    #[serde(default)]
+    stabilize: bool,
+    #[serde(default = "default_tool_stdin")]
+    stdin: bool,
+    #[serde(default)]
+    temp_extension: Option<String>,
+    #[serde(default)]
+    mode: ToolMode,
+    #[serde(default)]
+    scope: ToolScope,
+    #[serde(default)]
+    priority: i32,
 }
 
 fn default_tool_enabled() -> bool {
     true
 }
 
-/// Parses the `fix.tools` config table.
+fn default_tool_stdin() -> bool {
+    true
+}
+
+/// Builds a matcher for the files that would be passed to the enabled tools,
+/// or only to the tool named `tool_name` if specified.
+pub(crate) fn get_tools_matcher(
+    ui: &mut Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    tool_name: Option<&str>,
+) -> Result<Box<dyn Matcher>, CommandError> {
+    let mut tools_config = get_tools_config(ui, workspace_command)?;
+    if let Some(name) = tool_name {
+        tools_config.select(&[name])?;
+    }
+    let matcher = tools_config
+        .tools
+        .into_iter()
+        .map(|tool| tool.matcher)
+        .reduce(|matcher1, matcher2| Box::new(UnionMatcher::new(matcher1, matcher2)))
+        .expect("at least one tool should be enabled");
+    Ok(matcher)
+}
+
+/// Path of the tracked tools file, relative to the repo root.
+const REPO_TOOLS_CONFIG_PATH: &str = ".jj-fix-tools.toml";
+
+/// Returns the config with the tool definitions from the tracked tools file in
+/// the working-copy commit added under `fix.tools`, if the file exists and
+/// `fix.trust-repo-tools` is enabled.
+///
+/// The file contains one table per tool, in the same format as the entries of
+/// the `fix.tools` config table. It's layered above the user config and below
+/// the repo config, so the fields it sets override the user's fields of the
+/// same tool, and the other fields are kept. Since the tools run commands from
+/// the repo content, the file is ignored unless the user opted in.
+fn config_with_repo_tools(
+    ui: &Ui,
+    workspace_command: &WorkspaceCommandHelper,
+) -> Result<StackedConfig, CommandError> {
+    let settings = workspace_command.settings();
+    let mut config = settings.config().clone();
+    let Some(wc_commit_id) = workspace_command.get_wc_commit_id() else {
+        return Ok(config);
+    };
+    let store = workspace_command.repo().store();
+    let repo_path = RepoPath::from_internal_string(REPO_TOOLS_CONFIG_PATH);
+    let tree = store.get_commit(wc_commit_id)?.tree()?;
+    let Ok(Some(TreeValue::File { id, executable: _ })) =
+        tree.path_value(repo_path)?.into_resolved()
+    else {
+        return Ok(config);
+    };
+    if !settings.get_bool("fix.trust-repo-tools")? {
+        writeln!(
+            ui.hint_default(),
+            "Ignoring the tools in `{REPO_TOOLS_CONFIG_PATH}`. Set `fix.trust-repo-tools = true` \
+             to run them."
+        )?;
+        return Ok(config);
+    }
+    let mut text = String::new();
+    store.read_file(repo_path, &id)?.read_to_string(&mut text)?;
+    let path = workspace_command
+        .workspace_root()
+        .join(repo_path.to_fs_path_unchecked(Path::new("")));
+    let tools_layer = ConfigLayer::parse(ConfigSource::Repo, &text).map_err(|err| match err {
+        ConfigLoadError::Parse { error, .. } => ConfigLoadError::Parse {
+            error,
+            source_path: Some(path.clone()),
+        },
+        err => err,
+    })?;
+    let mut layer = ConfigLayer::empty(ConfigSource::Repo);
+    layer.path = Some(path);
+    let tools_table = layer
+        .ensure_table(["fix", "tools"])
+        .expect("new layer should have no values");
+    for (name, item) in tools_layer.data.iter() {
+        tools_table.insert(name, item.clone());
+    }
+    // Insert the layer below the repo config layers.
+    let repo_layers = config.layers_for(ConfigSource::Repo).to_vec();
+    config.remove_layers(ConfigSource::Repo);
+    config.add_layer(layer);
+    config.extend_layers(repo_layers);
+    Ok(config)
+}
+
+/// Asks for confirmation if `--include-unchanged-files` would fix more than
+/// `max_unchanged_files` files. A limit of 0 means no limit.
+fn check_unchanged_files_count(
+    ui: &Ui,
+    num_files: usize,
+    max_unchanged_files: usize,
+) -> Result<(), CommandError> {
+    if max_unchanged_files == 0 || num_files <= max_unchanged_files {
+        return Ok(());
+    }
+    if ui.prompt_yes_no(&format!("Fix {num_files} files?"), Some(false))? {
+        return Ok(());
+    }
+    Err(user_error_with_hint(
+        format!(
+            "Refusing to fix {num_files} files, which is more than `fix.max-unchanged-files` \
+             ({max_unchanged_files})"
+        ),
+        "Restrict the files to fix with a fileset, or pass `--yes` to fix them anyway.",
+    ))
+}
+
+/// Parses the `fix.tools` config table, including the tools from the tracked
+/// tools file.
 ///
 /// Fails if any of the commands or patterns are obviously unusable, but does
 /// not check for issues that might still occur later like missing executables.
 /// This is a place where we could fail earlier in some cases, though.
-fn get_tools_config(ui: &mut Ui, settings: &UserSettings) -> Result<ToolsConfig, CommandError> {
-    let mut tools: Vec<ToolConfig> = settings
+fn get_tools_config(
+    ui: &mut Ui,
+    workspace_command: &WorkspaceCommandHelper,
+) -> Result<ToolsConfig, CommandError> {
+    let config = config_with_repo_tools(ui, workspace_command)?;
+    let mut tools: Vec<ToolConfig> = config
         .table_keys("fix.tools")
         // Sort keys early so errors are deterministic.
         .sorted()
         .map(|name| -> Result<ToolConfig, CommandError> {
+            let tool: RawToolConfig = config.get(["fix", "tools", name])?;
+            let heading = format!("In `fix.tools.{name}`");
             let mut diagnostics = FilesetDiagnostics::new();
-            let tool: RawToolConfig = settings.get(["fix", "tools", name])?;
             let expression = FilesetExpression::union_all(
                 tool.patterns
                     .iter()
@@ -467,14 +2112,58 @@ fn get_tools_config(ui: &mut Ui, settings: &UserSettings) -> Result<ToolsConfig,
                     })
                     .try_collect()?,
             );
-            print_parse_diagnostics(ui, &format!("In `fix.tools.{name}`"), &diagnostics)?;
+            print_parse_diagnostics(ui, &heading, &diagnostics)?;
+            let content_pattern = tool
+                .content_pattern
+                .map(|pattern| regex::bytes::Regex::new(&pattern))
+                .transpose()
+                .map_err(|err| {
+                    config_error_with_message(format!("{heading}: Invalid `content-pattern`"), err)
+                })?;
+            if tool.scope == ToolScope::Commit {
+                if tool.mode != ToolMode::InPlace {
+                    return Err(config_error(format!(
+                        "{heading}: `scope = \"commit\"` requires `mode = \"in-place\"`"
+                    )));
+                }
+                if tool.command.uses_variable("path") {
+                    return Err(config_error(format!(
+                        "{heading}: `$path` can't be used with `scope = \"commit\"`"
+                    )));
+                }
+                if content_pattern.is_some() {
+                    return Err(config_error(format!(
+                        "{heading}: `content-pattern` can't be used with `scope = \"commit\"`"
+                    )));
+                }
+                if tool.stabilize {
+                    return Err(config_error(format!(
+                        "{heading}: `stabilize` can't be used with `scope = \"commit\"`"
+                    )));
+                }
+            }
+            tool.command
+                .check_platform_support()
+                .map_err(|err| config_error(format!("{heading}: {err}")))?;
             Ok(ToolConfig {
+                name: name.to_owned(),
                 command: tool.command,
                 matcher: expression.to_matcher(),
+                content_pattern,
                 enabled: tool.enabled,
+                max_concurrency: tool.max_concurrency,
+                thread_pool: OnceCell::new(),
+                stabilize: tool.stabilize,
+                stdin: tool.stdin,
+                temp_extension: tool.temp_extension,
+                mode: tool.mode,
+                scope: tool.scope,
+                priority: tool.priority,
             })
         })
         .try_collect()?;
+    // The sort is stable, so tools with the same priority stay sorted by name.
+    tools.sort_by_key(|tool| tool.priority);
     if tools.is_empty() {
         return Err(config_error("No `fix.tools` are configured"));
     }
@@ -48,6 +48,9 @@ pub(crate) struct ShowArgs {
     ///     https://jj-vcs.github.io/jj/latest/templates/#commit-keywords
     #[arg(long, short = 'T', add = ArgValueCandidates::new(complete::template_aliases))]
     template: Option<String>,
+    /// Don't exclude the files matching `ui.diff.exclude`
+    #[arg(long)]
+    no_exclude: bool,
     #[command(flatten)]
     format: DiffFormatArgs,
 }
@@ -65,7 +68,10 @@ pub(crate) fn cmd_show(
         None => workspace_command.settings().get_string("templates.show")?,
     };
     let template = workspace_command.parse_commit_template(ui, &template_string)?;
-    let diff_renderer = workspace_command.diff_renderer_for(&args.format)?;
+    let mut diff_renderer = workspace_command.diff_renderer_for(ui, &args.format)?;
+    if args.no_exclude {
+        diff_renderer = diff_renderer.with_excluded_paths(None);
+    }
     ui.request_pager();
     let mut formatter = ui.stdout_formatter();
     let formatter = formatter.as_mut();
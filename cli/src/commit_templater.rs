@@ -1852,6 +1852,8 @@ fn builtin_tree_diff_methods<'repo>() -> CommitTemplateBuildMethodFnMap<'repo, T
                             tree_diff,
                             path_converter,
                             &options,
+                            None,
+                            None,
                             conflict_marker_style,
                         )
                     })
@@ -1892,6 +1894,9 @@ fn builtin_tree_diff_methods<'repo>() -> CommitTemplateBuildMethodFnMap<'repo, T
                             store,
                             tree_diff,
                             &options,
+                            None,
+                            None,
+                            None,
                             conflict_marker_style,
                         )
                     })
@@ -1922,8 +1927,15 @@ fn builtin_tree_diff_methods<'repo>() -> CommitTemplateBuildMethodFnMap<'repo, T
             let out_property = (self_property, width_property).and_then(move |(diff, width)| {
                 let store = diff.from_tree.store();
                 let tree_diff = diff.diff_stream();
-                let stats = DiffStats::calculate(store, tree_diff, &options, conflict_marker_style)
-                    .block_on()?;
+                let stats = DiffStats::calculate(
+                    store,
+                    tree_diff,
+                    &options,
+                    None,
+                    None,
+                    conflict_marker_style,
+                )
+                .block_on()?;
                 Ok(DiffStatsFormatted {
                     stats,
                     path_converter,
@@ -2115,6 +2127,7 @@ impl Template for DiffStatsFormatted<'_> {
         diff_util::show_diff_stats(
             formatter.as_mut(),
             &self.stats,
+            &diff_util::DiffStatOptions::default(),
             self.path_converter,
             self.width,
         )
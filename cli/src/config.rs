@@ -647,6 +647,16 @@ impl CommandNameAndArgs {
         }
     }
 
+    /// Returns true if any argument refers to the variable `$name`.
+    pub fn uses_variable(&self, name: &str) -> bool {
+        let (_, args) = self.split_name_and_args();
+        args.iter().any(|arg| {
+            VARIABLE_REGEX
+                .captures_iter(arg)
+                .any(|caps| &caps[1] == name)
+        })
+    }
+
     /// Returns process builder configured with this.
     pub fn to_command(&self) -> Command {
         let empty: HashMap<&str, &str> = HashMap::new();
@@ -691,6 +701,80 @@ impl fmt::Display for CommandNameAndArgs {
     }
 }
 
+/// Command specified by config, or a script to be run by the platform shell.
+///
+/// Only settings that document shell script support use this type, so that
+/// e.g. editors and pagers can't be accidentally configured as shell scripts.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize)]
+#[serde(untagged)]
+pub enum CommandOrShellScript {
+    Command(CommandNameAndArgs),
+    /// Script to be run by the platform shell (`sh -c` or `cmd /C`).
+    ///
+    /// Interpolated variables are quoted for a POSIX shell, but the script
+    /// itself is passed to the shell as is. Since values can't be safely
+    /// quoted for `cmd`, variables can't be used on Windows.
+    Shell {
+        shell: String,
+    },
+}
+
+impl CommandOrShellScript {
+    /// Returns true if any argument refers to the variable `$name`.
+    pub fn uses_variable(&self, name: &str) -> bool {
+        match self {
+            CommandOrShellScript::Command(command) => command.uses_variable(name),
+            CommandOrShellScript::Shell { shell } => VARIABLE_REGEX
+                .captures_iter(shell)
+                .any(|caps| &caps[1] == name),
+        }
+    }
+
+    /// Returns an error if the command can't be run on this platform.
+    pub fn check_platform_support(&self) -> Result<(), String> {
+        match self {
+            CommandOrShellScript::Shell { shell }
+                if cfg!(windows) && VARIABLE_REGEX.is_match(shell) =>
+            {
+                Err("Variables can't be used in shell scripts on Windows".to_owned())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns process builder configured with this after interpolating
+    /// variables into the arguments or the script.
+    pub fn to_command_with_variables<V: AsRef<str>>(
+        &self,
+        variables: &HashMap<&str, V>,
+    ) -> Command {
+        match self {
+            CommandOrShellScript::Command(command) => command.to_command_with_variables(variables),
+            CommandOrShellScript::Shell { shell } => {
+                let (name, flag) = if cfg!(windows) {
+                    ("cmd", "/C")
+                } else {
+                    ("sh", "-c")
+                };
+                let mut cmd = Command::new(name);
+                cmd.arg(flag);
+                // The substituted values must not be interpreted by the shell.
+                cmd.arg(interpolate_shell_variables(shell, variables));
+                cmd
+            }
+        }
+    }
+}
+
+impl fmt::Display for CommandOrShellScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandOrShellScript::Command(command) => write!(f, "{command}"),
+            CommandOrShellScript::Shell { shell } => write!(f, "{shell}"),
+        }
+    }
+}
+
 // Not interested in $UPPER_CASE_VARIABLES
 static VARIABLE_REGEX: once_cell::sync::Lazy<Regex> =
     once_cell::sync::Lazy::new(|| Regex::new(r"\$([a-z0-9_]+)\b").unwrap());
@@ -715,6 +799,37 @@ pub fn interpolate_variables<V: AsRef<str>>(
         .collect()
 }
 
+/// Interpolates variables into the shell `script`, quoting the substituted
+/// values so they are passed to the command as single words.
+///
+/// The values are quoted for a POSIX shell, so this must not be used for
+/// `cmd` scripts.
+fn interpolate_shell_variables<V: AsRef<str>>(
+    script: &str,
+    variables: &HashMap<&str, V>,
+) -> String {
+    VARIABLE_REGEX
+        .replace_all(script, |caps: &Captures| {
+            let name = &caps[1];
+            if let Some(subst) = variables.get(name) {
+                shell_quote(subst.as_ref()).into_owned()
+            } else {
+                caps[0].to_owned()
+            }
+        })
+        .into_owned()
+}
+
+/// Quotes `word` for a POSIX shell if it contains any special characters.
+pub fn shell_quote(word: &str) -> Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "%+,-./:=@_".contains(c);
+    if !word.is_empty() && word.chars().all(is_safe) {
+        Cow::Borrowed(word)
+    } else {
+        Cow::Owned(format!("'{}'", word.replace('\'', r"'\''")))
+    }
+}
+
 /// Return all variable names found in the args, without the dollar sign
 pub fn find_all_variables(args: &[String]) -> impl Iterator<Item = &str> {
     let regex = &*VARIABLE_REGEX;
@@ -897,6 +1012,57 @@ mod tests {
         assert_eq!(args, ["-nw"].as_ref());
     }
 
+    #[test]
+    fn test_command_args_shell() {
+        let mut config = StackedConfig::empty();
+        config.add_layer(
+            ConfigLayer::parse(
+                ConfigSource::User,
+                indoc! {"
+                    shell = { shell = 'black - | isort --filename $path -' }
+                    array = ['emacs', '$path']
+                "},
+            )
+            .unwrap(),
+        );
+
+        let command_args: CommandOrShellScript = config.get("shell").unwrap();
+        assert_eq!(
+            command_args,
+            CommandOrShellScript::Shell {
+                shell: "black - | isort --filename $path -".to_owned()
+            }
+        );
+        assert_eq!(
+            command_args.to_string(),
+            "black - | isort --filename $path -"
+        );
+        if cfg!(windows) {
+            assert!(command_args.check_platform_support().is_err());
+        } else {
+            assert!(command_args.check_platform_support().is_ok());
+            let variables = hashmap! { "path" => "it's; rm" };
+            let command = command_args.to_command_with_variables(&variables);
+            let args = command.get_args().collect_vec();
+            assert_eq!(command.get_program(), "sh");
+            assert_eq!(args, ["-c", r"black - | isort --filename 'it'\''s; rm' -"]);
+        }
+
+        // Other commands can't be shell scripts.
+        assert!(config.get::<CommandNameAndArgs>("shell").is_err());
+
+        // Arrays are still accepted.
+        let command_args: CommandOrShellScript = config.get("array").unwrap();
+        assert_eq!(
+            command_args,
+            CommandOrShellScript::Command(CommandNameAndArgs::Vec(NonEmptyCommandArgsVec(
+                ["emacs", "$path"].map(|s| s.to_owned()).to_vec()
+            )))
+        );
+        assert!(command_args.uses_variable("path"));
+        assert!(command_args.check_platform_support().is_ok());
+    }
+
     #[test]
     fn test_resolved_config_values_empty() {
         let config = StackedConfig::empty();
@@ -13,17 +13,30 @@
 // limitations under the License.
 
 use std::borrow::Borrow;
+use std::borrow::Cow;
 use std::cmp::max;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
 use std::io;
+use std::io::Read as _;
+use std::io::Write as _;
 use std::iter;
 use std::mem;
 use std::ops::Range;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use bstr::BStr;
+use bstr::BString;
+use bstr::ByteSlice as _;
 use futures::executor::block_on_stream;
 use futures::stream::BoxStream;
+use futures::try_join;
+use futures::Stream;
 use futures::StreamExt as _;
 use futures::TryStreamExt as _;
 use itertools::Itertools;
@@ -31,37 +44,55 @@ use jj_lib::backend::BackendError;
 use jj_lib::backend::BackendResult;
 use jj_lib::backend::CommitId;
 use jj_lib::backend::CopyRecord;
+use jj_lib::backend::FileId;
 use jj_lib::backend::TreeValue;
 use jj_lib::commit::Commit;
 use jj_lib::config::ConfigGetError;
 use jj_lib::config::ConfigGetResultExt as _;
 use jj_lib::conflicts::materialize_merge_result_to_bytes;
+use jj_lib::conflicts::materialize_tree_value;
 use jj_lib::conflicts::materialized_diff_stream;
 use jj_lib::conflicts::ConflictMarkerStyle;
 use jj_lib::conflicts::MaterializedTreeDiffEntry;
 use jj_lib::conflicts::MaterializedTreeValue;
+use jj_lib::conflicts::MIN_CONFLICT_MARKER_LEN;
+use jj_lib::content_hash::blake2b_hash;
+use jj_lib::content_hash::ContentHash;
+use jj_lib::content_hash::DigestUpdate;
 use jj_lib::copies::CopiesTreeDiffEntry;
 use jj_lib::copies::CopiesTreeDiffEntryPath;
 use jj_lib::copies::CopyOperation;
 use jj_lib::copies::CopyRecords;
+use jj_lib::diff::count_changed_lines;
 use jj_lib::diff::find_line_ranges;
+use jj_lib::diff::CompareBytes;
 use jj_lib::diff::CompareBytesExactly;
 use jj_lib::diff::CompareBytesIgnoreAllWhitespace;
+use jj_lib::diff::CompareBytesIgnoreCrAtEol;
 use jj_lib::diff::CompareBytesIgnoreWhitespaceAmount;
 use jj_lib::diff::Diff;
 use jj_lib::diff::DiffHunk;
 use jj_lib::diff::DiffHunkContentVec;
+use jj_lib::diff::DiffHunkIntoIter;
 use jj_lib::diff::DiffHunkKind;
 use jj_lib::files::DiffLineHunkSide;
 use jj_lib::files::DiffLineIterator;
 use jj_lib::files::DiffLineNumber;
+use jj_lib::fileset::FilePattern;
+use jj_lib::fileset::FilesetExpression;
+use jj_lib::matchers::DifferenceMatcher;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::matchers::FilesMatcher;
 use jj_lib::matchers::Matcher;
+use jj_lib::merge::Merge;
 use jj_lib::merge::MergedTreeValue;
 use jj_lib::merged_tree::MergedTree;
+use jj_lib::merged_tree::TreeDiffEntry;
 use jj_lib::object_id::ObjectId;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::InvalidRepoPathError;
 use jj_lib::repo_path::RepoPath;
+use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::repo_path::RepoPathUiConverter;
 use jj_lib::rewrite::rebase_to_dest_parent;
 use jj_lib::settings::UserSettings;
@@ -71,7 +102,10 @@ use thiserror::Error;
 use tracing::instrument;
 use unicode_width::UnicodeWidthStr as _;
 
+use crate::cli_util::short_commit_hash;
+use crate::command_error::CommandError;
 use crate::config::CommandNameAndArgs;
+use crate::formatter::FormatRecorder;
 use crate::formatter::Formatter;
 use crate::merge_tools;
 use crate::merge_tools::generate_diff;
@@ -85,15 +119,46 @@ use crate::ui::Ui;
 
 #[derive(clap::Args, Clone, Debug)]
 #[command(next_help_heading = "Diff Formatting Options")]
-#[command(group(clap::ArgGroup::new("short-format").args(&["summary", "stat", "types", "name_only"])))]
-#[command(group(clap::ArgGroup::new("long-format").args(&["git", "color_words", "tool"])))]
+#[command(group(clap::ArgGroup::new("short-format").args(&["stat", "shortstat", "types", "name_only", "metadata_only", "json_stat", "numstat"])))]
+#[command(group(clap::ArgGroup::new("long-format").args(&["git", "color_words", "json", "tool"])))]
+#[command(group(clap::ArgGroup::new("sortable-stat").args(&["stat", "json_stat", "numstat"])))]
 pub struct DiffFormatArgs {
-    /// For each path, show only whether it was modified, added, or deleted
-    #[arg(long, short)]
+    /// For each path, show only whether it was modified, added, deleted, or
+    /// conflicted
+    ///
+    /// With `--stat`, the created, deleted, renamed, and copied files and the
+    /// changes of file types are listed after the histogram instead.
+    #[arg(
+        long,
+        short,
+        conflicts_with_all = ["shortstat", "types", "name_only", "metadata_only", "json_stat", "numstat"],
+    )]
     pub summary: bool,
     /// Show a histogram of the changes
     #[arg(long)]
     pub stat: bool,
+    /// Show the changed lines as a percentage of the file's line count
+    ///
+    /// The changed lines are the added or the removed lines, whichever are
+    /// more, so a line modified in place counts once. The percentage is
+    /// relative to the new content, or to the old content if the file was
+    /// deleted.
+    #[arg(long, requires = "stat")]
+    pub fraction: bool,
+    /// Lay out the histogram for a terminal N columns wide
+    ///
+    /// Defaults to the width of the terminal.
+    #[arg(long, value_name = "N", requires = "stat")]
+    stat_width: Option<usize>,
+    /// Sort the files of `--stat`, `--numstat`, and `--json-stat` by KEY
+    ///
+    /// The counts are sorted in descending order, and files with equal counts
+    /// are sorted by path.
+    #[arg(long, value_name = "KEY", requires = "sortable-stat")]
+    stat_sort: Option<DiffStatSort>,
+    /// Show only the total number of changed files, insertions, and deletions
+    #[arg(long)]
+    pub shortstat: bool,
     /// For each path, show only its type before and after
     ///
     /// The diff is shown as two letters. The first letter indicates the type
@@ -103,24 +168,65 @@ pub struct DiffFormatArgs {
     /// Git submodule.
     #[arg(long)]
     pub types: bool,
+    /// Show the full type names with `--types`, e.g. `regular file ->
+    /// symlink`
+    #[arg(long, requires = "types")]
+    pub long: bool,
     /// For each path, show only its path
     ///
     /// Typically useful for shell commands like:
     ///    `jj diff -r @- --name-only | xargs perl -pi -e's/OLD/NEW/g`
     #[arg(long)]
     pub name_only: bool,
+    /// For each path, show only changes to its type and file mode
+    ///
+    /// File contents aren't read. Each line shows the type before and after,
+    /// using the letters of `--types`, and the Git file mode before and after.
+    /// Paths whose type and mode are unchanged are omitted.
+    #[arg(long)]
+    pub metadata_only: bool,
+    /// Show one JSON object per changed path
+    ///
+    /// Each line of the output is an object with the fields `path`, `status`,
+    /// `old`, `new`, `binary`, `added`, and `removed`, plus `source_path` for
+    /// copies and renames. `old` and `new` describe the type, Git file mode,
+    /// and object ID of each side, or are null if the path is absent. Conflicts
+    /// are listed by their terms instead of being materialized, and their
+    /// lines aren't counted.
+    #[arg(long)]
+    pub json_stat: bool,
+    /// Show the exact numbers of added and removed lines per path
+    ///
+    /// Each line has the number of added lines, the number of removed lines,
+    /// and the path, separated by tabs. The counts of binary files are shown
+    /// as `-`. The last line has the total counts and an empty path.
+    #[arg(long)]
+    pub numstat: bool,
     /// Show a Git-format diff
     #[arg(long)]
     pub git: bool,
     /// Show a word-level diff with changes indicated only by color
     #[arg(long)]
     pub color_words: bool,
+    /// Show one JSON object per hunk
+    ///
+    /// Each line of the output is an object with the fields `path`,
+    /// `old_start`, `old_lines`, `new_start`, `new_lines`, `removed`, and
+    /// `added`. The line numbers are the same as in the `--git` hunk header.
+    #[arg(long)]
+    pub json: bool,
     /// Generate diff by external command
     #[arg(long)]
     pub tool: Option<String>,
     /// Number of lines of context to show
     #[arg(long)]
     context: Option<usize>,
+    /// Show added files first, then removed files, then modified files
+    ///
+    /// Renamed and copied files are shown as modified. Each group is preceded
+    /// by a header unless `diff.group-by-status.headers` is set to false.
+    #[arg(long)]
+    pub group_by_status: bool,
 
     // Short flags are set by command to avoid future conflicts.
     /// Ignore whitespace when comparing lines.
@@ -129,6 +235,95 @@ pub struct DiffFormatArgs {
     /// Ignore changes in amount of whitespace when comparing lines.
     #[arg(long, conflicts_with = "ignore_all_space")] // short = 'b'
     ignore_space_change: bool,
+    /// Ignore carriage returns at the end of lines when comparing lines.
+    #[arg(long)]
+    ignore_cr_at_eol: bool,
+    /// Ignore changes whose lines are all blank.
+    #[arg(long)]
+    ignore_blank_lines: bool,
+    /// Show line numbers in Git-format and color-words diffs
+    ///
+    /// In Git-format diffs, the number in the new file is shown before context
+    /// and added lines, and the number in the old file before removed lines.
+    /// The output is meant for viewing only and can't be applied as a patch.
+    /// Color-words diffs show line numbers unless
+    /// `diff.color-words.line-numbers` is turned off.
+    #[arg(long)]
+    line_numbers: bool,
+    /// Hide line numbers in Git-format and color-words diffs
+    #[arg(long, conflicts_with = "line_numbers")]
+    no_line_numbers: bool,
+    /// Show the nearest preceding function line after each hunk header in
+    /// Git-format diffs, like `@@ -1,2 +1,2 @@ fn foo() {`
+    ///
+    /// Function lines are lines of the old content that match the
+    /// `diff.git.function-pattern` regex.
+    #[arg(long)]
+    show_function_context: bool,
+    /// Color lines that were moved within a file differently in Git-format
+    /// diffs
+    ///
+    /// With `plain`, a removed line is shown as moved if an identical line is
+    /// added in another change of the same file, and vice versa. Blank lines
+    /// are never shown as moved.
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "plain",
+    )]
+    color_moved: Option<ColorMovedMode>,
+    /// Show the full object IDs instead of abbreviated ones in the `index`
+    /// lines of Git-format diffs
+    #[arg(long)]
+    full_index: bool,
+    /// Omit the `index`, mode, and copy/rename lines from Git-format diffs
+    ///
+    /// Only the `diff --git`, `---`, and `+++` lines precede the hunks of each
+    /// file, plus the `new file mode` and `deleted file mode` lines which `git
+    /// apply` needs. Copies and renames are shown as added and deleted files,
+    /// and files without content changes are omitted, so the output can still
+    /// be applied as a patch.
+    #[arg(long)]
+    minimal_headers: bool,
+    /// Show only the first and last N changed or context lines of each file
+    /// in Git-format diffs
+    ///
+    /// Every file whose diff is longer than 2*N lines is shortened regardless
+    /// of the file size, and the omitted part is replaced with a marker line.
+    #[arg(long, value_name = "N")]
+    preview: Option<usize>,
+    /// Show copies and renames in Git-format diffs only if at least PERCENT
+    /// of the file is unchanged
+    ///
+    /// Other copies and renames are shown as added and deleted files. Copies
+    /// and renames are detected by the backend, which may not report ones
+    /// below its own threshold. The default is 50.
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        num_args = 0..=1,
+        default_missing_value = "50",
+        value_parser = clap::value_parser!(u32).range(0..=100),
+    )]
+    find_renames: Option<u32>,
+    /// Show copies and renames in Git-format diffs as added and deleted files
+    #[arg(long, conflicts_with = "find_renames")]
+    no_renames: bool,
+    /// Treat all files as text
+    ///
+    /// Files that look like binary files are diffed line by line instead of
+    /// being reported as differing binary files.
+    #[arg(long)]
+    text: bool,
+    /// Show the diff in the opposite direction
+    ///
+    /// The diff goes from the new contents to the old contents, which is
+    /// useful for e.g. creating a patch that reverts the changes. Renames are
+    /// shown in reverse, and copies are shown as deleted files.
+    #[arg(long)]
+    pub reverse: bool,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -136,19 +331,55 @@ pub enum DiffFormat {
     // Non-trivial parameters are boxed in order to keep the variants small
     Summary,
     Stat(Box<DiffStatOptions>),
+    ShortStat(Box<DiffStatOptions>),
     Types,
+    LongTypes,
     NameOnly,
+    MetadataOnly,
+    JsonStat(Box<DiffStatOptions>),
+    NumStat(Box<DiffStatOptions>),
     Git(Box<UnifiedDiffOptions>),
     ColorWords(Box<ColorWordsDiffOptions>),
+    Json(Box<UnifiedDiffOptions>),
     Tool(Box<ExternalMergeTool>),
 }
 
+impl DiffFormat {
+    /// Returns true if this format shows only a per-file summary, not the
+    /// changed contents.
+    fn is_short(&self) -> bool {
+        match self {
+            DiffFormat::Summary
+            | DiffFormat::Stat(_)
+            | DiffFormat::ShortStat(_)
+            | DiffFormat::Types
+            | DiffFormat::LongTypes
+            | DiffFormat::NameOnly
+            | DiffFormat::MetadataOnly
+            | DiffFormat::JsonStat(_)
+            | DiffFormat::NumStat(_) => true,
+            DiffFormat::Git(_)
+            | DiffFormat::ColorWords(_)
+            | DiffFormat::Json(_)
+            | DiffFormat::Tool(_) => false,
+        }
+    }
+}
+
 /// Returns a list of requested diff formats, which will never be empty.
+///
+/// At most one short format (e.g. `--stat`) and one long format (e.g.
+/// `--git` or `--tool`) can be requested. If both are requested, the short
+/// format comes first, like `git diff --stat --patch`. The default format is
+/// used only if no format is requested. `ui.diff.tool` takes precedence over
+/// `ui.diff.format`. A hint is printed if `--tool` is combined with a short
+/// format, since both are shown.
 pub fn diff_formats_for(
+    ui: &Ui,
     settings: &UserSettings,
     args: &DiffFormatArgs,
-) -> Result<Vec<DiffFormat>, ConfigGetError> {
-    let formats = diff_formats_from_args(settings, args)?;
+) -> Result<Vec<DiffFormat>, CommandError> {
+    let formats = diff_formats_from_args(ui, settings, args)?;
     if formats.is_empty() {
         Ok(vec![default_diff_format(settings, args)?])
     } else {
@@ -158,34 +389,69 @@ pub fn diff_formats_for(
 
 /// Returns a list of requested diff formats for log-like commands, which may be
 /// empty.
+///
+/// `--patch` adds the default format unless a long format is requested
+/// explicitly.
 pub fn diff_formats_for_log(
+    ui: &Ui,
     settings: &UserSettings,
     args: &DiffFormatArgs,
     patch: bool,
-) -> Result<Vec<DiffFormat>, ConfigGetError> {
-    let mut formats = diff_formats_from_args(settings, args)?;
-    // --patch implies default if no format other than --summary is specified
-    if patch && matches!(formats.as_slice(), [] | [DiffFormat::Summary]) {
-        formats.push(default_diff_format(settings, args)?);
-        formats.dedup();
+) -> Result<Vec<DiffFormat>, CommandError> {
+    let mut formats = diff_formats_from_args(ui, settings, args)?;
+    // --patch implies default if no format other than short formats is specified
+    if patch && formats.iter().all(DiffFormat::is_short) {
+        let default_format = default_diff_format(settings, args)?;
+        // The default may be the same short format, e.g. --summary
+        if !formats.contains(&default_format) {
+            formats.push(default_format);
+        }
     }
     Ok(formats)
 }
 
 fn diff_formats_from_args(
+    ui: &Ui,
     settings: &UserSettings,
     args: &DiffFormatArgs,
-) -> Result<Vec<DiffFormat>, ConfigGetError> {
+) -> Result<Vec<DiffFormat>, CommandError> {
+    // Short formats are listed first so the summary precedes the patch.
     let mut formats = Vec::new();
-    if args.summary {
+    // With --stat, the summary is appended to the histogram.
+    if args.summary && !args.stat {
         formats.push(DiffFormat::Summary);
     }
-    if args.types {
+    if args.stat {
+        let mut options = DiffStatOptions::from_settings(settings)?;
+        options.merge_args(args);
+        formats.push(DiffFormat::Stat(Box::new(options)));
+    }
+    if args.shortstat {
+        let mut options = DiffStatOptions::from_settings(settings)?;
+        options.merge_args(args);
+        formats.push(DiffFormat::ShortStat(Box::new(options)));
+    }
+    if args.types && args.long {
+        formats.push(DiffFormat::LongTypes);
+    } else if args.types {
         formats.push(DiffFormat::Types);
     }
     if args.name_only {
         formats.push(DiffFormat::NameOnly);
     }
+    if args.metadata_only {
+        formats.push(DiffFormat::MetadataOnly);
+    }
+    if args.json_stat {
+        let mut options = DiffStatOptions::from_settings(settings)?;
+        options.merge_args(args);
+        formats.push(DiffFormat::JsonStat(Box::new(options)));
+    }
+    if args.numstat {
+        let mut options = DiffStatOptions::from_settings(settings)?;
+        options.merge_args(args);
+        formats.push(DiffFormat::NumStat(Box::new(options)));
+    }
     if args.git {
         let mut options = UnifiedDiffOptions::from_settings(settings)?;
         options.merge_args(args);
@@ -196,19 +462,52 @@ fn diff_formats_from_args(
         options.merge_args(args);
         formats.push(DiffFormat::ColorWords(Box::new(options)));
     }
-    if args.stat {
-        let mut options = DiffStatOptions::default();
+    if args.json {
+        let mut options = UnifiedDiffOptions::from_settings(settings)?;
         options.merge_args(args);
-        formats.push(DiffFormat::Stat(Box::new(options)));
+        formats.push(DiffFormat::Json(Box::new(options)));
     }
     if let Some(name) = &args.tool {
         let tool = merge_tools::get_external_tool_config(settings, name)?
             .unwrap_or_else(|| ExternalMergeTool::with_program(name));
+        check_diff_tool_variables(&tool, &format!("merge-tools.{name}.diff-args"))?;
+        // Both formats are shown, which may be unexpected if the tool is
+        // thought to replace the whole output.
+        if formats.iter().any(DiffFormat::is_short) {
+            writeln!(
+                ui.hint_default(),
+                "The summary is shown before the output of the diff tool `{name}`. Omit the \
+                 summary flags to show only the tool output."
+            )?;
+        }
         formats.push(DiffFormat::Tool(Box::new(tool)));
     }
     Ok(formats)
 }
 
+/// Rejects variables in the diff arguments of the `tool` which wouldn't be
+/// substituted.
+fn check_diff_tool_variables(
+    tool: &ExternalMergeTool,
+    config_name: &str,
+) -> Result<(), ConfigGetError> {
+    let Some(variable) = tool.find_unknown_diff_variable() else {
+        return Ok(());
+    };
+    let known_variables = tool
+        .diff_invocation_mode
+        .diff_args_variables()
+        .iter()
+        .map(|name| format!("${name}"))
+        .join(", ");
+    Err(ConfigGetError::Type {
+        name: config_name.to_owned(),
+        error: format!("Unknown variable ${variable} (available variables: {known_variables})")
+            .into(),
+        source_path: None,
+    })
+}
+
 fn default_diff_format(
     settings: &UserSettings,
     args: &DiffFormatArgs,
@@ -221,6 +520,11 @@ fn default_diff_format(
             None
         }
         .unwrap_or_else(|| ExternalMergeTool::with_diff_args(&args));
+        let config_name = match &args {
+            CommandNameAndArgs::String(name) => format!("merge-tools.{name}.diff-args"),
+            _ => "ui.diff.tool".to_owned(),
+        };
+        check_diff_tool_variables(&tool, &config_name)?;
         return Ok(DiffFormat::Tool(Box::new(tool)));
     }
     let name = if let Some(name) = settings.get_string("ui.diff.format").optional()? {
@@ -244,11 +548,31 @@ fn default_diff_format(
             options.merge_args(args);
             Ok(DiffFormat::ColorWords(Box::new(options)))
         }
+        "json" => {
+            let mut options = UnifiedDiffOptions::from_settings(settings)?;
+            options.merge_args(args);
+            Ok(DiffFormat::Json(Box::new(options)))
+        }
         "stat" => {
-            let mut options = DiffStatOptions::default();
+            let mut options = DiffStatOptions::from_settings(settings)?;
             options.merge_args(args);
             Ok(DiffFormat::Stat(Box::new(options)))
         }
+        "shortstat" => {
+            let mut options = DiffStatOptions::from_settings(settings)?;
+            options.merge_args(args);
+            Ok(DiffFormat::ShortStat(Box::new(options)))
+        }
+        "json-stat" => {
+            let mut options = DiffStatOptions::from_settings(settings)?;
+            options.merge_args(args);
+            Ok(DiffFormat::JsonStat(Box::new(options)))
+        }
+        "numstat" => {
+            let mut options = DiffStatOptions::from_settings(settings)?;
+            options.merge_args(args);
+            Ok(DiffFormat::NumStat(Box::new(options)))
+        }
         _ => Err(ConfigGetError::Type {
             name: "ui.diff.format".to_owned(),
             error: format!("Invalid diff format: {name}").into(),
@@ -257,6 +581,327 @@ fn default_diff_format(
     }
 }
 
+/// Returns the grouping options if `--group-by-status` is requested.
+pub fn diff_grouping_for(
+    settings: &UserSettings,
+    args: &DiffFormatArgs,
+) -> Result<Option<DiffGroupOptions>, ConfigGetError> {
+    if args.group_by_status {
+        Ok(Some(DiffGroupOptions::from_settings(settings)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Options for rendering files grouped by their change status.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DiffGroupOptions {
+    /// Whether to print a header before each group.
+    pub show_headers: bool,
+}
+
+impl DiffGroupOptions {
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
+        Ok(DiffGroupOptions {
+            show_headers: settings.get_bool("diff.group-by-status.headers")?,
+        })
+    }
+}
+
+/// Change status of a file, in the order groups are rendered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DiffStatus {
+    Added = 0,
+    Removed = 1,
+    Modified = 2,
+}
+
+impl DiffStatus {
+    fn from_entry(
+        path: &CopiesTreeDiffEntryPath,
+        before: &MergedTreeValue,
+        after: &MergedTreeValue,
+    ) -> Self {
+        if path.copy_operation().is_some() {
+            return DiffStatus::Modified;
+        }
+        match (before.is_present(), after.is_present()) {
+            (false, true) => DiffStatus::Added,
+            (true, false) => DiffStatus::Removed,
+            _ => DiffStatus::Modified,
+        }
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            DiffStatus::Added => "Added files",
+            DiffStatus::Removed => "Removed files",
+            DiffStatus::Modified => "Modified files",
+        }
+    }
+}
+
+/// Commands to normalize file contents before comparison, configured by the
+/// `diff.normalize` table.
+///
+/// The table keys are workspace-relative glob patterns. The command of the
+/// first key (in sorted order) matching a file receives the file content on
+/// stdin and prints the normalized content to stdout. Results are cached by
+/// command and file id.
+pub struct DiffNormalizer {
+    rules: Vec<(Box<dyn Matcher>, CommandNameAndArgs)>,
+    cache: Mutex<HashMap<(usize, FileId), Vec<u8>>>,
+}
+
+impl DiffNormalizer {
+    /// Loads normalization commands. Returns `Ok(None)` if none are
+    /// configured.
+    pub fn from_settings(settings: &UserSettings) -> Result<Option<Self>, ConfigGetError> {
+        let rules: Vec<_> = settings
+            .table_keys("diff.normalize")
+            .sorted()
+            .map(|glob| {
+                let command: CommandNameAndArgs = settings.get(["diff", "normalize", glob])?;
+                let pattern =
+                    FilePattern::root_file_glob(glob).map_err(|err| ConfigGetError::Type {
+                        name: format!("diff.normalize.{glob}"),
+                        error: err.into(),
+                        source_path: None,
+                    })?;
+                let matcher = FilesetExpression::pattern(pattern).to_matcher();
+                Ok((matcher, command))
+            })
+            .try_collect()?;
+        if rules.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(DiffNormalizer {
+            rules,
+            cache: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Replaces the content of a text file matching any of the patterns with
+    /// its normalized form. Other values are returned as is.
+    fn normalize(
+        &self,
+        path: &RepoPath,
+        value: MaterializedTreeValue,
+    ) -> BackendResult<MaterializedTreeValue> {
+        let index = match &value {
+            MaterializedTreeValue::File { .. } => self
+                .rules
+                .iter()
+                .position(|(matcher, _)| matcher.matches(path)),
+            _ => None,
+        };
+        let Some(index) = index else {
+            return Ok(value);
+        };
+        let MaterializedTreeValue::File {
+            id,
+            executable,
+            mut reader,
+        } = value
+        else {
+            unreachable!("only files should be normalized");
+        };
+        let cache_key = (index, id.clone());
+        let cached = self.cache.lock().unwrap().get(&cache_key).cloned();
+        let contents = if let Some(contents) = cached {
+            contents
+        } else {
+            let mut contents = vec![];
+            reader
+                .read_to_end(&mut contents)
+                .map_err(|err| BackendError::ReadFile {
+                    path: path.to_owned(),
+                    id: id.clone(),
+                    source: err.into(),
+                })?;
+            // Binary files are left to the usual binary file handling.
+            if !contents.contains(&b'\0') {
+                let (_, command) = &self.rules[index];
+                contents = run_normalize_command(command, &contents).map_err(|err| {
+                    BackendError::Other(Box::new(DiffNormalizeError {
+                        path: path.to_owned(),
+                        command: command.to_string(),
+                        source: err,
+                    }))
+                })?;
+            }
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(cache_key, contents.clone());
+            contents
+        };
+        Ok(MaterializedTreeValue::File {
+            id,
+            executable,
+            reader: Box::new(io::Cursor::new(contents)),
+        })
+    }
+}
+
+/// Error from a `diff.normalize` command.
+#[derive(Debug, Error)]
+#[error("Failed to normalize {} with `{command}`", path.as_internal_file_string())]
+pub struct DiffNormalizeError {
+    path: RepoPathBuf,
+    command: String,
+    source: io::Error,
+}
+
+fn run_normalize_command(command: &CommandNameAndArgs, contents: &[u8]) -> io::Result<Vec<u8>> {
+    let mut child = command
+        .to_command()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let output = std::thread::scope(|s| {
+        s.spawn(move || {
+            // The command may exit without reading all of its input.
+            stdin.write_all(contents).ok();
+        });
+        child.wait_with_output()
+    })?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "Command exited with {}",
+            output.status
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// File contents read while rendering diffs, shared between the formats of a
+/// single `show_diff()` call so that each blob is read only once.
+///
+/// Only text files of up to `MAX_CACHED_FILE_SIZE` bytes are cached, and no
+/// more files are cached once the contents add up to `MAX_CACHE_SIZE` bytes.
+/// Binary files are left unread after their first `PEEK_SIZE` bytes, so the
+/// formats can still stop reading them early.
+#[derive(Default)]
+pub struct DiffContentCache {
+    contents: Mutex<CachedContents>,
+}
+
+#[derive(Default)]
+struct CachedContents {
+    by_id: HashMap<FileId, Arc<[u8]>>,
+    total_size: usize,
+}
+
+const MAX_CACHED_FILE_SIZE: usize = 1 << 20;
+const MAX_CACHE_SIZE: usize = 64 << 20;
+
+impl DiffContentCache {
+    async fn materialize(
+        &self,
+        store: &Store,
+        path: &RepoPath,
+        value: MergedTreeValue,
+    ) -> BackendResult<MaterializedTreeValue> {
+        if let Some(Some(TreeValue::File { id, executable })) = value.as_resolved() {
+            if let Some(content) = self.contents.lock().unwrap().by_id.get(id) {
+                return Ok(MaterializedTreeValue::File {
+                    id: id.clone(),
+                    executable: *executable,
+                    reader: Box::new(io::Cursor::new(content.clone())),
+                });
+            }
+        }
+        let (id, executable, mut reader) = match materialize_tree_value(store, path, value).await? {
+            MaterializedTreeValue::File {
+                id,
+                executable,
+                reader,
+            } => (id, executable, reader),
+            other => return Ok(other),
+        };
+        let mut content = vec![];
+        let mut read_content = || -> io::Result<()> {
+            (&mut reader)
+                .take(PEEK_SIZE.try_into().unwrap())
+                .read_to_end(&mut content)?;
+            if !content.contains(&b'\0') {
+                let limit = MAX_CACHED_FILE_SIZE + 1 - content.len();
+                (&mut reader)
+                    .take(limit.try_into().unwrap())
+                    .read_to_end(&mut content)?;
+            }
+            Ok(())
+        };
+        read_content().map_err(|err| BackendError::ReadFile {
+            path: path.to_owned(),
+            id: id.clone(),
+            source: err.into(),
+        })?;
+        if content.contains(&b'\0') || content.len() > MAX_CACHED_FILE_SIZE {
+            // The rest of the file is read only if needed.
+            return Ok(MaterializedTreeValue::File {
+                id,
+                executable,
+                reader: Box::new(io::Cursor::new(content).chain(reader)),
+            });
+        }
+        let content: Arc<[u8]> = content.into();
+        let mut contents = self.contents.lock().unwrap();
+        if contents.total_size + content.len() <= MAX_CACHE_SIZE {
+            contents.total_size += content.len();
+            contents.by_id.insert(id.clone(), content.clone());
+        }
+        Ok(MaterializedTreeValue::File {
+            id,
+            executable,
+            reader: Box::new(io::Cursor::new(content)),
+        })
+    }
+}
+
+/// Like [`materialized_diff_stream()`], but normalizes the file contents if
+/// the `normalizer` is specified, and reads the file contents through the
+/// `content_cache` if specified.
+fn normalized_diff_stream<'a>(
+    store: &'a Store,
+    tree_diff: BoxStream<'a, CopiesTreeDiffEntry>,
+    normalizer: Option<&'a DiffNormalizer>,
+    content_cache: Option<&'a DiffContentCache>,
+) -> impl Stream<Item = MaterializedTreeDiffEntry> + 'a {
+    let entries = if let Some(cache) = content_cache {
+        tree_diff
+            .map(move |CopiesTreeDiffEntry { path, values }| async move {
+                let values = match values {
+                    Ok((before, after)) => {
+                        let before_future = cache.materialize(store, path.source(), before);
+                        let after_future = cache.materialize(store, path.target(), after);
+                        try_join!(before_future, after_future)
+                    }
+                    Err(err) => Err(err),
+                };
+                MaterializedTreeDiffEntry { path, values }
+            })
+            .buffered((store.concurrency() / 2).max(1))
+            .left_stream()
+    } else {
+        materialized_diff_stream(store, tree_diff).right_stream()
+    };
+    entries.map(move |entry| {
+        let Some(normalizer) = normalizer else {
+            return entry;
+        };
+        let MaterializedTreeDiffEntry { path, values } = entry;
+        let values = values.and_then(|(left, right)| {
+            let left = normalizer.normalize(path.source(), left)?;
+            let right = normalizer.normalize(path.target(), right)?;
+            Ok((left, right))
+        });
+        MaterializedTreeDiffEntry { path, values }
+    })
+}
+
 #[derive(Debug, Error)]
 pub enum DiffRenderError {
     #[error("Failed to generate diff")]
@@ -272,6 +917,14 @@ pub enum DiffRenderError {
     InvalidRepoPath(#[from] InvalidRepoPathError),
     #[error(transparent)]
     Io(#[from] io::Error),
+    #[error("Only the git and color-words formats can be used to compare contents directly")]
+    UnsupportedContentFormat,
+    #[error("Cannot compare lines of binary file {}", path.as_internal_file_string())]
+    BinaryFile { path: RepoPathBuf },
+    #[error("Only the git format can be used to show hunks with introduced lines")]
+    UnsupportedIntroducedLinesFormat,
+    #[error("External diff tools can only be used in a terminal")]
+    UnsupportedToolFormat,
 }
 
 /// Configuration and environment to render textual diff.
@@ -280,9 +933,23 @@ pub struct DiffRenderer<'a> {
     path_converter: &'a RepoPathUiConverter,
     conflict_marker_style: ConflictMarkerStyle,
     formats: Vec<DiffFormat>,
+    grouping: Option<DiffGroupOptions>,
+    normalizer: Option<DiffNormalizer>,
+    introduced_lines: Option<IntroducedLines>,
+    use_diff_label: bool,
+    reversed: bool,
+    excluded_paths: Option<Box<dyn Matcher>>,
 }
 
 impl<'a> DiffRenderer<'a> {
+    /// Creates a renderer for the given `formats`.
+    ///
+    /// The `path_converter` determines how paths are displayed in the summary,
+    /// stat, types, name-only, and color-words formats. The git and JSON
+    /// formats always use repo-relative paths with `/` separators. Callers
+    /// without a workspace can pass e.g.
+    /// `RepoPathUiConverter::Fs` with empty `cwd` and `base` paths to display
+    /// repo-relative native paths.
     pub fn new(
         repo: &'a dyn Repo,
         path_converter: &'a RepoPathUiConverter,
@@ -294,9 +961,57 @@ impl<'a> DiffRenderer<'a> {
             path_converter,
             conflict_marker_style,
             formats,
+            grouping: None,
+            normalizer: None,
+            introduced_lines: None,
+            use_diff_label: true,
+            reversed: false,
+            excluded_paths: None,
         }
     }
 
+    /// Sets whether to render added, removed, and modified files in separate
+    /// groups.
+    pub fn with_grouping(mut self, grouping: Option<DiffGroupOptions>) -> Self {
+        self.grouping = grouping;
+        self
+    }
+
+    /// Sets commands to normalize file contents before comparison.
+    pub fn with_normalizer(mut self, normalizer: Option<DiffNormalizer>) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    /// Sets lines to look for in the diff. If set, only hunks adding any of
+    /// these lines are shown, and only the git format is supported.
+    pub fn with_introduced_lines(mut self, introduced_lines: Option<IntroducedLines>) -> Self {
+        self.introduced_lines = introduced_lines;
+        self
+    }
+
+    /// Sets whether to wrap the output in the top-level `diff` label.
+    ///
+    /// The label is enabled by default. Frontends that embed the diff in
+    /// their own UI can disable it to apply their own theming.
+    pub fn with_diff_label(mut self, use_diff_label: bool) -> Self {
+        self.use_diff_label = use_diff_label;
+        self
+    }
+
+    /// Sets whether to show the diff from `to_tree` to `from_tree` instead.
+    pub fn with_reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+
+    /// Sets paths to leave out of the diff even if the matcher passed to the
+    /// render functions matches them.
+    pub fn with_excluded_paths(mut self, excluded_paths: Option<Box<dyn Matcher>>) -> Self {
+        self.excluded_paths = excluded_paths;
+        self
+    }
+
     /// Generates diff between `from_tree` and `to_tree`.
     #[allow(clippy::too_many_arguments)]
     pub fn show_diff(
@@ -309,7 +1024,70 @@ impl<'a> DiffRenderer<'a> {
         copy_records: &CopyRecords,
         width: usize,
     ) -> Result<(), DiffRenderError> {
-        formatter.with_label("diff", |formatter| {
+        self.show_diff_with_ui(
+            Some(ui),
+            formatter,
+            from_tree,
+            to_tree,
+            matcher,
+            copy_records,
+            width,
+        )
+    }
+
+    /// Renders diff between `from_tree` and `to_tree` without a terminal.
+    ///
+    /// The returned recorder contains the plain text of the diff, and the
+    /// labels of the text, which can be restyled by replaying the recorder to
+    /// another formatter. External diff tools can't be used, since they may
+    /// need to interact with the user.
+    pub fn render_diff(
+        &self,
+        from_tree: &MergedTree,
+        to_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        copy_records: &CopyRecords,
+        width: usize,
+    ) -> Result<FormatRecorder, DiffRenderError> {
+        let mut recorder = FormatRecorder::new();
+        self.show_diff_with_ui(
+            None,
+            &mut recorder,
+            from_tree,
+            to_tree,
+            matcher,
+            copy_records,
+            width,
+        )?;
+        Ok(recorder)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn show_diff_with_ui(
+        &self,
+        ui: Option<&Ui>,
+        formatter: &mut dyn Formatter,
+        from_tree: &MergedTree,
+        to_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        copy_records: &CopyRecords,
+        width: usize,
+    ) -> Result<(), DiffRenderError> {
+        let reversed_copy_records;
+        let (from_tree, to_tree, copy_records) = if self.reversed {
+            reversed_copy_records = reverse_copy_records(copy_records, to_tree)?;
+            (to_tree, from_tree, &reversed_copy_records)
+        } else {
+            (from_tree, to_tree, copy_records)
+        };
+        let difference_matcher;
+        let matcher = if let Some(excluded_paths) = &self.excluded_paths {
+            difference_matcher = DifferenceMatcher::new(matcher, excluded_paths);
+            &difference_matcher
+        } else {
+            matcher
+        };
+        let show_diff = |formatter: &mut dyn Formatter| {
             self.show_diff_inner(
                 ui,
                 formatter,
@@ -319,13 +1097,72 @@ impl<'a> DiffRenderer<'a> {
                 copy_records,
                 width,
             )
-        })
+        };
+        if self.use_diff_label {
+            formatter.with_label("diff", show_diff)
+        } else {
+            show_diff(formatter)
+        }
+    }
+
+    /// Generates diff between two contents that don't have to be in a tree,
+    /// e.g. files outside of the workspace. The `left_label` and
+    /// `right_label` are printed as their names.
+    ///
+    /// Only the git and color-words formats are supported.
+    pub fn show_content_diff(
+        &self,
+        formatter: &mut dyn Formatter,
+        left_label: &str,
+        left: &mut dyn io::Read,
+        right_label: &str,
+        right: &mut dyn io::Read,
+    ) -> Result<(), DiffRenderError> {
+        let mut left = (left_label, file_content_for_diff(left)?);
+        let mut right = (right_label, file_content_for_diff(right)?);
+        if self.reversed {
+            mem::swap(&mut left, &mut right);
+        }
+        let ((left_label, left), (right_label, right)) = (left, right);
+        let show_diff = |formatter: &mut dyn Formatter| {
+            for format in &self.formats {
+                match format {
+                    DiffFormat::Git(options) => {
+                        show_git_content_diff(
+                            formatter,
+                            left_label,
+                            &left.contents,
+                            right_label,
+                            &right.contents,
+                            options,
+                        )?;
+                    }
+                    DiffFormat::ColorWords(options) => {
+                        show_color_words_content_diff(
+                            formatter,
+                            left_label,
+                            &left,
+                            right_label,
+                            &right,
+                            options,
+                        )?;
+                    }
+                    _ => return Err(DiffRenderError::UnsupportedContentFormat),
+                }
+            }
+            Ok(())
+        };
+        if self.use_diff_label {
+            formatter.with_label("diff", show_diff)
+        } else {
+            show_diff(formatter)
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
     fn show_diff_inner(
         &self,
-        ui: &Ui,
+        ui: Option<&Ui>,
         formatter: &mut dyn Formatter,
         from_tree: &MergedTree,
         to_tree: &MergedTree,
@@ -333,61 +1170,228 @@ impl<'a> DiffRenderer<'a> {
         copy_records: &CopyRecords,
         width: usize,
     ) -> Result<(), DiffRenderError> {
-        let store = self.repo.store();
-        let path_converter = self.path_converter;
-        for format in &self.formats {
-            match format {
-                DiffFormat::Summary => {
-                    let tree_diff =
-                        from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
-                    show_diff_summary(formatter, tree_diff, path_converter)?;
-                }
-                DiffFormat::Stat(options) => {
-                    let tree_diff =
-                        from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
-                    let stats =
-                        DiffStats::calculate(store, tree_diff, options, self.conflict_marker_style)
-                            .block_on()?;
-                    show_diff_stats(formatter, &stats, path_converter, width)?;
-                }
-                DiffFormat::Types => {
-                    let tree_diff =
-                        from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
-                    show_types(formatter, tree_diff, path_converter)?;
-                }
-                DiffFormat::NameOnly => {
-                    let tree_diff =
-                        from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
-                    show_names(formatter, tree_diff, path_converter)?;
-                }
-                DiffFormat::Git(options) => {
-                    let tree_diff =
-                        from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
-                    show_git_diff(
-                        formatter,
-                        store,
-                        tree_diff,
-                        options,
-                        self.conflict_marker_style,
-                    )?;
-                }
-                DiffFormat::ColorWords(options) => {
-                    let tree_diff =
-                        from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
+        let Some(grouping) = &self.grouping else {
+            if self.formats.len() <= 1 {
+                let make_tree_diff =
+                    || from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
+                return self.show_diff_formats(
+                    ui,
+                    formatter,
+                    from_tree,
+                    to_tree,
+                    matcher,
+                    &make_tree_diff,
+                    width,
+                );
+            }
+            // Compute the diff only once to share it across the formats.
+            let tree_diff = from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
+            let entries = collect_tree_diff(tree_diff)?;
+            let make_tree_diff = || replay_tree_diff(&entries);
+            return self.show_diff_formats(
+                ui,
+                formatter,
+                from_tree,
+                to_tree,
+                matcher,
+                &make_tree_diff,
+                width,
+            );
+        };
+
+        let mut groups: [Vec<_>; 3] = Default::default();
+        let tree_diff = from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
+        for entry in collect_tree_diff(tree_diff)? {
+            let (path, before, after) = &entry;
+            let status = DiffStatus::from_entry(path, before, after);
+            groups[status as usize].push(entry);
+        }
+
+        let statuses = [DiffStatus::Added, DiffStatus::Removed, DiffStatus::Modified];
+        for (status, entries) in iter::zip(statuses, &groups) {
+            if entries.is_empty() {
+                continue;
+            }
+            if grouping.show_headers {
+                writeln!(formatter.labeled("group_header"), "{}:", status.header())?;
+            }
+            // Tools comparing whole directories need a matcher instead of a
+            // list of entries.
+            let group_matcher = FilesMatcher::new(
+                entries
+                    .iter()
+                    .flat_map(|(path, _, _)| [path.source(), path.target()]),
+            );
+            let make_tree_diff = || replay_tree_diff(entries);
+            self.show_diff_formats(
+                ui,
+                formatter,
+                from_tree,
+                to_tree,
+                &group_matcher,
+                &make_tree_diff,
+                width,
+            )?;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn show_diff_formats<'b>(
+        &self,
+        ui: Option<&Ui>,
+        formatter: &mut dyn Formatter,
+        from_tree: &MergedTree,
+        to_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        make_tree_diff: &dyn Fn() -> BoxStream<'b, CopiesTreeDiffEntry>,
+        width: usize,
+    ) -> Result<(), DiffRenderError> {
+        let store = self.repo.store();
+        let path_converter = self.path_converter;
+        if self.introduced_lines.is_some()
+            && !self
+                .formats
+                .iter()
+                .all(|format| matches!(format, DiffFormat::Git(_)))
+        {
+            return Err(DiffRenderError::UnsupportedIntroducedLinesFormat);
+        }
+        // Keep file contents in memory only if more than one format reads them.
+        let content_format_count = self
+            .formats
+            .iter()
+            .filter(|format| {
+                matches!(
+                    format,
+                    DiffFormat::Stat(_)
+                        | DiffFormat::ShortStat(_)
+                        | DiffFormat::NumStat(_)
+                        | DiffFormat::JsonStat(_)
+                        | DiffFormat::Git(_)
+                        | DiffFormat::ColorWords(_)
+                        | DiffFormat::Json(_)
+                )
+            })
+            .count();
+        let content_cache = (content_format_count > 1).then(DiffContentCache::default);
+        let content_cache = content_cache.as_ref();
+        for format in &self.formats {
+            match format {
+                DiffFormat::Summary => {
+                    let tree_diff = make_tree_diff();
+                    show_diff_summary(formatter, tree_diff, path_converter)?;
+                }
+                DiffFormat::Stat(options) => {
+                    let tree_diff = make_tree_diff();
+                    let stats = DiffStats::calculate(
+                        store,
+                        tree_diff,
+                        options,
+                        self.normalizer.as_ref(),
+                        content_cache,
+                        self.conflict_marker_style,
+                    )
+                    .block_on()?;
+                    show_diff_stats(formatter, &stats, options, path_converter, width)?;
+                }
+                DiffFormat::ShortStat(options) => {
+                    let tree_diff = make_tree_diff();
+                    let stats = DiffStats::calculate(
+                        store,
+                        tree_diff,
+                        options,
+                        self.normalizer.as_ref(),
+                        content_cache,
+                        self.conflict_marker_style,
+                    )
+                    .block_on()?;
+                    show_diff_stats_summary(formatter, &stats, options)?;
+                }
+                DiffFormat::NumStat(options) => {
+                    let tree_diff = make_tree_diff();
+                    let stats = DiffStats::calculate(
+                        store,
+                        tree_diff,
+                        options,
+                        self.normalizer.as_ref(),
+                        content_cache,
+                        self.conflict_marker_style,
+                    )
+                    .block_on()?;
+                    show_diff_numstat(formatter, &stats, path_converter)?;
+                }
+                DiffFormat::Types => {
+                    let tree_diff = make_tree_diff();
+                    show_types(formatter, tree_diff, path_converter)?;
+                }
+                DiffFormat::LongTypes => {
+                    let tree_diff = make_tree_diff();
+                    show_long_types(formatter, tree_diff, path_converter)?;
+                }
+                DiffFormat::NameOnly => {
+                    let tree_diff = make_tree_diff();
+                    show_names(formatter, tree_diff, path_converter)?;
+                }
+                DiffFormat::MetadataOnly => {
+                    let tree_diff = make_tree_diff();
+                    show_metadata_changes(formatter, tree_diff, path_converter)?;
+                }
+                DiffFormat::JsonStat(options) => {
+                    let tree_diff = make_tree_diff();
+                    show_json_stat(
+                        formatter,
+                        store,
+                        tree_diff,
+                        options,
+                        self.normalizer.as_ref(),
+                        content_cache,
+                        self.conflict_marker_style,
+                    )?;
+                }
+                DiffFormat::Git(options) => {
+                    let tree_diff = make_tree_diff();
+                    show_git_diff(
+                        formatter,
+                        store,
+                        tree_diff,
+                        options,
+                        self.normalizer.as_ref(),
+                        content_cache,
+                        self.introduced_lines.as_ref(),
+                        self.conflict_marker_style,
+                    )?;
+                }
+                DiffFormat::ColorWords(options) => {
+                    let tree_diff = make_tree_diff();
                     show_color_words_diff(
                         formatter,
                         store,
                         tree_diff,
                         path_converter,
                         options,
+                        self.normalizer.as_ref(),
+                        content_cache,
+                        self.conflict_marker_style,
+                    )?;
+                }
+                DiffFormat::Json(options) => {
+                    let tree_diff = make_tree_diff();
+                    show_json_diff(
+                        formatter,
+                        store,
+                        tree_diff,
+                        options,
+                        self.normalizer.as_ref(),
+                        content_cache,
                         self.conflict_marker_style,
                     )?;
                 }
                 DiffFormat::Tool(tool) => {
+                    let ui = ui.ok_or(DiffRenderError::UnsupportedToolFormat)?;
                     match tool.diff_invocation_mode {
                         DiffToolMode::FileByFile => {
-                            let tree_diff =
-                                from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
+                            let tree_diff = make_tree_diff();
                             show_file_by_file_diff(
                                 ui,
                                 formatter,
@@ -484,22 +1488,92 @@ pub fn get_copy_records<'a>(
     Ok(block_on_stream(stream).filter_ok(|record| matcher.matches(&record.target)))
 }
 
+/// Inverts the renames in `copy_records` for a diff from `to_tree` back to
+/// the original tree.
+///
+/// Copies are dropped since the inverse of a copy is a deletion.
+fn reverse_copy_records(
+    copy_records: &CopyRecords,
+    to_tree: &MergedTree,
+) -> BackendResult<CopyRecords> {
+    let mut reversed = CopyRecords::default();
+    let mut records = vec![];
+    for record in copy_records.iter() {
+        if to_tree.path_value(&record.source)?.is_absent() {
+            records.push(Ok(CopyRecord {
+                target: record.source.clone(),
+                source: record.target.clone(),
+                ..record.clone()
+            }));
+        }
+    }
+    reversed.add_records(records)?;
+    Ok(reversed)
+}
+
+type CollectedTreeDiffEntry = (CopiesTreeDiffEntryPath, MergedTreeValue, MergedTreeValue);
+
+/// Collects the entries of `tree_diff` so they can be iterated multiple times
+/// without computing the diff again.
+fn collect_tree_diff(
+    mut tree_diff: BoxStream<CopiesTreeDiffEntry>,
+) -> Result<Vec<CollectedTreeDiffEntry>, DiffRenderError> {
+    async {
+        let mut entries = vec![];
+        while let Some(CopiesTreeDiffEntry { path, values }) = tree_diff.next().await {
+            let (before, after) = values?;
+            entries.push((path, before, after));
+        }
+        Ok(entries)
+    }
+    .block_on()
+}
+
+fn replay_tree_diff(entries: &[CollectedTreeDiffEntry]) -> BoxStream<'_, CopiesTreeDiffEntry> {
+    let entries = entries
+        .iter()
+        .map(|(path, before, after)| CopiesTreeDiffEntry {
+            path: path.clone(),
+            values: Ok((before.clone(), after.clone())),
+        });
+    futures::stream::iter(entries).boxed()
+}
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct LineDiffOptions {
     /// How equivalence of lines is tested.
     pub compare_mode: LineCompareMode,
-    // TODO: add --ignore-blank-lines, etc. which aren't mutually exclusive.
+    /// Whether to label changes that only differ in whitespace as
+    /// `whitespace_change`.
+    pub dim_whitespace_changes: bool,
+    /// Whether to compare files that look like binary files as text.
+    pub force_text: bool,
+    /// Whether to ignore changes whose lines are all blank.
+    pub ignore_blank_lines: bool,
 }
 
 impl LineDiffOptions {
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
+        Ok(LineDiffOptions {
+            compare_mode: LineCompareMode::default(),
+            dim_whitespace_changes: settings.get_bool("diff.dim-whitespace-changes")?,
+            force_text: false,
+            ignore_blank_lines: false,
+        })
+    }
+
     fn merge_args(&mut self, args: &DiffFormatArgs) {
         self.compare_mode = if args.ignore_all_space {
             LineCompareMode::IgnoreAllSpace
         } else if args.ignore_space_change {
             LineCompareMode::IgnoreSpaceChange
+        } else if args.ignore_cr_at_eol {
+            LineCompareMode::IgnoreCrAtEol
         } else {
             LineCompareMode::Exact
         };
+        self.force_text = args.text;
+        self.ignore_blank_lines = args.ignore_blank_lines;
     }
 }
 
@@ -512,16 +1586,56 @@ pub enum LineCompareMode {
     IgnoreAllSpace,
     /// Compares lines ignoring changes in whitespace amount.
     IgnoreSpaceChange,
+    /// Compares lines ignoring a carriage return at the end of line.
+    IgnoreCrAtEol,
+}
+
+/// Options to shorten long context regions between changes in a hunk.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ContextGapOptions {
+    /// Number of context lines to keep at each end of the region.
+    pub lines: usize,
+    /// Minimum number of lines to hide in the gap.
+    pub threshold: usize,
+}
+
+impl ContextGapOptions {
+    /// Loads the options, or returns `None` if the context gap is disabled.
+    pub fn from_settings(settings: &UserSettings) -> Result<Option<Self>, ConfigGetError> {
+        if !settings.get_bool("diff.context-gap.enabled")? {
+            return Ok(None);
+        }
+        Ok(Some(ContextGapOptions {
+            lines: settings.get("diff.context-gap.lines")?,
+            threshold: settings.get("diff.context-gap.threshold")?,
+        }))
+    }
+}
+
+/// Returns the number of lines to show at each end of the context region of
+/// `num_lines` between two changes.
+///
+/// Regions which are short enough to be shown in full are shortened if
+/// `context_gap` is enabled and at least `threshold` lines can be hidden.
+fn num_context_lines_between_changes(
+    context: usize,
+    context_gap: Option<ContextGapOptions>,
+    num_lines: usize,
+) -> usize {
+    match context_gap {
+        Some(gap)
+            if num_lines <= 2 * context && num_lines >= 2 * gap.lines + max(gap.threshold, 1) =>
+        {
+            gap.lines
+        }
+        _ => context,
+    }
 }
 
 fn diff_by_line<'input, T: AsRef<[u8]> + ?Sized + 'input>(
     inputs: impl IntoIterator<Item = &'input T>,
     options: &LineDiffOptions,
 ) -> Diff<'input> {
-    // TODO: If we add --ignore-blank-lines, its tokenizer will have to attach
-    // blank lines to the preceding range. Maybe it can also be implemented as a
-    // post-process (similar to refine_changed_regions()) that expands unchanged
-    // regions across blank lines.
     match options.compare_mode {
         LineCompareMode::Exact => {
             Diff::for_tokenizer(inputs, find_line_ranges, CompareBytesExactly)
@@ -532,6 +1646,46 @@ fn diff_by_line<'input, T: AsRef<[u8]> + ?Sized + 'input>(
         LineCompareMode::IgnoreSpaceChange => {
             Diff::for_tokenizer(inputs, find_line_ranges, CompareBytesIgnoreWhitespaceAmount)
         }
+        LineCompareMode::IgnoreCrAtEol => {
+            Diff::for_tokenizer(inputs, find_line_ranges, CompareBytesIgnoreCrAtEol)
+        }
+    }
+}
+
+/// Returns true if the `left` and `right` sides of a change only differ in
+/// whitespace.
+fn is_whitespace_only_change(left: &[u8], right: &[u8]) -> bool {
+    CompareBytes::eq(&CompareBytesIgnoreAllWhitespace, left, right)
+}
+
+/// Returns true if all lines removed and added by a change are blank, and the
+/// change should be ignored if `options.ignore_blank_lines` is set.
+fn is_ignored_blank_lines_change(options: &LineDiffOptions, left: &[u8], right: &[u8]) -> bool {
+    options.ignore_blank_lines && left.iter().chain(right).all(u8::is_ascii_whitespace)
+}
+
+/// Mapping from file name or extension to language name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LanguageMap {
+    names: HashMap<String, String>,
+}
+
+impl LanguageMap {
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
+        Ok(LanguageMap {
+            names: settings.get("diff.languages")?,
+        })
+    }
+
+    /// Looks up the language of the file by its name, then by its extension.
+    pub fn detect(&self, path: &RepoPath) -> Option<&str> {
+        let file_name = path.components().next_back()?.as_internal_str();
+        let extension = file_name.rsplit_once('.').map(|(_, ext)| ext);
+        [Some(file_name), extension]
+            .into_iter()
+            .flatten()
+            .find_map(|key| self.names.get(key))
+            .map(String::as_str)
     }
 }
 
@@ -543,6 +1697,24 @@ pub struct ColorWordsDiffOptions {
     pub line_diff: LineDiffOptions,
     /// Maximum number of removed/added word alternation to inline.
     pub max_inline_alternation: Option<usize>,
+    /// Languages to annotate file headers with, or `None` if disabled.
+    pub languages: Option<LanguageMap>,
+    /// Note to print if all changes were hidden by whitespace-ignoring modes.
+    pub whitespace_only_note: String,
+    /// How long context regions between changes are shortened.
+    pub context_gap: Option<ContextGapOptions>,
+    /// Whether to label conflict marker lines. This is set for the contents
+    /// of conflicted files.
+    pub label_conflict_markers: bool,
+    /// How changed lines are split into tokens to find the changed parts.
+    pub tokenizer: ColorWordsTokenizer,
+    /// Whether to show the old and new line numbers before each line.
+    pub line_numbers: bool,
+    /// Number of unchanged characters to show around the changes within an
+    /// inlined line, or `None` to show the whole line.
+    pub inline_context: Option<usize>,
+    /// How skipped context lines between changes are marked.
+    pub gap_marker: ColorWordsGapMarker,
 }
 
 impl ColorWordsDiffOptions {
@@ -558,10 +1730,34 @@ impl ColorWordsDiffOptions {
                 })?),
             }
         };
+        let inline_context = {
+            let name = "diff.color-words.inline-context";
+            match settings.get_int(name)? {
+                -1 => None, // unlimited
+                n => Some(usize::try_from(n).map_err(|err| ConfigGetError::Type {
+                    name: name.to_owned(),
+                    error: err.into(),
+                    source_path: None,
+                })?),
+            }
+        };
+        let languages = if settings.get_bool("diff.color-words.show-language")? {
+            Some(LanguageMap::from_settings(settings)?)
+        } else {
+            None
+        };
         Ok(ColorWordsDiffOptions {
             context: settings.get("diff.color-words.context")?,
-            line_diff: LineDiffOptions::default(),
+            line_diff: LineDiffOptions::from_settings(settings)?,
             max_inline_alternation,
+            languages,
+            whitespace_only_note: settings.get("diff.color-words.whitespace-only-note")?,
+            context_gap: ContextGapOptions::from_settings(settings)?,
+            label_conflict_markers: false,
+            tokenizer: ColorWordsTokenizer::from_settings(settings)?,
+            line_numbers: settings.get_bool("diff.color-words.line-numbers")?,
+            inline_context,
+            gap_marker: settings.get("diff.color-words.gap-marker")?,
         })
     }
 
@@ -570,7 +1766,126 @@ impl ColorWordsDiffOptions {
             self.context = context;
         }
         self.line_diff.merge_args(args);
+        if args.line_numbers {
+            self.line_numbers = true;
+        } else if args.no_line_numbers {
+            self.line_numbers = false;
+        }
+    }
+}
+
+/// How skipped context lines are marked in color-words diffs.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorWordsGapMarker {
+    /// `...` with the number of skipped lines.
+    #[default]
+    Ellipsis,
+    /// `@@ N @@` with the new line number where the diff resumes.
+    LineNumber,
+}
+
+/// How changed lines are split into tokens in color-words diffs.
+#[derive(Clone, Debug, Default)]
+pub enum ColorWordsTokenizer {
+    /// Words and individual non-word characters.
+    #[default]
+    Word,
+    /// Individual characters.
+    Char,
+    /// Matches of the regex, and individual characters between them.
+    Regex(regex::bytes::Regex),
+}
+
+impl ColorWordsTokenizer {
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
+        let name = "diff.color-words.tokenizer";
+        let value = settings.get_string(name)?;
+        let to_config_error =
+            |error: Box<dyn std::error::Error + Send + Sync>| ConfigGetError::Type {
+                name: name.to_owned(),
+                error,
+                source_path: None,
+            };
+        match value.as_str() {
+            "word" => Ok(ColorWordsTokenizer::Word),
+            "char" => Ok(ColorWordsTokenizer::Char),
+            _ => {
+                let Some(pattern) = value.strip_prefix("regex:") else {
+                    return Err(to_config_error(
+                        format!("Invalid tokenizer: {value}").into(),
+                    ));
+                };
+                let regex =
+                    regex::bytes::Regex::new(pattern).map_err(|err| to_config_error(err.into()))?;
+                Ok(ColorWordsTokenizer::Regex(regex))
+            }
+        }
+    }
+
+    /// Compares the `inputs` token by token.
+    fn diff<'input, T: AsRef<[u8]> + ?Sized + 'input>(
+        &self,
+        inputs: impl IntoIterator<Item = &'input T>,
+    ) -> Diff<'input> {
+        match self {
+            ColorWordsTokenizer::Word => Diff::by_word(inputs),
+            ColorWordsTokenizer::Char => {
+                Diff::for_tokenizer(inputs, find_char_ranges, CompareBytesExactly)
+            }
+            ColorWordsTokenizer::Regex(regex) => Diff::for_tokenizer(
+                inputs,
+                |text| find_regex_ranges(regex, text),
+                CompareBytesExactly,
+            ),
+        }
+    }
+}
+
+impl PartialEq for ColorWordsTokenizer {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ColorWordsTokenizer::Word, ColorWordsTokenizer::Word)
+            | (ColorWordsTokenizer::Char, ColorWordsTokenizer::Char) => true,
+            (ColorWordsTokenizer::Regex(regex1), ColorWordsTokenizer::Regex(regex2)) => {
+                regex1.as_str() == regex2.as_str()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ColorWordsTokenizer {}
+
+/// Returns the byte ranges of the (UTF-8) characters in `text`.
+fn find_char_ranges(text: &[u8]) -> Vec<Range<usize>> {
+    text.char_indices()
+        .map(|(start, end, _)| start..end)
+        .collect()
+}
+
+/// Returns the byte ranges of the matches of `regex` in `text`, and of the
+/// individual characters between them.
+fn find_regex_ranges(regex: &regex::bytes::Regex, text: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut pos = 0;
+    let add_char_ranges = |ranges: &mut Vec<Range<usize>>, start: usize, end: usize| {
+        ranges.extend(
+            find_char_ranges(&text[start..end])
+                .into_iter()
+                .map(|range| range.start + start..range.end + start),
+        );
+    };
+    for m in regex.find_iter(text) {
+        if m.is_empty() {
+            continue;
+        }
+        add_char_ranges(&mut ranges, pos, m.start());
+        ranges.push(m.range());
+        pos = m.end();
     }
+    add_char_ranges(&mut ranges, pos, text.len());
+    ranges
 }
 
 fn show_color_words_diff_hunks(
@@ -581,6 +1896,13 @@ fn show_color_words_diff_hunks(
 ) -> io::Result<()> {
     let line_diff = diff_by_line([left, right], &options.line_diff);
     let mut line_number = DiffLineNumber { left: 1, right: 1 };
+    // Line numbers can't exceed the number of lines, so the columns are wide
+    // enough for the longer content.
+    let num_lines = max(
+        left.split_inclusive(|b| *b == b'\n').count(),
+        right.split_inclusive(|b| *b == b'\n').count(),
+    );
+    let line_number_width = max(4, num_lines.to_string().len());
     // Matching entries shouldn't appear consecutively in diff of two inputs.
     // However, if the inputs have conflicts, there may be a hunk that can be
     // resolved, resulting [matching, resolved, matching] sequence.
@@ -590,20 +1912,69 @@ fn show_color_words_diff_hunks(
     for hunk in line_diff.hunks() {
         match hunk.kind {
             DiffHunkKind::Matching => contexts.push(hunk.contents),
+            // Ignored changes are shown only as context of other changes.
+            DiffHunkKind::Different
+                if is_ignored_blank_lines_change(
+                    &options.line_diff,
+                    hunk.contents[0],
+                    hunk.contents[1],
+                ) =>
+            {
+                contexts.push(hunk.contents);
+            }
             DiffHunkKind::Different => {
-                let num_after = if emitted { options.context } else { 0 };
+                let position = if emitted {
+                    let num_lines = contexts
+                        .iter()
+                        .map(|contents| contents[0].split_inclusive(|b| *b == b'\n').count())
+                        .sum();
+                    let num_context = num_context_lines_between_changes(
+                        options.context,
+                        options.context_gap,
+                        num_lines,
+                    );
+                    ContextPosition::Between {
+                        num_after: num_context,
+                        num_before: num_context,
+                    }
+                } else {
+                    ContextPosition::Leading {
+                        num_before: options.context,
+                    }
+                };
                 line_number = show_color_words_context_lines(
                     formatter,
                     &contexts,
                     line_number,
                     options,
-                    num_after,
-                    options.context,
+                    line_number_width,
+                    position,
                 )?;
                 contexts.clear();
                 emitted = true;
-                line_number =
-                    show_color_words_diff_lines(formatter, &hunk.contents, line_number, options)?;
+                let [left, right] = hunk.contents[..].try_into().unwrap();
+                if options.line_diff.dim_whitespace_changes
+                    && is_whitespace_only_change(left, right)
+                {
+                    formatter.with_label("whitespace_change", |formatter| {
+                        line_number = show_color_words_diff_lines(
+                            formatter,
+                            &hunk.contents,
+                            line_number,
+                            options,
+                            line_number_width,
+                        )?;
+                        io::Result::Ok(())
+                    })?;
+                } else {
+                    line_number = show_color_words_diff_lines(
+                        formatter,
+                        &hunk.contents,
+                        line_number,
+                        options,
+                        line_number_width,
+                    )?;
+                }
             }
         }
     }
@@ -614,23 +1985,70 @@ fn show_color_words_diff_hunks(
             &contexts,
             line_number,
             options,
-            options.context,
-            0,
+            line_number_width,
+            ContextPosition::Trailing {
+                num_after: options.context,
+            },
+        )?;
+        // The newline itself isn't visible in the lines above.
+        if !left.is_empty() && !right.is_empty() {
+            match (left.ends_with(b"\n"), right.ends_with(b"\n")) {
+                (true, false) => writeln!(
+                    formatter.labeled("removed"),
+                    "    (newline at end of file removed)"
+                )?,
+                (false, true) => writeln!(
+                    formatter.labeled("added"),
+                    "    (newline at end of file added)"
+                )?,
+                _ => {}
+            }
+        }
+    } else if (options.line_diff.compare_mode != LineCompareMode::Exact
+        || options.line_diff.ignore_blank_lines)
+        && left != right
+    {
+        // The contents differ, but only in whitespace that was ignored.
+        writeln!(
+            formatter.labeled("whitespace-only"),
+            "    {}",
+            options.whitespace_only_note
         )?;
     }
     Ok(())
 }
 
-/// Prints `num_after` lines, ellipsis, and `num_before` lines.
+/// Where unchanged lines are relative to the changes, and how many of them are
+/// shown as context.
+#[derive(Clone, Copy, Debug)]
+enum ContextPosition {
+    /// Before the first change, which is preceded by `num_before` lines.
+    Leading { num_before: usize },
+    /// Between two changes, which are followed and preceded by `num_after` and
+    /// `num_before` lines respectively.
+    Between { num_after: usize, num_before: usize },
+    /// After the last change, which is followed by `num_after` lines.
+    Trailing { num_after: usize },
+}
+
+/// Prints the context lines after the previous change, a marker of the skipped
+/// lines, and the context lines before the next change.
 fn show_color_words_context_lines(
     formatter: &mut dyn Formatter,
     contexts: &[DiffHunkContentVec],
     mut line_number: DiffLineNumber,
     options: &ColorWordsDiffOptions,
-    num_after: usize,
-    num_before: usize,
+    line_number_width: usize,
+    position: ContextPosition,
 ) -> io::Result<DiffLineNumber> {
-    const SKIPPED_CONTEXT_LINE: &str = "    ...\n";
+    let (num_after, num_before) = match position {
+        ContextPosition::Leading { num_before } => (0, num_before),
+        ContextPosition::Between {
+            num_after,
+            num_before,
+        } => (num_after, num_before),
+        ContextPosition::Trailing { num_after } => (num_after, 0),
+    };
     let extract = |side: usize| -> (Vec<&[u8]>, Vec<&[u8]>, u32) {
         let mut lines = contexts
             .iter()
@@ -649,12 +2067,22 @@ fn show_color_words_context_lines(
             for line in left_lines {
                 show_color_words_line_number(
                     formatter,
+                    options,
+                    line_number_width,
                     Some(line_number.left),
                     Some(line_number.right),
                 )?;
-                show_color_words_inline_hunks(
+                with_conflict_marker_label(
                     formatter,
-                    &[(DiffLineHunkSide::Both, line.as_ref())],
+                    options,
+                    || line,
+                    |formatter| {
+                        show_color_words_inline_hunks(
+                            formatter,
+                            &[(DiffLineHunkSide::Both, line.as_ref())],
+                            options.inline_context,
+                        )
+                    },
                 )?;
                 line_number.left += 1;
                 line_number.right += 1;
@@ -668,6 +2096,7 @@ fn show_color_words_context_lines(
                 &[BStr::new(&left), BStr::new(&right)],
                 line_number,
                 options,
+                line_number_width,
             )
         }
     };
@@ -676,16 +2105,34 @@ fn show_color_words_context_lines(
     let (right_after, mut right_before, num_right_skipped) = extract(1);
     line_number = show(formatter, &left_after, &right_after, line_number)?;
     if num_left_skipped > 0 || num_right_skipped > 0 {
-        write!(formatter, "{SKIPPED_CONTEXT_LINE}")?;
-        line_number.left += num_left_skipped;
-        line_number.right += num_right_skipped;
+        let mut num_left_skipped = num_left_skipped;
+        let mut num_right_skipped = num_right_skipped;
         if left_before.len() > num_before {
             left_before.pop();
-            line_number.left += 1;
+            num_left_skipped += 1;
         }
         if right_before.len() > num_before {
             right_before.pop();
-            line_number.right += 1;
+            num_right_skipped += 1;
+        }
+        line_number.left += num_left_skipped;
+        line_number.right += num_right_skipped;
+        // There's no line to resume at after the trailing context.
+        if options.gap_marker == ColorWordsGapMarker::LineNumber
+            && !matches!(position, ContextPosition::Trailing { .. })
+        {
+            write!(formatter, "    ")?;
+            write!(
+                formatter.labeled("hunk_header"),
+                "@@ {} @@",
+                line_number.right
+            )?;
+            writeln!(formatter)?;
+        } else {
+            // The number of skipped lines can differ if blank lines are ignored.
+            let num_skipped = num_left_skipped.max(num_right_skipped);
+            let unit = if num_skipped == 1 { "line" } else { "lines" };
+            writeln!(formatter, "    ... ({num_skipped} {unit})")?;
         }
     }
     left_before.reverse();
@@ -699,8 +2146,10 @@ fn show_color_words_diff_lines(
     contents: &[&BStr],
     mut line_number: DiffLineNumber,
     options: &ColorWordsDiffOptions,
+    line_number_width: usize,
 ) -> io::Result<DiffLineNumber> {
-    let word_diff_hunks = Diff::by_word(contents).hunks().collect_vec();
+    let word_diff = options.tokenizer.diff(contents);
+    let word_diff_hunks = word_diff.hunks().collect_vec();
     let can_inline = match options.max_inline_alternation {
         None => true,     // unlimited
         Some(0) => false, // no need to count alternation
@@ -715,6 +2164,8 @@ fn show_color_words_diff_lines(
         for diff_line in diff_line_iter.by_ref() {
             show_color_words_line_number(
                 formatter,
+                options,
+                line_number_width,
                 diff_line
                     .has_left_content()
                     .then_some(diff_line.line_number.left),
@@ -722,19 +2173,40 @@ fn show_color_words_diff_lines(
                     .has_right_content()
                     .then_some(diff_line.line_number.right),
             )?;
-            show_color_words_inline_hunks(formatter, &diff_line.hunks)?;
+            let line = || -> BString { diff_line.hunks.iter().map(|(_, data)| *data).collect() };
+            with_conflict_marker_label(formatter, options, line, |formatter| {
+                show_color_words_inline_hunks(formatter, &diff_line.hunks, options.inline_context)
+            })?;
         }
         line_number = diff_line_iter.next_line_number();
     } else {
         let (left_lines, right_lines) = unzip_diff_hunks_to_lines(&word_diff_hunks);
         for tokens in &left_lines {
-            show_color_words_line_number(formatter, Some(line_number.left), None)?;
-            show_color_words_single_sided_line(formatter, tokens, "removed")?;
+            show_color_words_line_number(
+                formatter,
+                options,
+                line_number_width,
+                Some(line_number.left),
+                None,
+            )?;
+            let line = || tokens.iter().map(|(_, data)| *data).collect_vec().concat();
+            with_conflict_marker_label(formatter, options, line, |formatter| {
+                show_color_words_single_sided_line(formatter, tokens, "removed")
+            })?;
             line_number.left += 1;
         }
         for tokens in &right_lines {
-            show_color_words_line_number(formatter, None, Some(line_number.right))?;
-            show_color_words_single_sided_line(formatter, tokens, "added")?;
+            show_color_words_line_number(
+                formatter,
+                options,
+                line_number_width,
+                None,
+                Some(line_number.right),
+            )?;
+            let line = || tokens.iter().map(|(_, data)| *data).collect_vec().concat();
+            with_conflict_marker_label(formatter, options, line, |formatter| {
+                show_color_words_single_sided_line(formatter, tokens, "added")
+            })?;
             line_number.right += 1;
         }
     }
@@ -743,34 +2215,76 @@ fn show_color_words_diff_lines(
 
 fn show_color_words_line_number(
     formatter: &mut dyn Formatter,
+    options: &ColorWordsDiffOptions,
+    width: usize,
     left_line_number: Option<u32>,
     right_line_number: Option<u32>,
 ) -> io::Result<()> {
+    if !options.line_numbers {
+        return Ok(());
+    }
     if let Some(line_number) = left_line_number {
         formatter.with_label("removed", |formatter| {
-            write!(formatter.labeled("line_number"), "{line_number:>4}")
+            write!(formatter.labeled("line_number"), "{line_number:>width$}")
         })?;
         write!(formatter, " ")?;
     } else {
-        write!(formatter, "     ")?;
+        write!(formatter, "{:width$} ", "")?;
     }
     if let Some(line_number) = right_line_number {
         formatter.with_label("added", |formatter| {
-            write!(formatter.labeled("line_number"), "{line_number:>4}",)
+            write!(formatter.labeled("line_number"), "{line_number:>width$}",)
         })?;
         write!(formatter, ": ")?;
     } else {
-        write!(formatter, "    : ")?;
+        write!(formatter, "{:width$}: ", "")?;
     }
     Ok(())
 }
 
-/// Prints line hunks which may contain tokens originating from both sides.
+/// Calls `write_line` with the `conflict_marker` label if the line is a
+/// conflict marker and `options` asks for labeling them.
+///
+/// The line content is built by `line` only if it needs to be checked.
+fn with_conflict_marker_label<L: AsRef<[u8]>>(
+    formatter: &mut dyn Formatter,
+    options: &ColorWordsDiffOptions,
+    line: impl FnOnce() -> L,
+    write_line: impl FnOnce(&mut dyn Formatter) -> io::Result<()>,
+) -> io::Result<()> {
+    if options.label_conflict_markers && is_conflict_marker_line(line().as_ref()) {
+        formatter.with_label("conflict_marker", write_line)
+    } else {
+        write_line(formatter)
+    }
+}
+
+/// Returns true if the `line` starts with a marker that could have been
+/// written when materializing a conflict, like `<<<<<<<` or `%%%%%%%`.
+fn is_conflict_marker_line(line: &[u8]) -> bool {
+    let Some(&first) = line.first() else {
+        return false;
+    };
+    if !b"<>+-%|=".contains(&first) {
+        return false;
+    }
+    let len = line.iter().take_while(|&&b| b == first).count();
+    len >= MIN_CONFLICT_MARKER_LEN && line.get(len).map_or(true, |b| b.is_ascii_whitespace())
+}
+
+/// Prints the hunks of a line. If `inline_context` is set and the line has
+/// changes, the unchanged text is shortened to that many characters next to
+/// each change.
 fn show_color_words_inline_hunks(
     formatter: &mut dyn Formatter,
     line_hunks: &[(DiffLineHunkSide, &BStr)],
+    inline_context: Option<usize>,
 ) -> io::Result<()> {
-    for (side, data) in line_hunks {
+    let has_changes = line_hunks
+        .iter()
+        .any(|(side, _)| *side != DiffLineHunkSide::Both);
+    let inline_context = inline_context.filter(|_| has_changes);
+    for (i, (side, data)) in line_hunks.iter().enumerate() {
         let label = match side {
             DiffLineHunkSide::Both => None,
             DiffLineHunkSide::Left => Some("removed"),
@@ -780,6 +2294,16 @@ fn show_color_words_inline_hunks(
             formatter.with_label(label, |formatter| {
                 formatter.with_label("token", |formatter| formatter.write_all(data))
             })?;
+        } else if let Some(num_chars) = inline_context {
+            // Text before the first change and after the last change is
+            // only needed next to the change.
+            let num_head_chars = if i == 0 { 0 } else { num_chars };
+            let num_tail_chars = if i == line_hunks.len() - 1 {
+                0
+            } else {
+                num_chars
+            };
+            show_color_words_elided_text(formatter, data, num_head_chars, num_tail_chars)?;
         } else {
             formatter.write_all(data)?;
         }
@@ -791,6 +2315,34 @@ fn show_color_words_inline_hunks(
     Ok(())
 }
 
+/// Prints the unchanged `data`, keeping only the first `num_head_chars` and
+/// the last `num_tail_chars` characters if the text is longer than that. The
+/// trailing newline is always kept.
+fn show_color_words_elided_text(
+    formatter: &mut dyn Formatter,
+    data: &BStr,
+    num_head_chars: usize,
+    num_tail_chars: usize,
+) -> io::Result<()> {
+    let (text, newline) = match data.strip_suffix(b"\n") {
+        Some(text) => (text, "\n"),
+        None => (data.as_bytes(), ""),
+    };
+    let char_starts = text.char_indices().map(|(start, _, _)| start).collect_vec();
+    // The ellipsis replaces at least one character.
+    if num_head_chars + num_tail_chars >= char_starts.len() {
+        return formatter.write_all(data);
+    }
+    let head_end = char_starts[num_head_chars];
+    let tail_start = char_starts
+        .get(char_starts.len() - num_tail_chars)
+        .map_or(text.len(), |start| *start);
+    formatter.write_all(&text[..head_end])?;
+    write!(formatter, "\u{2026}")?;
+    formatter.write_all(&text[tail_start..])?;
+    write!(formatter, "{newline}")
+}
+
 /// Prints left/right-only line tokens with the given label.
 fn show_color_words_single_sided_line(
     formatter: &mut dyn Formatter,
@@ -860,28 +2412,107 @@ impl FileContent {
     }
 }
 
+// Determine whether a file is binary by whether the first 8k bytes contain a
+// null character; this is the same heuristic used by git as of writing: https://github.com/git/git/blob/eea0e59ffbed6e33d171ace5be13cde9faa41639/xdiff-interface.c#L192-L198
+const PEEK_SIZE: usize = 8000;
+
 fn file_content_for_diff(reader: &mut dyn io::Read) -> io::Result<FileContent> {
-    // If this is a binary file, don't show the full contents.
-    // Determine whether it's binary by whether the first 8k bytes contain a null
-    // character; this is the same heuristic used by git as of writing: https://github.com/git/git/blob/eea0e59ffbed6e33d171ace5be13cde9faa41639/xdiff-interface.c#L192-L198
-    const PEEK_SIZE: usize = 8000;
-    // TODO: currently we look at the whole file, even though for binary files we
-    // only need to know the file size. To change that we'd have to extend all
-    // the data backends to support getting the length.
     let mut contents = vec![];
     reader.read_to_end(&mut contents)?;
 
-    let start = &contents[..PEEK_SIZE.min(contents.len())];
     Ok(FileContent {
-        is_binary: start.contains(&b'\0'),
+        is_binary: is_binary_content(&contents),
         contents,
     })
 }
 
-fn diff_content(
-    path: &RepoPath,
+/// Reads file content from a tree for diff. The content of binary files isn't
+/// shown, so only the start of them is read to detect that they're binary,
+/// unless `force_text` is set. Binary files in a tree can still be compared by
+/// their ids.
+fn tree_file_content_for_diff(
+    reader: &mut dyn io::Read,
+    force_text: bool,
+) -> io::Result<FileContent> {
+    let mut contents = vec![];
+    (&mut *reader)
+        .take(PEEK_SIZE.try_into().unwrap())
+        .read_to_end(&mut contents)?;
+    let is_binary = !force_text && contents.contains(&b'\0');
+    if !is_binary {
+        reader.read_to_end(&mut contents)?;
+    }
+    Ok(FileContent {
+        is_binary,
+        contents,
+    })
+}
+
+fn is_binary_content(contents: &[u8]) -> bool {
+    let start = &contents[..PEEK_SIZE.min(contents.len())];
+    start.contains(&b'\0')
+}
+
+/// File content summary to calculate diff stat.
+enum StatContent {
+    /// Likely text content to be compared line by line.
+    Text(Vec<u8>),
+    /// Likely binary content. Only the size in bytes is recorded.
+    Binary(u64),
+}
+
+impl StatContent {
+    fn size(&self) -> u64 {
+        match self {
+            StatContent::Text(contents) => contents.len() as u64,
+            StatContent::Binary(size) => *size,
+        }
+    }
+}
+
+/// Reads file content for diff stat. Unlike `file_content_for_diff()`, this
+/// doesn't keep the content of binary files in memory.
+fn file_content_for_stat(reader: &mut dyn io::Read, force_text: bool) -> io::Result<StatContent> {
+    let mut contents = vec![];
+    (&mut *reader)
+        .take(PEEK_SIZE.try_into().unwrap())
+        .read_to_end(&mut contents)?;
+    if !force_text && contents.contains(&b'\0') {
+        let rest_size = io::copy(reader, &mut io::sink())?;
+        Ok(StatContent::Binary(contents.len() as u64 + rest_size))
+    } else {
+        reader.read_to_end(&mut contents)?;
+        Ok(StatContent::Text(contents))
+    }
+}
+
+fn stat_content(
+    path: &RepoPath,
+    value: MaterializedTreeValue,
+    conflict_marker_style: ConflictMarkerStyle,
+    force_text: bool,
+) -> BackendResult<StatContent> {
+    match value {
+        MaterializedTreeValue::File { id, mut reader, .. } => {
+            file_content_for_stat(&mut reader, force_text).map_err(|err| BackendError::ReadFile {
+                path: path.to_owned(),
+                id,
+                source: err.into(),
+            })
+        }
+        _ => {
+            let content = diff_content(path, value, conflict_marker_style, force_text)?;
+            debug_assert!(!content.is_binary);
+            Ok(StatContent::Text(content.contents))
+        }
+    }
+}
+
+fn diff_content(
+    path: &RepoPath,
     value: MaterializedTreeValue,
     conflict_marker_style: ConflictMarkerStyle,
+    force_text: bool,
 ) -> BackendResult<FileContent> {
     match value {
         MaterializedTreeValue::Absent => Ok(FileContent::empty()),
@@ -889,12 +2520,15 @@ fn diff_content(
             is_binary: false,
             contents: format!("Access denied: {err}").into_bytes(),
         }),
-        MaterializedTreeValue::File { id, mut reader, .. } => file_content_for_diff(&mut reader)
-            .map_err(|err| BackendError::ReadFile {
-                path: path.to_owned(),
-                id,
-                source: err.into(),
-            }),
+        MaterializedTreeValue::File { id, mut reader, .. } => {
+            tree_file_content_for_diff(&mut reader, force_text).map_err(|err| {
+                BackendError::ReadFile {
+                    path: path.to_owned(),
+                    id,
+                    source: err.into(),
+                }
+            })
+        }
         MaterializedTreeValue::Symlink { id: _, target } => Ok(FileContent {
             // Unix file paths can't contain null bytes.
             is_binary: false,
@@ -944,15 +2578,38 @@ fn basic_diff_file_type(value: &MaterializedTreeValue) -> &'static str {
     }
 }
 
+/// Prints file header followed by the detected language if enabled.
+fn show_color_words_file_header(
+    formatter: &mut dyn Formatter,
+    header: &str,
+    path: &RepoPath,
+    options: &ColorWordsDiffOptions,
+) -> io::Result<()> {
+    let language = options
+        .languages
+        .as_ref()
+        .and_then(|languages| languages.detect(path));
+    formatter.with_label("header", |formatter| {
+        write!(formatter, "{header}")?;
+        if let Some(language) = language {
+            write!(formatter.labeled("language"), " ({language})")?;
+        }
+        writeln!(formatter)
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn show_color_words_diff(
     formatter: &mut dyn Formatter,
     store: &Store,
     tree_diff: BoxStream<CopiesTreeDiffEntry>,
     path_converter: &RepoPathUiConverter,
     options: &ColorWordsDiffOptions,
+    normalizer: Option<&DiffNormalizer>,
+    content_cache: Option<&DiffContentCache>,
     conflict_marker_style: ConflictMarkerStyle,
 ) -> Result<(), DiffRenderError> {
-    let mut diff_stream = materialized_diff_stream(store, tree_diff);
+    let mut diff_stream = normalized_diff_stream(store, tree_diff, normalizer, content_cache);
     async {
         while let Some(MaterializedTreeDiffEntry { path, values }) = diff_stream.next().await {
             let left_path = path.source();
@@ -980,13 +2637,33 @@ pub fn show_color_words_diff(
                 }
                 _ => {}
             }
+            let is_file_conflict = |value: &MaterializedTreeValue| {
+                matches!(value, MaterializedTreeValue::FileConflict { .. })
+            };
+            let conflict_options;
+            let options = if is_file_conflict(&left_value) || is_file_conflict(&right_value) {
+                conflict_options = ColorWordsDiffOptions {
+                    label_conflict_markers: true,
+                    ..options.clone()
+                };
+                &conflict_options
+            } else {
+                options
+            };
             if left_value.is_absent() {
                 let description = basic_diff_file_type(&right_value);
-                writeln!(
-                    formatter.labeled("header"),
-                    "Added {description} {right_ui_path}:"
+                show_color_words_file_header(
+                    formatter,
+                    &format!("Added {description} {right_ui_path}:"),
+                    right_path,
+                    options,
+                )?;
+                let right_content = diff_content(
+                    right_path,
+                    right_value,
+                    conflict_marker_style,
+                    options.line_diff.force_text,
                 )?;
-                let right_content = diff_content(right_path, right_value, conflict_marker_style)?;
                 if right_content.is_empty() {
                     writeln!(formatter.labeled("empty"), "    (empty)")?;
                 } else if right_content.is_binary {
@@ -994,7 +2671,27 @@ pub fn show_color_words_diff(
                 } else {
                     show_color_words_diff_hunks(formatter, &[], &right_content.contents, options)?;
                 }
+            } else if let (
+                MaterializedTreeValue::GitSubmodule(left_id),
+                MaterializedTreeValue::GitSubmodule(right_id),
+            ) = (&left_value, &right_value)
+            {
+                // The contents of the submodule aren't available, so only the
+                // change of the checked out commit is shown.
+                writeln!(
+                    formatter.labeled("header"),
+                    "Submodule {right_ui_path} moved from {} to {}",
+                    short_commit_hash(left_id),
+                    short_commit_hash(right_id)
+                )?;
             } else if right_value.is_present() {
+                let is_symlink_target_change = matches!(
+                    (&left_value, &right_value),
+                    (
+                        MaterializedTreeValue::Symlink { .. },
+                        MaterializedTreeValue::Symlink { .. }
+                    )
+                );
                 let description = match (&left_value, &right_value) {
                     (
                         MaterializedTreeValue::File {
@@ -1048,21 +2745,40 @@ pub fn show_color_words_diff(
                         )
                     }
                 };
-                let left_content = diff_content(left_path, left_value, conflict_marker_style)?;
-                let right_content = diff_content(right_path, right_value, conflict_marker_style)?;
-                if left_path == right_path {
-                    writeln!(
-                        formatter.labeled("header"),
-                        "{description} {right_ui_path}:"
-                    )?;
+                let left_content = diff_content(
+                    left_path,
+                    left_value,
+                    conflict_marker_style,
+                    options.line_diff.force_text,
+                )?;
+                let right_content = diff_content(
+                    right_path,
+                    right_value,
+                    conflict_marker_style,
+                    options.line_diff.force_text,
+                )?;
+                let header = if left_path == right_path {
+                    format!("{description} {right_ui_path}:")
                 } else {
-                    writeln!(
-                        formatter.labeled("header"),
-                        "{description} {right_ui_path} ({left_ui_path} => {right_ui_path}):"
-                    )?;
-                }
+                    format!("{description} {right_ui_path} ({left_ui_path} => {right_ui_path}):")
+                };
+                show_color_words_file_header(formatter, &header, right_path, options)?;
                 if left_content.is_binary || right_content.is_binary {
                     writeln!(formatter.labeled("binary"), "    (binary)")?;
+                } else if is_symlink_target_change {
+                    // A symlink target is a single line, so highlight the
+                    // changed path components inline no matter how many of
+                    // them differ.
+                    let options = ColorWordsDiffOptions {
+                        max_inline_alternation: None,
+                        ..options.clone()
+                    };
+                    show_color_words_diff_hunks(
+                        formatter,
+                        &left_content.contents,
+                        &right_content.contents,
+                        &options,
+                    )?;
                 } else {
                     show_color_words_diff_hunks(
                         formatter,
@@ -1073,11 +2789,18 @@ pub fn show_color_words_diff(
                 }
             } else {
                 let description = basic_diff_file_type(&left_value);
-                writeln!(
-                    formatter.labeled("header"),
-                    "Removed {description} {right_ui_path}:"
+                show_color_words_file_header(
+                    formatter,
+                    &format!("Removed {description} {right_ui_path}:"),
+                    right_path,
+                    options,
+                )?;
+                let left_content = diff_content(
+                    left_path,
+                    left_value,
+                    conflict_marker_style,
+                    options.line_diff.force_text,
                 )?;
-                let left_content = diff_content(left_path, left_value, conflict_marker_style)?;
                 if left_content.is_empty() {
                     writeln!(formatter.labeled("empty"), "    (empty)")?;
                 } else if left_content.is_binary {
@@ -1092,6 +2815,34 @@ pub fn show_color_words_diff(
     .block_on()
 }
 
+fn show_color_words_content_diff(
+    formatter: &mut dyn Formatter,
+    left_label: &str,
+    left: &FileContent,
+    right_label: &str,
+    right: &FileContent,
+    options: &ColorWordsDiffOptions,
+) -> io::Result<()> {
+    if left.contents == right.contents {
+        return Ok(());
+    }
+    formatter.with_label("header", |formatter| {
+        if left_label == right_label {
+            writeln!(formatter, "Modified regular file {right_label}:")
+        } else {
+            writeln!(
+                formatter,
+                "Modified regular file {right_label} ({left_label} => {right_label}):"
+            )
+        }
+    })?;
+    if !options.line_diff.force_text && (left.is_binary || right.is_binary) {
+        writeln!(formatter.labeled("binary"), "    (binary)")
+    } else {
+        show_color_words_diff_hunks(formatter, &left.contents, &right.contents, options)
+    }
+}
+
 pub fn show_file_by_file_diff(
     ui: &Ui,
     formatter: &mut dyn Formatter,
@@ -1107,7 +2858,8 @@ pub fn show_file_by_file_diff(
      -> Result<PathBuf, DiffRenderError> {
         let fs_path = path.to_fs_path(wc_dir)?;
         std::fs::create_dir_all(fs_path.parent().unwrap())?;
-        let content = diff_content(path, value, conflict_marker_style)?;
+        // The tool is given the full content of binary files too.
+        let content = diff_content(path, value, conflict_marker_style, true)?;
         std::fs::write(&fs_path, content.contents)?;
         Ok(fs_path)
     };
@@ -1154,6 +2906,8 @@ pub fn show_file_by_file_diff(
                 &maplit::hashmap! {
                     "left" => left_path.to_str().expect("temp_dir should be valid utf-8"),
                     "right" => right_path.to_str().expect("temp_dir should be valid utf-8"),
+                    "left_path" => left_ui_path.as_str(),
+                    "right_path" => right_ui_path.as_str(),
                 },
             )
             .map_err(DiffRenderError::DiffGenerate)?;
@@ -1163,30 +2917,73 @@ pub fn show_file_by_file_diff(
     .block_on()
 }
 
+const GIT_DUMMY_HASH: &str = "0000000000";
+const GIT_SYMLINK_MODE: &str = "120000";
+// TODO: What should we actually do here?
+const GIT_SUBMODULE_MODE: &str = "040000";
+
+/// Formats the object IDs of the `index` line of a Git diff.
+///
+/// The IDs are abbreviated unless `full_index` is set, in which case a missing
+/// ID is shown as zeros of the same length as the other ID.
+fn format_git_index_hashes(left: &str, right: &str, full_index: bool) -> (String, String) {
+    if !full_index {
+        let abbreviate = |hash: &str| hash[..hash.len().min(GIT_DUMMY_HASH.len())].to_owned();
+        return (abbreviate(left), abbreviate(right));
+    }
+    let len = [left, right]
+        .into_iter()
+        .filter(|hash| *hash != GIT_DUMMY_HASH)
+        .map(|hash| hash.len())
+        .max()
+        .unwrap_or(40);
+    let expand = |hash: &str| {
+        if hash == GIT_DUMMY_HASH {
+            "0".repeat(len)
+        } else {
+            hash.to_owned()
+        }
+    };
+    (expand(left), expand(right))
+}
+
+fn git_file_mode(executable: bool) -> &'static str {
+    if executable {
+        "100755"
+    } else {
+        "100644"
+    }
+}
+
 struct GitDiffPart {
     /// Octal mode string or `None` if the file is absent.
     mode: Option<&'static str>,
+    /// Full hex object ID, or `GIT_DUMMY_HASH` if there's no object.
     hash: String,
     content: FileContent,
 }
 
+impl GitDiffPart {
+    fn absent() -> Self {
+        GitDiffPart {
+            mode: None,
+            hash: GIT_DUMMY_HASH.to_owned(),
+            content: FileContent::empty(),
+        }
+    }
+}
+
 fn git_diff_part(
     path: &RepoPath,
     value: MaterializedTreeValue,
     conflict_marker_style: ConflictMarkerStyle,
+    force_text: bool,
 ) -> Result<GitDiffPart, DiffRenderError> {
-    const DUMMY_HASH: &str = "0000000000";
     let mode;
-    let mut hash;
+    let hash;
     let content;
     match value {
-        MaterializedTreeValue::Absent => {
-            return Ok(GitDiffPart {
-                mode: None,
-                hash: DUMMY_HASH.to_owned(),
-                content: FileContent::empty(),
-            });
-        }
+        MaterializedTreeValue::Absent => return Ok(GitDiffPart::absent()),
         MaterializedTreeValue::AccessDenied(err) => {
             return Err(DiffRenderError::AccessDenied {
                 path: path.as_internal_file_string().to_owned(),
@@ -1198,12 +2995,12 @@ fn git_diff_part(
             executable,
             mut reader,
         } => {
-            mode = if executable { "100755" } else { "100644" };
+            mode = git_file_mode(executable);
             hash = id.hex();
-            content = file_content_for_diff(&mut reader)?;
+            content = tree_file_content_for_diff(&mut reader, force_text)?;
         }
         MaterializedTreeValue::Symlink { id, target } => {
-            mode = "120000";
+            mode = GIT_SYMLINK_MODE;
             hash = id.hex();
             content = FileContent {
                 // Unix file paths can't contain null bytes.
@@ -1212,18 +3009,21 @@ fn git_diff_part(
             };
         }
         MaterializedTreeValue::GitSubmodule(id) => {
-            // TODO: What should we actually do here?
-            mode = "040000";
+            mode = GIT_SUBMODULE_MODE;
             hash = id.hex();
-            content = FileContent::empty();
+            // Like Git, show the checked out commit as the content.
+            content = FileContent {
+                is_binary: false,
+                contents: format!("Subproject commit {hash}\n").into_bytes(),
+            };
         }
         MaterializedTreeValue::FileConflict {
             id: _,
             contents,
             executable,
         } => {
-            mode = if executable { "100755" } else { "100644" };
-            hash = DUMMY_HASH.to_owned();
+            mode = git_file_mode(executable);
+            hash = GIT_DUMMY_HASH.to_owned();
             content = FileContent {
                 is_binary: false, // TODO: are we sure this is never binary?
                 contents: materialize_merge_result_to_bytes(&contents, conflict_marker_style)
@@ -1232,7 +3032,7 @@ fn git_diff_part(
         }
         MaterializedTreeValue::OtherConflict { id } => {
             mode = "100644";
-            hash = DUMMY_HASH.to_owned();
+            hash = GIT_DUMMY_HASH.to_owned();
             content = FileContent {
                 is_binary: false,
                 contents: id.describe().into_bytes(),
@@ -1242,7 +3042,6 @@ fn git_diff_part(
             panic!("Unexpected tree in diff at path {path:?}");
         }
     }
-    hash.truncate(10);
     Ok(GitDiffPart {
         mode: Some(mode),
         hash,
@@ -1250,19 +3049,74 @@ fn git_diff_part(
     })
 }
 
+/// Default minimum similarity index of copies and renames in Git diffs, which
+/// matches the threshold of `git diff -M`.
+const DEFAULT_RENAME_THRESHOLD: u32 = 50;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct UnifiedDiffOptions {
     /// Number of context lines to show.
     pub context: usize,
     /// How lines are tokenized and compared.
     pub line_diff: LineDiffOptions,
+    /// How long context regions between changes are shortened.
+    pub context_gap: Option<ContextGapOptions>,
+    /// Whether to show a line replaced by a single line as one line with
+    /// inline markers.
+    pub compact_single_line: bool,
+    /// Whether to show the line number before each line.
+    pub line_numbers: bool,
+    /// Whether to show the numbers of added and removed lines after each hunk
+    /// header.
+    pub hunk_line_counts: bool,
+    /// Whether to color only the changed words of modified lines as removed or
+    /// added, and the rest of the lines as context.
+    pub word_highlight: bool,
+    /// Number of lines to show at the start and end of each file's diff. Diffs
+    /// longer than twice this are shortened.
+    pub preview: Option<usize>,
+    /// Minimum similarity index in percent to show a copy or rename as such,
+    /// or `None` to show them as added and deleted files.
+    pub rename_threshold: Option<u32>,
+    /// Whether to show the nearest preceding function line after each hunk
+    /// header.
+    pub show_function_context: bool,
+    /// Pattern of the lines shown by `show_function_context`.
+    pub function_pattern: FunctionPattern,
+    /// How lines moved within a file are detected and labeled as `moved`.
+    pub color_moved: ColorMovedMode,
+    /// Whether to show the full object IDs instead of abbreviated ones in the
+    /// `index` lines.
+    pub full_index: bool,
+    /// Whether to omit the `index`, mode change, and copy/rename lines of each
+    /// file.
+    pub minimal_headers: bool,
 }
 
 impl UnifiedDiffOptions {
     pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
         Ok(UnifiedDiffOptions {
             context: settings.get("diff.git.context")?,
-            line_diff: LineDiffOptions::default(),
+            line_diff: LineDiffOptions::from_settings(settings)?,
+            context_gap: ContextGapOptions::from_settings(settings)?,
+            compact_single_line: settings.get_bool("diff.git.compact-single-line")?,
+            line_numbers: false,
+            hunk_line_counts: settings.get_bool("diff.git.hunk-line-counts")?,
+            word_highlight: settings.get_bool("diff.git.word-highlight")?,
+            preview: None,
+            rename_threshold: Some(DEFAULT_RENAME_THRESHOLD),
+            show_function_context: settings.get_bool("diff.git.show-function-context")?,
+            function_pattern: FunctionPattern::new(
+                &settings.get_string("diff.git.function-pattern")?,
+            )
+            .map_err(|err| ConfigGetError::Type {
+                name: "diff.git.function-pattern".to_owned(),
+                error: err.into(),
+                source_path: None,
+            })?,
+            color_moved: ColorMovedMode::No,
+            full_index: false,
+            minimal_headers: false,
         })
     }
 
@@ -1271,31 +3125,182 @@ impl UnifiedDiffOptions {
             self.context = context;
         }
         self.line_diff.merge_args(args);
+        if args.line_numbers {
+            self.line_numbers = true;
+        } else if args.no_line_numbers {
+            self.line_numbers = false;
+        }
+        if let Some(preview) = args.preview {
+            self.preview = Some(preview);
+        }
+        if let Some(threshold) = args.find_renames {
+            self.rename_threshold = Some(threshold);
+        } else if args.no_renames {
+            self.rename_threshold = None;
+        }
+        self.show_function_context |= args.show_function_context;
+        if let Some(mode) = args.color_moved {
+            self.color_moved = mode;
+        }
+        self.full_index = args.full_index;
+        self.minimal_headers = args.minimal_headers;
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-enum DiffLineType {
+/// How moved lines are detected in Git-format diffs.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum ColorMovedMode {
+    /// Don't detect moved lines.
+    #[default]
+    No,
+    /// Any line removed in one change and added in another is moved.
+    Plain,
+}
+
+/// Regex matching the function lines shown after hunk headers.
+#[derive(Clone, Debug)]
+pub struct FunctionPattern(regex::bytes::Regex);
+
+impl FunctionPattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(FunctionPattern(regex::bytes::Regex::new(pattern)?))
+    }
+
+    /// Returns the last line before `line_index` in `lines` that matches the
+    /// pattern, without the trailing whitespace.
+    fn find_before<'a>(&self, lines: &[&'a [u8]], line_index: usize) -> Option<&'a [u8]> {
+        lines[..line_index.min(lines.len())]
+            .iter()
+            .rev()
+            .find(|line| self.0.is_match(line))
+            .map(|line| line.trim_end())
+    }
+}
+
+impl Default for FunctionPattern {
+    /// Matches lines starting with a letter, `$`, or `_`, like Git.
+    fn default() -> Self {
+        FunctionPattern::new("^[[:alpha:]$_]").unwrap()
+    }
+}
+
+impl PartialEq for FunctionPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for FunctionPattern {}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DiffLineType {
     Context,
     Removed,
     Added,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
-enum DiffTokenType {
+pub enum DiffTokenType {
     Matching,
     Different,
 }
 
-type DiffTokenVec<'content> = Vec<(DiffTokenType, &'content [u8])>;
+pub type DiffTokenVec<'content> = Vec<(DiffTokenType, &'content [u8])>;
+
+/// Hunk of unified diff with line ranges (0-based) of the left and right sides.
+pub struct UnifiedDiffHunk<'content> {
+    pub left_line_range: Range<usize>,
+    pub right_line_range: Range<usize>,
+    pub lines: Vec<(DiffLineType, DiffTokenVec<'content>)>,
+}
+
+/// Stable identifier of a removed or added line in [`UnifiedDiffHunk`].
+///
+/// The identifier is derived from the line type, the 1-based line number in
+/// the side the line belongs to (left for removed lines, right for added
+/// lines), and the line content including the trailing newline. It's the first
+/// 16 bytes of the BLAKE2b-512 [`ContentHash`] of the tuple `(sigil,
+/// line_number, content)`, where `sigil` is `b'-'` or `b'+'` and
+/// `line_number` is hashed as `u64`.
+///
+/// The identifier stays the same across diff regenerations as long as the
+/// line keeps its content and position, no matter how other hunks change.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DiffLineId([u8; 16]);
+
+impl DiffLineId {
+    fn new(line_type: DiffLineType, line_number: usize, tokens: &[(DiffTokenType, &[u8])]) -> Self {
+        struct Input<'a> {
+            sigil: u8,
+            line_number: u64,
+            content: &'a [u8],
+        }
+
+        impl ContentHash for Input<'_> {
+            fn hash(&self, state: &mut impl DigestUpdate) {
+                self.sigil.hash(state);
+                self.line_number.hash(state);
+                self.content.hash(state);
+            }
+        }
+
+        let sigil = match line_type {
+            DiffLineType::Context => b' ',
+            DiffLineType::Removed => b'-',
+            DiffLineType::Added => b'+',
+        };
+        let content = tokens
+            .iter()
+            .map(|(_, content)| *content)
+            .collect_vec()
+            .concat();
+        let hash = blake2b_hash(&Input {
+            sigil,
+            line_number: line_number.try_into().unwrap(),
+            content: &content,
+        });
+        DiffLineId(hash[..16].try_into().unwrap())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
 
-struct UnifiedDiffHunk<'content> {
-    left_line_range: Range<usize>,
-    right_line_range: Range<usize>,
-    lines: Vec<(DiffLineType, DiffTokenVec<'content>)>,
+impl fmt::Display for DiffLineId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{b:02x}")?;
+        }
+        Ok(())
+    }
 }
 
 impl<'content> UnifiedDiffHunk<'content> {
+    /// Returns identifiers of the lines, or `None` for context lines.
+    pub fn line_ids(&self) -> Vec<Option<DiffLineId>> {
+        let mut left_line_number = self.left_line_range.start;
+        let mut right_line_number = self.right_line_range.start;
+        self.lines
+            .iter()
+            .map(|(line_type, tokens)| match line_type {
+                DiffLineType::Context => {
+                    left_line_number += 1;
+                    right_line_number += 1;
+                    None
+                }
+                DiffLineType::Removed => {
+                    left_line_number += 1;
+                    Some(DiffLineId::new(*line_type, left_line_number, tokens))
+                }
+                DiffLineType::Added => {
+                    right_line_number += 1;
+                    Some(DiffLineId::new(*line_type, right_line_number, tokens))
+                }
+            })
+            .collect()
+    }
+
     fn extend_context_lines(&mut self, lines: impl IntoIterator<Item = &'content [u8]>) {
         let old_len = self.lines.len();
         self.lines.extend(lines.into_iter().map(|line| {
@@ -1321,20 +3326,59 @@ impl<'content> UnifiedDiffHunk<'content> {
     }
 }
 
-fn unified_diff_hunks<'content>(
+/// Computes unified diff hunks of the given contents.
+///
+/// The line diff is computed up front, but the hunks are built lazily from it
+/// as the returned iterator is consumed, so only the lines of one hunk are
+/// kept in memory at a time. The contents themselves must stay in memory.
+pub fn unified_diff_hunks<'content>(
     left_content: &'content [u8],
     right_content: &'content [u8],
     options: &UnifiedDiffOptions,
-) -> Vec<UnifiedDiffHunk<'content>> {
-    let mut hunks = vec![];
-    let mut current_hunk = UnifiedDiffHunk {
-        left_line_range: 0..0,
-        right_line_range: 0..0,
-        lines: vec![],
-    };
+) -> UnifiedDiffHunkIterator<'content> {
     let diff = diff_by_line([left_content, right_content], &options.line_diff);
-    let mut diff_hunks = diff.hunks().peekable();
-    while let Some(hunk) = diff_hunks.next() {
+    UnifiedDiffHunkIterator {
+        diff_hunks: diff.into_hunks().peekable(),
+        current_hunk: UnifiedDiffHunk {
+            left_line_range: 0..0,
+            right_line_range: 0..0,
+            lines: vec![],
+        },
+        context: options.context,
+        context_gap: options.context_gap,
+        line_diff: options.line_diff.clone(),
+    }
+}
+
+/// Iterator over the hunks computed by [`unified_diff_hunks()`].
+pub struct UnifiedDiffHunkIterator<'content> {
+    diff_hunks: iter::Peekable<DiffHunkIntoIter<'content>>,
+    current_hunk: UnifiedDiffHunk<'content>,
+    context: usize,
+    context_gap: Option<ContextGapOptions>,
+    line_diff: LineDiffOptions,
+}
+
+impl<'content> UnifiedDiffHunkIterator<'content> {
+    /// Returns true if the `hunk` only changes blank lines, and should be
+    /// omitted. Hunks with other changes still show the blank lines.
+    fn is_ignored(&self, hunk: &UnifiedDiffHunk) -> bool {
+        if !self.line_diff.ignore_blank_lines {
+            return false;
+        }
+        let changed = hunk
+            .lines
+            .iter()
+            .filter(|(line_type, _)| *line_type != DiffLineType::Context)
+            .flat_map(|(_, tokens)| concat_line_tokens(tokens))
+            .collect_vec();
+        is_ignored_blank_lines_change(&self.line_diff, &changed, &[])
+    }
+
+    /// Adds the next diff hunk to the current hunk. Returns the previous hunk
+    /// if it's complete.
+    fn process_diff_hunk(&mut self, hunk: DiffHunk<'content>) -> Option<UnifiedDiffHunk<'content>> {
+        let mut finished_hunk = None;
         match hunk.kind {
             DiffHunkKind::Matching => {
                 // Just use the right (i.e. new) content. We could count the
@@ -1342,43 +3386,227 @@ fn unified_diff_hunks<'content>(
                 // context lines should match the displayed content.
                 let [_, right] = hunk.contents[..].try_into().unwrap();
                 let mut lines = right.split_inclusive(|b| *b == b'\n').fuse();
-                if !current_hunk.lines.is_empty() {
+                let has_next = self.diff_hunks.peek().is_some();
+                // A region between changes that would be shown in full can
+                // still be split into two hunks if the context gap is enabled.
+                let num_context = if !self.current_hunk.lines.is_empty() && has_next {
+                    let num_lines = lines.clone().count();
+                    num_context_lines_between_changes(self.context, self.context_gap, num_lines)
+                } else {
+                    self.context
+                };
+                if !self.current_hunk.lines.is_empty() {
                     // The previous hunk line should be either removed/added.
-                    current_hunk.extend_context_lines(lines.by_ref().take(options.context));
+                    self.current_hunk
+                        .extend_context_lines(lines.by_ref().take(num_context));
                 }
-                let before_lines = if diff_hunks.peek().is_some() {
-                    lines.by_ref().rev().take(options.context).collect()
+                let before_lines = if has_next {
+                    lines.by_ref().rev().take(num_context).collect()
                 } else {
                     vec![] // No more hunks
                 };
                 let num_skip_lines = lines.count();
                 if num_skip_lines > 0 {
-                    let left_start = current_hunk.left_line_range.end + num_skip_lines;
-                    let right_start = current_hunk.right_line_range.end + num_skip_lines;
-                    if !current_hunk.lines.is_empty() {
-                        hunks.push(current_hunk);
-                    }
-                    current_hunk = UnifiedDiffHunk {
+                    let left_start = self.current_hunk.left_line_range.end + num_skip_lines;
+                    let right_start = self.current_hunk.right_line_range.end + num_skip_lines;
+                    let next_hunk = UnifiedDiffHunk {
                         left_line_range: left_start..left_start,
                         right_line_range: right_start..right_start,
                         lines: vec![],
                     };
+                    let prev_hunk = mem::replace(&mut self.current_hunk, next_hunk);
+                    if !prev_hunk.lines.is_empty() {
+                        finished_hunk = Some(prev_hunk);
+                    }
                 }
                 // The next hunk should be of DiffHunk::Different type if any.
-                current_hunk.extend_context_lines(before_lines.into_iter().rev());
+                self.current_hunk
+                    .extend_context_lines(before_lines.into_iter().rev());
             }
             DiffHunkKind::Different => {
                 let (left_lines, right_lines) =
                     unzip_diff_hunks_to_lines(Diff::by_word(hunk.contents).hunks());
-                current_hunk.extend_removed_lines(left_lines);
-                current_hunk.extend_added_lines(right_lines);
+                self.current_hunk.extend_removed_lines(left_lines);
+                self.current_hunk.extend_added_lines(right_lines);
+            }
+        }
+        finished_hunk
+    }
+}
+
+impl<'content> Iterator for UnifiedDiffHunkIterator<'content> {
+    type Item = UnifiedDiffHunk<'content>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(hunk) = self.diff_hunks.next() {
+            if let Some(finished_hunk) = self.process_diff_hunk(hunk) {
+                if !self.is_ignored(&finished_hunk) {
+                    return Some(finished_hunk);
+                }
+            }
+        }
+        let last_hunk = UnifiedDiffHunk {
+            left_line_range: 0..0,
+            right_line_range: 0..0,
+            lines: vec![],
+        };
+        let last_hunk = mem::replace(&mut self.current_hunk, last_hunk);
+        (!last_hunk.lines.is_empty() && !self.is_ignored(&last_hunk)).then_some(last_hunk)
+    }
+}
+
+/// Line removed or added by [`file_line_diff()`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChangedLine {
+    /// Either [`DiffLineType::Removed`] or [`DiffLineType::Added`].
+    pub line_type: DiffLineType,
+    /// 1-based line number in the side the line belongs to (left for removed
+    /// lines, right for added lines).
+    pub line_number: usize,
+    /// Line content including the trailing newline if any.
+    pub content: Vec<u8>,
+}
+
+/// Computes the lines removed from and added to the file at `path` between
+/// the `from` and `to` trees.
+///
+/// Returns the removed and added lines in order of their line numbers. This is
+/// a simpler alternative to [`unified_diff_hunks()`] for callers that only need
+/// the net line changes. Symlinks, conflicts, etc. are compared by their
+/// textual representation as in the other diff formats. If either side is a
+/// binary file, [`DiffRenderError::BinaryFile`] is returned.
+pub async fn file_line_diff(
+    repo: &dyn Repo,
+    from: &MergedTree,
+    to: &MergedTree,
+    path: &RepoPath,
+    conflict_marker_style: ConflictMarkerStyle,
+    options: &LineDiffOptions,
+) -> Result<(Vec<ChangedLine>, Vec<ChangedLine>), DiffRenderError> {
+    let store = repo.store();
+    let left_value = materialize_tree_value(store, path, from.path_value(path)?).await?;
+    let right_value = materialize_tree_value(store, path, to.path_value(path)?).await?;
+    let left_content = diff_content(path, left_value, conflict_marker_style, options.force_text)?;
+    let right_content = diff_content(path, right_value, conflict_marker_style, options.force_text)?;
+    if left_content.is_binary || right_content.is_binary {
+        return Err(DiffRenderError::BinaryFile {
+            path: path.to_owned(),
+        });
+    }
+
+    let unified_options = UnifiedDiffOptions {
+        context: 0,
+        line_diff: options.clone(),
+        context_gap: None,
+        compact_single_line: false,
+        line_numbers: false,
+        hunk_line_counts: false,
+        word_highlight: false,
+        preview: None,
+        rename_threshold: None,
+        show_function_context: false,
+        function_pattern: FunctionPattern::default(),
+        color_moved: ColorMovedMode::No,
+        full_index: false,
+        minimal_headers: false,
+    };
+    let mut removed_lines = vec![];
+    let mut added_lines = vec![];
+    for hunk in unified_diff_hunks(
+        &left_content.contents,
+        &right_content.contents,
+        &unified_options,
+    ) {
+        let mut left_line_number = hunk.left_line_range.start;
+        let mut right_line_number = hunk.right_line_range.start;
+        for (line_type, tokens) in &hunk.lines {
+            let (lines, line_number) = match line_type {
+                DiffLineType::Context => continue,
+                DiffLineType::Removed => (&mut removed_lines, &mut left_line_number),
+                DiffLineType::Added => (&mut added_lines, &mut right_line_number),
+            };
+            *line_number += 1;
+            lines.push(ChangedLine {
+                line_type: *line_type,
+                line_number: *line_number,
+                content: tokens
+                    .iter()
+                    .map(|(_, content)| *content)
+                    .collect_vec()
+                    .concat(),
+            });
+        }
+    }
+    Ok((removed_lines, added_lines))
+}
+
+/// Lines added by a change, to limit a diff to the hunks that still contain
+/// them.
+///
+/// Lines are compared by their exact content including the trailing newline.
+/// If a line was modified after the change, it no longer counts as
+/// introduced. Blank lines are ignored since they would match almost any
+/// hunk.
+#[derive(Clone, Debug, Default)]
+pub struct IntroducedLines {
+    lines: HashMap<RepoPathBuf, HashSet<Vec<u8>>>,
+}
+
+impl IntroducedLines {
+    /// Collects the lines added between `from_tree` and `to_tree`. Binary
+    /// files are skipped.
+    pub async fn from_trees(
+        repo: &dyn Repo,
+        from_tree: &MergedTree,
+        to_tree: &MergedTree,
+        conflict_marker_style: ConflictMarkerStyle,
+    ) -> Result<Self, DiffRenderError> {
+        let mut lines = HashMap::new();
+        let mut tree_diff = from_tree.diff_stream(to_tree, &EverythingMatcher);
+        while let Some(TreeDiffEntry { path, values }) = tree_diff.next().await {
+            values?;
+            let (_, added_lines) = match file_line_diff(
+                repo,
+                from_tree,
+                to_tree,
+                &path,
+                conflict_marker_style,
+                &LineDiffOptions::default(),
+            )
+            .await
+            {
+                Ok(diff) => diff,
+                Err(DiffRenderError::BinaryFile { .. }) => continue,
+                Err(err) => return Err(err),
+            };
+            let added_lines: HashSet<_> = added_lines
+                .into_iter()
+                .map(|line| line.content)
+                .filter(|content| !content.iter().all(u8::is_ascii_whitespace))
+                .collect();
+            if !added_lines.is_empty() {
+                lines.insert(path, added_lines);
             }
         }
+        Ok(IntroducedLines { lines })
+    }
+
+    /// Paths of the files with introduced lines.
+    pub fn paths(&self) -> impl Iterator<Item = &RepoPath> {
+        self.lines.keys().map(AsRef::as_ref)
     }
-    if !current_hunk.lines.is_empty() {
-        hunks.push(current_hunk);
+
+    /// Returns true if the `hunk` of the file at `path` adds any of the
+    /// introduced lines.
+    pub fn matches_hunk(&self, path: &RepoPath, hunk: &UnifiedDiffHunk) -> bool {
+        let Some(lines) = self.lines.get(path) else {
+            return false;
+        };
+        hunk.lines
+            .iter()
+            .filter(|(line_type, _)| *line_type == DiffLineType::Added)
+            .any(|(_, tokens)| lines.contains(&concat_line_tokens(tokens)))
     }
-    hunks
 }
 
 /// Splits `(left, right)` hunk pairs into `(left_lines, right_lines)`.
@@ -1438,55 +3666,421 @@ where
     (left_lines, right_lines)
 }
 
+/// Returns the start line number of the `range` as shown in a hunk header.
+///
+/// "If the chunk size is 0, the first number is one lower than one would
+/// expect." - <https://www.artima.com/weblogs/viewpost.jsp?thread=164293>
+///
+/// The POSIX spec also states that "the ending line number of an empty range
+/// shall be the number of the preceding line, or 0 if the range is at the
+/// start of the file."
+/// - <https://pubs.opengroup.org/onlinepubs/9799919799/utilities/diff.html>
+fn to_line_number(range: Range<usize>) -> usize {
+    if range.is_empty() {
+        range.start
+    } else {
+        range.start + 1
+    }
+}
+
 fn show_unified_diff_hunks(
     formatter: &mut dyn Formatter,
     left_content: &[u8],
     right_content: &[u8],
     options: &UnifiedDiffOptions,
 ) -> io::Result<()> {
-    // "If the chunk size is 0, the first number is one lower than one would
-    // expect." - https://www.artima.com/weblogs/viewpost.jsp?thread=164293
-    //
-    // The POSIX spec also states that "the ending line number of an empty range
-    // shall be the number of the preceding line, or 0 if the range is at the
-    // start of the file."
-    // - https://pubs.opengroup.org/onlinepubs/9799919799/utilities/diff.html
-    fn to_line_number(range: Range<usize>) -> usize {
-        if range.is_empty() {
-            range.start
-        } else {
-            range.start + 1
-        }
-    }
+    let hunks = unified_diff_hunks(left_content, right_content, options);
+    write_unified_diff_hunks(formatter, left_content, hunks, options)
+}
 
-    for hunk in unified_diff_hunks(left_content, right_content, options) {
-        writeln!(
-            formatter.labeled("hunk_header"),
-            "@@ -{},{} +{},{} @@",
-            to_line_number(hunk.left_line_range.clone()),
-            hunk.left_line_range.len(),
-            to_line_number(hunk.right_line_range.clone()),
-            hunk.right_line_range.len()
-        )?;
-        for (line_type, tokens) in &hunk.lines {
-            let (label, sigil) = match line_type {
-                DiffLineType::Context => ("context", " "),
-                DiffLineType::Removed => ("removed", "-"),
-                DiffLineType::Added => ("added", "+"),
-            };
-            formatter.with_label(label, |formatter| {
-                write!(formatter, "{sigil}")?;
-                show_diff_line_tokens(formatter, tokens)
-            })?;
-            let (_, content) = tokens.last().expect("hunk line must not be empty");
-            if !content.ends_with(b"\n") {
-                write!(formatter, "\n\\ No newline at end of file\n")?;
-            }
-        }
-    }
+/// Writes the `hunks` of the diff from `left_content`, which is used to find
+/// the function lines of the hunks.
+fn write_unified_diff_hunks<'content>(
+    formatter: &mut dyn Formatter,
+    left_content: &[u8],
+    hunks: impl IntoIterator<Item = UnifiedDiffHunk<'content>>,
+    options: &UnifiedDiffOptions,
+) -> io::Result<()> {
+    let left_lines = if options.show_function_context {
+        left_content.split_inclusive(|b| *b == b'\n').collect_vec()
+    } else {
+        vec![]
+    };
+    if options.preview.is_none() && options.color_moved == ColorMovedMode::No {
+        let moved_lines = HashSet::new();
+        return write_all_unified_diff_hunks(formatter, &left_lines, hunks, &moved_lines, options);
+    }
+    // The hunks have to be buffered to find the moved lines and to count the
+    // lines.
+    let hunks = hunks.into_iter().collect_vec();
+    let moved_lines = match options.color_moved {
+        ColorMovedMode::No => HashSet::new(),
+        ColorMovedMode::Plain => find_moved_lines(&hunks),
+    };
+    let num_lines: usize = hunks.iter().map(|hunk| hunk.lines.len()).sum();
+    let num_preview_lines = match options.preview {
+        Some(n) if num_lines > n * 2 => n,
+        _ => {
+            return write_all_unified_diff_hunks(
+                formatter,
+                &left_lines,
+                hunks,
+                &moved_lines,
+                options,
+            );
+        }
+    };
+    let (head, tail) = preview_unified_diff_hunks(hunks, num_preview_lines);
+    write_all_unified_diff_hunks(formatter, &left_lines, head, &moved_lines, options)?;
+    writeln!(
+        formatter.labeled("hunk_header"),
+        "... (large file, preview only)"
+    )?;
+    write_all_unified_diff_hunks(formatter, &left_lines, tail, &moved_lines, options)
+}
+
+/// Finds the removed and added lines of the `hunks` whose content is added or
+/// removed respectively in another change, like Git's `--color-moved=plain`.
+///
+/// Lines are identified by their type and 1-based line number in the side they
+/// belong to. A change is a run of removed and added lines between context
+/// lines.
+fn find_moved_lines(hunks: &[UnifiedDiffHunk]) -> HashSet<(DiffLineType, usize)> {
+    let mut changed_lines = vec![];
+    let mut change_index = 0;
+    for hunk in hunks {
+        let mut prev_line_type = None;
+        let mut left_line_number = hunk.left_line_range.start;
+        let mut right_line_number = hunk.right_line_range.start;
+        for (line_type, tokens) in &hunk.lines {
+            let line_number = match line_type {
+                DiffLineType::Context => {
+                    left_line_number += 1;
+                    right_line_number += 1;
+                    prev_line_type = Some(*line_type);
+                    continue;
+                }
+                DiffLineType::Removed => {
+                    left_line_number += 1;
+                    left_line_number
+                }
+                DiffLineType::Added => {
+                    right_line_number += 1;
+                    right_line_number
+                }
+            };
+            if prev_line_type.map_or(true, |t| t == DiffLineType::Context) {
+                change_index += 1;
+            }
+            prev_line_type = Some(*line_type);
+            let content = concat_line_tokens(tokens);
+            if content.iter().all(|b| b.is_ascii_whitespace()) {
+                continue;
+            }
+            changed_lines.push((*line_type, line_number, content, change_index));
+        }
+    }
+
+    // Changes removing and adding each line content
+    let mut changes_by_content: HashMap<&[u8], [HashSet<usize>; 2]> = HashMap::new();
+    for (line_type, _, content, change_index) in &changed_lines {
+        let side = usize::from(*line_type == DiffLineType::Added);
+        changes_by_content.entry(content).or_default()[side].insert(*change_index);
+    }
+    changed_lines
+        .iter()
+        .filter(|(line_type, _, content, change_index)| {
+            let other_side = usize::from(*line_type == DiffLineType::Removed);
+            changes_by_content[content.as_slice()][other_side]
+                .iter()
+                .any(|index| index != change_index)
+        })
+        .map(|(line_type, line_number, _, _)| (*line_type, *line_number))
+        .collect()
+}
+
+/// Splits hunks into the first and last `num_lines` lines. The total number of
+/// lines must be greater than `2 * num_lines`.
+fn preview_unified_diff_hunks(
+    hunks: Vec<UnifiedDiffHunk>,
+    num_lines: usize,
+) -> (Vec<UnifiedDiffHunk>, Vec<UnifiedDiffHunk>) {
+    let mut head = vec![];
+    let mut tail = vec![];
+    let mut num_head_lines = 0;
+    let mut hunks = hunks.into_iter();
+    for hunk in hunks.by_ref() {
+        let len = hunk.lines.len();
+        if num_head_lines + len < num_lines {
+            num_head_lines += len;
+            head.push(hunk);
+            continue;
+        }
+        let (first, rest) = split_unified_diff_hunk(hunk, num_lines - num_head_lines);
+        if !first.lines.is_empty() {
+            head.push(first);
+        }
+        // Put the remainder back so the tail can be taken from it.
+        let rest_hunks = iter::once(rest).chain(hunks).collect_vec();
+        let mut num_tail_lines = 0;
+        for hunk in rest_hunks.into_iter().rev() {
+            let len = hunk.lines.len();
+            if num_tail_lines + len <= num_lines {
+                num_tail_lines += len;
+                tail.push(hunk);
+                continue;
+            }
+            let (_, last) = split_unified_diff_hunk(hunk, len - (num_lines - num_tail_lines));
+            if !last.lines.is_empty() {
+                tail.push(last);
+            }
+            break;
+        }
+        tail.reverse();
+        break;
+    }
+    (head, tail)
+}
+
+/// Splits hunk into two at the given line index.
+fn split_unified_diff_hunk(
+    hunk: UnifiedDiffHunk,
+    index: usize,
+) -> (UnifiedDiffHunk, UnifiedDiffHunk) {
+    let mut first_lines = hunk.lines;
+    let second_lines = first_lines.split_off(index);
+    let num_left = first_lines
+        .iter()
+        .filter(|(t, _)| *t != DiffLineType::Added)
+        .count();
+    let num_right = first_lines
+        .iter()
+        .filter(|(t, _)| *t != DiffLineType::Removed)
+        .count();
+    let left_mid = hunk.left_line_range.start + num_left;
+    let right_mid = hunk.right_line_range.start + num_right;
+    let first = UnifiedDiffHunk {
+        left_line_range: hunk.left_line_range.start..left_mid,
+        right_line_range: hunk.right_line_range.start..right_mid,
+        lines: first_lines,
+    };
+    let second = UnifiedDiffHunk {
+        left_line_range: left_mid..hunk.left_line_range.end,
+        right_line_range: right_mid..hunk.right_line_range.end,
+        lines: second_lines,
+    };
+    (first, second)
+}
+
+fn write_all_unified_diff_hunks<'content>(
+    formatter: &mut dyn Formatter,
+    left_lines: &[&[u8]],
+    hunks: impl IntoIterator<Item = UnifiedDiffHunk<'content>>,
+    moved_lines: &HashSet<(DiffLineType, usize)>,
+    options: &UnifiedDiffOptions,
+) -> io::Result<()> {
+    for hunk in hunks {
+        formatter.with_label("hunk_header", |formatter| {
+            write!(
+                formatter,
+                "@@ -{},{} +{},{} @@",
+                to_line_number(hunk.left_line_range.clone()),
+                hunk.left_line_range.len(),
+                to_line_number(hunk.right_line_range.clone()),
+                hunk.right_line_range.len()
+            )?;
+            if options.hunk_line_counts {
+                let count = |line_type| hunk.lines.iter().filter(|(t, _)| *t == line_type).count();
+                let num_added = count(DiffLineType::Added);
+                let num_removed = count(DiffLineType::Removed);
+                write!(formatter, " (+{num_added} -{num_removed})")?;
+            }
+            if options.show_function_context {
+                let function_line = options
+                    .function_pattern
+                    .find_before(left_lines, hunk.left_line_range.start);
+                if let Some(line) = function_line {
+                    write!(formatter, " ")?;
+                    formatter.write_all(line)?;
+                }
+            }
+            writeln!(formatter)
+        })?;
+        let mut lines = hunk.lines.iter().peekable();
+        let mut prev_line_type = None;
+        let mut whitespace_change = false;
+        let mut modified_lines = false;
+        let mut left_line_number = hunk.left_line_range.start;
+        let mut right_line_number = hunk.right_line_range.start;
+        while let Some((line_type, tokens)) = lines.next() {
+            let starts_change = *line_type != DiffLineType::Context
+                && prev_line_type.map_or(true, |t| t == DiffLineType::Context);
+            if *line_type == DiffLineType::Context {
+                if whitespace_change {
+                    formatter.pop_label()?;
+                    whitespace_change = false;
+                }
+            } else if starts_change {
+                // Removed and added lines up to the next context line belong
+                // to the same change.
+                let rest = lines
+                    .clone()
+                    .take_while(|(t, _)| *t != DiffLineType::Context);
+                let changed_lines = iter::once((line_type, tokens))
+                    .chain(rest.map(|(line_type, tokens)| (line_type, tokens)));
+                let mut left = vec![];
+                let mut right = vec![];
+                let mut num_removed = 0;
+                let mut num_added = 0;
+                for (line_type, tokens) in changed_lines {
+                    let side = match line_type {
+                        DiffLineType::Removed => {
+                            num_removed += 1;
+                            &mut left
+                        }
+                        _ => {
+                            num_added += 1;
+                            &mut right
+                        }
+                    };
+                    side.extend(concat_line_tokens(tokens));
+                }
+                if options.line_diff.dim_whitespace_changes
+                    && is_whitespace_only_change(&left, &right)
+                {
+                    formatter.push_label("whitespace_change")?;
+                    whitespace_change = true;
+                }
+                modified_lines = num_removed > 0 && num_added > 0;
+            }
+            if options.compact_single_line
+                && *line_type == DiffLineType::Removed
+                && prev_line_type.map_or(true, |t| t == DiffLineType::Context)
+            {
+                let mut rest = lines.clone();
+                if let Some((DiffLineType::Added, added_tokens)) = rest.next() {
+                    let left_line = concat_line_tokens(tokens);
+                    let right_line = concat_line_tokens(added_tokens);
+                    let is_single = rest
+                        .peek()
+                        .map_or(true, |(t, _)| *t == DiffLineType::Context);
+                    if is_single && left_line.ends_with(b"\n") && right_line.ends_with(b"\n") {
+                        left_line_number += 1;
+                        right_line_number += 1;
+                        if options.line_numbers {
+                            show_unified_line_number(formatter, right_line_number)?;
+                        }
+                        show_compact_line(formatter, &left_line, &right_line)?;
+                        lines.next();
+                        prev_line_type = Some(DiffLineType::Added);
+                        continue;
+                    }
+                }
+            }
+            prev_line_type = Some(*line_type);
+            let (label, sigil, line_number) = match line_type {
+                DiffLineType::Context => {
+                    left_line_number += 1;
+                    right_line_number += 1;
+                    ("context", " ", right_line_number)
+                }
+                DiffLineType::Removed => {
+                    left_line_number += 1;
+                    ("removed", "-", left_line_number)
+                }
+                DiffLineType::Added => {
+                    right_line_number += 1;
+                    ("added", "+", right_line_number)
+                }
+            };
+            let moved = moved_lines.contains(&(*line_type, line_number));
+            if moved {
+                formatter.push_label("moved")?;
+            }
+            if options.word_highlight && modified_lines && *line_type != DiffLineType::Context {
+                formatter.with_label(label, |formatter| {
+                    if options.line_numbers {
+                        show_unified_line_number(formatter, line_number)?;
+                    }
+                    write!(formatter, "{sigil}")
+                })?;
+                show_word_highlighted_line_tokens(formatter, tokens, label)?;
+            } else {
+                formatter.with_label(label, |formatter| {
+                    if options.line_numbers {
+                        show_unified_line_number(formatter, line_number)?;
+                    }
+                    write!(formatter, "{sigil}")?;
+                    show_diff_line_tokens(formatter, tokens)
+                })?;
+            }
+            if moved {
+                formatter.pop_label()?;
+            }
+            let (_, content) = tokens.last().expect("hunk line must not be empty");
+            if !content.ends_with(b"\n") {
+                write!(formatter, "\n\\ No newline at end of file\n")?;
+            }
+        }
+        if whitespace_change {
+            formatter.pop_label()?;
+        }
+    }
     Ok(())
 }
 
+fn show_unified_line_number(formatter: &mut dyn Formatter, line_number: usize) -> io::Result<()> {
+    write!(formatter.labeled("line_number"), "{line_number:>4}")?;
+    write!(formatter, ": ")
+}
+
+fn concat_line_tokens(tokens: &[(DiffTokenType, &[u8])]) -> Vec<u8> {
+    tokens
+        .iter()
+        .flat_map(|(_, content)| *content)
+        .copied()
+        .collect()
+}
+
+/// Prints a line replaced by another line as `~` followed by the words that
+/// were removed and added wrapped in `[-...-]` and `{+...+}` respectively.
+fn show_compact_line(
+    formatter: &mut dyn Formatter,
+    left_line: &[u8],
+    right_line: &[u8],
+) -> io::Result<()> {
+    let [left_line, right_line] =
+        [left_line, right_line].map(|line| line.strip_suffix(b"\n").unwrap_or(line));
+    write!(formatter, "~")?;
+    for hunk in Diff::by_word([left_line, right_line]).hunks() {
+        match hunk.kind {
+            DiffHunkKind::Matching => formatter.write_all(hunk.contents[1])?,
+            DiffHunkKind::Different => {
+                let [left, right] = hunk.contents[..].try_into().unwrap();
+                if !left.is_empty() {
+                    formatter.with_label("removed", |formatter| {
+                        formatter.with_label("token", |formatter| {
+                            write!(formatter, "[-")?;
+                            formatter.write_all(left)?;
+                            write!(formatter, "-]")
+                        })
+                    })?;
+                }
+                if !right.is_empty() {
+                    formatter.with_label("added", |formatter| {
+                        formatter.with_label("token", |formatter| {
+                            write!(formatter, "{{+")?;
+                            formatter.write_all(right)?;
+                            write!(formatter, "+}}")
+                        })
+                    })?;
+                }
+            }
+        }
+    }
+    writeln!(formatter)
+}
+
 fn show_diff_line_tokens(
     formatter: &mut dyn Formatter,
     tokens: &[(DiffTokenType, &[u8])],
@@ -1502,97 +4096,411 @@ fn show_diff_line_tokens(
     Ok(())
 }
 
+/// Prints the tokens of a modified line with only the different tokens labeled
+/// as `label`, and the matching tokens labeled as context.
+fn show_word_highlighted_line_tokens(
+    formatter: &mut dyn Formatter,
+    tokens: &[(DiffTokenType, &[u8])],
+    label: &str,
+) -> io::Result<()> {
+    for (token_type, content) in tokens {
+        match token_type {
+            DiffTokenType::Matching => {
+                formatter.with_label("context", |formatter| formatter.write_all(content))?;
+            }
+            DiffTokenType::Different => formatter.with_label(label, |formatter| {
+                formatter.with_label("token", |formatter| formatter.write_all(content))
+            })?,
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn show_git_diff(
     formatter: &mut dyn Formatter,
     store: &Store,
     tree_diff: BoxStream<CopiesTreeDiffEntry>,
     options: &UnifiedDiffOptions,
+    normalizer: Option<&DiffNormalizer>,
+    content_cache: Option<&DiffContentCache>,
+    introduced_lines: Option<&IntroducedLines>,
     conflict_marker_style: ConflictMarkerStyle,
 ) -> Result<(), DiffRenderError> {
-    let mut diff_stream = materialized_diff_stream(store, tree_diff);
+    let mut diff_stream = normalized_diff_stream(store, tree_diff, normalizer, content_cache);
     async {
         while let Some(MaterializedTreeDiffEntry { path, values }) = diff_stream.next().await {
             let left_path = path.source();
             let right_path = path.target();
-            let left_path_string = left_path.as_internal_file_string();
-            let right_path_string = right_path.as_internal_file_string();
             let (left_value, right_value) = values?;
 
-            let left_part = git_diff_part(left_path, left_value, conflict_marker_style)?;
-            let right_part = git_diff_part(right_path, right_value, conflict_marker_style)?;
+            let force_text = options.line_diff.force_text;
+            let left_part =
+                git_diff_part(left_path, left_value, conflict_marker_style, force_text)?;
+            let right_part =
+                git_diff_part(right_path, right_value, conflict_marker_style, force_text)?;
 
-            formatter.with_label("file_header", |formatter| {
-                writeln!(
+            let type_changed = is_git_type_change(left_part.mode, right_part.mode);
+            let Some(operation) = path.copy_operation() else {
+                // Like Git, show a change of the file type as a deletion and
+                // a creation.
+                if type_changed {
+                    show_git_diff_file(
+                        formatter,
+                        [left_path, left_path],
+                        [left_part, GitDiffPart::absent()],
+                        None,
+                        options,
+                        introduced_lines,
+                    )?;
+                    show_git_diff_file(
+                        formatter,
+                        [right_path, right_path],
+                        [GitDiffPart::absent(), right_part],
+                        None,
+                        options,
+                        introduced_lines,
+                    )?;
+                    continue;
+                }
+                show_git_diff_file(
                     formatter,
-                    "diff --git a/{left_path_string} b/{right_path_string}"
+                    [left_path, right_path],
+                    [left_part, right_part],
+                    None,
+                    options,
+                    introduced_lines,
                 )?;
-                let left_hash = &left_part.hash;
-                let right_hash = &right_part.hash;
-                match (left_part.mode, right_part.mode) {
-                    (None, Some(right_mode)) => {
-                        writeln!(formatter, "new file mode {right_mode}")?;
-                        writeln!(formatter, "index {left_hash}..{right_hash}")?;
-                    }
-                    (Some(left_mode), None) => {
-                        writeln!(formatter, "deleted file mode {left_mode}")?;
-                        writeln!(formatter, "index {left_hash}..{right_hash}")?;
-                    }
-                    (Some(left_mode), Some(right_mode)) => {
-                        if let Some(op) = path.copy_operation() {
-                            let operation = match op {
-                                CopyOperation::Copy => "copy",
-                                CopyOperation::Rename => "rename",
-                            };
-                            // TODO: include similarity index?
-                            writeln!(formatter, "{operation} from {left_path_string}")?;
-                            writeln!(formatter, "{operation} to {right_path_string}")?;
-                        }
-                        if left_mode != right_mode {
-                            writeln!(formatter, "old mode {left_mode}")?;
-                            writeln!(formatter, "new mode {right_mode}")?;
-                            if left_hash != right_hash {
-                                writeln!(formatter, "index {left_hash}..{right_hash}")?;
-                            }
-                        } else if left_hash != right_hash {
-                            writeln!(formatter, "index {left_hash}..{right_hash} {left_mode}")?;
-                        }
-                    }
-                    (None, None) => panic!("either left or right part should be present"),
-                }
-                Ok::<(), DiffRenderError>(())
-            })?;
-
-            if left_part.content.contents == right_part.content.contents {
-                continue; // no content hunks
-            }
-
-            let left_path = match left_part.mode {
-                Some(_) => format!("a/{left_path_string}"),
-                None => "/dev/null".to_owned(),
-            };
-            let right_path = match right_part.mode {
-                Some(_) => format!("b/{right_path_string}"),
-                None => "/dev/null".to_owned(),
+                continue;
             };
-            if left_part.content.is_binary || right_part.content.is_binary {
-                // TODO: add option to emit Git binary diff
-                writeln!(
+            let similarity = git_similarity_index(&left_part, &right_part);
+            // Minimal headers can't express copies and renames.
+            if !options.minimal_headers
+                && !type_changed
+                && options
+                    .rename_threshold
+                    .is_some_and(|threshold| similarity >= threshold)
+            {
+                show_git_diff_file(
                     formatter,
-                    "Binary files {left_path} and {right_path} differ"
+                    [left_path, right_path],
+                    [left_part, right_part],
+                    Some((operation, similarity)),
+                    options,
+                    introduced_lines,
                 )?;
-            } else {
-                formatter.with_label("file_header", |formatter| {
-                    writeln!(formatter, "--- {left_path}")?;
-                    writeln!(formatter, "+++ {right_path}")?;
-                    io::Result::Ok(())
-                })?;
-                show_unified_diff_hunks(
+                continue;
+            }
+            // Show the copy or rename as an added file, and a rename also as
+            // a deleted file.
+            if operation == CopyOperation::Rename {
+                show_git_diff_file(
                     formatter,
-                    &left_part.content.contents,
-                    &right_part.content.contents,
+                    [left_path, left_path],
+                    [left_part, GitDiffPart::absent()],
+                    None,
                     options,
+                    introduced_lines,
                 )?;
             }
+            show_git_diff_file(
+                formatter,
+                [right_path, right_path],
+                [GitDiffPart::absent(), right_part],
+                None,
+                options,
+                introduced_lines,
+            )?;
+        }
+        Ok(())
+    }
+    .block_on()
+}
+
+/// Returns true if the Git modes are of different file types, e.g. a regular
+/// file and a symlink. Changes of the executable bit aren't type changes.
+fn is_git_type_change(left_mode: Option<&str>, right_mode: Option<&str>) -> bool {
+    let (Some(left_mode), Some(right_mode)) = (left_mode, right_mode) else {
+        return false;
+    };
+    let is_special = |mode: &str| mode == GIT_SYMLINK_MODE || mode == GIT_SUBMODULE_MODE;
+    left_mode != right_mode && (is_special(left_mode) || is_special(right_mode))
+}
+
+/// Shows the Git diff of a single file. `copy` is the operation and the
+/// similarity index if the right path was copied or renamed from the left path.
+fn show_git_diff_file(
+    formatter: &mut dyn Formatter,
+    [left_path, right_path]: [&RepoPath; 2],
+    [left_part, right_part]: [GitDiffPart; 2],
+    copy: Option<(CopyOperation, u32)>,
+    options: &UnifiedDiffOptions,
+    introduced_lines: Option<&IntroducedLines>,
+) -> Result<(), DiffRenderError> {
+    let left_path_string = left_path.as_internal_file_string();
+    let right_path_string = right_path.as_internal_file_string();
+    // Files without hunks adding any of the introduced lines are
+    // omitted entirely.
+    let filtered_hunks = if let Some(introduced_lines) = introduced_lines {
+        if left_part.content.is_binary || right_part.content.is_binary {
+            return Ok(());
+        }
+        let hunks = unified_diff_hunks(
+            &left_part.content.contents,
+            &right_part.content.contents,
+            options,
+        )
+        .filter(|hunk| introduced_lines.matches_hunk(right_path, hunk))
+        .collect_vec();
+        if hunks.is_empty() {
+            return Ok(());
+        }
+        Some(hunks)
+    } else {
+        None
+    };
+
+    // Only the start of binary files is read, so compare them by hash.
+    let content_equal = if left_part.content.is_binary || right_part.content.is_binary {
+        left_part.hash == right_part.hash
+    } else {
+        left_part.content.contents == right_part.content.contents
+    };
+    let both_present = left_part.mode.is_some() && right_part.mode.is_some();
+    if options.minimal_headers && both_present && content_equal {
+        return Ok(()); // only mode changes, which aren't shown
+    }
+
+    formatter.with_label("file_header", |formatter| {
+        writeln!(
+            formatter,
+            "diff --git a/{left_path_string} b/{right_path_string}"
+        )?;
+        // Like Git, emit the index line only if the object IDs differ. Both
+        // sides of a changed conflict have the dummy ID, which would make
+        // the index line meaningless.
+        let content_changed = left_part.hash != right_part.hash;
+        let (left_hash, right_hash) =
+            format_git_index_hashes(&left_part.hash, &right_part.hash, options.full_index);
+        match (left_part.mode, right_part.mode) {
+            // `git apply` needs these lines to create and delete files.
+            (None, Some(right_mode)) => {
+                writeln!(formatter, "new file mode {right_mode}")?;
+                if !options.minimal_headers {
+                    writeln!(formatter, "index {left_hash}..{right_hash}")?;
+                }
+            }
+            (Some(left_mode), None) => {
+                writeln!(formatter, "deleted file mode {left_mode}")?;
+                if !options.minimal_headers {
+                    writeln!(formatter, "index {left_hash}..{right_hash}")?;
+                }
+            }
+            (Some(_), Some(_)) if options.minimal_headers => {}
+            (Some(left_mode), Some(right_mode)) => {
+                if let Some((op, similarity)) = copy {
+                    let operation = match op {
+                        CopyOperation::Copy => "copy",
+                        CopyOperation::Rename => "rename",
+                    };
+                    writeln!(formatter, "similarity index {similarity}%")?;
+                    writeln!(formatter, "{operation} from {left_path_string}")?;
+                    writeln!(formatter, "{operation} to {right_path_string}")?;
+                }
+                if left_mode != right_mode {
+                    writeln!(formatter, "old mode {left_mode}")?;
+                    writeln!(formatter, "new mode {right_mode}")?;
+                    if content_changed {
+                        writeln!(formatter, "index {left_hash}..{right_hash}")?;
+                    }
+                } else if content_changed {
+                    writeln!(formatter, "index {left_hash}..{right_hash} {left_mode}")?;
+                }
+            }
+            (None, None) => panic!("either left or right part should be present"),
+        }
+        Ok::<(), DiffRenderError>(())
+    })?;
+
+    if content_equal {
+        return Ok(()); // no content hunks
+    }
+
+    let left_path = match left_part.mode {
+        Some(_) => format!("a/{left_path_string}"),
+        None => "/dev/null".to_owned(),
+    };
+    let right_path = match right_part.mode {
+        Some(_) => format!("b/{right_path_string}"),
+        None => "/dev/null".to_owned(),
+    };
+    if left_part.content.is_binary || right_part.content.is_binary {
+        // TODO: add option to emit Git binary diff
+        writeln!(
+            formatter,
+            "Binary files {left_path} and {right_path} differ"
+        )?;
+    } else {
+        formatter.with_label("file_header", |formatter| {
+            writeln!(formatter, "--- {left_path}")?;
+            writeln!(formatter, "+++ {right_path}")?;
+            io::Result::Ok(())
+        })?;
+        if let Some(hunks) = filtered_hunks {
+            write_unified_diff_hunks(formatter, &left_part.content.contents, hunks, options)?;
+        } else {
+            show_unified_diff_hunks(
+                formatter,
+                &left_part.content.contents,
+                &right_part.content.contents,
+                options,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the percentage of the larger content that's unchanged, like the
+/// similarity index of `git diff -M`. Binary files are only similar if they're
+/// identical.
+fn git_similarity_index(left_part: &GitDiffPart, right_part: &GitDiffPart) -> u32 {
+    let (left, right) = (&left_part.content, &right_part.content);
+    if left.is_binary || right.is_binary {
+        return if left_part.hash == right_part.hash {
+            100
+        } else {
+            0
+        };
+    }
+    let max_len = left.contents.len().max(right.contents.len());
+    if max_len == 0 {
+        return 100;
+    }
+    let diff = Diff::by_line([&left.contents, &right.contents]);
+    let num_matching: usize = diff
+        .hunks()
+        .filter(|hunk| hunk.kind == DiffHunkKind::Matching)
+        .map(|hunk| hunk.contents[0].len())
+        .sum();
+    (num_matching * 100 / max_len).try_into().unwrap()
+}
+
+/// Writes a `diff --git` block comparing two contents that don't have to be
+/// in a tree, e.g. files outside of the workspace. The `left_path` and
+/// `right_path` are used in the synthetic headers, like `git diff --no-index`.
+pub fn show_git_content_diff(
+    formatter: &mut dyn Formatter,
+    left_path: &str,
+    left: &[u8],
+    right_path: &str,
+    right: &[u8],
+    options: &UnifiedDiffOptions,
+) -> io::Result<()> {
+    if left == right {
+        return Ok(());
+    }
+    // Like `git diff --no-index`, absolute paths aren't doubly prefixed.
+    let left_path = left_path.trim_start_matches('/');
+    let right_path = right_path.trim_start_matches('/');
+    formatter.with_label("file_header", |formatter| {
+        writeln!(formatter, "diff --git a/{left_path} b/{right_path}")
+    })?;
+    if !options.line_diff.force_text && (is_binary_content(left) || is_binary_content(right)) {
+        return writeln!(
+            formatter,
+            "Binary files a/{left_path} and b/{right_path} differ"
+        );
+    }
+    formatter.with_label("file_header", |formatter| {
+        writeln!(formatter, "--- a/{left_path}")?;
+        writeln!(formatter, "+++ b/{right_path}")
+    })?;
+    show_unified_diff_hunks(formatter, left, right, options)
+}
+
+/// Hunk object emitted by [`show_json_diff()`].
+#[derive(serde::Serialize)]
+struct JsonDiffHunk<'a> {
+    path: &'a str,
+    old_start: usize,
+    old_lines: usize,
+    new_start: usize,
+    new_lines: usize,
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+/// Writes one JSON object per hunk, each on its own line.
+///
+/// Hunks are written as soon as the file is diffed, so the output can be
+/// consumed incrementally. Binary files and files without content changes
+/// produce no output.
+#[instrument(skip_all)]
+pub fn show_json_diff(
+    formatter: &mut dyn Formatter,
+    store: &Store,
+    tree_diff: BoxStream<CopiesTreeDiffEntry>,
+    options: &UnifiedDiffOptions,
+    normalizer: Option<&DiffNormalizer>,
+    content_cache: Option<&DiffContentCache>,
+    conflict_marker_style: ConflictMarkerStyle,
+) -> Result<(), DiffRenderError> {
+    fn line_text(tokens: &[(DiffTokenType, &[u8])]) -> String {
+        let content = tokens
+            .iter()
+            .map(|(_, content)| *content)
+            .collect_vec()
+            .concat();
+        let content = content.strip_suffix(b"\n").unwrap_or(&content);
+        String::from_utf8_lossy(content).into_owned()
+    }
+
+    let mut diff_stream = normalized_diff_stream(store, tree_diff, normalizer, content_cache);
+    async {
+        while let Some(MaterializedTreeDiffEntry { path, values }) = diff_stream.next().await {
+            let (left_value, right_value) = values?;
+            let force_text = options.line_diff.force_text;
+            let left_part =
+                git_diff_part(path.source(), left_value, conflict_marker_style, force_text)?;
+            let right_part = git_diff_part(
+                path.target(),
+                right_value,
+                conflict_marker_style,
+                force_text,
+            )?;
+            if left_part.content.contents == right_part.content.contents
+                || left_part.content.is_binary
+                || right_part.content.is_binary
+            {
+                continue;
+            }
+            let hunks = unified_diff_hunks(
+                &left_part.content.contents,
+                &right_part.content.contents,
+                options,
+            );
+            for hunk in hunks {
+                let mut added = vec![];
+                let mut removed = vec![];
+                for (line_type, tokens) in &hunk.lines {
+                    match line_type {
+                        DiffLineType::Context => {}
+                        DiffLineType::Removed => removed.push(line_text(tokens)),
+                        DiffLineType::Added => added.push(line_text(tokens)),
+                    }
+                }
+                let json_hunk = JsonDiffHunk {
+                    path: path.target().as_internal_file_string(),
+                    old_start: to_line_number(hunk.left_line_range.clone()),
+                    old_lines: hunk.left_line_range.len(),
+                    new_start: to_line_number(hunk.right_line_range.clone()),
+                    new_lines: hunk.right_line_range.len(),
+                    added,
+                    removed,
+                };
+                serde_json::to_writer(&mut *formatter, &json_hunk).map_err(io::Error::from)?;
+                writeln!(formatter)?;
+            }
         }
         Ok(())
     }
@@ -1633,6 +4541,9 @@ pub fn diff_status_label_and_char(
         }
     } else {
         match (before.is_present(), after.is_present()) {
+            // Like Git's "unmerged" status, since 'C' means copied. Conflicted
+            // additions are still reported as added.
+            (true, true) if !after.is_resolved() => ("conflict", 'U'),
             (true, true) => ("modified", 'M'),
             (false, true) => ("added", 'A'),
             (true, false) => ("removed", 'D'),
@@ -1645,11 +4556,95 @@ pub fn diff_status_label_and_char(
 pub struct DiffStatOptions {
     /// How lines are tokenized and compared.
     pub line_diff: LineDiffOptions,
+    /// Whether to show the changed lines as a percentage of the file.
+    pub show_fraction: bool,
+    /// Whether to group the digits of large numbers with commas.
+    pub group_digits: bool,
+    /// Width to lay out the histogram for instead of the terminal width.
+    pub width: Option<usize>,
+    /// Maximum length of the bar of `+` and `-` characters.
+    pub max_graph_width: Option<usize>,
+    /// Whether to list the created, deleted, renamed, and copied files and
+    /// the changes of file types after the histogram.
+    pub show_summary: bool,
+    /// Order of the files.
+    pub sort: DiffStatSort,
 }
 
 impl DiffStatOptions {
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
+        Ok(DiffStatOptions {
+            line_diff: LineDiffOptions::default(),
+            show_fraction: false,
+            group_digits: settings.get_bool("diff.stat.group-digits")?,
+            width: None,
+            max_graph_width: settings.get("diff.stat.graph-width").optional()?,
+            show_summary: false,
+            sort: DiffStatSort::Path,
+        })
+    }
+
     fn merge_args(&mut self, args: &DiffFormatArgs) {
         self.line_diff.merge_args(args);
+        self.show_fraction = args.fraction;
+        self.show_summary = args.summary;
+        if let Some(width) = args.stat_width {
+            self.width = Some(width);
+        }
+        if let Some(sort) = args.stat_sort {
+            self.sort = sort;
+        }
+    }
+}
+
+/// Order of the files in diff stats.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum DiffStatSort {
+    /// Sort by path.
+    #[default]
+    Path,
+    /// Sort by the number of added lines.
+    Added,
+    /// Sort by the number of removed lines.
+    Removed,
+    /// Sort by the total number of changed lines.
+    Total,
+}
+
+impl DiffStatSort {
+    /// Returns the count of added and removed lines to sort the files by in
+    /// descending order, or `None` if the files are sorted by path.
+    fn count_fn(self) -> Option<fn(usize, usize) -> usize> {
+        match self {
+            DiffStatSort::Path => None,
+            DiffStatSort::Added => Some(|added, _| added),
+            DiffStatSort::Removed => Some(|_, removed| removed),
+            DiffStatSort::Total => Some(|added, removed| added + removed),
+        }
+    }
+
+    /// Returns true if the files are sorted by path, which is the order of the
+    /// tree diff.
+    fn is_path_order(self) -> bool {
+        self.count_fn().is_none()
+    }
+
+    /// Sorts the `files` by this key in descending order, then by target path.
+    ///
+    /// `key` returns the number of added and removed lines and the target path
+    /// of a file. The files are left as is if they're sorted by path, since
+    /// tree diffs are already sorted by path.
+    fn sort_files<T>(self, files: &mut [T], key: impl Fn(&T) -> (usize, usize, &RepoPath)) {
+        let Some(count) = self.count_fn() else {
+            return;
+        };
+        files.sort_by(|a, b| {
+            let (a_added, a_removed, a_path) = key(a);
+            let (b_added, b_removed, b_path) = key(b);
+            count(b_added, b_removed)
+                .cmp(&count(a_added, a_removed))
+                .then_with(|| a_path.cmp(b_path))
+        });
     }
 }
 
@@ -1664,19 +4659,38 @@ impl DiffStats {
         store: &Store,
         tree_diff: BoxStream<'_, CopiesTreeDiffEntry>,
         options: &DiffStatOptions,
+        normalizer: Option<&DiffNormalizer>,
+        content_cache: Option<&DiffContentCache>,
         conflict_marker_style: ConflictMarkerStyle,
     ) -> BackendResult<Self> {
-        let entries = materialized_diff_stream(store, tree_diff)
+        let entries = normalized_diff_stream(store, tree_diff, normalizer, content_cache)
             .map(|MaterializedTreeDiffEntry { path, values }| {
                 let (left, right) = values?;
-                let left_content = diff_content(path.source(), left, conflict_marker_style)?;
-                let right_content = diff_content(path.target(), right, conflict_marker_style)?;
-                let stat = get_diff_stat_entry(path, &left_content, &right_content, options);
+                let file_types = stat_file_types(&left, &right);
+                if is_conflict_value(&left) || is_conflict_value(&right) {
+                    return Ok(DiffStatEntry::conflict(path, file_types));
+                }
+                let force_text = options.line_diff.force_text;
+                let left_content =
+                    stat_content(path.source(), left, conflict_marker_style, force_text)?;
+                let right_content =
+                    stat_content(path.target(), right, conflict_marker_style, force_text)?;
+                let stat =
+                    get_diff_stat_entry(path, &left_content, &right_content, file_types, options);
                 BackendResult::Ok(stat)
             })
             .try_collect()
             .await?;
-        Ok(DiffStats { entries })
+        let mut stats = DiffStats { entries };
+        stats.sort(options.sort);
+        Ok(stats)
+    }
+
+    /// Sorts the stats by the given key in descending order, then by path.
+    fn sort(&mut self, sort: DiffStatSort) {
+        sort.sort_files(&mut self.entries, |entry| {
+            (entry.added, entry.removed, entry.path.target())
+        });
     }
 
     /// List of stats per file.
@@ -1700,46 +4714,117 @@ pub struct DiffStatEntry {
     pub path: CopiesTreeDiffEntryPath,
     pub added: usize,
     pub removed: usize,
+    /// Number of lines in the right content, or in the left content if the
+    /// file was deleted.
+    pub total_lines: usize,
+    /// Sizes of the left and right contents in bytes if either side is binary.
+    /// Lines aren't counted for binary files.
+    pub binary_sizes: Option<(u64, u64)>,
+    /// Types of the left and right sides as described by
+    /// `basic_diff_file_type()`, or `None` if the side is absent.
+    pub file_types: [Option<&'static str>; 2],
+    /// Whether either side is conflicted. Lines aren't counted for conflicts
+    /// since the materialized conflict markers would inflate the numbers.
+    pub is_conflict: bool,
+}
+
+impl DiffStatEntry {
+    fn conflict(path: CopiesTreeDiffEntryPath, file_types: [Option<&'static str>; 2]) -> Self {
+        DiffStatEntry {
+            path,
+            added: 0,
+            removed: 0,
+            total_lines: 0,
+            binary_sizes: None,
+            file_types,
+            is_conflict: true,
+        }
+    }
+}
+
+fn is_conflict_value(value: &MaterializedTreeValue) -> bool {
+    matches!(
+        value,
+        MaterializedTreeValue::FileConflict { .. } | MaterializedTreeValue::OtherConflict { .. }
+    )
+}
+
+fn stat_file_types(
+    left: &MaterializedTreeValue,
+    right: &MaterializedTreeValue,
+) -> [Option<&'static str>; 2] {
+    [left, right].map(|value| (!value.is_absent()).then(|| basic_diff_file_type(value)))
 }
 
 fn get_diff_stat_entry(
     path: CopiesTreeDiffEntryPath,
-    left_content: &FileContent,
-    right_content: &FileContent,
+    left_content: &StatContent,
+    right_content: &StatContent,
+    file_types: [Option<&'static str>; 2],
     options: &DiffStatOptions,
 ) -> DiffStatEntry {
-    // TODO: this matches git's behavior, which is to count the number of newlines
-    // in the file. but that behavior seems unhelpful; no one really cares how
-    // many `0x0a` characters are in an image.
-    let diff = diff_by_line(
-        [&left_content.contents, &right_content.contents],
-        &options.line_diff,
-    );
-    let mut added = 0;
-    let mut removed = 0;
-    for hunk in diff.hunks() {
-        match hunk.kind {
-            DiffHunkKind::Matching => {}
-            DiffHunkKind::Different => {
-                let [left, right] = hunk.contents[..].try_into().unwrap();
-                removed += left.split_inclusive(|b| *b == b'\n').count();
-                added += right.split_inclusive(|b| *b == b'\n').count();
-            }
+    let (left, right) = match (left_content, right_content) {
+        (StatContent::Text(left), StatContent::Text(right)) => (left, right),
+        _ => {
+            // Like Git, report size change instead of the number of newlines,
+            // which is meaningless for binary files.
+            return DiffStatEntry {
+                path,
+                added: 0,
+                removed: 0,
+                total_lines: 0,
+                binary_sizes: Some((left_content.size(), right_content.size())),
+                file_types,
+                is_conflict: false,
+            };
         }
-    }
+    };
+    let diff = diff_by_line([left, right], &options.line_diff);
+    let (added, removed) = count_changed_lines(diff.into_hunks().filter(|hunk| match hunk.kind {
+        DiffHunkKind::Matching => true,
+        DiffHunkKind::Different => {
+            let [left, right] = hunk.contents[..].try_into().unwrap();
+            !is_ignored_blank_lines_change(&options.line_diff, left, right)
+        }
+    }));
+    let is_deleted = file_types[1].is_none();
+    let total_content = if is_deleted { left } else { right };
     DiffStatEntry {
         path,
         added,
         removed,
+        total_lines: total_content.split_inclusive(|b| *b == b'\n').count(),
+        binary_sizes: None,
+        file_types,
+        is_conflict: false,
     }
 }
 
+/// Shortens `path` to `max_width` by replacing leading characters with `...`.
+///
+/// Like Git, the path is cut at a path separator if the kept part contains
+/// one, so that the remaining components are complete.
+fn elide_path_start(path: &str, max_width: usize) -> (Cow<'_, str>, usize) {
+    let (elided, width) = text_util::elide_start(path, "...", max_width);
+    // The path is truncated only if a new string with the ellipsis is built.
+    if let Cow::Owned(elided) = &elided {
+        let tail = &elided["...".len()..];
+        if let Some(pos) = tail.find(std::path::MAIN_SEPARATOR).filter(|&pos| pos > 0) {
+            let tail = &tail[pos..];
+            return (Cow::Owned(format!("...{tail}")), "...".len() + tail.width());
+        }
+    }
+    (elided, width)
+}
+
 pub fn show_diff_stats(
     formatter: &mut dyn Formatter,
     stats: &DiffStats,
+    options: &DiffStatOptions,
     path_converter: &RepoPathUiConverter,
     display_width: usize,
 ) -> io::Result<()> {
+    let display_width = options.width.unwrap_or(display_width);
     let ui_paths = stats
         .entries()
         .iter()
@@ -1759,52 +4844,214 @@ pub fn show_diff_stats(
         .max()
         .unwrap_or(0);
 
-    let number_padding = max_diffs.to_string().len();
+    let number_padding = format_count(max_diffs, options.group_digits).len();
+    let fraction_width = if options.show_fraction {
+        " (100% of file)".len()
+    } else {
+        0
+    };
     // 4 characters padding for the graph
-    let available_width = display_width.saturating_sub(4 + " | ".len() + number_padding);
+    let available_width =
+        display_width.saturating_sub(4 + " | ".len() + number_padding + fraction_width);
     // Always give at least a tiny bit of room
     let available_width = max(available_width, 5);
     let max_path_width = max_path_width.clamp(3, (0.7 * available_width as f64) as usize);
+    // replace start of path with ellipsis if the path is too long
+    let elided_paths = ui_paths
+        .iter()
+        .map(|ui_path| elide_path_start(ui_path, max_path_width))
+        .collect_vec();
+    // Paths cut at a path separator may be shorter than the limit.
+    let max_path_width = elided_paths
+        .iter()
+        .map(|(_, width)| *width)
+        .max()
+        .map_or(max_path_width, |width| max(width, 3));
     let max_bar_length = available_width.saturating_sub(max_path_width);
+    let max_bar_length = options
+        .max_graph_width
+        .map_or(max_bar_length, |width| max_bar_length.min(width));
     let factor = if max_diffs < max_bar_length {
         1.0
     } else {
         max_bar_length as f64 / max_diffs as f64
     };
 
-    for (stat, ui_path) in iter::zip(stats.entries(), &ui_paths) {
+    for (stat, (path, path_width)) in iter::zip(stats.entries(), elided_paths) {
+        let path_pad_width = max_path_width - path_width;
+        if stat.is_conflict {
+            write!(formatter, "{path}{:path_pad_width$} | ", "")?;
+            writeln!(formatter.labeled("conflict"), "conflict")?;
+            continue;
+        }
+        if let Some((left_size, right_size)) = stat.binary_sizes {
+            writeln!(
+                formatter,
+                "{path}{:path_pad_width$} | Bin {} -> {} bytes",
+                "", // pad to max_path_width
+                format_count(left_size, options.group_digits),
+                format_count(right_size, options.group_digits),
+            )?;
+            continue;
+        }
         let bar_added = (stat.added as f64 * factor).ceil() as usize;
         let bar_removed = (stat.removed as f64 * factor).ceil() as usize;
-        // replace start of path with ellipsis if the path is too long
-        let (path, path_width) = text_util::elide_start(ui_path, "...", max_path_width);
-        let path_pad_width = max_path_width - path_width;
         write!(
             formatter,
             "{path}{:path_pad_width$} | {:>number_padding$}{}",
             "", // pad to max_path_width
-            stat.added + stat.removed,
-            if bar_added + bar_removed > 0 { " " } else { "" },
+            format_count(stat.added + stat.removed, options.group_digits),
+            if bar_added + bar_removed > 0 || options.show_fraction {
+                " "
+            } else {
+                ""
+            },
         )?;
+        if options.show_fraction {
+            write!(
+                formatter,
+                "({:>3}% of file){}",
+                changed_percentage(stat),
+                if bar_added + bar_removed > 0 { " " } else { "" },
+            )?;
+        }
         write!(formatter.labeled("added"), "{}", "+".repeat(bar_added))?;
         writeln!(formatter.labeled("removed"), "{}", "-".repeat(bar_removed))?;
     }
+    show_diff_stats_summary(formatter, stats, options)?;
+    if options.show_summary {
+        show_diff_stats_structural_changes(formatter, stats, path_converter)?;
+    }
+    Ok(())
+}
 
+/// Lists the created, deleted, renamed, and copied files, and the files whose
+/// type changed, like `git diff --stat --summary`.
+fn show_diff_stats_structural_changes(
+    formatter: &mut dyn Formatter,
+    stats: &DiffStats,
+    path_converter: &RepoPathUiConverter,
+) -> io::Result<()> {
+    for stat in stats.entries() {
+        let path = &stat.path;
+        match (path.copy_operation(), stat.file_types) {
+            (Some(op), _) => {
+                let (label, verb) = match op {
+                    CopyOperation::Copy => ("copied", "copy"),
+                    CopyOperation::Rename => ("renamed", "rename"),
+                };
+                let ui_path = path_converter.format_copied_path(path.source(), path.target());
+                writeln!(formatter.labeled(label), "{verb} {ui_path}")?;
+            }
+            (None, [None, Some(file_type)]) => {
+                let ui_path = path_converter.format_file_path(path.target());
+                writeln!(formatter.labeled("added"), "create {file_type} {ui_path}")?;
+            }
+            (None, [Some(file_type), None]) => {
+                let ui_path = path_converter.format_file_path(path.target());
+                writeln!(formatter.labeled("removed"), "delete {file_type} {ui_path}")?;
+            }
+            (None, [Some(left_type), Some(right_type)]) if left_type != right_type => {
+                let ui_path = path_converter.format_file_path(path.target());
+                writeln!(
+                    formatter.labeled("modified"),
+                    "change {left_type} -> {right_type} {ui_path}"
+                )?;
+            }
+            (None, _) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of changed lines relative to the total number of lines in
+/// percent, capped at 100.
+///
+/// A modified line is both removed and added, so the larger of the two counts
+/// is used as the number of changed lines.
+fn changed_percentage(stat: &DiffStatEntry) -> usize {
+    let changed = max(stat.added, stat.removed);
+    if changed == 0 {
+        return 0;
+    }
+    // An emptied file has no lines left, but all of its content changed.
+    (changed * 100)
+        .checked_div(stat.total_lines)
+        .map_or(100, |percentage| percentage.min(100))
+}
+
+/// Prints the total number of changed files, insertions, and deletions.
+pub fn show_diff_stats_summary(
+    formatter: &mut dyn Formatter,
+    stats: &DiffStats,
+    options: &DiffStatOptions,
+) -> io::Result<()> {
     let total_added = stats.count_total_added();
     let total_removed = stats.count_total_removed();
     let total_files = stats.entries().len();
     writeln!(
         formatter.labeled("stat-summary"),
         "{} file{} changed, {} insertion{}(+), {} deletion{}(-)",
-        total_files,
+        format_count(total_files, options.group_digits),
         if total_files == 1 { "" } else { "s" },
-        total_added,
+        format_count(total_added, options.group_digits),
         if total_added == 1 { "" } else { "s" },
-        total_removed,
+        format_count(total_removed, options.group_digits),
         if total_removed == 1 { "" } else { "s" },
     )?;
     Ok(())
 }
 
+/// Prints the exact numbers of added and removed lines per file, separated
+/// by tabs, followed by the totals.
+///
+/// Unlike [`show_diff_stats()`], the output doesn't depend on the terminal, so
+/// it's suitable for scripts.
+pub fn show_diff_numstat(
+    formatter: &mut dyn Formatter,
+    stats: &DiffStats,
+    path_converter: &RepoPathUiConverter,
+) -> io::Result<()> {
+    for stat in stats.entries() {
+        let ui_path = if stat.path.copy_operation().is_some() {
+            path_converter.format_copied_path(stat.path.source(), stat.path.target())
+        } else {
+            path_converter.format_file_path(stat.path.target())
+        };
+        if stat.binary_sizes.is_some() || stat.is_conflict {
+            writeln!(formatter, "-\t-\t{ui_path}")?;
+        } else {
+            writeln!(formatter, "{}\t{}\t{ui_path}", stat.added, stat.removed)?;
+        }
+    }
+    writeln!(
+        formatter,
+        "{}\t{}\t",
+        stats.count_total_added(),
+        stats.count_total_removed()
+    )?;
+    Ok(())
+}
+
+/// Formats the `count` in decimal, optionally with a comma between every three
+/// digits.
+///
+/// The output doesn't depend on the locale, so it's stable across systems.
+fn format_count(count: impl fmt::Display, group_digits: bool) -> String {
+    let digits = count.to_string();
+    if !group_digits {
+        return digits;
+    }
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
 pub fn show_types(
     formatter: &mut dyn Formatter,
     mut tree_diff: BoxStream<CopiesTreeDiffEntry>,
@@ -1826,6 +5073,49 @@ pub fn show_types(
     .block_on()
 }
 
+/// Like [`show_types()`], but spells out the type names.
+pub fn show_long_types(
+    formatter: &mut dyn Formatter,
+    mut tree_diff: BoxStream<CopiesTreeDiffEntry>,
+    path_converter: &RepoPathUiConverter,
+) -> Result<(), DiffRenderError> {
+    async {
+        while let Some(CopiesTreeDiffEntry { path, values }) = tree_diff.next().await {
+            let (before, after) = values?;
+            writeln!(
+                formatter.labeled("modified"),
+                "{} -> {} {}",
+                diff_type_name(&before),
+                diff_type_name(&after),
+                path_converter.format_copied_path(path.source(), path.target())
+            )?;
+        }
+        Ok(())
+    }
+    .block_on()
+}
+
+/// Returns the name of the type of `value`, using the same names as
+/// `basic_diff_file_type()`.
+fn diff_type_name(value: &MergedTreeValue) -> &'static str {
+    match value.as_resolved() {
+        Some(None) => "absent",
+        Some(Some(TreeValue::File { executable, .. })) => {
+            if *executable {
+                "executable file"
+            } else {
+                "regular file"
+            }
+        }
+        Some(Some(TreeValue::Symlink(_))) => "symlink",
+        Some(Some(TreeValue::GitSubmodule(_))) => "Git submodule",
+        None => "conflict",
+        Some(Some(TreeValue::Tree(_))) | Some(Some(TreeValue::Conflict(_))) => {
+            panic!("Unexpected {value:?} in diff")
+        }
+    }
+}
+
 fn diff_summary_char(value: &MergedTreeValue) -> char {
     match value.as_resolved() {
         Some(None) => '-',
@@ -1839,6 +5129,249 @@ fn diff_summary_char(value: &MergedTreeValue) -> char {
     }
 }
 
+pub fn show_metadata_changes(
+    formatter: &mut dyn Formatter,
+    mut tree_diff: BoxStream<CopiesTreeDiffEntry>,
+    path_converter: &RepoPathUiConverter,
+) -> Result<(), DiffRenderError> {
+    async {
+        while let Some(CopiesTreeDiffEntry { path, values }) = tree_diff.next().await {
+            let (before, after) = values?;
+            let mut changes = vec![];
+            let (before_type, after_type) = (diff_summary_char(&before), diff_summary_char(&after));
+            if before_type != after_type {
+                changes.push(format!("type {before_type} -> {after_type}"));
+            }
+            // Conflicts have no single mode
+            if let (Some(before_mode), Some(after_mode)) =
+                (git_mode_for_value(&before), git_mode_for_value(&after))
+            {
+                if before_mode != after_mode {
+                    changes.push(format!("mode {before_mode} -> {after_mode}"));
+                }
+            }
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(
+                formatter.labeled("modified"),
+                "{}: {}",
+                path_converter.format_copied_path(path.source(), path.target()),
+                changes.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+    .block_on()
+}
+
+/// Path object emitted by [`show_json_stat()`].
+#[derive(serde::Serialize)]
+struct JsonStatEntry {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_path: Option<String>,
+    status: &'static str,
+    old: Option<JsonTreeValue>,
+    new: Option<JsonTreeValue>,
+    binary: bool,
+    added: Option<usize>,
+    removed: Option<usize>,
+}
+
+/// One side of a [`JsonStatEntry`], or one term of a conflict.
+#[derive(serde::Serialize)]
+struct JsonTreeValue {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    mode: Option<&'static str>,
+    id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conflict: Option<JsonConflict>,
+}
+
+/// Terms of a conflicted [`JsonTreeValue`]. Absent terms are null.
+#[derive(serde::Serialize)]
+struct JsonConflict {
+    removes: Vec<Option<JsonTreeValue>>,
+    adds: Vec<Option<JsonTreeValue>>,
+}
+
+impl JsonTreeValue {
+    fn resolved(kind: &'static str, mode: &'static str, id: String) -> Self {
+        JsonTreeValue {
+            kind,
+            mode: Some(mode),
+            id: Some(id),
+            conflict: None,
+        }
+    }
+
+    fn from_tree_value(value: &TreeValue) -> Self {
+        match value {
+            TreeValue::File { id, executable, .. } => {
+                Self::resolved("file", git_file_mode(*executable), id.hex())
+            }
+            TreeValue::Symlink(id) => Self::resolved("symlink", GIT_SYMLINK_MODE, id.hex()),
+            TreeValue::GitSubmodule(id) => {
+                Self::resolved("git-submodule", GIT_SUBMODULE_MODE, id.hex())
+            }
+            TreeValue::Tree(_) | TreeValue::Conflict(_) => {
+                panic!("Unexpected {value:?} in diff")
+            }
+        }
+    }
+
+    fn conflict<T>(terms: &Merge<Option<T>>, to_json: impl Fn(&T) -> Self) -> Self {
+        let to_json = |term: &Option<T>| term.as_ref().map(&to_json);
+        JsonTreeValue {
+            kind: "conflict",
+            mode: None,
+            id: None,
+            conflict: Some(JsonConflict {
+                removes: terms.removes().map(to_json).collect(),
+                adds: terms.adds().map(to_json).collect(),
+            }),
+        }
+    }
+
+    /// Describes the value, or returns `None` if it's absent.
+    fn from_materialized(value: &MaterializedTreeValue) -> Option<Self> {
+        match value {
+            MaterializedTreeValue::Absent => None,
+            MaterializedTreeValue::AccessDenied(_) => Some(JsonTreeValue {
+                kind: "access-denied",
+                mode: None,
+                id: None,
+                conflict: None,
+            }),
+            MaterializedTreeValue::File { id, executable, .. } => {
+                Some(Self::resolved("file", git_file_mode(*executable), id.hex()))
+            }
+            MaterializedTreeValue::Symlink { id, .. } => {
+                Some(Self::resolved("symlink", GIT_SYMLINK_MODE, id.hex()))
+            }
+            MaterializedTreeValue::GitSubmodule(id) => Some(Self::resolved(
+                "git-submodule",
+                GIT_SUBMODULE_MODE,
+                id.hex(),
+            )),
+            MaterializedTreeValue::FileConflict { id, executable, .. } => {
+                Some(Self::conflict(id, |id| {
+                    Self::resolved("file", git_file_mode(*executable), id.hex())
+                }))
+            }
+            MaterializedTreeValue::OtherConflict { id } => {
+                Some(Self::conflict(id, Self::from_tree_value))
+            }
+            MaterializedTreeValue::Tree(_) => panic!("Unexpected tree in diff"),
+        }
+    }
+}
+
+/// Writes one JSON object per changed path, each on its own line.
+///
+/// Lines are counted like [`DiffStats::calculate()`], except that conflicts
+/// aren't materialized, so their lines aren't counted. Entries are written as
+/// soon as the path is diffed unless they're sorted by a count of lines.
+#[instrument(skip_all)]
+pub fn show_json_stat(
+    formatter: &mut dyn Formatter,
+    store: &Store,
+    tree_diff: BoxStream<CopiesTreeDiffEntry>,
+    options: &DiffStatOptions,
+    normalizer: Option<&DiffNormalizer>,
+    content_cache: Option<&DiffContentCache>,
+    conflict_marker_style: ConflictMarkerStyle,
+) -> Result<(), DiffRenderError> {
+    let write_entry = |formatter: &mut dyn Formatter, entry: &JsonStatEntry| {
+        serde_json::to_writer(&mut *formatter, entry).map_err(io::Error::from)?;
+        writeln!(formatter)
+    };
+    let is_path_order = options.sort.is_path_order();
+    let mut sorted_entries = vec![];
+    let mut diff_stream = normalized_diff_stream(store, tree_diff, normalizer, content_cache);
+    async {
+        while let Some(MaterializedTreeDiffEntry { path, values }) = diff_stream.next().await {
+            let (left, right) = values?;
+            let status = match path.copy_operation() {
+                Some(CopyOperation::Copy) => "copied",
+                Some(CopyOperation::Rename) => "renamed",
+                None if left.is_absent() => "added",
+                None if right.is_absent() => "removed",
+                None => "modified",
+            };
+            let old = JsonTreeValue::from_materialized(&left);
+            let new = JsonTreeValue::from_materialized(&right);
+            let is_conflict = |value: &Option<JsonTreeValue>| {
+                value.as_ref().is_some_and(|value| value.conflict.is_some())
+            };
+            let (binary, counts) = if is_conflict(&old) || is_conflict(&new) {
+                (false, None)
+            } else {
+                let file_types = stat_file_types(&left, &right);
+                let force_text = options.line_diff.force_text;
+                let left_content =
+                    stat_content(path.source(), left, conflict_marker_style, force_text)?;
+                let right_content =
+                    stat_content(path.target(), right, conflict_marker_style, force_text)?;
+                let stat = get_diff_stat_entry(
+                    path.clone(),
+                    &left_content,
+                    &right_content,
+                    file_types,
+                    options,
+                );
+                if stat.binary_sizes.is_some() {
+                    (true, None)
+                } else {
+                    (false, Some((stat.added, stat.removed)))
+                }
+            };
+            let entry = JsonStatEntry {
+                path: path.target().as_internal_file_string().to_owned(),
+                source_path: path
+                    .copy_operation()
+                    .map(|_| path.source().as_internal_file_string().to_owned()),
+                status,
+                old,
+                new,
+                binary,
+                added: counts.map(|(added, _)| added),
+                removed: counts.map(|(_, removed)| removed),
+            };
+            if is_path_order {
+                write_entry(formatter, &entry)?;
+            } else {
+                sorted_entries.push((path.target().to_owned(), entry));
+            }
+        }
+        options
+            .sort
+            .sort_files(&mut sorted_entries, |(path, entry)| {
+                (entry.added.unwrap_or(0), entry.removed.unwrap_or(0), path)
+            });
+        for (_, entry) in &sorted_entries {
+            write_entry(formatter, entry)?;
+        }
+        Ok(())
+    }
+    .block_on()
+}
+
+/// Returns the Git file mode of the value, or `None` if it's a conflict.
+fn git_mode_for_value(value: &MergedTreeValue) -> Option<&'static str> {
+    match value.as_resolved()? {
+        None => Some("000000"),
+        Some(TreeValue::File { executable, .. }) => Some(git_file_mode(*executable)),
+        Some(TreeValue::Symlink(_)) => Some(GIT_SYMLINK_MODE),
+        Some(TreeValue::GitSubmodule(_)) => Some(GIT_SUBMODULE_MODE),
+        Some(TreeValue::Tree(_)) | Some(TreeValue::Conflict(_)) => {
+            panic!("Unexpected {value:?} in diff")
+        }
+    }
+}
+
 pub fn show_names(
     formatter: &mut dyn Formatter,
     mut tree_diff: BoxStream<CopiesTreeDiffEntry>,
@@ -1856,3 +5389,204 @@ pub fn show_names(
     }
     .block_on()
 }
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use testutils::create_tree;
+    use testutils::TestRepo;
+
+    use super::*;
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0, false), "0");
+        assert_eq!(format_count(1234567, false), "1234567");
+        assert_eq!(format_count(0, true), "0");
+        assert_eq!(format_count(123, true), "123");
+        assert_eq!(format_count(1234, true), "1,234");
+        assert_eq!(format_count(123456, true), "123,456");
+        assert_eq!(format_count(1234567, true), "1,234,567");
+    }
+
+    #[test]
+    fn test_unified_diff_line_ids() {
+        let options = UnifiedDiffOptions {
+            context: 1,
+            line_diff: LineDiffOptions::default(),
+            context_gap: None,
+            compact_single_line: false,
+            line_numbers: false,
+            hunk_line_counts: false,
+            word_highlight: false,
+            preview: None,
+            rename_threshold: None,
+            show_function_context: false,
+            function_pattern: FunctionPattern::default(),
+            color_moved: ColorMovedMode::No,
+            full_index: false,
+            minimal_headers: false,
+        };
+        let line_ids = |left: &str, right: &str| {
+            unified_diff_hunks(left.as_bytes(), right.as_bytes(), &options)
+                .map(|hunk| hunk.line_ids())
+                .collect_vec()
+        };
+
+        let ids1 = line_ids("a\nb\nc\nd\ne\nf\n", "a\nB\nc\nd\ne\nF\n");
+        assert_eq!(ids1.len(), 2);
+        assert_eq!(
+            ids1[0].iter().map(Option::is_some).collect_vec(),
+            [false, true, true, false]
+        );
+        assert_eq!(
+            ids1[1].iter().map(Option::is_some).collect_vec(),
+            [false, true, true]
+        );
+        assert_eq!(ids1[0][1].unwrap().to_string().len(), 32);
+        assert_ne!(ids1[0][1], ids1[0][2]);
+
+        // Lines keep their identifiers if other hunks change
+        let ids2 = line_ids("a\nb\nc\nd\ne\nf\n", "a\nB\nc\nd\ne\nG\n");
+        assert_eq!(ids1[0], ids2[0]);
+        assert_eq!(ids1[1][1], ids2[1][1]);
+        assert_ne!(ids1[1][2], ids2[1][2]);
+
+        // The same content at another position has another identifier
+        let ids3 = line_ids("a\nb\nc\nd\ne\nf\n", "B\nc\nd\ne\nF\n");
+        assert_eq!(ids1[0][1], ids3[0][1]);
+        assert_ne!(ids1[0][2], ids3[0][2]);
+    }
+
+    #[test]
+    fn test_unified_diff_hunks_iter() {
+        let mut options = UnifiedDiffOptions {
+            context: 1,
+            line_diff: LineDiffOptions::default(),
+            context_gap: None,
+            compact_single_line: false,
+            line_numbers: false,
+            hunk_line_counts: false,
+            word_highlight: false,
+            preview: None,
+            rename_threshold: None,
+            show_function_context: false,
+            function_pattern: FunctionPattern::default(),
+            color_moved: ColorMovedMode::No,
+            full_index: false,
+            minimal_headers: false,
+        };
+        let left = b"a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let right = b"a\nB\nc\nd\ne\nf\n\ng\nh\ni\nj\nK\nl\n";
+        let line_ranges = |hunk: UnifiedDiffHunk| (hunk.left_line_range, hunk.right_line_range);
+
+        // Hunks are yielded one by one
+        let mut hunks = unified_diff_hunks(left, right, &options);
+        assert_eq!(hunks.next().map(line_ranges), Some((0..3, 0..3)));
+        assert_eq!(hunks.next().map(line_ranges), Some((5..7, 5..8)));
+        assert_eq!(hunks.next().map(line_ranges), Some((9..12, 10..13)));
+        assert!(hunks.next().is_none());
+        assert!(hunks.next().is_none());
+
+        // Hunks only changing blank lines are skipped
+        options.line_diff.ignore_blank_lines = true;
+        let hunks = unified_diff_hunks(left, right, &options);
+        assert_eq!(
+            hunks.map(line_ranges).collect_vec(),
+            [(0..3, 0..3), (9..12, 10..13)]
+        );
+    }
+
+    #[test]
+    fn test_file_line_diff() {
+        let test_repo = TestRepo::init();
+        let repo = &test_repo.repo;
+        let path = RepoPath::from_internal_string("file");
+        let tree1 = create_tree(repo, &[(path, "a\nb\nc\nd\n")]);
+        let tree2 = create_tree(repo, &[(path, "a\nB\nc\nd\ne")]);
+        let line = |line_type, line_number, content: &str| ChangedLine {
+            line_type,
+            line_number,
+            content: content.as_bytes().to_vec(),
+        };
+        let line_diff = |from, to, path| {
+            file_line_diff(
+                repo.as_ref(),
+                from,
+                to,
+                path,
+                ConflictMarkerStyle::Diff,
+                &LineDiffOptions::default(),
+            )
+            .block_on()
+        };
+
+        let (removed, added) = line_diff(&tree1, &tree2, path).unwrap();
+        assert_eq!(removed, [line(DiffLineType::Removed, 2, "b\n")]);
+        assert_eq!(
+            added,
+            [
+                line(DiffLineType::Added, 2, "B\n"),
+                line(DiffLineType::Added, 5, "e"),
+            ]
+        );
+
+        // Unchanged and absent files have no changed lines
+        let (removed, added) = line_diff(&tree1, &tree1, path).unwrap();
+        assert!(removed.is_empty() && added.is_empty());
+        let missing_path = RepoPath::from_internal_string("missing");
+        let (removed, added) = line_diff(&tree1, &tree2, missing_path).unwrap();
+        assert!(removed.is_empty() && added.is_empty());
+
+        // Added files consist of added lines
+        let empty_tree = create_tree(repo, &[]);
+        let (removed, added) = line_diff(&empty_tree, &tree1, path).unwrap();
+        assert!(removed.is_empty());
+        assert_eq!(added.len(), 4);
+
+        // Binary files can't be compared line by line
+        let tree3 = create_tree(repo, &[(path, "a\0b\n")]);
+        assert_matches!(
+            line_diff(&tree1, &tree3, path),
+            Err(DiffRenderError::BinaryFile { .. })
+        );
+    }
+
+    #[test]
+    fn test_render_diff() {
+        let test_repo = TestRepo::init();
+        let repo = &test_repo.repo;
+        let path = RepoPath::from_internal_string("file");
+        let tree1 = create_tree(repo, &[(path, "a\nb\n")]);
+        let tree2 = create_tree(repo, &[(path, "a\nB\n")]);
+        let path_converter = RepoPathUiConverter::Fs {
+            cwd: "".into(),
+            base: "".into(),
+        };
+        let render = |format| {
+            DiffRenderer::new(
+                repo.as_ref(),
+                &path_converter,
+                ConflictMarkerStyle::Diff,
+                vec![format],
+            )
+            .render_diff(
+                &tree1,
+                &tree2,
+                &EverythingMatcher,
+                &CopyRecords::default(),
+                80,
+            )
+        };
+
+        let recorder = render(DiffFormat::Summary).unwrap();
+        assert_eq!(recorder.data(), b"M file\n");
+
+        // External tools need a terminal
+        let tool = ExternalMergeTool::with_program("false");
+        assert_matches!(
+            render(DiffFormat::Tool(Box::new(tool))),
+            Err(DiffRenderError::UnsupportedToolFormat)
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::io;
+use std::io::Read;
 use std::io::Write;
 use std::process::Command;
 use std::process::ExitStatus;
@@ -49,7 +50,9 @@ pub struct ExternalMergeTool {
     /// if not specified in the config.
     pub program: String,
     /// Arguments to pass to the program when generating diffs.
-    /// `$left` and `$right` are replaced with the corresponding directories.
+    /// `$left` and `$right` are replaced with the corresponding directories,
+    /// or files if invoked file by file. See
+    /// [`DiffToolMode::diff_args_variables()`] for the other variables.
     pub diff_args: Vec<String>,
     /// Exit codes to be treated as success when generating diffs.
     pub diff_expected_exit_codes: Vec<i32>,
@@ -94,6 +97,21 @@ pub enum DiffToolMode {
     FileByFile,
 }
 
+impl DiffToolMode {
+    /// Returns the names of the variables which can be used in `diff-args`
+    /// when the tool is invoked in this mode.
+    ///
+    /// In file-by-file mode, `$left_path` and `$right_path` are replaced with
+    /// the paths of the files relative to the current directory, which are
+    /// suitable for display.
+    pub fn diff_args_variables(self) -> &'static [&'static str] {
+        match self {
+            DiffToolMode::Dir => &["left", "right"],
+            DiffToolMode::FileByFile => &["left", "right", "left_path", "right_path"],
+        }
+    }
+}
+
 impl Default for ExternalMergeTool {
     fn default() -> Self {
         Self {
@@ -135,6 +153,13 @@ impl ExternalMergeTool {
         Self::with_args_inner(command_args, |tool| &mut tool.merge_args)
     }
 
+    /// Returns the first variable in `diff_args` which isn't available in the
+    /// configured invocation mode.
+    pub fn find_unknown_diff_variable(&self) -> Option<&str> {
+        let known_variables = self.diff_invocation_mode.diff_args_variables();
+        find_all_variables(&self.diff_args).find(|name| !known_variables.contains(name))
+    }
+
     fn with_args_inner(
         command_args: &CommandNameAndArgs,
         get_mut_args: impl FnOnce(&mut Self) -> &mut Vec<String>,
@@ -457,7 +482,7 @@ pub fn invoke_external_diff(
             tool_binary: tool.program.clone(),
             source,
         })?;
-    let copy_result = io::copy(&mut child.stdout.take().unwrap(), writer);
+    let copy_result = copy_with_trailing_newline(&mut child.stdout.take().unwrap(), writer);
     // Non-zero exit code isn't an error. For example, the traditional diff command
     // will exit with 1 if inputs are different.
     let exit_status = child.wait().map_err(ExternalToolError::Io)?;
@@ -476,6 +501,28 @@ pub fn invoke_external_diff(
     Ok(())
 }
 
+/// Copies all of `reader` to `writer`, and terminates the output with a
+/// newline unless it's empty or already ends with one. This keeps the output
+/// of external diff tools consistent with the builtin diff formats.
+fn copy_with_trailing_newline(reader: &mut dyn Read, writer: &mut dyn Write) -> io::Result<()> {
+    let mut buf = [0; 8192];
+    let mut last_byte = None;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        writer.write_all(&buf[..n])?;
+        last_byte = Some(buf[n - 1]);
+    }
+    if last_byte.is_some_and(|b| b != b'\n') {
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -539,4 +586,17 @@ mod tests {
             ["left", "right", "1", "2", "output", "right"],
         );
     }
+
+    #[test]
+    fn test_copy_with_trailing_newline() {
+        let copy = |input: &[u8]| {
+            let mut output = vec![];
+            copy_with_trailing_newline(&mut &input[..], &mut output).unwrap();
+            output
+        };
+        assert_eq!(copy(b""), b"");
+        assert_eq!(copy(b"foo\n"), b"foo\n");
+        assert_eq!(copy(b"foo"), b"foo\n");
+        assert_eq!(copy(b"foo\n\n"), b"foo\n\n");
+    }
 }
@@ -16,6 +16,41 @@ pub const UPDATE_HZ: u32 = 30;
 pub const INITIAL_DELAY: Duration = Duration::from_millis(250);
 
 pub fn snapshot_progress(ui: &Ui) -> Option<impl Fn(&RepoPath) + '_> {
+    let progress = line_progress(ui)?;
+    Some(move |path: &RepoPath| {
+        // Future work: Display current path after exactly, say, 250ms has elapsed, to
+        // better handle large single files
+        progress(&|line_width| {
+            let max_path_width = line_width.saturating_sub(13); // Account for "Snapshotting "
+            let fs_path = path.to_fs_path_unchecked(Path::new(""));
+            let (display_path, _) =
+                text_util::elide_start(fs_path.to_str().unwrap(), "...", max_path_width);
+            format!("Snapshotting {display_path}")
+        });
+    })
+}
+
+/// Returns a callback that shows a one-line progress message, e.g. the number
+/// of processed items. Like [`snapshot_progress()`], nothing is shown during
+/// fast operations, and the line is cleared when the callback is dropped.
+pub fn message_progress(ui: &Ui) -> Option<impl Fn(&str) + Sync + '_> {
+    let progress = line_progress(ui)?;
+    Some(move |message: &str| {
+        progress(&|line_width| {
+            text_util::elide_end(message, "...", line_width)
+                .0
+                .into_owned()
+        });
+    })
+}
+
+/// Returns a callback that replaces the progress line with the line built by
+/// the function passed to it, which gets the terminal width.
+///
+/// The line isn't built at all during the initial delay, nor more often than
+/// `UPDATE_HZ` times per second. The line is cleared when the callback is
+/// dropped.
+fn line_progress(ui: &Ui) -> Option<impl Fn(&dyn Fn(usize) -> String) + Sync + '_> {
     struct State {
         guard: Option<OutputGuard>,
         output: ProgressOutput<std::io::Stderr>,
@@ -32,12 +67,10 @@ pub fn snapshot_progress(ui: &Ui) -> Option<impl Fn(&RepoPath) + '_> {
         next_display_time,
     });
 
-    Some(move |path: &RepoPath| {
+    Some(move |build_line: &dyn Fn(usize) -> String| {
         let mut state = state.lock().unwrap();
         let now = Instant::now();
         if now < state.next_display_time {
-            // Future work: Display current path after exactly, say, 250ms has elapsed, to
-            // better handle large single files
             return;
         }
         state.next_display_time = now + Duration::from_secs(1) / UPDATE_HZ;
@@ -51,16 +84,8 @@ pub fn snapshot_progress(ui: &Ui) -> Option<impl Fn(&RepoPath) + '_> {
         }
 
         let line_width = state.output.term_width().map(usize::from).unwrap_or(80);
-        let max_path_width = line_width.saturating_sub(13); // Account for "Snapshotting "
-        let fs_path = path.to_fs_path_unchecked(Path::new(""));
-        let (display_path, _) =
-            text_util::elide_start(fs_path.to_str().unwrap(), "...", max_path_width);
-
-        _ = write!(
-            state.output,
-            "\r{}Snapshotting {display_path}",
-            Clear(ClearType::CurrentLine),
-        );
+        let line = build_line(line_width);
+        _ = write!(state.output, "\r{}{line}", Clear(ClearType::CurrentLine));
         _ = state.output.flush();
     })
 }
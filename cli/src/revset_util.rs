@@ -26,6 +26,7 @@ use jj_lib::config::ConfigNamePathBuf;
 use jj_lib::config::ConfigSource;
 use jj_lib::config::StackedConfig;
 use jj_lib::id_prefix::IdPrefixContext;
+use jj_lib::op_store::WorkspaceId;
 use jj_lib::repo::Repo;
 use jj_lib::revset;
 use jj_lib::revset::DefaultSymbolResolver;
@@ -56,6 +57,10 @@ const USER_IMMUTABLE_HEADS: &str = "immutable_heads";
 pub enum UserRevsetEvaluationError {
     #[error(transparent)]
     Resolution(RevsetResolutionError),
+    /// A workspace other than the current one doesn't have a working-copy
+    /// commit, so it doesn't exist (anymore).
+    #[error(transparent)]
+    UnknownWorkspace(RevsetResolutionError),
     #[error(transparent)]
     Evaluation(RevsetEvaluationError),
 }
@@ -65,6 +70,7 @@ pub struct RevsetExpressionEvaluator<'repo> {
     repo: &'repo dyn Repo,
     extensions: Arc<RevsetExtensions>,
     id_prefix_context: &'repo IdPrefixContext,
+    workspace_id: Option<&'repo WorkspaceId>,
     expression: Rc<UserRevsetExpression>,
 }
 
@@ -73,12 +79,14 @@ impl<'repo> RevsetExpressionEvaluator<'repo> {
         repo: &'repo dyn Repo,
         extensions: Arc<RevsetExtensions>,
         id_prefix_context: &'repo IdPrefixContext,
+        workspace_id: Option<&'repo WorkspaceId>,
         expression: Rc<UserRevsetExpression>,
     ) -> Self {
         RevsetExpressionEvaluator {
             repo,
             extensions,
             id_prefix_context,
+            workspace_id,
             expression,
         }
     }
@@ -107,7 +115,17 @@ impl<'repo> RevsetExpressionEvaluator<'repo> {
     /// Evaluates the expression.
     pub fn evaluate(&self) -> Result<Box<dyn Revset + 'repo>, UserRevsetEvaluationError> {
         self.resolve()
-            .map_err(UserRevsetEvaluationError::Resolution)?
+            .map_err(|err| match &err {
+                // The current workspace exists even if it was forgotten.
+                RevsetResolutionError::WorkspaceMissingWorkingCopy { name }
+                    if self
+                        .workspace_id
+                        .is_some_and(|id| id.as_str() != name.as_str()) =>
+                {
+                    UserRevsetEvaluationError::UnknownWorkspace(err)
+                }
+                _ => UserRevsetEvaluationError::Resolution(err),
+            })?
             .evaluate(self.repo)
             .map_err(UserRevsetEvaluationError::Evaluation)
     }
@@ -30,6 +30,10 @@ struct Args {
     /// Path to the "after" directory
     after: PathBuf,
 
+    /// Labels to be printed by the "print-labels" command
+    #[arg(long)]
+    label: Vec<String>,
+
     /// Ignored argument
     #[arg(long)]
     _ignore: Vec<String>,
@@ -92,6 +96,11 @@ fn main() {
             ["print", message] => {
                 println!("{message}");
             }
+            ["print-labels"] => {
+                for label in &args.label {
+                    println!("{label}");
+                }
+            }
             ["print-files-before"] => {
                 for base_name in files_recursively(&args.before).iter().sorted() {
                     println!("{base_name}");
@@ -14,6 +14,7 @@
 
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
 use std::process::ExitCode;
 
@@ -29,6 +30,10 @@ use itertools::Itertools;
 /// `fake-formatter --stdout foo --stderr bar --fail` is similar to
 ///   `echo foo; echo bar >&2; false`.
 /// `fake-formatter --tee foo` is similar to `tee foo`).
+/// `fake-formatter --in-place foo` is similar to `sed -i '' foo`.
+/// `fake-formatter --file foo` is similar to `cat foo`.
+/// `fake-formatter --in-place-dir foo` is similar to `find foo -type f -exec
+///   sed -i '' {} +`.
 ///
 /// This program acts as a portable alternative to that class of shell commands.
 #[derive(Parser, Debug)]
@@ -64,50 +69,55 @@ struct Args {
     /// Duplicate stdout into this file.
     #[arg(long)]
     tee: Option<PathBuf>,
+
+    /// Read this file instead of stdin, and overwrite it instead of writing
+    /// to stdout.
+    #[arg(long)]
+    in_place: Option<PathBuf>,
+
+    /// Read this file instead of stdin.
+    #[arg(long, conflicts_with = "in_place")]
+    file: Option<PathBuf>,
+
+    /// Rewrite every file under this directory like `--in-place`.
+    #[arg(long, conflicts_with_all = ["in_place", "file", "stdout", "tee"])]
+    in_place_dir: Option<PathBuf>,
 }
 
 fn main() -> ExitCode {
     let args: Args = Args::parse();
     // Code formatters tend to print errors before printing the result.
-    if let Some(data) = args.stderr {
+    if let Some(data) = &args.stderr {
         eprint!("{data}");
     }
-    let stdout = if let Some(data) = args.stdout {
+    if let Some(dir) = &args.in_place_dir {
+        for path in files_in_dir(dir) {
+            let input = std::fs::read_to_string(&path).unwrap();
+            std::fs::write(&path, transform(&args, &input)).unwrap();
+        }
+        return exit_code(&args);
+    }
+    let stdout = if let Some(data) = &args.stdout {
         // Other content-altering flags don't apply to --stdout.
         assert!(!args.reverse);
         assert!(!args.uppercase);
         assert!(!args.lowercase);
         assert!(args.append.is_none());
-        data
+        data.clone()
     } else {
-        let mut stdout = std::io::stdin()
-            .lines()
-            .map(|line| {
-                format!("{}\n", {
-                    let line = if args.reverse {
-                        line.unwrap().chars().rev().collect()
-                    } else {
-                        line.unwrap()
-                    };
-                    if args.uppercase {
-                        assert!(!args.lowercase);
-                        line.to_uppercase()
-                    } else if args.lowercase {
-                        assert!(!args.uppercase);
-                        line.to_lowercase()
-                    } else {
-                        line
-                    }
-                })
-            })
-            .join("");
-        if let Some(line) = args.append {
-            stdout.push_str(&line);
-        }
-        stdout
+        let input = if let Some(path) = args.in_place.as_ref().or(args.file.as_ref()) {
+            std::fs::read_to_string(path).unwrap()
+        } else {
+            std::io::read_to_string(std::io::stdin()).unwrap()
+        };
+        transform(&args, &input)
     };
-    print!("{stdout}");
-    if let Some(path) = args.tee {
+    if let Some(path) = &args.in_place {
+        std::fs::write(path, &stdout).unwrap();
+    } else {
+        print!("{stdout}");
+    }
+    if let Some(path) = &args.tee {
         let mut file = OpenOptions::new()
             .create(true)
             .append(true)
@@ -115,6 +125,53 @@ fn main() -> ExitCode {
             .unwrap();
         write!(file, "{stdout}").unwrap();
     }
+    exit_code(&args)
+}
+
+/// Applies the content-altering flags to the input.
+fn transform(args: &Args, input: &str) -> String {
+    let mut output = input
+        .lines()
+        .map(|line| {
+            format!("{}\n", {
+                let line = if args.reverse {
+                    line.chars().rev().collect()
+                } else {
+                    line.to_owned()
+                };
+                if args.uppercase {
+                    assert!(!args.lowercase);
+                    line.to_uppercase()
+                } else if args.lowercase {
+                    assert!(!args.uppercase);
+                    line.to_lowercase()
+                } else {
+                    line
+                }
+            })
+        })
+        .join("");
+    if let Some(line) = &args.append {
+        output.push_str(line);
+    }
+    output
+}
+
+/// Lists the files under the directory recursively.
+fn files_in_dir(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(files_in_dir(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files
+}
+
+fn exit_code(args: &Args) -> ExitCode {
     if args.fail {
         ExitCode::FAILURE
     } else {
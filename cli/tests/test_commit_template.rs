@@ -1100,6 +1100,7 @@ fn test_log_diff_predefined_formats() {
     [38;5;2m+[4mb[24m[39m
     [38;5;2m+[4mc[24m[39m
     [1mdiff --git a/rename-source b/rename-target[0m
+    [1msimilarity index 100%[0m
     [1mrename from rename-source[0m
     [1mrename to rename-target[0m
     === stat ===
@@ -1146,6 +1147,7 @@ fn test_log_diff_predefined_formats() {
     [38;5;2m<<log diff git added::+>>[4m<<log diff git added token::b>>[24m<<log diff git added::>>[39m
     [38;5;2m<<log diff git added::+>>[4m<<log diff git added token::c>>[24m[39m
     [1m<<log diff git file_header::diff --git a/rename-source b/rename-target>>[0m
+    [1m<<log diff git file_header::similarity index 100%>>[0m
     [1m<<log diff git file_header::rename from rename-source>>[0m
     [1m<<log diff git file_header::rename to rename-target>>[0m
     <<log::=== stat ===>>
@@ -1192,6 +1194,7 @@ fn test_log_diff_predefined_formats() {
     +b
     +c
     diff --git a/rename-source b/rename-target
+    similarity index 100%
     rename from rename-source
     rename to rename-target
     === stat ===
@@ -1229,7 +1232,7 @@ fn test_log_diff_predefined_formats() {
     insta::assert_snapshot!(stdout, @r"
     === color_words ===
     Modified regular file file1:
-        ...
+        ... (2 lines)
             3: c
     Modified regular file file2:
        1     : a
@@ -1253,6 +1256,7 @@ fn test_log_diff_predefined_formats() {
     +b
     +c
     diff --git a/rename-source b/rename-target
+    similarity index 100%
     rename from rename-source
     rename to rename-target
     === stat ===
@@ -1300,7 +1304,7 @@ fn test_log_diff_predefined_formats() {
     let stdout = test_env.jj_cmd_success(&repo_path, &["log", "--no-graph", "-r@", "-T", template]);
     insta::assert_snapshot!(stdout, @r###"
     Modified regular file file1:
-        ...
+        ... (2 lines)
             3: c
     "###);
 
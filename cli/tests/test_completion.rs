@@ -743,7 +743,7 @@ fn test_files() {
     │    A f_dir/dir_file_1
     │    A f_dir/dir_file_2
     │    A f_dir/dir_file_3
-    │    M f_modified
+    │    U f_modified
     ○  rlvkpnrz test.user@example.com 2001-02-03 08:05:09 first 2a2f433c
     │  first
     │  A f_deleted
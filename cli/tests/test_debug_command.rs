@@ -227,6 +227,43 @@ fn test_debug_tree() {
     );
 }
 
+#[test]
+fn test_debug_tree_working_copy() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let workspace_path = test_env.env_root().join("repo");
+    std::fs::write(workspace_path.join(".gitignore"), "ignored\n").unwrap();
+    std::fs::write(workspace_path.join("file1"), "contents 1").unwrap();
+    test_env.jj_cmd_ok(&workspace_path, &["new"]);
+    let wc_state_before = test_env.jj_cmd_success(
+        &workspace_path,
+        &["debug", "local-working-copy", "--ignore-working-copy"],
+    );
+    std::fs::write(workspace_path.join("file2"), "contents 2").unwrap();
+    std::fs::write(workspace_path.join("ignored"), "ignored").unwrap();
+
+    // New files are included, ignored files aren't
+    let stdout = test_env.jj_cmd_success(&workspace_path, &["debug", "tree", "--working-copy"]);
+    assert_snapshot!(stdout, @r#"
+    .gitignore: Ok(Resolved(Some(File { id: FileId("ea10ec85c10797d76afeef56f8bfc26da40913bb"), executable: false })))
+    file1: Ok(Resolved(Some(File { id: FileId("498e9b01d79cb8d31cdf0df1a663cc1fcefd9de3"), executable: false })))
+    file2: Ok(Resolved(Some(File { id: FileId("b2496eaffe394cd50a9db4de5787f45f09fd9722"), executable: false })))
+    "#);
+
+    // Neither the working-copy commit nor the working-copy state is updated
+    let stdout =
+        test_env.jj_cmd_success(&workspace_path, &["debug", "tree", "--ignore-working-copy"]);
+    assert_snapshot!(stdout, @r#"
+    .gitignore: Ok(Resolved(Some(File { id: FileId("ea10ec85c10797d76afeef56f8bfc26da40913bb"), executable: false })))
+    file1: Ok(Resolved(Some(File { id: FileId("498e9b01d79cb8d31cdf0df1a663cc1fcefd9de3"), executable: false })))
+    "#);
+    let wc_state_after = test_env.jj_cmd_success(
+        &workspace_path,
+        &["debug", "local-working-copy", "--ignore-working-copy"],
+    );
+    assert_eq!(wc_state_before, wc_state_after);
+}
+
 #[test]
 fn test_debug_operation_id() {
     let test_env = TestEnvironment::default();
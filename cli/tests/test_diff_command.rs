@@ -111,9 +111,11 @@ fn test_diff_basic() {
      3
     -4
     diff --git a/file1 b/file3
+    similarity index 100%
     rename from file1
     rename to file3
     diff --git a/file2 b/file4
+    similarity index 100%
     copy from file2
     copy to file4
     "###);
@@ -130,9 +132,11 @@ fn test_diff_basic() {
     @@ -4,1 +3,0 @@
     -4
     diff --git a/file1 b/file3
+    similarity index 100%
     rename from file1
     rename to file3
     diff --git a/file2 b/file4
+    similarity index 100%
     copy from file2
     copy to file4
     ");
@@ -150,9 +154,11 @@ fn test_diff_basic() {
     <<diff context:: 3>>
     [38;5;1m<<diff removed::->>[4m<<diff removed token::4>>[24m[39m
     [1m<<diff file_header::diff --git a/file1 b/file3>>[0m
+    [1m<<diff file_header::similarity index 100%>>[0m
     [1m<<diff file_header::rename from file1>>[0m
     [1m<<diff file_header::rename to file3>>[0m
     [1m<<diff file_header::diff --git a/file2 b/file4>>[0m
+    [1m<<diff file_header::similarity index 100%>>[0m
     [1m<<diff file_header::copy from file2>>[0m
     [1m<<diff file_header::copy to file4>>[0m
     "###);
@@ -173,9 +179,11 @@ fn test_diff_basic() {
      3
     -4
     diff --git a/file1 b/file3
+    similarity index 100%
     rename from file1
     rename to file3
     diff --git a/file2 b/file4
+    similarity index 100%
     copy from file2
     copy to file4
     "###);
@@ -224,991 +232,2380 @@ fn test_diff_basic() {
 }
 
 #[test]
-fn test_diff_empty() {
+fn test_diff_header() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
-    std::fs::write(repo_path.join("file1"), "").unwrap();
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Added regular file file1:
-        (empty)
-    "###);
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "add file1"]);
 
-    test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::remove_file(repo_path.join("file1")).unwrap();
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Removed regular file file1:
-        (empty)
-    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--header", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    Commit ID: ee807f7deadc1688384de66d352e49a8e875039a
+    Change ID: qpvuntsmwlqtpsluzzsnyyzlmlwvmlnu
+    Author   : Test User <test.user@example.com> (2001-02-03 08:05:08)
+    Committer: Test User <test.user@example.com> (2001-02-03 08:05:08)
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
-    insta::assert_snapshot!(stdout, @r###"
-    file1 | 0
-    1 file changed, 0 insertions(+), 0 deletions(-)
-    "###);
+        add file1
+
+    diff --git a/file1 b/file1
+    new file mode 100644
+    index 0000000000..257cc5642c
+    --- /dev/null
+    +++ b/file1
+    @@ -0,0 +1,1 @@
+    +foo
+    ");
+
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--header", "--from=@-"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the argument '--header' cannot be used with '--from <REVSET>'
+
+    Usage: jj diff --header [FILESETS]...
+
+    For more information, try '--help'.
+    ");
 }
 
 #[test]
-fn test_diff_file_mode() {
+fn test_diff_follow_renames() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
-    // Test content+mode/mode-only changes of empty/non-empty files:
-    // - file1: ("",  x) -> ("2", n)  empty, content+mode
-    // - file2: ("1", x) -> ("1", n)  non-empty, mode-only
-    // - file3: ("1", n) -> ("2", x)  non-empty, content+mode
-    // - file4: ("",  n) -> ("",  x)  empty, mode-only
-
-    std::fs::write(repo_path.join("file1"), "").unwrap();
-    std::fs::write(repo_path.join("file2"), "1\n").unwrap();
-    std::fs::write(repo_path.join("file3"), "1\n").unwrap();
-    std::fs::write(repo_path.join("file4"), "").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "x", "file1", "file2"]);
-
-    test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::write(repo_path.join("file1"), "2\n").unwrap();
-    std::fs::write(repo_path.join("file3"), "2\n").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "n", "file1", "file2"]);
-    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "x", "file3", "file4"]);
-
+    std::fs::write(repo_path.join("file1"), "1\n2\n3\n4\n").unwrap();
+    std::fs::write(repo_path.join("other"), "foo\n").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["new"]);
     std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(repo_path.join("file2"), "1\n2\n3\n5\n").unwrap();
+    std::fs::write(repo_path.join("other"), "bar\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
     std::fs::remove_file(repo_path.join("file2")).unwrap();
-    std::fs::remove_file(repo_path.join("file3")).unwrap();
-    std::fs::remove_file(repo_path.join("file4")).unwrap();
+    std::fs::write(repo_path.join("file3"), "0\n1\n2\n3\n5\n").unwrap();
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@--"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Added executable file file1:
-        (empty)
-    Added executable file file2:
-            1: 1
-    Added regular file file3:
-            1: 1
-    Added regular file file4:
-        (empty)
-    "###);
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@-"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Executable file became non-executable at file1:
-            1: 2
-    Executable file became non-executable at file2:
-    Non-executable file became executable at file3:
-       1    1: 12
-    Non-executable file became executable at file4:
-    "###);
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Removed regular file file1:
-       1     : 2
-    Removed regular file file2:
-       1     : 1
-    Removed executable file file3:
-       1     : 2
-    Removed executable file file4:
-        (empty)
-    "###);
+    // Without --follow-renames, the source path only matches the deletion
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@-", "--summary", "file1"]);
+    insta::assert_snapshot!(stdout, @"D file1");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "-r@-", "--summary", "--follow-renames", "file1"],
+    );
+    insta::assert_snapshot!(stdout, @"R {file1 => file2}");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "-r@-", "--summary", "--follow-renames", "file2"],
+    );
+    insta::assert_snapshot!(stdout, @"R {file1 => file2}");
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@--", "--git"]);
+    // A file renamed multiple times is shown as a single rename
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--from=@--", "--git", "--follow-renames", "file3"],
+    );
     insta::assert_snapshot!(stdout, @r"
-    diff --git a/file1 b/file1
-    new file mode 100755
-    index 0000000000..e69de29bb2
-    diff --git a/file2 b/file2
-    new file mode 100755
-    index 0000000000..d00491fd7e
-    --- /dev/null
-    +++ b/file2
-    @@ -0,0 +1,1 @@
-    +1
-    diff --git a/file3 b/file3
-    new file mode 100644
-    index 0000000000..d00491fd7e
-    --- /dev/null
+    diff --git a/file1 b/file3
+    similarity index 60%
+    rename from file1
+    rename to file3
+    index 94ebaf9001..888fa5db94 100644
+    --- a/file1
     +++ b/file3
-    @@ -0,0 +1,1 @@
-    +1
-    diff --git a/file4 b/file4
-    new file mode 100644
-    index 0000000000..e69de29bb2
+    @@ -1,4 +1,5 @@
+    +0
+     1
+     2
+     3
+    -4
+    +5
     ");
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@-", "--git"]);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--from=@--",
+            "--summary",
+            "--follow-renames",
+            "file1",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"R {file1 => file3}");
+}
+
+#[test]
+fn test_diff_group_by_status() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("a-modified"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("b-removed"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("d-removed"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("renamed-from"), "1\n2\n3\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("a-modified"), "bar\n").unwrap();
+    std::fs::remove_file(repo_path.join("b-removed")).unwrap();
+    std::fs::remove_file(repo_path.join("d-removed")).unwrap();
+    std::fs::write(repo_path.join("c-added"), "baz\n").unwrap();
+    std::fs::write(repo_path.join("e-added"), "qux\n").unwrap();
+    std::fs::rename(repo_path.join("renamed-from"), repo_path.join("renamed-to")).unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "--group-by-status"]);
     insta::assert_snapshot!(stdout, @r"
-    diff --git a/file1 b/file1
-    old mode 100755
-    new mode 100644
-    index e69de29bb2..0cfbf08886
-    --- a/file1
-    +++ b/file1
+    Added files:
+    A c-added
+    A e-added
+    Removed files:
+    D b-removed
+    D d-removed
+    Modified files:
+    M a-modified
+    R {renamed-from => renamed-to}
+    ");
+
+    // Composes with multiple formats, and headers can be colored
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--color=debug",
+            "--stat",
+            "--git",
+            "--group-by-status",
+            "glob:?-*",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    [1m<<diff group_header::Added files:>>[0m
+    <<diff::c-added | 1 >>[38;5;2m<<diff added::+>>[38;5;1m<<diff removed::>>[39m
+    <<diff::e-added | 1 >>[38;5;2m<<diff added::+>>[38;5;1m<<diff removed::>>[39m
+    <<diff stat-summary::2 files changed, 2 insertions(+), 0 deletions(-)>>
+    [1m<<diff file_header::diff --git a/c-added b/c-added>>[0m
+    [1m<<diff file_header::new file mode 100644>>[0m
+    [1m<<diff file_header::index 0000000000..76018072e0>>[0m
+    [1m<<diff file_header::--- /dev/null>>[0m
+    [1m<<diff file_header::+++ b/c-added>>[0m
+    [38;5;6m<<diff hunk_header::@@ -0,0 +1,1 @@>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::baz>>[24m[39m
+    [1m<<diff file_header::diff --git a/e-added b/e-added>>[0m
+    [1m<<diff file_header::new file mode 100644>>[0m
+    [1m<<diff file_header::index 0000000000..100b0dec8c>>[0m
+    [1m<<diff file_header::--- /dev/null>>[0m
+    [1m<<diff file_header::+++ b/e-added>>[0m
+    [38;5;6m<<diff hunk_header::@@ -0,0 +1,1 @@>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::qux>>[24m[39m
+    [1m<<diff group_header::Removed files:>>[0m
+    <<diff::b-removed | 1 >>[38;5;1m<<diff removed::->>[39m
+    <<diff::d-removed | 1 >>[38;5;1m<<diff removed::->>[39m
+    <<diff stat-summary::2 files changed, 0 insertions(+), 2 deletions(-)>>
+    [1m<<diff file_header::diff --git a/b-removed b/b-removed>>[0m
+    [1m<<diff file_header::deleted file mode 100644>>[0m
+    [1m<<diff file_header::index 257cc5642c..0000000000>>[0m
+    [1m<<diff file_header::--- a/b-removed>>[0m
+    [1m<<diff file_header::+++ /dev/null>>[0m
+    [38;5;6m<<diff hunk_header::@@ -1,1 +0,0 @@>>[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::foo>>[24m[39m
+    [1m<<diff file_header::diff --git a/d-removed b/d-removed>>[0m
+    [1m<<diff file_header::deleted file mode 100644>>[0m
+    [1m<<diff file_header::index 257cc5642c..0000000000>>[0m
+    [1m<<diff file_header::--- a/d-removed>>[0m
+    [1m<<diff file_header::+++ /dev/null>>[0m
+    [38;5;6m<<diff hunk_header::@@ -1,1 +0,0 @@>>[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::foo>>[24m[39m
+    [1m<<diff group_header::Modified files:>>[0m
+    <<diff::a-modified | 2 >>[38;5;2m<<diff added::+>>[38;5;1m<<diff removed::->>[39m
+    <<diff stat-summary::1 file changed, 1 insertion(+), 1 deletion(-)>>
+    [1m<<diff file_header::diff --git a/a-modified b/a-modified>>[0m
+    [1m<<diff file_header::index 257cc5642c..5716ca5987 100644>>[0m
+    [1m<<diff file_header::--- a/a-modified>>[0m
+    [1m<<diff file_header::+++ b/a-modified>>[0m
+    [38;5;6m<<diff hunk_header::@@ -1,1 +1,1 @@>>[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::foo>>[24m<<diff removed::>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::bar>>[24m<<diff added::>>[39m
+    ");
+
+    // Headers can be disabled
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--summary",
+            "--group-by-status",
+            "--config=diff.group-by-status.headers=false",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    A c-added
+    A e-added
+    D b-removed
+    D d-removed
+    M a-modified
+    R {renamed-from => renamed-to}
+    ");
+
+    // Works with tools comparing directories
+    let edit_script = test_env.set_up_fake_diff_editor();
+    std::fs::write(
+        &edit_script,
+        "print-files-before\0print --\0print-files-after",
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--group-by-status", "--tool=fake-diff-editor"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Added files:
+    --
+    c-added
+    e-added
+    Removed files:
+    b-removed
+    d-removed
+    --
+    Modified files:
+    a-modified
+    renamed-from
+    --
+    a-modified
+    renamed-to
+    ");
+}
+
+#[test]
+fn test_diff_workspaces() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let main_path = test_env.env_root().join("repo");
+    let secondary_path = test_env.env_root().join("secondary");
+    std::fs::write(main_path.join("file"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&main_path, &["commit", "-m", "base"]);
+    test_env.jj_cmd_ok(
+        &main_path,
+        &["workspace", "add", "--name", "second", "../secondary"],
+    );
+    std::fs::write(main_path.join("file"), "main\n").unwrap();
+    std::fs::write(secondary_path.join("file"), "secondary\n").unwrap();
+    std::fs::write(secondary_path.join("added"), "added\n").unwrap();
+    // Snapshot the secondary working copy
+    test_env.jj_cmd_ok(&secondary_path, &["status"]);
+
+    let stdout = test_env.jj_cmd_success(
+        &main_path,
+        &["diff", "--git", "--from", "default@", "--to", "second@"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/added b/added
+    new file mode 100644
+    index 0000000000..d5f7fc3f74
+    --- /dev/null
+    +++ b/added
     @@ -0,0 +1,1 @@
-    +2
-    diff --git a/file2 b/file2
-    old mode 100755
-    new mode 100644
-    diff --git a/file3 b/file3
-    old mode 100644
-    new mode 100755
-    index d00491fd7e..0cfbf08886
-    --- a/file3
-    +++ b/file3
+    +added
+    diff --git a/file b/file
+    index ba2906d066..344306889e 100644
+    --- a/file
+    +++ b/file
     @@ -1,1 +1,1 @@
-    -1
-    +2
-    diff --git a/file4 b/file4
-    old mode 100644
-    new mode 100755
+    -main
+    +secondary
     ");
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@", "--git"]);
+
+    // The working copy of the current workspace is "@"
+    let stdout = test_env.jj_cmd_success(
+        &secondary_path,
+        &["diff", "--summary", "--from", "@", "--to", "default@"],
+    );
     insta::assert_snapshot!(stdout, @r"
-    diff --git a/file1 b/file1
-    deleted file mode 100644
-    index 0cfbf08886..0000000000
-    --- a/file1
-    +++ /dev/null
-    @@ -1,1 +0,0 @@
-    -2
-    diff --git a/file2 b/file2
-    deleted file mode 100644
-    index d00491fd7e..0000000000
-    --- a/file2
-    +++ /dev/null
-    @@ -1,1 +0,0 @@
-    -1
-    diff --git a/file3 b/file3
-    deleted file mode 100755
-    index 0cfbf08886..0000000000
-    --- a/file3
-    +++ /dev/null
-    @@ -1,1 +0,0 @@
-    -2
-    diff --git a/file4 b/file4
-    deleted file mode 100755
-    index e69de29bb2..0000000000
+    D added
+    M file
+    ");
+
+    let stderr = test_env.jj_cmd_failure(&main_path, &["diff", "--from", "nonexistent@"]);
+    insta::assert_snapshot!(stderr, @r"
+    Error: Workspace `nonexistent` doesn't have a working-copy commit
+    Hint: Use `jj workspace list` to see the existing workspaces.
     ");
 }
 
 #[test]
-fn test_diff_types() {
+fn test_diff_json() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
-    let file_path = repo_path.join("foo");
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("binary"), b"\0").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "a\nB\nc\nd\ne\nf\ng\nh\nj\nk\n").unwrap();
+    std::fs::remove_file(repo_path.join("file2")).unwrap();
+    std::fs::write(repo_path.join("file3"), "\"quoted\"\nno newline").unwrap();
+    std::fs::write(repo_path.join("binary"), b"\0\0").unwrap();
 
-    // Missing
-    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=missing"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--json", "--context=1"]);
+    insta::assert_snapshot!(stdout, @r#"
+    {"path":"file1","old_start":1,"old_lines":3,"new_start":1,"new_lines":3,"added":["B"],"removed":["b"]}
+    {"path":"file1","old_start":8,"old_lines":3,"new_start":8,"new_lines":3,"added":["k"],"removed":["i"]}
+    {"path":"file2","old_start":1,"old_lines":1,"new_start":0,"new_lines":0,"added":[],"removed":["foo"]}
+    {"path":"file3","old_start":0,"old_lines":0,"new_start":1,"new_lines":2,"added":["\"quoted\"","no newline"],"removed":[]}
+    "#);
 
-    // Normal file
-    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=file"]);
-    std::fs::write(&file_path, "foo").unwrap();
-
-    // Conflict (add/add)
-    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=conflict"]);
-    std::fs::write(&file_path, "foo").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
-    std::fs::write(&file_path, "bar").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["squash", r#"--into=description("conflict")"#]);
-
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        use std::path::PathBuf;
+    // Can be combined with a short format
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--json", "--summary", "file2"]);
+    insta::assert_snapshot!(stdout, @r#"
+    D file2
+    {"path":"file2","old_start":1,"old_lines":1,"new_start":0,"new_lines":0,"added":[],"removed":["foo"]}
+    "#);
 
-        // Executable
-        test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=executable"]);
-        std::fs::write(&file_path, "foo").unwrap();
-        std::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    // Can be set as the default format
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--config=ui.diff.format=json", "file2"],
+    );
+    insta::assert_snapshot!(stdout, @r#"
+    {"path":"file2","old_start":1,"old_lines":1,"new_start":0,"new_lines":0,"added":[],"removed":["foo"]}
+    "#);
 
-        // Symlink
-        test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=symlink"]);
-        std::os::unix::fs::symlink(PathBuf::from("."), &file_path).unwrap();
-    }
+    // Can't be combined with another long format
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--json", "--git"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the argument '--json' cannot be used with '--git'
 
-    let diff = |from: &str, to: &str| {
-        test_env.jj_cmd_success(
-            &repo_path,
-            &[
-                "diff",
-                "--types",
-                &format!(r#"--from=description("{from}")"#),
-                &format!(r#"--to=description("{to}")"#),
-            ],
-        )
-    };
-    insta::assert_snapshot!(diff("missing", "file"), @r###"
-    -F foo
-    "###);
-    insta::assert_snapshot!(diff("file", "conflict"), @r###"
-    FC foo
-    "###);
-    insta::assert_snapshot!(diff("conflict", "missing"), @r###"
-    C- foo
-    "###);
+    Usage: jj diff --json [FILESETS]...
 
-    #[cfg(unix)]
-    {
-        insta::assert_snapshot!(diff("symlink", "file"), @r###"
-        LF foo
-        "###);
-        insta::assert_snapshot!(diff("missing", "executable"), @r###"
-        -F foo
-        "###);
-    }
+    For more information, try '--help'.
+    ");
 }
 
 #[test]
-fn test_diff_name_only() {
+fn test_diff_json_stat() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("binary"), b"\0").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::write(repo_path.join("deleted"), "d").unwrap();
-    std::fs::write(repo_path.join("modified"), "m").unwrap();
-    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["diff", "--name-only"]), @r###"
-    deleted
-    modified
-    "###);
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-mfirst"]);
-    std::fs::remove_file(repo_path.join("deleted")).unwrap();
-    std::fs::write(repo_path.join("modified"), "mod").unwrap();
-    std::fs::write(repo_path.join("added"), "add").unwrap();
-    std::fs::create_dir(repo_path.join("sub")).unwrap();
-    std::fs::write(repo_path.join("sub/added"), "sub/add").unwrap();
-    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["diff", "--name-only"]).replace('\\', "/"),
-    @r###"
-    added
-    deleted
-    modified
-    sub/added
-    "###);
+    std::fs::write(repo_path.join("file1"), "a\nB\nc\nd\n").unwrap();
+    std::fs::remove_file(repo_path.join("file2")).unwrap();
+    std::fs::write(repo_path.join("file3"), "bar\n").unwrap();
+    std::fs::write(repo_path.join("binary"), b"\0\0").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--json-stat"]);
+    insta::assert_snapshot!(stdout, @r#"
+    {"path":"binary","status":"modified","old":{"type":"file","mode":"100644","id":"f76dd238ade08917e6712764a16a22005a50573d"},"new":{"type":"file","mode":"100644","id":"09f370e38f498a462e1ca0faa724559b6630c04f"},"binary":true,"added":null,"removed":null}
+    {"path":"file1","status":"modified","old":{"type":"file","mode":"100644","id":"de980441c3ab03a8c07dda1ad27b8a11f39deb1e"},"new":{"type":"file","mode":"100644","id":"a7bc997ebe8cf84988b83d2e83f1d193124fe593"},"binary":false,"added":2,"removed":1}
+    {"path":"file2","status":"removed","old":{"type":"file","mode":"100644","id":"257cc5642cb1a054f08cc83f2d943e56fd3ebe99"},"new":null,"binary":false,"added":0,"removed":1}
+    {"path":"file3","status":"added","old":null,"new":{"type":"file","mode":"100644","id":"5716ca5987cbf97d6bb54920bea6adde242d87e6"},"binary":false,"added":1,"removed":0}
+    "#);
+
+    // Can be set as the default format, and combined with a long format
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--config=ui.diff.format=json-stat", "file2"],
+    );
+    insta::assert_snapshot!(stdout, @r#"
+    {"path":"file2","status":"removed","old":{"type":"file","mode":"100644","id":"257cc5642cb1a054f08cc83f2d943e56fd3ebe99"},"new":null,"binary":false,"added":0,"removed":1}
+    "#);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--json-stat", "--git", "file3"]);
+    insta::assert_snapshot!(stdout, @r#"
+    {"path":"file3","status":"added","old":null,"new":{"type":"file","mode":"100644","id":"5716ca5987cbf97d6bb54920bea6adde242d87e6"},"binary":false,"added":1,"removed":0}
+    diff --git a/file3 b/file3
+    new file mode 100644
+    index 0000000000..5716ca5987
+    --- /dev/null
+    +++ b/file3
+    @@ -0,0 +1,1 @@
+    +bar
+    "#);
+
+    // Conflicts are listed by their terms
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m=left"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m=right"]);
+    std::fs::write(repo_path.join("file1"), "a\nb\nC\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(left)", "@"]);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--json-stat", "--from=description(right)", "file1"],
+    );
+    insta::assert_snapshot!(stdout, @r#"
+    {"path":"file1","status":"modified","old":{"type":"file","mode":"100644","id":"6dcce7d0cfdbcdb3076b2dca72674fb9d7d13ef8"},"new":{"type":"conflict","mode":null,"id":null,"conflict":{"removes":[{"type":"file","mode":"100644","id":"de980441c3ab03a8c07dda1ad27b8a11f39deb1e"}],"adds":[{"type":"file","mode":"100644","id":"a7bc997ebe8cf84988b83d2e83f1d193124fe593"},{"type":"file","mode":"100644","id":"6dcce7d0cfdbcdb3076b2dca72674fb9d7d13ef8"}]}},"binary":false,"added":null,"removed":null}
+    "#);
 }
 
 #[test]
-fn test_diff_bad_args() {
+fn test_diff_numstat() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
-    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "-s", "--types"]);
-    insta::assert_snapshot!(stderr, @r###"
-    error: the argument '--summary' cannot be used with '--types'
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("binary"), b"\0").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "a\nB\nc\nd\n").unwrap();
+    std::fs::remove_file(repo_path.join("file2")).unwrap();
+    std::fs::write(repo_path.join("file3"), "bar\n".repeat(1234)).unwrap();
+    std::fs::write(repo_path.join("binary"), b"\0\0").unwrap();
 
-    Usage: jj diff --summary [FILESETS]...
+    // Counts aren't scaled or grouped, and the last line has the totals
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--numstat", "--config=diff.stat.group-digits=true"],
+    );
+    insta::assert_snapshot!(stdout.replace('\t', "<TAB>"), @r"
+    -<TAB>-<TAB>binary
+    2<TAB>1<TAB>file1
+    0<TAB>1<TAB>file2
+    1234<TAB>0<TAB>file3
+    1236<TAB>2<TAB>
+    ");
 
-    For more information, try '--help'.
-    "###);
+    // Can be set as the default format
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--config=ui.diff.format=numstat", "file2"],
+    );
+    insta::assert_snapshot!(stdout.replace('\t', "<TAB>"), @r"
+    0<TAB>1<TAB>file2
+    0<TAB>1<TAB>
+    ");
 
-    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--color-words", "--git"]);
-    insta::assert_snapshot!(stderr, @r###"
-    error: the argument '--color-words' cannot be used with '--git'
+    // Can't be combined with another short format
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--numstat", "--stat"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the argument '--numstat' cannot be used with '--stat'
 
-    Usage: jj diff --color-words [FILESETS]...
+    Usage: jj diff --numstat [FILESETS]...
 
     For more information, try '--help'.
-    "###);
+    ");
 }
 
 #[test]
-fn test_diff_relative_paths() {
-    let test_env = TestEnvironment::default();
+fn test_diff_reverse() {
+    let mut test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
-    std::fs::create_dir_all(repo_path.join("dir1").join("subdir1")).unwrap();
-    std::fs::create_dir(repo_path.join("dir2")).unwrap();
-    std::fs::write(repo_path.join("file1"), "foo1\n").unwrap();
-    std::fs::write(repo_path.join("dir1").join("file2"), "foo2\n").unwrap();
-    std::fs::write(
-        repo_path.join("dir1").join("subdir1").join("file3"),
-        "foo3\n",
-    )
-    .unwrap();
-    std::fs::write(repo_path.join("dir2").join("file4"), "foo4\n").unwrap();
+    std::fs::write(repo_path.join("file1"), "a\nb\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "bar\n").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::write(repo_path.join("file1"), "bar1\n").unwrap();
-    std::fs::write(repo_path.join("dir1").join("file2"), "bar2\n").unwrap();
+    std::fs::write(repo_path.join("file1"), "a\nB\nc\n").unwrap();
+    std::fs::remove_file(repo_path.join("file2")).unwrap();
+    std::fs::rename(repo_path.join("file3"), repo_path.join("file4")).unwrap();
+    std::fs::write(repo_path.join("file5"), "baz\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--reverse", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file1
+    index 7be73ce3c1..422c2b7ab3 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,3 +1,2 @@
+     a
+    -B
+    -c
+    +b
+    diff --git a/file2 b/file2
+    new file mode 100644
+    index 0000000000..257cc5642c
+    --- /dev/null
+    +++ b/file2
+    @@ -0,0 +1,1 @@
+    +foo
+    diff --git a/file4 b/file3
+    similarity index 100%
+    rename from file4
+    rename to file3
+    diff --git a/file5 b/file5
+    deleted file mode 100644
+    index 76018072e0..0000000000
+    --- a/file5
+    +++ /dev/null
+    @@ -1,1 +0,0 @@
+    -baz
+    ");
+
+    // Added and removed counts are swapped
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--reverse", "--stat"]);
+    insta::assert_snapshot!(stdout, @r"
+    file1            | 3 +--
+    file2            | 1 +
+    {file4 => file3} | 0
+    file5            | 1 -
+    4 files changed, 2 insertions(+), 3 deletions(-)
+    ");
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--reverse", "--summary"]);
+    insta::assert_snapshot!(stdout, @r"
+    M file1
+    A file2
+    R {file4 => file3}
+    D file5
+    ");
+
+    // Applies to external tools too
+    let edit_script = test_env.set_up_fake_diff_editor();
     std::fs::write(
-        repo_path.join("dir1").join("subdir1").join("file3"),
-        "bar3\n",
+        &edit_script,
+        "print-files-before\0print --\0print-files-after",
     )
     .unwrap();
-    std::fs::write(repo_path.join("dir2").join("file4"), "bar4\n").unwrap();
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--reverse", "--tool=fake-diff-editor"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    file1
+    file4
+    file5
+    --
+    file1
+    file2
+    file3
+    ");
+}
 
-    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff"]);
-    #[cfg(unix)]
-    insta::assert_snapshot!(stdout, @r###"
-    Modified regular file file2:
-       1    1: foo2bar2
-    Modified regular file subdir1/file3:
-       1    1: foo3bar3
-    Modified regular file ../dir2/file4:
-       1    1: foo4bar4
-    Modified regular file ../file1:
-       1    1: foo1bar1
-    "###);
-    #[cfg(windows)]
+#[test]
+fn test_diff_color_words_added_removed_gutter() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let lines = |word: &str| (1..=3).map(|n| format!("{word} {n}\n")).collect::<String>();
+    std::fs::write(repo_path.join("modified"), lines("old")).unwrap();
+    std::fs::write(repo_path.join("removed"), lines("removed")).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("modified"), lines("new")).unwrap();
+    std::fs::remove_file(repo_path.join("removed")).unwrap();
+    std::fs::write(repo_path.join("added"), lines("added")).unwrap();
+
+    // The line numbers of pure additions and removals are aligned with the
+    // gutter of modified lines
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--color-words"]);
+    insta::assert_snapshot!(stdout, @r"
+    Added regular file added:
+            1: added 1
+            2: added 2
+            3: added 3
+    Modified regular file modified:
+       1    1: oldnew 1
+       2    2: oldnew 2
+       3    3: oldnew 3
+    Removed regular file removed:
+       1     : removed 1
+       2     : removed 2
+       3     : removed 3
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--color-words", "--color=debug", "added", "removed"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    [38;5;3m<<diff header::Added regular file added:>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::added 1>>[24m[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::added 2>>[24m[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::added 3>>[24m[39m
+    [38;5;3m<<diff header::Removed regular file removed:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff::     : >>[4m[38;5;1m<<diff removed token::removed 1>>[24m[39m
+    [38;5;1m<<diff removed line_number::   2>>[39m<<diff::     : >>[4m[38;5;1m<<diff removed token::removed 2>>[24m[39m
+    [38;5;1m<<diff removed line_number::   3>>[39m<<diff::     : >>[4m[38;5;1m<<diff removed token::removed 3>>[24m[39m
+    ");
+}
+
+#[test]
+fn test_diff_empty() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
     insta::assert_snapshot!(stdout, @r###"
-    Modified regular file file2:
-       1    1: foo2bar2
-    Modified regular file subdir1\file3:
-       1    1: foo3bar3
-    Modified regular file ..\dir2\file4:
-       1    1: foo4bar4
-    Modified regular file ..\file1:
-       1    1: foo1bar1
+    Added regular file file1:
+        (empty)
     "###);
 
-    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "-s"]);
-    #[cfg(unix)]
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
     insta::assert_snapshot!(stdout, @r###"
-    M file2
-    M subdir1/file3
-    M ../dir2/file4
-    M ../file1
+    Removed regular file file1:
+        (empty)
     "###);
-    #[cfg(windows)]
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
     insta::assert_snapshot!(stdout, @r###"
-    M file2
-    M subdir1\file3
-    M ..\dir2\file4
-    M ..\file1
+    file1 | 0
+    1 file changed, 0 insertions(+), 0 deletions(-)
     "###);
-
-    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "--types"]);
-    #[cfg(unix)]
-    insta::assert_snapshot!(stdout, @r###"
-    FF file2
-    FF subdir1/file3
-    FF ../dir2/file4
-    FF ../file1
-    "###);
-    #[cfg(windows)]
-    insta::assert_snapshot!(stdout, @r###"
-    FF file2
-    FF subdir1\file3
-    FF ..\dir2\file4
-    FF ..\file1
-    "###);
-
-    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "--git"]);
-    insta::assert_snapshot!(stdout, @r###"
-    diff --git a/dir1/file2 b/dir1/file2
-    index 54b060eee9..1fe912cdd8 100644
-    --- a/dir1/file2
-    +++ b/dir1/file2
-    @@ -1,1 +1,1 @@
-    -foo2
-    +bar2
-    diff --git a/dir1/subdir1/file3 b/dir1/subdir1/file3
-    index c1ec6c6f12..f3c8b75ec6 100644
-    --- a/dir1/subdir1/file3
-    +++ b/dir1/subdir1/file3
-    @@ -1,1 +1,1 @@
-    -foo3
-    +bar3
-    diff --git a/dir2/file4 b/dir2/file4
-    index a0016dbc4c..17375f7a12 100644
-    --- a/dir2/file4
-    +++ b/dir2/file4
-    @@ -1,1 +1,1 @@
-    -foo4
-    +bar4
-    diff --git a/file1 b/file1
-    index 1715acd6a5..05c4fe6772 100644
-    --- a/file1
-    +++ b/file1
-    @@ -1,1 +1,1 @@
-    -foo1
-    +bar1
-    "###);
-
-    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "--stat"]);
-    #[cfg(unix)]
-    insta::assert_snapshot!(stdout, @r###"
-    file2         | 2 +-
-    subdir1/file3 | 2 +-
-    ../dir2/file4 | 2 +-
-    ../file1      | 2 +-
-    4 files changed, 4 insertions(+), 4 deletions(-)
-    "###);
-    #[cfg(windows)]
-    insta::assert_snapshot!(stdout, @r###"
-    file2         | 2 +-
-    subdir1\file3 | 2 +-
-    ..\dir2\file4 | 2 +-
-    ..\file1      | 2 +-
-    4 files changed, 4 insertions(+), 4 deletions(-)
-    "###);
-}
+}
 
 #[test]
-fn test_diff_hunks() {
+fn test_diff_file_mode() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
-    // Test added, removed, inserted, and modified lines. The modified line
-    // contains unchanged words.
+    // Test content+mode/mode-only changes of empty/non-empty files:
+    // - file1: ("",  x) -> ("2", n)  empty, content+mode
+    // - file2: ("1", x) -> ("1", n)  non-empty, mode-only
+    // - file3: ("1", n) -> ("2", x)  non-empty, content+mode
+    // - file4: ("",  n) -> ("",  x)  empty, mode-only
+
     std::fs::write(repo_path.join("file1"), "").unwrap();
-    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
-    std::fs::write(repo_path.join("file3"), "foo\nbaz qux blah blah\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "1\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "1\n").unwrap();
+    std::fs::write(repo_path.join("file4"), "").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "x", "file1", "file2"]);
+
     test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
-    std::fs::write(repo_path.join("file2"), "").unwrap();
-    std::fs::write(repo_path.join("file3"), "foo\nbar\nbaz quux blah blah\n").unwrap();
+    std::fs::write(repo_path.join("file1"), "2\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "2\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "n", "file1", "file2"]);
+    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "x", "file3", "file4"]);
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::remove_file(repo_path.join("file2")).unwrap();
+    std::fs::remove_file(repo_path.join("file3")).unwrap();
+    std::fs::remove_file(repo_path.join("file4")).unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@--"]);
     insta::assert_snapshot!(stdout, @r###"
-    Modified regular file file1:
-            1: foo
-    Modified regular file file2:
-       1     : foo
-    Modified regular file file3:
-       1    1: foo
-            2: bar
-       2    3: baz quxquux blah blah
+    Added executable file file1:
+        (empty)
+    Added executable file file2:
+            1: 1
+    Added regular file file3:
+            1: 1
+    Added regular file file4:
+        (empty)
     "###);
-
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--color=debug"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@-"]);
     insta::assert_snapshot!(stdout, @r###"
-    [38;5;3m<<diff header::Modified regular file file1:>>[39m
-    <<diff::     >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::foo>>[24m[39m
-    [38;5;3m<<diff header::Modified regular file file2:>>[39m
-    [38;5;1m<<diff removed line_number::   1>>[39m<<diff::     : >>[4m[38;5;1m<<diff removed token::foo>>[24m[39m
-    [38;5;3m<<diff header::Modified regular file file3:>>[39m
-    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: foo>>
-    <<diff::     >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::bar>>[24m[39m
-    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: baz >>[4m[38;5;1m<<diff removed token::qux>>[38;5;2m<<diff added token::quux>>[24m[39m<<diff:: blah blah>>
+    Executable file became non-executable at file1:
+            1: 2
+    Executable file became non-executable at file2:
+    Non-executable file became executable at file3:
+       1    1: 12
+    Non-executable file became executable at file4:
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Removed regular file file1:
+       1     : 2
+    Removed regular file file2:
+       1     : 1
+    Removed executable file file3:
+       1     : 2
+    Removed executable file file4:
+        (empty)
     "###);
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@--", "--git"]);
     insta::assert_snapshot!(stdout, @r"
     diff --git a/file1 b/file1
-    index e69de29bb2..257cc5642c 100644
+    new file mode 100755
+    index 0000000000..e69de29bb2
+    diff --git a/file2 b/file2
+    new file mode 100755
+    index 0000000000..d00491fd7e
+    --- /dev/null
+    +++ b/file2
+    @@ -0,0 +1,1 @@
+    +1
+    diff --git a/file3 b/file3
+    new file mode 100644
+    index 0000000000..d00491fd7e
+    --- /dev/null
+    +++ b/file3
+    @@ -0,0 +1,1 @@
+    +1
+    diff --git a/file4 b/file4
+    new file mode 100644
+    index 0000000000..e69de29bb2
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@-", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file1
+    old mode 100755
+    new mode 100644
+    index e69de29bb2..0cfbf08886
     --- a/file1
     +++ b/file1
     @@ -0,0 +1,1 @@
-    +foo
+    +2
     diff --git a/file2 b/file2
-    index 257cc5642c..e69de29bb2 100644
-    --- a/file2
-    +++ b/file2
-    @@ -1,1 +0,0 @@
-    -foo
+    old mode 100755
+    new mode 100644
     diff --git a/file3 b/file3
-    index 221a95a095..a543ef3892 100644
+    old mode 100644
+    new mode 100755
+    index d00491fd7e..0cfbf08886
     --- a/file3
     +++ b/file3
-    @@ -1,2 +1,3 @@
-     foo
-    -baz qux blah blah
-    +bar
-    +baz quux blah blah
+    @@ -1,1 +1,1 @@
+    -1
+    +2
+    diff --git a/file4 b/file4
+    old mode 100644
+    new mode 100755
     ");
-
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--color=debug"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-r@", "--git"]);
     insta::assert_snapshot!(stdout, @r"
-    [1m<<diff file_header::diff --git a/file1 b/file1>>[0m
-    [1m<<diff file_header::index e69de29bb2..257cc5642c 100644>>[0m
-    [1m<<diff file_header::--- a/file1>>[0m
-    [1m<<diff file_header::+++ b/file1>>[0m
-    [38;5;6m<<diff hunk_header::@@ -0,0 +1,1 @@>>[39m
-    [38;5;2m<<diff added::+>>[4m<<diff added token::foo>>[24m[39m
-    [1m<<diff file_header::diff --git a/file2 b/file2>>[0m
-    [1m<<diff file_header::index 257cc5642c..e69de29bb2 100644>>[0m
-    [1m<<diff file_header::--- a/file2>>[0m
-    [1m<<diff file_header::+++ b/file2>>[0m
-    [38;5;6m<<diff hunk_header::@@ -1,1 +0,0 @@>>[39m
-    [38;5;1m<<diff removed::->>[4m<<diff removed token::foo>>[24m[39m
-    [1m<<diff file_header::diff --git a/file3 b/file3>>[0m
-    [1m<<diff file_header::index 221a95a095..a543ef3892 100644>>[0m
-    [1m<<diff file_header::--- a/file3>>[0m
-    [1m<<diff file_header::+++ b/file3>>[0m
-    [38;5;6m<<diff hunk_header::@@ -1,2 +1,3 @@>>[39m
-    <<diff context:: foo>>
-    [38;5;1m<<diff removed::-baz >>[4m<<diff removed token::qux>>[24m<<diff removed:: blah blah>>[39m
-    [38;5;2m<<diff added::+>>[4m<<diff added token::bar>>[24m[39m
-    [38;5;2m<<diff added::+baz >>[4m<<diff added token::quux>>[24m<<diff added:: blah blah>>[39m
+    diff --git a/file1 b/file1
+    deleted file mode 100644
+    index 0cfbf08886..0000000000
+    --- a/file1
+    +++ /dev/null
+    @@ -1,1 +0,0 @@
+    -2
+    diff --git a/file2 b/file2
+    deleted file mode 100644
+    index d00491fd7e..0000000000
+    --- a/file2
+    +++ /dev/null
+    @@ -1,1 +0,0 @@
+    -1
+    diff --git a/file3 b/file3
+    deleted file mode 100755
+    index 0cfbf08886..0000000000
+    --- a/file3
+    +++ /dev/null
+    @@ -1,1 +0,0 @@
+    -2
+    diff --git a/file4 b/file4
+    deleted file mode 100755
+    index e69de29bb2..0000000000
     ");
 }
 
 #[test]
-fn test_diff_color_words_inlining_threshold() {
+fn test_diff_git_index_and_mode_lines() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("content"), "a\n").unwrap();
+    std::fs::write(repo_path.join("mode"), "a\n").unwrap();
+    std::fs::write(repo_path.join("mode-and-content"), "a\n").unwrap();
+    std::fs::write(repo_path.join("rename-source"), "rename\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("content"), "b\n").unwrap();
+    std::fs::write(repo_path.join("mode-and-content"), "b\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["file", "chmod", "x", "mode", "mode-and-content"],
+    );
+    std::fs::rename(
+        repo_path.join("rename-source"),
+        repo_path.join("rename-target"),
+    )
+    .unwrap();
 
-    let render_diff = |max_alternation: i32, args: &[&str]| {
-        let config = format!("diff.color-words.max-inline-alternation={max_alternation}");
-        test_env.jj_cmd_success(&repo_path, &[&["diff", "--config", &config], args].concat())
-    };
-
-    let file1_path = "file1-single-line";
-    let file2_path = "file2-multiple-lines-in-single-hunk";
-    let file3_path = "file3-changes-across-lines";
-    std::fs::write(
-        repo_path.join(file1_path),
-        indoc! {"
-            == adds ==
-            a b c
-            == removes ==
-            a b c d e f g
-            == adds + removes ==
-            a b c d e
-            == adds + removes + adds ==
-            a b c d e
-            == adds + removes + adds + removes ==
-            a b c d e f g
-        "},
-    )
-    .unwrap();
-    std::fs::write(
-        repo_path.join(file2_path),
-        indoc! {"
-            == adds; removes; adds + removes ==
-            a b c
-            a b c d e f g
-            a b c d e
-            == adds + removes + adds; adds + removes + adds + removes ==
-            a b c d e
-            a b c d e f g
-        "},
-    )
-    .unwrap();
-    std::fs::write(
-        repo_path.join(file3_path),
-        indoc! {"
-            == adds ==
-            a b c
-            == removes ==
-            a b c d
-            e f g
-            == adds + removes ==
-            a b c
-            d e
-            == adds + removes + adds ==
-            a b c
-            d e
-            == adds + removes + adds + removes ==
-            a b
-            c d e f g
-        "},
-    )
-    .unwrap();
+    // Same mode and content (a rename): no index line. Same mode and different
+    // content: index line with the mode. Different mode and same content: only
+    // the mode lines. Different mode and content: the mode lines and the index
+    // line without the mode.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/content b/content
+    index 7898192261..6178079822 100644
+    --- a/content
+    +++ b/content
+    @@ -1,1 +1,1 @@
+    -a
+    +b
+    diff --git a/mode b/mode
+    old mode 100644
+    new mode 100755
+    diff --git a/mode-and-content b/mode-and-content
+    old mode 100644
+    new mode 100755
+    index 7898192261..6178079822
+    --- a/mode-and-content
+    +++ b/mode-and-content
+    @@ -1,1 +1,1 @@
+    -a
+    +b
+    diff --git a/rename-source b/rename-target
+    similarity index 100%
+    rename from rename-source
+    rename to rename-target
+    ");
+}
+
+#[test]
+fn test_diff_git_conflict_index_line() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let file_path = repo_path.join("file");
+
+    std::fs::write(&file_path, "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "base"]);
+    std::fs::write(&file_path, "b\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "side-a"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(base)", "-m", "side-b"]);
+    std::fs::write(&file_path, "c\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["new", "description(side-a)", "description(side-b)"],
+    );
     test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::write(
-        repo_path.join(file1_path),
-        indoc! {"
-            == adds ==
-            a X b Y Z c
-            == removes ==
-            a c f
-            == adds + removes ==
-            a X b d
-            == adds + removes + adds ==
-            a X b d Y
-            == adds + removes + adds + removes ==
-            X a Y b d Z e
-        "},
-    )
-    .unwrap();
-    std::fs::write(
-        repo_path.join(file2_path),
-        indoc! {"
-            == adds; removes; adds + removes ==
-            a X b Y Z c
-            a c f
-            a X b d
-            == adds + removes + adds; adds + removes + adds + removes ==
-            a X b d Y
-            X a Y b d Z e
-        "},
-    )
-    .unwrap();
-    std::fs::write(
-        repo_path.join(file3_path),
-        indoc! {"
-            == adds ==
-            a X b
-            Y Z c
-            == removes ==
-            a c f
-            == adds + removes ==
-            a
-            X b d
-            == adds + removes + adds ==
-            a X b d
-            Y
-            == adds + removes + adds + removes ==
-            X a Y b d
-            Z e
-        "},
-    )
-    .unwrap();
+    let conflict = std::fs::read_to_string(&file_path).unwrap();
+    std::fs::write(&file_path, format!("x\n{conflict}")).unwrap();
 
-    // default
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Modified regular file file1-single-line:
-       1    1: == adds ==
-       2    2: a X b Y Z c
-       3    3: == removes ==
-       4    4: a b c d e f g
-       5    5: == adds + removes ==
-       6    6: a X b c d e
-       7    7: == adds + removes + adds ==
-       8    8: a X b c d eY
-       9    9: == adds + removes + adds + removes ==
-      10     : a b c d e f g
-           10: X a Y b d Z e
-    Modified regular file file2-multiple-lines-in-single-hunk:
-       1    1: == adds; removes; adds + removes ==
-       2    2: a X b Y Z c
-       3    3: a b c d e f g
-       4    4: a X b c d e
-       5    5: == adds + removes + adds; adds + removes + adds + removes ==
-       6     : a b c d e
-       7     : a b c d e f g
-            6: a X b d Y
-            7: X a Y b d Z e
-    Modified regular file file3-changes-across-lines:
-       1    1: == adds ==
-       2    2: a X b
-       2    3: Y Z c
-       3    4: == removes ==
-       4    5: a b c d
-       5    5: e f g
-       6    6: == adds + removes ==
-       7    7: a
-       7    8: X b c
-       8    8: d e
-       9    9: == adds + removes + adds ==
-      10   10: a X b c
-      11   10: d e
-      11   11: Y
-      12   12: == adds + removes + adds + removes ==
-      13     : a b
-      14     : c d e f g
-           13: X a Y b d
-           14: Z e
-    "###);
+    // Conflicts have no object IDs, so the index line is omitted even though
+    // the content differs.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    --- a/file
+    +++ b/file
+    @@ -1,3 +1,4 @@
+    +x
+     <<<<<<< Conflict 1 of 1
+     %%%%%%% Changes from base to side #1
+     -a
+    ");
 
-    // -1: inline all
-    insta::assert_snapshot!(render_diff(-1, &[]), @r###"
-    Modified regular file file1-single-line:
-       1    1: == adds ==
-       2    2: a X b Y Z c
-       3    3: == removes ==
-       4    4: a b c d e f g
-       5    5: == adds + removes ==
-       6    6: a X b c d e
-       7    7: == adds + removes + adds ==
-       8    8: a X b c d eY
-       9    9: == adds + removes + adds + removes ==
-      10   10: X a Y b c d Z e f g
-    Modified regular file file2-multiple-lines-in-single-hunk:
-       1    1: == adds; removes; adds + removes ==
-       2    2: a X b Y Z c
-       3    3: a b c d e f g
-       4    4: a X b c d e
-       5    5: == adds + removes + adds; adds + removes + adds + removes ==
-       6    6: a X b c d eY
-       7    7: X a Y b c d Z e f g
-    Modified regular file file3-changes-across-lines:
-       1    1: == adds ==
-       2    2: a X b
-       2    3: Y Z c
-       3    4: == removes ==
-       4    5: a b c d
-       5    5: e f g
-       6    6: == adds + removes ==
-       7    7: a
-       7    8: X b c
-       8    8: d e
-       9    9: == adds + removes + adds ==
-      10   10: a X b c
-      11   10: d e
-      11   11: Y
-      12   12: == adds + removes + adds + removes ==
-      13   13: X a Y b
-      14   13: c d
-      14   14: Z e f g
-    "###);
+    // Mode-only change of a conflict
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-"]);
+    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "x", "file"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    old mode 100644
+    new mode 100755
+    ");
+}
 
-    // 0: no inlining
-    insta::assert_snapshot!(render_diff(0, &[]), @r###"
-    Modified regular file file1-single-line:
-       1    1: == adds ==
-       2     : a b c
-            2: a X b Y Z c
-       3    3: == removes ==
-       4     : a b c d e f g
-            4: a c f
-       5    5: == adds + removes ==
-       6     : a b c d e
-            6: a X b d
-       7    7: == adds + removes + adds ==
-       8     : a b c d e
-            8: a X b d Y
-       9    9: == adds + removes + adds + removes ==
-      10     : a b c d e f g
-           10: X a Y b d Z e
-    Modified regular file file2-multiple-lines-in-single-hunk:
-       1    1: == adds; removes; adds + removes ==
-       2     : a b c
-       3     : a b c d e f g
-       4     : a b c d e
-            2: a X b Y Z c
-            3: a c f
-            4: a X b d
-       5    5: == adds + removes + adds; adds + removes + adds + removes ==
-       6     : a b c d e
-       7     : a b c d e f g
-            6: a X b d Y
-            7: X a Y b d Z e
-    Modified regular file file3-changes-across-lines:
-       1    1: == adds ==
-       2     : a b c
-            2: a X b
-            3: Y Z c
-       3    4: == removes ==
-       4     : a b c d
-       5     : e f g
-            5: a c f
-       6    6: == adds + removes ==
-       7     : a b c
-       8     : d e
-            7: a
-            8: X b d
-       9    9: == adds + removes + adds ==
-      10     : a b c
-      11     : d e
-           10: a X b d
-           11: Y
-      12   12: == adds + removes + adds + removes ==
-      13     : a b
-      14     : c d e f g
-           13: X a Y b d
-           14: Z e
-    "###);
+#[test]
+fn test_diff_conflict_marker_len() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let file_path = repo_path.join("file");
 
-    // 1: inline adds-only or removes-only lines
-    insta::assert_snapshot!(render_diff(1, &[]), @r###"
-    Modified regular file file1-single-line:
-       1    1: == adds ==
-       2    2: a X b Y Z c
-       3    3: == removes ==
-       4    4: a b c d e f g
-       5    5: == adds + removes ==
-       6     : a b c d e
-            6: a X b d
-       7    7: == adds + removes + adds ==
-       8     : a b c d e
-            8: a X b d Y
-       9    9: == adds + removes + adds + removes ==
-      10     : a b c d e f g
-           10: X a Y b d Z e
-    Modified regular file file2-multiple-lines-in-single-hunk:
-       1    1: == adds; removes; adds + removes ==
-       2     : a b c
-       3     : a b c d e f g
-       4     : a b c d e
-            2: a X b Y Z c
-            3: a c f
-            4: a X b d
-       5    5: == adds + removes + adds; adds + removes + adds + removes ==
-       6     : a b c d e
-       7     : a b c d e f g
-            6: a X b d Y
-            7: X a Y b d Z e
-    Modified regular file file3-changes-across-lines:
-       1    1: == adds ==
-       2    2: a X b
-       2    3: Y Z c
-       3    4: == removes ==
-       4    5: a b c d
-       5    5: e f g
-       6    6: == adds + removes ==
-       7     : a b c
-       8     : d e
-            7: a
-            8: X b d
-       9    9: == adds + removes + adds ==
-      10     : a b c
-      11     : d e
-           10: a X b d
-           11: Y
-      12   12: == adds + removes + adds + removes ==
-      13     : a b
-      14     : c d e f g
-           13: X a Y b d
-           14: Z e
-    "###);
+    // The file content looks like conflict markers, so longer markers are used
+    std::fs::write(&file_path, "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "base"]);
+    std::fs::write(&file_path, "<<<<<<<\nb\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "side-a"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(base)", "-m", "side-b"]);
+    std::fs::write(&file_path, "=======\nc\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["new", "description(side-a)", "description(side-b)"],
+    );
+    let materialized = std::fs::read_to_string(&file_path).unwrap();
+    insta::assert_snapshot!(materialized, @r"
+    <<<<<<<<<<< Conflict 1 of 1
+    %%%%%%%%%%% Changes from base to side #1
+    -a
+    +<<<<<<<
+    +b
+    +++++++++++ Contents of side #2
+    =======
+    c
+    >>>>>>>>>>> Conflict 1 of 1 ends
+    ");
 
-    // 2: inline up to adds + removes lines
-    insta::assert_snapshot!(render_diff(2, &[]), @r###"
-    Modified regular file file1-single-line:
-       1    1: == adds ==
-       2    2: a X b Y Z c
-       3    3: == removes ==
-       4    4: a b c d e f g
-       5    5: == adds + removes ==
-       6    6: a X b c d e
-       7    7: == adds + removes + adds ==
-       8     : a b c d e
-            8: a X b d Y
-       9    9: == adds + removes + adds + removes ==
-      10     : a b c d e f g
-           10: X a Y b d Z e
-    Modified regular file file2-multiple-lines-in-single-hunk:
-       1    1: == adds; removes; adds + removes ==
-       2    2: a X b Y Z c
-       3    3: a b c d e f g
-       4    4: a X b c d e
-       5    5: == adds + removes + adds; adds + removes + adds + removes ==
-       6     : a b c d e
-       7     : a b c d e f g
-            6: a X b d Y
-            7: X a Y b d Z e
-    Modified regular file file3-changes-across-lines:
-       1    1: == adds ==
-       2    2: a X b
-       2    3: Y Z c
-       3    4: == removes ==
-       4    5: a b c d
-       5    5: e f g
-       6    6: == adds + removes ==
-       7    7: a
-       7    8: X b c
-       8    8: d e
-       9    9: == adds + removes + adds ==
-      10     : a b c
-      11     : d e
-           10: a X b d
-           11: Y
-      12   12: == adds + removes + adds + removes ==
-      13     : a b
-      14     : c d e f g
-           13: X a Y b d
-           14: Z e
-    "###);
+    // The diff shows the same markers as the working copy
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--from=description(base)", "--context=100"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index 7898192261..0000000000 100644
+    --- a/file
+    +++ b/file
+    @@ -1,1 +1,9 @@
+    -a
+    +<<<<<<<<<<< Conflict 1 of 1
+    +%%%%%%%%%%% Changes from base to side #1
+    +-a
+    ++<<<<<<<
+    ++b
+    ++++++++++++ Contents of side #2
+    +=======
+    +c
+    +>>>>>>>>>>> Conflict 1 of 1 ends
+    ");
+}
 
-    // 3: inline up to adds + removes + adds lines
-    insta::assert_snapshot!(render_diff(3, &[]), @r###"
-    Modified regular file file1-single-line:
-       1    1: == adds ==
-       2    2: a X b Y Z c
-       3    3: == removes ==
-       4    4: a b c d e f g
-       5    5: == adds + removes ==
-       6    6: a X b c d e
-       7    7: == adds + removes + adds ==
-       8    8: a X b c d eY
-       9    9: == adds + removes + adds + removes ==
-      10     : a b c d e f g
-           10: X a Y b d Z e
-    Modified regular file file2-multiple-lines-in-single-hunk:
-       1    1: == adds; removes; adds + removes ==
-       2    2: a X b Y Z c
-       3    3: a b c d e f g
-       4    4: a X b c d e
-       5    5: == adds + removes + adds; adds + removes + adds + removes ==
-       6     : a b c d e
-       7     : a b c d e f g
-            6: a X b d Y
-            7: X a Y b d Z e
-    Modified regular file file3-changes-across-lines:
-       1    1: == adds ==
-       2    2: a X b
-       2    3: Y Z c
-       3    4: == removes ==
-       4    5: a b c d
-       5    5: e f g
-       6    6: == adds + removes ==
-       7    7: a
-       7    8: X b c
-       8    8: d e
-       9    9: == adds + removes + adds ==
-      10   10: a X b c
-      11   10: d e
-      11   11: Y
-      12   12: == adds + removes + adds + removes ==
-      13     : a b
-      14     : c d e f g
-           13: X a Y b d
-           14: Z e
-    "###);
+#[test]
+fn test_diff_summary_conflict() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
 
-    // 4: inline up to adds + removes + adds + removes lines
-    insta::assert_snapshot!(render_diff(4, &[]), @r###"
-    Modified regular file file1-single-line:
-       1    1: == adds ==
-       2    2: a X b Y Z c
-       3    3: == removes ==
-       4    4: a b c d e f g
-       5    5: == adds + removes ==
-       6    6: a X b c d e
-       7    7: == adds + removes + adds ==
-       8    8: a X b c d eY
-       9    9: == adds + removes + adds + removes ==
-      10   10: X a Y b c d Z e f g
-    Modified regular file file2-multiple-lines-in-single-hunk:
-       1    1: == adds; removes; adds + removes ==
-       2    2: a X b Y Z c
-       3    3: a b c d e f g
-       4    4: a X b c d e
-       5    5: == adds + removes + adds; adds + removes + adds + removes ==
-       6    6: a X b c d eY
-       7    7: X a Y b c d Z e f g
-    Modified regular file file3-changes-across-lines:
-       1    1: == adds ==
-       2    2: a X b
-       2    3: Y Z c
-       3    4: == removes ==
-       4    5: a b c d
-       5    5: e f g
-       6    6: == adds + removes ==
-       7    7: a
-       7    8: X b c
-       8    8: d e
-       9    9: == adds + removes + adds ==
-      10   10: a X b c
-      11   10: d e
-      11   11: Y
-      12   12: == adds + removes + adds + removes ==
-      13   13: X a Y b
-      14   13: c d
-      14   14: Z e f g
-    "###);
+    std::fs::write(repo_path.join("file"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=left"]);
+    std::fs::write(repo_path.join("file"), "left\n").unwrap();
+    std::fs::write(repo_path.join("other"), "other\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m=right"]);
+    std::fs::write(repo_path.join("file"), "right\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(left)", "@"]);
 
-    // context words in added/removed lines should be labeled as such
-    insta::assert_snapshot!(render_diff(2, &["--color=always"]), @r###"
-    [38;5;3mModified regular file file1-single-line:[39m
-    [38;5;1m   1[39m [38;5;2m   1[39m: == adds ==
-    [38;5;1m   2[39m [38;5;2m   2[39m: a [4m[38;5;2mX [24m[39mb [4m[38;5;2mY Z [24m[39mc
-    [38;5;1m   3[39m [38;5;2m   3[39m: == removes ==
-    [38;5;1m   4[39m [38;5;2m   4[39m: a [4m[38;5;1mb [24m[39mc [4m[38;5;1md e [24m[39mf[4m[38;5;1m g[24m[39m
-    [38;5;1m   5[39m [38;5;2m   5[39m: == adds + removes ==
-    [38;5;1m   6[39m [38;5;2m   6[39m: a [4m[38;5;2mX [24m[39mb [4m[38;5;1mc [24m[39md[4m[38;5;1m e[24m[39m
-    [38;5;1m   7[39m [38;5;2m   7[39m: == adds + removes + adds ==
-    [38;5;1m   8[39m     : [38;5;1ma b [4mc [24md [4me[24m[39m
-         [38;5;2m   8[39m: [38;5;2ma [4mX [24mb d [4mY[24m[39m
-    [38;5;1m   9[39m [38;5;2m   9[39m: == adds + removes + adds + removes ==
-    [38;5;1m  10[39m     : [38;5;1ma b [4mc [24md e[4m f g[24m[39m
-         [38;5;2m  10[39m: [4m[38;5;2mX [24ma [4mY [24mb d [4mZ [24me[39m
-    [38;5;3mModified regular file file2-multiple-lines-in-single-hunk:[39m
-    [38;5;1m   1[39m [38;5;2m   1[39m: == adds; removes; adds + removes ==
-    [38;5;1m   2[39m [38;5;2m   2[39m: a [4m[38;5;2mX [24m[39mb [4m[38;5;2mY Z [24m[39mc
-    [38;5;1m   3[39m [38;5;2m   3[39m: a [4m[38;5;1mb [24m[39mc [4m[38;5;1md e [24m[39mf[4m[38;5;1m g[24m[39m
-    [38;5;1m   4[39m [38;5;2m   4[39m: a [4m[38;5;2mX [24m[39mb [4m[38;5;1mc [24m[39md[4m[38;5;1m e[24m[39m
-    [38;5;1m   5[39m [38;5;2m   5[39m: == adds + removes + adds; adds + removes + adds + removes ==
-    [38;5;1m   6[39m     : [38;5;1ma b [4mc [24md [4me[24m[39m
-    [38;5;1m   7[39m     : [38;5;1ma b [4mc [24md e[4m f g[24m[39m
+    // Conflicted paths are marked with 'U'
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "-s", "--color=debug", "--from=description(right)"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    [38;5;9m<<diff conflict::U file>>[39m
+    [38;5;2m<<diff added::A other>>[39m
+    ");
+
+    // Conflicted additions are still marked with 'A'
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "--from=root()"]);
+    insta::assert_snapshot!(stdout, @r"
+    A file
+    A other
+    ");
+
+    // Resolving a conflict is a modification
+    std::fs::write(repo_path.join("file"), "resolved\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "--from=description(right)"]);
+    insta::assert_snapshot!(stdout, @r"
+    M file
+    A other
+    ");
+}
+
+#[test]
+fn test_diff_stat_conflict() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=left"]);
+    std::fs::write(repo_path.join("file"), "left\n").unwrap();
+    std::fs::write(repo_path.join("other"), "other\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m=right"]);
+    std::fs::write(repo_path.join("file"), "right\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(left)", "@"]);
+
+    // Conflict markers aren't counted as changed lines
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--stat",
+            "--color=debug",
+            "--from=description(right)",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    <<diff::file  | >>[38;5;9m<<diff conflict::conflict>>[39m
+    <<diff::other | 1 >>[38;5;2m<<diff added::+>>[38;5;1m<<diff removed::>>[39m
+    <<diff stat-summary::2 files changed, 1 insertion(+), 0 deletions(-)>>
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--numstat", "--from=description(right)"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    -	-	file
+    1	0	other
+    1	0
+    ");
+
+    // Lines are counted once the conflict is resolved
+    std::fs::write(repo_path.join("file"), "resolved\n").unwrap();
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--from=description(right)"]);
+    insta::assert_snapshot!(stdout, @r"
+    file  | 2 +-
+    other | 1 +
+    2 files changed, 2 insertions(+), 1 deletion(-)
+    ");
+}
+
+#[test]
+fn test_diff_stat_sort() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("a"), "1\n2\n3\n4\n").unwrap();
+    std::fs::write(repo_path.join("b"), "1\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("a"), "").unwrap();
+    std::fs::write(repo_path.join("b"), "1\n2\n3\n").unwrap();
+    std::fs::write(repo_path.join("c"), "c1\nc2\n").unwrap();
+    std::fs::write(repo_path.join("d"), "d1\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--stat-sort=path"]);
+    insta::assert_snapshot!(stdout, @r"
+    a   | 4 ----
+    b   | 2 ++
+    c   | 2 ++
+    d   | 1 +
+    4 files changed, 5 insertions(+), 4 deletions(-)
+    ");
+
+    // Files with equal counts are sorted by path
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--stat-sort=added"]);
+    insta::assert_snapshot!(stdout, @r"
+    b   | 2 ++
+    c   | 2 ++
+    d   | 1 +
+    a   | 4 ----
+    4 files changed, 5 insertions(+), 4 deletions(-)
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--stat-sort=removed"]);
+    insta::assert_snapshot!(stdout, @r"
+    a   | 4 ----
+    b   | 2 ++
+    c   | 2 ++
+    d   | 1 +
+    4 files changed, 5 insertions(+), 4 deletions(-)
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--numstat", "--stat-sort=total"]);
+    insta::assert_snapshot!(stdout, @r"
+    0	4	a
+    2	0	b
+    2	0	c
+    1	0	d
+    5	4
+    ");
+
+    // JSON entries are sorted too
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--json-stat", "--stat-sort=added"]);
+    insta::assert_snapshot!(stdout, @r#"
+    {"path":"b","status":"modified","old":{"type":"file","mode":"100644","id":"d00491fd7e5bb6fa28c517a0bb32b8b506539d4d"},"new":{"type":"file","mode":"100644","id":"01e79c32a8c99c557f0757da7cb6d65b3414466d"},"binary":false,"added":2,"removed":0}
+    {"path":"c","status":"added","old":null,"new":{"type":"file","mode":"100644","id":"d0aaf976aff18cfd95e5d6b20de694185239a40d"},"binary":false,"added":2,"removed":0}
+    {"path":"d","status":"added","old":null,"new":{"type":"file","mode":"100644","id":"6f1852975b9306ae5d8dfdf0d4cb1f5cb36ac229"},"binary":false,"added":1,"removed":0}
+    {"path":"a","status":"modified","old":{"type":"file","mode":"100644","id":"94ebaf900161394059478fd88aec30e59092a1d7"},"new":{"type":"file","mode":"100644","id":"e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"},"binary":false,"added":0,"removed":4}
+    "#);
+
+    // The sort key requires a stat format
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--stat-sort=total"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the following required arguments were not provided:
+      <--stat|--json-stat|--numstat>
+
+    Usage: jj diff --stat-sort <KEY> <--stat|--json-stat|--numstat> [FILESETS]...
+
+    For more information, try '--help'.
+    ");
+}
+
+#[test]
+fn test_diff_color_words_conflict_markers() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=left"]);
+    std::fs::write(repo_path.join("file"), "left\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m=right"]);
+    std::fs::write(repo_path.join("file"), "right\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(left)", "@"]);
+
+    // Conflict markers are labeled
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--color=debug", "--from=description(right)"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    [38;5;3m<<diff header::Created conflict in file:>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[1m[4m[38;5;2m<<diff conflict_marker added token::<<<<<<< Conflict 1 of 1>>[0m
+    <<diff::     >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: >>[1m[4m[38;5;2m<<diff conflict_marker added token::%%%%%%% Changes from base to side #1>>[0m
+    <<diff::     >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::-base>>[24m[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   4>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::+left>>[24m[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   5>>[39m<<diff::: >>[1m[4m[38;5;2m<<diff conflict_marker added token::+++++++ Contents of side #2>>[0m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   6>>[39m<<diff::: right>>
+    <<diff::     >>[38;5;2m<<diff added line_number::   7>>[39m<<diff::: >>[1m[4m[38;5;2m<<diff conflict_marker added token::>>>>>>> Conflict 1 of 1 ends>>[0m
+    ");
+
+    // Lines that only look like conflict markers in regular files aren't
+    std::fs::write(repo_path.join("file"), "<<<<<<< not a marker\n").unwrap();
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--color=debug", "--from=description(right)"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    [38;5;3m<<diff header::Modified regular file file:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[4m[38;5;1m<<diff removed token::right>>[38;5;2m<<diff added token::<<<<<<< not a marker>>[24m[39m<<diff::>>
+    ");
+}
+
+#[test]
+fn test_diff_types() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let file_path = repo_path.join("foo");
+
+    // Missing
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=missing"]);
+
+    // Normal file
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=file"]);
+    std::fs::write(&file_path, "foo").unwrap();
+
+    // Conflict (add/add)
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=conflict"]);
+    std::fs::write(&file_path, "foo").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "root()"]);
+    std::fs::write(&file_path, "bar").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["squash", r#"--into=description("conflict")"#]);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        use std::path::PathBuf;
+
+        // Executable
+        test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=executable"]);
+        std::fs::write(&file_path, "foo").unwrap();
+        std::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Symlink
+        test_env.jj_cmd_ok(&repo_path, &["new", "root()", "-m=symlink"]);
+        std::os::unix::fs::symlink(PathBuf::from("."), &file_path).unwrap();
+    }
+
+    let diff = |from: &str, to: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "diff",
+                "--types",
+                &format!(r#"--from=description("{from}")"#),
+                &format!(r#"--to=description("{to}")"#),
+            ],
+        )
+    };
+    insta::assert_snapshot!(diff("missing", "file"), @r###"
+    -F foo
+    "###);
+    insta::assert_snapshot!(diff("file", "conflict"), @r###"
+    FC foo
+    "###);
+    insta::assert_snapshot!(diff("conflict", "missing"), @r###"
+    C- foo
+    "###);
+
+    #[cfg(unix)]
+    {
+        insta::assert_snapshot!(diff("symlink", "file"), @r###"
+        LF foo
+        "###);
+        insta::assert_snapshot!(diff("missing", "executable"), @r###"
+        -F foo
+        "###);
+    }
+
+    // The type names can be spelled out
+    let long_diff = |from: &str, to: &str| {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "diff",
+                "--types",
+                "--long",
+                &format!(r#"--from=description("{from}")"#),
+                &format!(r#"--to=description("{to}")"#),
+            ],
+        )
+    };
+    insta::assert_snapshot!(long_diff("missing", "file"), @"absent -> regular file foo");
+    insta::assert_snapshot!(long_diff("file", "conflict"), @"regular file -> conflict foo");
+    insta::assert_snapshot!(long_diff("conflict", "missing"), @"conflict -> absent foo");
+
+    #[cfg(unix)]
+    {
+        insta::assert_snapshot!(long_diff("symlink", "file"), @"symlink -> regular file foo");
+        insta::assert_snapshot!(long_diff("missing", "executable"), @"absent -> executable file foo");
+    }
+
+    // --long requires --types
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--long"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the following required arguments were not provided:
+      --types
+
+    Usage: jj diff --types --long [FILESETS]...
+
+    For more information, try '--help'.
+    ");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_diff_symlink_target() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::os::unix::fs::symlink("a/b/c/d/e", repo_path.join("link")).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("link")).unwrap();
+    std::os::unix::fs::symlink("x/b/y/d/z", repo_path.join("link")).unwrap();
+
+    // Changed path components are highlighted inline even if there are more
+    // alternations than max-inline-alternation.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--color=debug"]);
+    insta::assert_snapshot!(stdout, @r"
+    [38;5;3m<<diff header::Symlink target changed at link:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[4m[38;5;1m<<diff removed token::a>>[38;5;2m<<diff added token::x>>[24m[39m<<diff::/b/>>[4m[38;5;1m<<diff removed token::c>>[38;5;2m<<diff added token::y>>[24m[39m<<diff::/d/>>[4m[38;5;1m<<diff removed token::e>>[38;5;2m<<diff added token::z>>[24m[39m<<diff::>>
+    ");
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--config=diff.color-words.max-inline-alternation=0"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Symlink target changed at link:
+       1    1: ax/b/cy/d/ez
+    ");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_diff_git_type_change() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file-to-link"), "content\n").unwrap();
+    std::os::unix::fs::symlink("target", repo_path.join("link-to-file")).unwrap();
+    std::fs::write(repo_path.join("exec"), "exec\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file-to-link")).unwrap();
+    std::os::unix::fs::symlink("target", repo_path.join("file-to-link")).unwrap();
+    std::fs::remove_file(repo_path.join("link-to-file")).unwrap();
+    std::fs::write(repo_path.join("link-to-file"), "content\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "x", "exec"]);
+
+    // A type change is shown as a deletion and a creation, but a change of the
+    // executable bit is a mode change
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/exec b/exec
+    old mode 100644
+    new mode 100755
+    diff --git a/file-to-link b/file-to-link
+    deleted file mode 100644
+    index d95f3ad14d..0000000000
+    --- a/file-to-link
+    +++ /dev/null
+    @@ -1,1 +0,0 @@
+    -content
+    diff --git a/file-to-link b/file-to-link
+    new file mode 120000
+    index 0000000000..1de565933b
+    --- /dev/null
+    +++ b/file-to-link
+    @@ -0,0 +1,1 @@
+    +target
+    \ No newline at end of file
+    diff --git a/link-to-file b/link-to-file
+    deleted file mode 120000
+    index 1de565933b..0000000000
+    --- a/link-to-file
+    +++ /dev/null
+    @@ -1,1 +0,0 @@
+    -target
+    \ No newline at end of file
+    diff --git a/link-to-file b/link-to-file
+    new file mode 100644
+    index 0000000000..d95f3ad14d
+    --- /dev/null
+    +++ b/link-to-file
+    @@ -0,0 +1,1 @@
+    +content
+    ");
+}
+
+#[test]
+fn test_diff_short_and_long_formats() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "foo\n").unwrap();
+
+    // The short format comes first regardless of the argument order.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--stat"]);
+    insta::assert_snapshot!(stdout, @r"
+    file | 1 +
+    1 file changed, 1 insertion(+), 0 deletions(-)
+    diff --git a/file b/file
+    new file mode 100644
+    index 0000000000..257cc5642c
+    --- /dev/null
+    +++ b/file
+    @@ -0,0 +1,1 @@
+    +foo
+    ");
+
+    // --patch implies the default format unless a long format is specified.
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--no-graph", "-T''", "-r@", "--patch", "--stat"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    file | 1 +
+    1 file changed, 1 insertion(+), 0 deletions(-)
+    Added regular file file:
+            1: foo
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-T''",
+            "-r@",
+            "--patch",
+            "--types",
+            "--git",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    -F file
+    diff --git a/file b/file
+    new file mode 100644
+    index 0000000000..257cc5642c
+    --- /dev/null
+    +++ b/file
+    @@ -0,0 +1,1 @@
+    +foo
+    ");
+
+    // The default format shouldn't be duplicated.
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--no-graph",
+            "-T''",
+            "-r@",
+            "--patch",
+            "--summary",
+            "--config=ui.diff.format=summary",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @"A file");
+}
+
+#[test]
+fn test_diff_name_only() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("deleted"), "d").unwrap();
+    std::fs::write(repo_path.join("modified"), "m").unwrap();
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["diff", "--name-only"]), @r###"
+    deleted
+    modified
+    "###);
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-mfirst"]);
+    std::fs::remove_file(repo_path.join("deleted")).unwrap();
+    std::fs::write(repo_path.join("modified"), "mod").unwrap();
+    std::fs::write(repo_path.join("added"), "add").unwrap();
+    std::fs::create_dir(repo_path.join("sub")).unwrap();
+    std::fs::write(repo_path.join("sub/added"), "sub/add").unwrap();
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["diff", "--name-only"]).replace('\\', "/"),
+    @r###"
+    added
+    deleted
+    modified
+    sub/added
+    "###);
+}
+
+#[test]
+fn test_diff_bad_args() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "-s", "--types"]);
+    insta::assert_snapshot!(stderr, @r###"
+    error: the argument '--summary' cannot be used with '--types'
+
+    Usage: jj diff --summary [FILESETS]...
+
+    For more information, try '--help'.
+    "###);
+
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--color-words", "--git"]);
+    insta::assert_snapshot!(stderr, @r###"
+    error: the argument '--color-words' cannot be used with '--git'
+
+    Usage: jj diff --color-words [FILESETS]...
+
+    For more information, try '--help'.
+    "###);
+}
+
+#[test]
+fn test_diff_relative_paths() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::create_dir_all(repo_path.join("dir1").join("subdir1")).unwrap();
+    std::fs::create_dir(repo_path.join("dir2")).unwrap();
+    std::fs::write(repo_path.join("file1"), "foo1\n").unwrap();
+    std::fs::write(repo_path.join("dir1").join("file2"), "foo2\n").unwrap();
+    std::fs::write(
+        repo_path.join("dir1").join("subdir1").join("file3"),
+        "foo3\n",
+    )
+    .unwrap();
+    std::fs::write(repo_path.join("dir2").join("file4"), "foo4\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "bar1\n").unwrap();
+    std::fs::write(repo_path.join("dir1").join("file2"), "bar2\n").unwrap();
+    std::fs::write(
+        repo_path.join("dir1").join("subdir1").join("file3"),
+        "bar3\n",
+    )
+    .unwrap();
+    std::fs::write(repo_path.join("dir2").join("file4"), "bar4\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff"]);
+    #[cfg(unix)]
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file2:
+       1    1: foo2bar2
+    Modified regular file subdir1/file3:
+       1    1: foo3bar3
+    Modified regular file ../dir2/file4:
+       1    1: foo4bar4
+    Modified regular file ../file1:
+       1    1: foo1bar1
+    "###);
+    #[cfg(windows)]
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file2:
+       1    1: foo2bar2
+    Modified regular file subdir1\file3:
+       1    1: foo3bar3
+    Modified regular file ..\dir2\file4:
+       1    1: foo4bar4
+    Modified regular file ..\file1:
+       1    1: foo1bar1
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "-s"]);
+    #[cfg(unix)]
+    insta::assert_snapshot!(stdout, @r###"
+    M file2
+    M subdir1/file3
+    M ../dir2/file4
+    M ../file1
+    "###);
+    #[cfg(windows)]
+    insta::assert_snapshot!(stdout, @r###"
+    M file2
+    M subdir1\file3
+    M ..\dir2\file4
+    M ..\file1
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "--types"]);
+    #[cfg(unix)]
+    insta::assert_snapshot!(stdout, @r###"
+    FF file2
+    FF subdir1/file3
+    FF ../dir2/file4
+    FF ../file1
+    "###);
+    #[cfg(windows)]
+    insta::assert_snapshot!(stdout, @r###"
+    FF file2
+    FF subdir1\file3
+    FF ..\dir2\file4
+    FF ..\file1
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/dir1/file2 b/dir1/file2
+    index 54b060eee9..1fe912cdd8 100644
+    --- a/dir1/file2
+    +++ b/dir1/file2
+    @@ -1,1 +1,1 @@
+    -foo2
+    +bar2
+    diff --git a/dir1/subdir1/file3 b/dir1/subdir1/file3
+    index c1ec6c6f12..f3c8b75ec6 100644
+    --- a/dir1/subdir1/file3
+    +++ b/dir1/subdir1/file3
+    @@ -1,1 +1,1 @@
+    -foo3
+    +bar3
+    diff --git a/dir2/file4 b/dir2/file4
+    index a0016dbc4c..17375f7a12 100644
+    --- a/dir2/file4
+    +++ b/dir2/file4
+    @@ -1,1 +1,1 @@
+    -foo4
+    +bar4
+    diff --git a/file1 b/file1
+    index 1715acd6a5..05c4fe6772 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,1 +1,1 @@
+    -foo1
+    +bar1
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir1"), &["diff", "--stat"]);
+    #[cfg(unix)]
+    insta::assert_snapshot!(stdout, @r###"
+    file2         | 2 +-
+    subdir1/file3 | 2 +-
+    ../dir2/file4 | 2 +-
+    ../file1      | 2 +-
+    4 files changed, 4 insertions(+), 4 deletions(-)
+    "###);
+    #[cfg(windows)]
+    insta::assert_snapshot!(stdout, @r###"
+    file2         | 2 +-
+    subdir1\file3 | 2 +-
+    ..\dir2\file4 | 2 +-
+    ..\file1      | 2 +-
+    4 files changed, 4 insertions(+), 4 deletions(-)
+    "###);
+}
+
+#[test]
+fn test_diff_hunks() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    // Test added, removed, inserted, and modified lines. The modified line
+    // contains unchanged words.
+    std::fs::write(repo_path.join("file1"), "").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "foo\nbaz qux blah blah\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "").unwrap();
+    std::fs::write(repo_path.join("file3"), "foo\nbar\nbaz quux blah blah\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1:
+            1: foo
+    Modified regular file file2:
+       1     : foo
+    Modified regular file file3:
+       1    1: foo
+            2: bar
+       2    3: baz quxquux blah blah
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--color=debug"]);
+    insta::assert_snapshot!(stdout, @r###"
+    [38;5;3m<<diff header::Modified regular file file1:>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::foo>>[24m[39m
+    [38;5;3m<<diff header::Modified regular file file2:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff::     : >>[4m[38;5;1m<<diff removed token::foo>>[24m[39m
+    [38;5;3m<<diff header::Modified regular file file3:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: foo>>
+    <<diff::     >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::bar>>[24m[39m
+    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: baz >>[4m[38;5;1m<<diff removed token::qux>>[38;5;2m<<diff added token::quux>>[24m[39m<<diff:: blah blah>>
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file1
+    index e69de29bb2..257cc5642c 100644
+    --- a/file1
+    +++ b/file1
+    @@ -0,0 +1,1 @@
+    +foo
+    diff --git a/file2 b/file2
+    index 257cc5642c..e69de29bb2 100644
+    --- a/file2
+    +++ b/file2
+    @@ -1,1 +0,0 @@
+    -foo
+    diff --git a/file3 b/file3
+    index 221a95a095..a543ef3892 100644
+    --- a/file3
+    +++ b/file3
+    @@ -1,2 +1,3 @@
+     foo
+    -baz qux blah blah
+    +bar
+    +baz quux blah blah
+    ");
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--color=debug"]);
+    insta::assert_snapshot!(stdout, @r"
+    [1m<<diff file_header::diff --git a/file1 b/file1>>[0m
+    [1m<<diff file_header::index e69de29bb2..257cc5642c 100644>>[0m
+    [1m<<diff file_header::--- a/file1>>[0m
+    [1m<<diff file_header::+++ b/file1>>[0m
+    [38;5;6m<<diff hunk_header::@@ -0,0 +1,1 @@>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::foo>>[24m[39m
+    [1m<<diff file_header::diff --git a/file2 b/file2>>[0m
+    [1m<<diff file_header::index 257cc5642c..e69de29bb2 100644>>[0m
+    [1m<<diff file_header::--- a/file2>>[0m
+    [1m<<diff file_header::+++ b/file2>>[0m
+    [38;5;6m<<diff hunk_header::@@ -1,1 +0,0 @@>>[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::foo>>[24m[39m
+    [1m<<diff file_header::diff --git a/file3 b/file3>>[0m
+    [1m<<diff file_header::index 221a95a095..a543ef3892 100644>>[0m
+    [1m<<diff file_header::--- a/file3>>[0m
+    [1m<<diff file_header::+++ b/file3>>[0m
+    [38;5;6m<<diff hunk_header::@@ -1,2 +1,3 @@>>[39m
+    <<diff context:: foo>>
+    [38;5;1m<<diff removed::-baz >>[4m<<diff removed token::qux>>[24m<<diff removed:: blah blah>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::bar>>[24m[39m
+    [38;5;2m<<diff added::+baz >>[4m<<diff added token::quux>>[24m<<diff added:: blah blah>>[39m
+    ");
+}
+
+#[test]
+fn test_diff_color_words_wide_line_numbers() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let content = (1..=10000).map(|i| format!("{i}\n")).join("");
+    std::fs::write(repo_path.join("file"), &content).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(
+        repo_path.join("file"),
+        content.replace("9998\n", "changed\n") + "added\n",
+    )
+    .unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file:
+        ... (9994 lines)
+     9995  9995: 9995
+     9996  9996: 9996
+     9997  9997: 9997
+     9998  9998: 9998changed
+     9999  9999: 9999
+    10000 10000: 10000
+          10001: added
+    ");
+}
+
+#[test]
+fn test_diff_color_words_no_line_numbers() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file"), "a\nb\nc\nd\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "a\nB\nc\nd\ne\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--no-line-numbers"]);
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file:
+    a
+    bB
+    c
+    d
+    e
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--config=diff.color-words.line-numbers=false"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file:
+    a
+    bB
+    c
+    d
+    e
+    ");
+
+    // --line-numbers overrides the config
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--config=diff.color-words.line-numbers=false",
+            "--line-numbers",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file:
+       1    1: a
+       2    2: bB
+       3    3: c
+       4    4: d
+            5: e
+    ");
+
+    // The flags apply to Git-format diffs too
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--no-line-numbers"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index d68dd4031d..6fe8acc878 100644
+    --- a/file
+    +++ b/file
+    @@ -1,4 +1,5 @@
+     a
+    -b
+    +B
+     c
+     d
+    +e
+    ");
+    let stderr =
+        test_env.jj_cmd_cli_error(&repo_path, &["diff", "--line-numbers", "--no-line-numbers"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the argument '--line-numbers' cannot be used with '--no-line-numbers'
+
+    Usage: jj diff --line-numbers [FILESETS]...
+
+    For more information, try '--help'.
+    ");
+}
+
+#[test]
+fn test_diff_color_words_gap_marker() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let lines = (1..=20).map(|i| format!("{i}\n")).collect_vec();
+    std::fs::write(repo_path.join("file"), lines.concat()).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    let mut lines = lines;
+    lines[3] = "four\n".to_owned();
+    lines.remove(10);
+    lines[14] = "sixteen\n".to_owned();
+    std::fs::write(repo_path.join("file"), lines.concat()).unwrap();
+
+    // The skipped lines before a change are marked with the new line number
+    // where the diff resumes
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--context=1",
+            "--config=diff.color-words.gap-marker='line-number'",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file:
+        @@ 3 @@
+       3    3: 3
+       4    4: 4four
+       5    5: 5
+        @@ 10 @@
+      10   10: 10
+      11     : 11
+      12   11: 12
+        @@ 14 @@
+      15   14: 15
+      16   15: 16sixteen
+      17   16: 17
+        ... (3 lines)
+    ");
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--context=1",
+            "--color=debug",
+            "--config=diff.color-words.gap-marker='line-number'",
+        ],
+    );
+    insta::assert_snapshot!(stdout.lines().nth(1).unwrap(), @"<<diff::    >>[38;5;6m<<diff hunk_header::@@ 3 @@>>[39m<<diff::>>");
+}
+
+#[test]
+fn test_diff_color_words_inline_context() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(
+        repo_path.join("file"),
+        "function(){return 1}; function(){return 2}; function(){return 3};\nshort\n",
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(
+        repo_path.join("file"),
+        "function(){return 0}; function(){return 2}; function(){return 4};\nshort\n",
+    )
+    .unwrap();
+
+    // Unchanged text is shortened around each change, but unchanged lines are
+    // kept
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--config=diff.color-words.max-inline-alternation=-1",
+            "--config=diff.color-words.inline-context=5",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file:
+       1    1: …turn 10}; fu…turn 34};
+       2    2: short
+    ");
+
+    // Whole lines are shown by default
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--config=diff.color-words.max-inline-alternation=-1",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file:
+       1    1: function(){return 10}; function(){return 2}; function(){return 34};
+       2    2: short
+    ");
+}
+
+#[test]
+fn test_diff_color_words_tokenizer() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file"), "let identifierName = 1.23;\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "let identifierNome = 1.24;\n").unwrap();
+
+    let render_diff = |tokenizer: &str| {
+        let config = format!("diff.color-words.tokenizer={tokenizer}");
+        test_env.jj_cmd_success(&repo_path, &["diff", "--color=debug", "--config", &config])
+    };
+
+    // Word tokenizer (default)
+    insta::assert_snapshot!(render_diff("word"), @r"
+    [38;5;3m<<diff header::Modified regular file file:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff::     : >>[38;5;1m<<diff removed::let >>[4m<<diff removed token::identifierName>>[24m<<diff removed:: = 1.>>[4m<<diff removed token::23>>[24m<<diff removed::;>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[38;5;2m<<diff added::let >>[4m<<diff added token::identifierNome>>[24m<<diff added:: = 1.>>[4m<<diff added token::24>>[24m<<diff added::;>>[39m
+    ");
+
+    // Character tokenizer
+    insta::assert_snapshot!(render_diff("char"), @r"
+    [38;5;3m<<diff header::Modified regular file file:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff::     : >>[38;5;1m<<diff removed::let identifierN>>[4m<<diff removed token::a>>[24m<<diff removed::me = 1.2>>[4m<<diff removed token::3>>[24m<<diff removed::;>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[38;5;2m<<diff added::let identifierN>>[4m<<diff added token::o>>[24m<<diff added::me = 1.2>>[4m<<diff added token::4>>[24m<<diff added::;>>[39m
+    ");
+
+    // Regex tokenizer, unmatched bytes are split into characters
+    insta::assert_snapshot!(render_diff("'regex:[a-zA-Z]+'"), @r"
+    [38;5;3m<<diff header::Modified regular file file:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff::     : >>[38;5;1m<<diff removed::let >>[4m<<diff removed token::identifierName>>[24m<<diff removed:: = 1.2>>[4m<<diff removed token::3>>[24m<<diff removed::;>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[38;5;2m<<diff added::let >>[4m<<diff added token::identifierNome>>[24m<<diff added:: = 1.2>>[4m<<diff added token::4>>[24m<<diff added::;>>[39m
+    ");
+
+    // Invalid tokenizer
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["diff", "--config=diff.color-words.tokenizer=line"],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Config error: Invalid type or value for diff.color-words.tokenizer
+    Caused by: Invalid tokenizer: line
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    ");
+}
+
+#[test]
+fn test_diff_color_words_inlining_threshold() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let render_diff = |max_alternation: i32, args: &[&str]| {
+        let config = format!("diff.color-words.max-inline-alternation={max_alternation}");
+        test_env.jj_cmd_success(&repo_path, &[&["diff", "--config", &config], args].concat())
+    };
+
+    let file1_path = "file1-single-line";
+    let file2_path = "file2-multiple-lines-in-single-hunk";
+    let file3_path = "file3-changes-across-lines";
+    std::fs::write(
+        repo_path.join(file1_path),
+        indoc! {"
+            == adds ==
+            a b c
+            == removes ==
+            a b c d e f g
+            == adds + removes ==
+            a b c d e
+            == adds + removes + adds ==
+            a b c d e
+            == adds + removes + adds + removes ==
+            a b c d e f g
+        "},
+    )
+    .unwrap();
+    std::fs::write(
+        repo_path.join(file2_path),
+        indoc! {"
+            == adds; removes; adds + removes ==
+            a b c
+            a b c d e f g
+            a b c d e
+            == adds + removes + adds; adds + removes + adds + removes ==
+            a b c d e
+            a b c d e f g
+        "},
+    )
+    .unwrap();
+    std::fs::write(
+        repo_path.join(file3_path),
+        indoc! {"
+            == adds ==
+            a b c
+            == removes ==
+            a b c d
+            e f g
+            == adds + removes ==
+            a b c
+            d e
+            == adds + removes + adds ==
+            a b c
+            d e
+            == adds + removes + adds + removes ==
+            a b
+            c d e f g
+        "},
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(
+        repo_path.join(file1_path),
+        indoc! {"
+            == adds ==
+            a X b Y Z c
+            == removes ==
+            a c f
+            == adds + removes ==
+            a X b d
+            == adds + removes + adds ==
+            a X b d Y
+            == adds + removes + adds + removes ==
+            X a Y b d Z e
+        "},
+    )
+    .unwrap();
+    std::fs::write(
+        repo_path.join(file2_path),
+        indoc! {"
+            == adds; removes; adds + removes ==
+            a X b Y Z c
+            a c f
+            a X b d
+            == adds + removes + adds; adds + removes + adds + removes ==
+            a X b d Y
+            X a Y b d Z e
+        "},
+    )
+    .unwrap();
+    std::fs::write(
+        repo_path.join(file3_path),
+        indoc! {"
+            == adds ==
+            a X b
+            Y Z c
+            == removes ==
+            a c f
+            == adds + removes ==
+            a
+            X b d
+            == adds + removes + adds ==
+            a X b d
+            Y
+            == adds + removes + adds + removes ==
+            X a Y b d
+            Z e
+        "},
+    )
+    .unwrap();
+
+    // default
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1-single-line:
+       1    1: == adds ==
+       2    2: a X b Y Z c
+       3    3: == removes ==
+       4    4: a b c d e f g
+       5    5: == adds + removes ==
+       6    6: a X b c d e
+       7    7: == adds + removes + adds ==
+       8    8: a X b c d eY
+       9    9: == adds + removes + adds + removes ==
+      10     : a b c d e f g
+           10: X a Y b d Z e
+    Modified regular file file2-multiple-lines-in-single-hunk:
+       1    1: == adds; removes; adds + removes ==
+       2    2: a X b Y Z c
+       3    3: a b c d e f g
+       4    4: a X b c d e
+       5    5: == adds + removes + adds; adds + removes + adds + removes ==
+       6     : a b c d e
+       7     : a b c d e f g
+            6: a X b d Y
+            7: X a Y b d Z e
+    Modified regular file file3-changes-across-lines:
+       1    1: == adds ==
+       2    2: a X b
+       2    3: Y Z c
+       3    4: == removes ==
+       4    5: a b c d
+       5    5: e f g
+       6    6: == adds + removes ==
+       7    7: a
+       7    8: X b c
+       8    8: d e
+       9    9: == adds + removes + adds ==
+      10   10: a X b c
+      11   10: d e
+      11   11: Y
+      12   12: == adds + removes + adds + removes ==
+      13     : a b
+      14     : c d e f g
+           13: X a Y b d
+           14: Z e
+    "###);
+
+    // -1: inline all
+    insta::assert_snapshot!(render_diff(-1, &[]), @r###"
+    Modified regular file file1-single-line:
+       1    1: == adds ==
+       2    2: a X b Y Z c
+       3    3: == removes ==
+       4    4: a b c d e f g
+       5    5: == adds + removes ==
+       6    6: a X b c d e
+       7    7: == adds + removes + adds ==
+       8    8: a X b c d eY
+       9    9: == adds + removes + adds + removes ==
+      10   10: X a Y b c d Z e f g
+    Modified regular file file2-multiple-lines-in-single-hunk:
+       1    1: == adds; removes; adds + removes ==
+       2    2: a X b Y Z c
+       3    3: a b c d e f g
+       4    4: a X b c d e
+       5    5: == adds + removes + adds; adds + removes + adds + removes ==
+       6    6: a X b c d eY
+       7    7: X a Y b c d Z e f g
+    Modified regular file file3-changes-across-lines:
+       1    1: == adds ==
+       2    2: a X b
+       2    3: Y Z c
+       3    4: == removes ==
+       4    5: a b c d
+       5    5: e f g
+       6    6: == adds + removes ==
+       7    7: a
+       7    8: X b c
+       8    8: d e
+       9    9: == adds + removes + adds ==
+      10   10: a X b c
+      11   10: d e
+      11   11: Y
+      12   12: == adds + removes + adds + removes ==
+      13   13: X a Y b
+      14   13: c d
+      14   14: Z e f g
+    "###);
+
+    // 0: no inlining
+    insta::assert_snapshot!(render_diff(0, &[]), @r###"
+    Modified regular file file1-single-line:
+       1    1: == adds ==
+       2     : a b c
+            2: a X b Y Z c
+       3    3: == removes ==
+       4     : a b c d e f g
+            4: a c f
+       5    5: == adds + removes ==
+       6     : a b c d e
+            6: a X b d
+       7    7: == adds + removes + adds ==
+       8     : a b c d e
+            8: a X b d Y
+       9    9: == adds + removes + adds + removes ==
+      10     : a b c d e f g
+           10: X a Y b d Z e
+    Modified regular file file2-multiple-lines-in-single-hunk:
+       1    1: == adds; removes; adds + removes ==
+       2     : a b c
+       3     : a b c d e f g
+       4     : a b c d e
+            2: a X b Y Z c
+            3: a c f
+            4: a X b d
+       5    5: == adds + removes + adds; adds + removes + adds + removes ==
+       6     : a b c d e
+       7     : a b c d e f g
+            6: a X b d Y
+            7: X a Y b d Z e
+    Modified regular file file3-changes-across-lines:
+       1    1: == adds ==
+       2     : a b c
+            2: a X b
+            3: Y Z c
+       3    4: == removes ==
+       4     : a b c d
+       5     : e f g
+            5: a c f
+       6    6: == adds + removes ==
+       7     : a b c
+       8     : d e
+            7: a
+            8: X b d
+       9    9: == adds + removes + adds ==
+      10     : a b c
+      11     : d e
+           10: a X b d
+           11: Y
+      12   12: == adds + removes + adds + removes ==
+      13     : a b
+      14     : c d e f g
+           13: X a Y b d
+           14: Z e
+    "###);
+
+    // 1: inline adds-only or removes-only lines
+    insta::assert_snapshot!(render_diff(1, &[]), @r###"
+    Modified regular file file1-single-line:
+       1    1: == adds ==
+       2    2: a X b Y Z c
+       3    3: == removes ==
+       4    4: a b c d e f g
+       5    5: == adds + removes ==
+       6     : a b c d e
+            6: a X b d
+       7    7: == adds + removes + adds ==
+       8     : a b c d e
+            8: a X b d Y
+       9    9: == adds + removes + adds + removes ==
+      10     : a b c d e f g
+           10: X a Y b d Z e
+    Modified regular file file2-multiple-lines-in-single-hunk:
+       1    1: == adds; removes; adds + removes ==
+       2     : a b c
+       3     : a b c d e f g
+       4     : a b c d e
+            2: a X b Y Z c
+            3: a c f
+            4: a X b d
+       5    5: == adds + removes + adds; adds + removes + adds + removes ==
+       6     : a b c d e
+       7     : a b c d e f g
+            6: a X b d Y
+            7: X a Y b d Z e
+    Modified regular file file3-changes-across-lines:
+       1    1: == adds ==
+       2    2: a X b
+       2    3: Y Z c
+       3    4: == removes ==
+       4    5: a b c d
+       5    5: e f g
+       6    6: == adds + removes ==
+       7     : a b c
+       8     : d e
+            7: a
+            8: X b d
+       9    9: == adds + removes + adds ==
+      10     : a b c
+      11     : d e
+           10: a X b d
+           11: Y
+      12   12: == adds + removes + adds + removes ==
+      13     : a b
+      14     : c d e f g
+           13: X a Y b d
+           14: Z e
+    "###);
+
+    // 2: inline up to adds + removes lines
+    insta::assert_snapshot!(render_diff(2, &[]), @r###"
+    Modified regular file file1-single-line:
+       1    1: == adds ==
+       2    2: a X b Y Z c
+       3    3: == removes ==
+       4    4: a b c d e f g
+       5    5: == adds + removes ==
+       6    6: a X b c d e
+       7    7: == adds + removes + adds ==
+       8     : a b c d e
+            8: a X b d Y
+       9    9: == adds + removes + adds + removes ==
+      10     : a b c d e f g
+           10: X a Y b d Z e
+    Modified regular file file2-multiple-lines-in-single-hunk:
+       1    1: == adds; removes; adds + removes ==
+       2    2: a X b Y Z c
+       3    3: a b c d e f g
+       4    4: a X b c d e
+       5    5: == adds + removes + adds; adds + removes + adds + removes ==
+       6     : a b c d e
+       7     : a b c d e f g
+            6: a X b d Y
+            7: X a Y b d Z e
+    Modified regular file file3-changes-across-lines:
+       1    1: == adds ==
+       2    2: a X b
+       2    3: Y Z c
+       3    4: == removes ==
+       4    5: a b c d
+       5    5: e f g
+       6    6: == adds + removes ==
+       7    7: a
+       7    8: X b c
+       8    8: d e
+       9    9: == adds + removes + adds ==
+      10     : a b c
+      11     : d e
+           10: a X b d
+           11: Y
+      12   12: == adds + removes + adds + removes ==
+      13     : a b
+      14     : c d e f g
+           13: X a Y b d
+           14: Z e
+    "###);
+
+    // 3: inline up to adds + removes + adds lines
+    insta::assert_snapshot!(render_diff(3, &[]), @r###"
+    Modified regular file file1-single-line:
+       1    1: == adds ==
+       2    2: a X b Y Z c
+       3    3: == removes ==
+       4    4: a b c d e f g
+       5    5: == adds + removes ==
+       6    6: a X b c d e
+       7    7: == adds + removes + adds ==
+       8    8: a X b c d eY
+       9    9: == adds + removes + adds + removes ==
+      10     : a b c d e f g
+           10: X a Y b d Z e
+    Modified regular file file2-multiple-lines-in-single-hunk:
+       1    1: == adds; removes; adds + removes ==
+       2    2: a X b Y Z c
+       3    3: a b c d e f g
+       4    4: a X b c d e
+       5    5: == adds + removes + adds; adds + removes + adds + removes ==
+       6     : a b c d e
+       7     : a b c d e f g
+            6: a X b d Y
+            7: X a Y b d Z e
+    Modified regular file file3-changes-across-lines:
+       1    1: == adds ==
+       2    2: a X b
+       2    3: Y Z c
+       3    4: == removes ==
+       4    5: a b c d
+       5    5: e f g
+       6    6: == adds + removes ==
+       7    7: a
+       7    8: X b c
+       8    8: d e
+       9    9: == adds + removes + adds ==
+      10   10: a X b c
+      11   10: d e
+      11   11: Y
+      12   12: == adds + removes + adds + removes ==
+      13     : a b
+      14     : c d e f g
+           13: X a Y b d
+           14: Z e
+    "###);
+
+    // 4: inline up to adds + removes + adds + removes lines
+    insta::assert_snapshot!(render_diff(4, &[]), @r###"
+    Modified regular file file1-single-line:
+       1    1: == adds ==
+       2    2: a X b Y Z c
+       3    3: == removes ==
+       4    4: a b c d e f g
+       5    5: == adds + removes ==
+       6    6: a X b c d e
+       7    7: == adds + removes + adds ==
+       8    8: a X b c d eY
+       9    9: == adds + removes + adds + removes ==
+      10   10: X a Y b c d Z e f g
+    Modified regular file file2-multiple-lines-in-single-hunk:
+       1    1: == adds; removes; adds + removes ==
+       2    2: a X b Y Z c
+       3    3: a b c d e f g
+       4    4: a X b c d e
+       5    5: == adds + removes + adds; adds + removes + adds + removes ==
+       6    6: a X b c d eY
+       7    7: X a Y b c d Z e f g
+    Modified regular file file3-changes-across-lines:
+       1    1: == adds ==
+       2    2: a X b
+       2    3: Y Z c
+       3    4: == removes ==
+       4    5: a b c d
+       5    5: e f g
+       6    6: == adds + removes ==
+       7    7: a
+       7    8: X b c
+       8    8: d e
+       9    9: == adds + removes + adds ==
+      10   10: a X b c
+      11   10: d e
+      11   11: Y
+      12   12: == adds + removes + adds + removes ==
+      13   13: X a Y b
+      14   13: c d
+      14   14: Z e f g
+    "###);
+
+    // context words in added/removed lines should be labeled as such
+    insta::assert_snapshot!(render_diff(2, &["--color=always"]), @r###"
+    [38;5;3mModified regular file file1-single-line:[39m
+    [38;5;1m   1[39m [38;5;2m   1[39m: == adds ==
+    [38;5;1m   2[39m [38;5;2m   2[39m: a [4m[38;5;2mX [24m[39mb [4m[38;5;2mY Z [24m[39mc
+    [38;5;1m   3[39m [38;5;2m   3[39m: == removes ==
+    [38;5;1m   4[39m [38;5;2m   4[39m: a [4m[38;5;1mb [24m[39mc [4m[38;5;1md e [24m[39mf[4m[38;5;1m g[24m[39m
+    [38;5;1m   5[39m [38;5;2m   5[39m: == adds + removes ==
+    [38;5;1m   6[39m [38;5;2m   6[39m: a [4m[38;5;2mX [24m[39mb [4m[38;5;1mc [24m[39md[4m[38;5;1m e[24m[39m
+    [38;5;1m   7[39m [38;5;2m   7[39m: == adds + removes + adds ==
+    [38;5;1m   8[39m     : [38;5;1ma b [4mc [24md [4me[24m[39m
+         [38;5;2m   8[39m: [38;5;2ma [4mX [24mb d [4mY[24m[39m
+    [38;5;1m   9[39m [38;5;2m   9[39m: == adds + removes + adds + removes ==
+    [38;5;1m  10[39m     : [38;5;1ma b [4mc [24md e[4m f g[24m[39m
+         [38;5;2m  10[39m: [4m[38;5;2mX [24ma [4mY [24mb d [4mZ [24me[39m
+    [38;5;3mModified regular file file2-multiple-lines-in-single-hunk:[39m
+    [38;5;1m   1[39m [38;5;2m   1[39m: == adds; removes; adds + removes ==
+    [38;5;1m   2[39m [38;5;2m   2[39m: a [4m[38;5;2mX [24m[39mb [4m[38;5;2mY Z [24m[39mc
+    [38;5;1m   3[39m [38;5;2m   3[39m: a [4m[38;5;1mb [24m[39mc [4m[38;5;1md e [24m[39mf[4m[38;5;1m g[24m[39m
+    [38;5;1m   4[39m [38;5;2m   4[39m: a [4m[38;5;2mX [24m[39mb [4m[38;5;1mc [24m[39md[4m[38;5;1m e[24m[39m
+    [38;5;1m   5[39m [38;5;2m   5[39m: == adds + removes + adds; adds + removes + adds + removes ==
+    [38;5;1m   6[39m     : [38;5;1ma b [4mc [24md [4me[24m[39m
+    [38;5;1m   7[39m     : [38;5;1ma b [4mc [24md e[4m f g[24m[39m
          [38;5;2m   6[39m: [38;5;2ma [4mX [24mb d [4mY[24m[39m
          [38;5;2m   7[39m: [4m[38;5;2mX [24ma [4mY [24mb d [4mZ [24me[39m
     [38;5;3mModified regular file file3-changes-across-lines:[39m
@@ -1233,1240 +2630,2932 @@ fn test_diff_color_words_inlining_threshold() {
          [38;5;2m  13[39m: [4m[38;5;2mX [24ma [4mY [24mb d[4m[24m[39m
          [38;5;2m  14[39m: [4m[38;5;2mZ[24m e[39m
     "###);
-    insta::assert_snapshot!(render_diff(2, &["--color=debug"]), @r###"
-    [38;5;3m<<diff header::Modified regular file file1-single-line:>>[39m
-    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: == adds ==>>
-    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b >>[4m[38;5;2m<<diff added token::Y Z >>[24m[39m<<diff::c>>
-    [38;5;1m<<diff removed line_number::   3>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: == removes ==>>
-    [38;5;1m<<diff removed line_number::   4>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   4>>[39m<<diff::: a >>[4m[38;5;1m<<diff removed token::b >>[24m[39m<<diff::c >>[4m[38;5;1m<<diff removed token::d e >>[24m[39m<<diff::f>>[4m[38;5;1m<<diff removed token:: g>>[24m[39m<<diff::>>
-    [38;5;1m<<diff removed line_number::   5>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   5>>[39m<<diff::: == adds + removes ==>>
-    [38;5;1m<<diff removed line_number::   6>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   6>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b >>[4m[38;5;1m<<diff removed token::c >>[24m[39m<<diff::d>>[4m[38;5;1m<<diff removed token:: e>>[24m[39m<<diff::>>
-    [38;5;1m<<diff removed line_number::   7>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   7>>[39m<<diff::: == adds + removes + adds ==>>
-    [38;5;1m<<diff removed line_number::   8>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c >>[24m<<diff removed::d >>[4m<<diff removed token::e>>[24m<<diff removed::>>[39m
-    <<diff::     >>[38;5;2m<<diff added line_number::   8>>[39m<<diff::: >>[38;5;2m<<diff added::a >>[4m<<diff added token::X >>[24m<<diff added::b d >>[4m<<diff added token::Y>>[24m<<diff added::>>[39m
-    [38;5;1m<<diff removed line_number::   9>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   9>>[39m<<diff::: == adds + removes + adds + removes ==>>
-    [38;5;1m<<diff removed line_number::  10>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c >>[24m<<diff removed::d e>>[4m<<diff removed token:: f g>>[24m<<diff removed::>>[39m
-    <<diff::     >>[38;5;2m<<diff added line_number::  10>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::X >>[24m<<diff added::a >>[4m<<diff added token::Y >>[24m<<diff added::b d >>[4m<<diff added token::Z >>[24m<<diff added::e>>[39m
-    [38;5;3m<<diff header::Modified regular file file2-multiple-lines-in-single-hunk:>>[39m
-    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: == adds; removes; adds + removes ==>>
-    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b >>[4m[38;5;2m<<diff added token::Y Z >>[24m[39m<<diff::c>>
-    [38;5;1m<<diff removed line_number::   3>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: a >>[4m[38;5;1m<<diff removed token::b >>[24m[39m<<diff::c >>[4m[38;5;1m<<diff removed token::d e >>[24m[39m<<diff::f>>[4m[38;5;1m<<diff removed token:: g>>[24m[39m<<diff::>>
-    [38;5;1m<<diff removed line_number::   4>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   4>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b >>[4m[38;5;1m<<diff removed token::c >>[24m[39m<<diff::d>>[4m[38;5;1m<<diff removed token:: e>>[24m[39m<<diff::>>
-    [38;5;1m<<diff removed line_number::   5>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   5>>[39m<<diff::: == adds + removes + adds; adds + removes + adds + removes ==>>
-    [38;5;1m<<diff removed line_number::   6>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c >>[24m<<diff removed::d >>[4m<<diff removed token::e>>[24m<<diff removed::>>[39m
-    [38;5;1m<<diff removed line_number::   7>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c >>[24m<<diff removed::d e>>[4m<<diff removed token:: f g>>[24m<<diff removed::>>[39m
-    <<diff::     >>[38;5;2m<<diff added line_number::   6>>[39m<<diff::: >>[38;5;2m<<diff added::a >>[4m<<diff added token::X >>[24m<<diff added::b d >>[4m<<diff added token::Y>>[24m<<diff added::>>[39m
-    <<diff::     >>[38;5;2m<<diff added line_number::   7>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::X >>[24m<<diff added::a >>[4m<<diff added token::Y >>[24m<<diff added::b d >>[4m<<diff added token::Z >>[24m<<diff added::e>>[39m
-    [38;5;3m<<diff header::Modified regular file file3-changes-across-lines:>>[39m
-    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: == adds ==>>
-    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b>>[4m[38;5;2m<<diff added token::>>[24m[39m
-    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::Y Z>>[24m[39m<<diff:: c>>
-    [38;5;1m<<diff removed line_number::   3>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   4>>[39m<<diff::: == removes ==>>
-    [38;5;1m<<diff removed line_number::   4>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   5>>[39m<<diff::: a >>[4m[38;5;1m<<diff removed token::b >>[24m[39m<<diff::c >>[4m[38;5;1m<<diff removed token::d>>[24m[39m
-    [38;5;1m<<diff removed line_number::   5>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   5>>[39m<<diff::: >>[4m[38;5;1m<<diff removed token::e >>[24m[39m<<diff::f>>[4m[38;5;1m<<diff removed token:: g>>[24m[39m<<diff::>>
-    [38;5;1m<<diff removed line_number::   6>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   6>>[39m<<diff::: == adds + removes ==>>
-    [38;5;1m<<diff removed line_number::   7>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   7>>[39m<<diff::: a>>[4m[38;5;2m<<diff added token::>>[24m[39m
-    [38;5;1m<<diff removed line_number::   7>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   8>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::X>>[24m[39m<<diff:: b >>[4m[38;5;1m<<diff removed token::c>>[24m[39m
-    [38;5;1m<<diff removed line_number::   8>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   8>>[39m<<diff::: d>>[4m[38;5;1m<<diff removed token:: e>>[24m[39m<<diff::>>
-    [38;5;1m<<diff removed line_number::   9>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   9>>[39m<<diff::: == adds + removes + adds ==>>
-    [38;5;1m<<diff removed line_number::  10>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c>>[24m[39m
-    [38;5;1m<<diff removed line_number::  11>>[39m<<diff::     : >>[38;5;1m<<diff removed::d>>[4m<<diff removed token:: e>>[24m<<diff removed::>>[39m
-    <<diff::     >>[38;5;2m<<diff added line_number::  10>>[39m<<diff::: >>[38;5;2m<<diff added::a >>[4m<<diff added token::X >>[24m<<diff added::b d>>[4m<<diff added token::>>[24m[39m
-    <<diff::     >>[38;5;2m<<diff added line_number::  11>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::Y>>[24m<<diff added::>>[39m
-    [38;5;1m<<diff removed line_number::  12>>[39m<<diff:: >>[38;5;2m<<diff added line_number::  12>>[39m<<diff::: == adds + removes + adds + removes ==>>
-    [38;5;1m<<diff removed line_number::  13>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b>>[4m<<diff removed token::>>[24m[39m
-    [38;5;1m<<diff removed line_number::  14>>[39m<<diff::     : >>[4m[38;5;1m<<diff removed token::c>>[24m<<diff removed:: d e>>[4m<<diff removed token:: f g>>[24m<<diff removed::>>[39m
-    <<diff::     >>[38;5;2m<<diff added line_number::  13>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::X >>[24m<<diff added::a >>[4m<<diff added token::Y >>[24m<<diff added::b d>>[4m<<diff added token::>>[24m[39m
-    <<diff::     >>[38;5;2m<<diff added line_number::  14>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::Z>>[24m<<diff added:: e>>[39m
+    insta::assert_snapshot!(render_diff(2, &["--color=debug"]), @r###"
+    [38;5;3m<<diff header::Modified regular file file1-single-line:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: == adds ==>>
+    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b >>[4m[38;5;2m<<diff added token::Y Z >>[24m[39m<<diff::c>>
+    [38;5;1m<<diff removed line_number::   3>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: == removes ==>>
+    [38;5;1m<<diff removed line_number::   4>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   4>>[39m<<diff::: a >>[4m[38;5;1m<<diff removed token::b >>[24m[39m<<diff::c >>[4m[38;5;1m<<diff removed token::d e >>[24m[39m<<diff::f>>[4m[38;5;1m<<diff removed token:: g>>[24m[39m<<diff::>>
+    [38;5;1m<<diff removed line_number::   5>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   5>>[39m<<diff::: == adds + removes ==>>
+    [38;5;1m<<diff removed line_number::   6>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   6>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b >>[4m[38;5;1m<<diff removed token::c >>[24m[39m<<diff::d>>[4m[38;5;1m<<diff removed token:: e>>[24m[39m<<diff::>>
+    [38;5;1m<<diff removed line_number::   7>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   7>>[39m<<diff::: == adds + removes + adds ==>>
+    [38;5;1m<<diff removed line_number::   8>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c >>[24m<<diff removed::d >>[4m<<diff removed token::e>>[24m<<diff removed::>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   8>>[39m<<diff::: >>[38;5;2m<<diff added::a >>[4m<<diff added token::X >>[24m<<diff added::b d >>[4m<<diff added token::Y>>[24m<<diff added::>>[39m
+    [38;5;1m<<diff removed line_number::   9>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   9>>[39m<<diff::: == adds + removes + adds + removes ==>>
+    [38;5;1m<<diff removed line_number::  10>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c >>[24m<<diff removed::d e>>[4m<<diff removed token:: f g>>[24m<<diff removed::>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::  10>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::X >>[24m<<diff added::a >>[4m<<diff added token::Y >>[24m<<diff added::b d >>[4m<<diff added token::Z >>[24m<<diff added::e>>[39m
+    [38;5;3m<<diff header::Modified regular file file2-multiple-lines-in-single-hunk:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: == adds; removes; adds + removes ==>>
+    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b >>[4m[38;5;2m<<diff added token::Y Z >>[24m[39m<<diff::c>>
+    [38;5;1m<<diff removed line_number::   3>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: a >>[4m[38;5;1m<<diff removed token::b >>[24m[39m<<diff::c >>[4m[38;5;1m<<diff removed token::d e >>[24m[39m<<diff::f>>[4m[38;5;1m<<diff removed token:: g>>[24m[39m<<diff::>>
+    [38;5;1m<<diff removed line_number::   4>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   4>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b >>[4m[38;5;1m<<diff removed token::c >>[24m[39m<<diff::d>>[4m[38;5;1m<<diff removed token:: e>>[24m[39m<<diff::>>
+    [38;5;1m<<diff removed line_number::   5>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   5>>[39m<<diff::: == adds + removes + adds; adds + removes + adds + removes ==>>
+    [38;5;1m<<diff removed line_number::   6>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c >>[24m<<diff removed::d >>[4m<<diff removed token::e>>[24m<<diff removed::>>[39m
+    [38;5;1m<<diff removed line_number::   7>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c >>[24m<<diff removed::d e>>[4m<<diff removed token:: f g>>[24m<<diff removed::>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   6>>[39m<<diff::: >>[38;5;2m<<diff added::a >>[4m<<diff added token::X >>[24m<<diff added::b d >>[4m<<diff added token::Y>>[24m<<diff added::>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::   7>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::X >>[24m<<diff added::a >>[4m<<diff added token::Y >>[24m<<diff added::b d >>[4m<<diff added token::Z >>[24m<<diff added::e>>[39m
+    [38;5;3m<<diff header::Modified regular file file3-changes-across-lines:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: == adds ==>>
+    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   2>>[39m<<diff::: a >>[4m[38;5;2m<<diff added token::X >>[24m[39m<<diff::b>>[4m[38;5;2m<<diff added token::>>[24m[39m
+    [38;5;1m<<diff removed line_number::   2>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::Y Z>>[24m[39m<<diff:: c>>
+    [38;5;1m<<diff removed line_number::   3>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   4>>[39m<<diff::: == removes ==>>
+    [38;5;1m<<diff removed line_number::   4>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   5>>[39m<<diff::: a >>[4m[38;5;1m<<diff removed token::b >>[24m[39m<<diff::c >>[4m[38;5;1m<<diff removed token::d>>[24m[39m
+    [38;5;1m<<diff removed line_number::   5>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   5>>[39m<<diff::: >>[4m[38;5;1m<<diff removed token::e >>[24m[39m<<diff::f>>[4m[38;5;1m<<diff removed token:: g>>[24m[39m<<diff::>>
+    [38;5;1m<<diff removed line_number::   6>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   6>>[39m<<diff::: == adds + removes ==>>
+    [38;5;1m<<diff removed line_number::   7>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   7>>[39m<<diff::: a>>[4m[38;5;2m<<diff added token::>>[24m[39m
+    [38;5;1m<<diff removed line_number::   7>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   8>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::X>>[24m[39m<<diff:: b >>[4m[38;5;1m<<diff removed token::c>>[24m[39m
+    [38;5;1m<<diff removed line_number::   8>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   8>>[39m<<diff::: d>>[4m[38;5;1m<<diff removed token:: e>>[24m[39m<<diff::>>
+    [38;5;1m<<diff removed line_number::   9>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   9>>[39m<<diff::: == adds + removes + adds ==>>
+    [38;5;1m<<diff removed line_number::  10>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b >>[4m<<diff removed token::c>>[24m[39m
+    [38;5;1m<<diff removed line_number::  11>>[39m<<diff::     : >>[38;5;1m<<diff removed::d>>[4m<<diff removed token:: e>>[24m<<diff removed::>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::  10>>[39m<<diff::: >>[38;5;2m<<diff added::a >>[4m<<diff added token::X >>[24m<<diff added::b d>>[4m<<diff added token::>>[24m[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::  11>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::Y>>[24m<<diff added::>>[39m
+    [38;5;1m<<diff removed line_number::  12>>[39m<<diff:: >>[38;5;2m<<diff added line_number::  12>>[39m<<diff::: == adds + removes + adds + removes ==>>
+    [38;5;1m<<diff removed line_number::  13>>[39m<<diff::     : >>[38;5;1m<<diff removed::a b>>[4m<<diff removed token::>>[24m[39m
+    [38;5;1m<<diff removed line_number::  14>>[39m<<diff::     : >>[4m[38;5;1m<<diff removed token::c>>[24m<<diff removed:: d e>>[4m<<diff removed token:: f g>>[24m<<diff removed::>>[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::  13>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::X >>[24m<<diff added::a >>[4m<<diff added token::Y >>[24m<<diff added::b d>>[4m<<diff added token::>>[24m[39m
+    <<diff::     >>[38;5;2m<<diff added line_number::  14>>[39m<<diff::: >>[4m[38;5;2m<<diff added token::Z>>[24m<<diff added:: e>>[39m
+    "###);
+}
+
+#[test]
+fn test_diff_color_words_show_language() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("lib.rs"), "a\n").unwrap();
+    std::fs::write(repo_path.join("Makefile"), "a\n").unwrap();
+    std::fs::write(repo_path.join("BUILD"), "a\n").unwrap();
+    std::fs::write(repo_path.join("unknown.ext"), "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("lib.rs"), "b\n").unwrap();
+    std::fs::remove_file(repo_path.join("Makefile")).unwrap();
+    std::fs::write(repo_path.join("BUILD"), "b\n").unwrap();
+    std::fs::write(repo_path.join("unknown.ext"), "b\n").unwrap();
+
+    // Disabled by default
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "lib.rs"]);
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file lib.rs:
+       1    1: ab
+    ");
+
+    test_env.add_config(indoc! {"
+        diff.color-words.show-language = true
+        diff.languages.BUILD = 'Starlark'
+    "});
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--color=debug"]);
+    insta::assert_snapshot!(stdout, @r"
+    [38;5;3m<<diff header::Modified regular file BUILD:>><<diff header language:: (Starlark)>><<diff header::>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[4m[38;5;1m<<diff removed token::a>>[38;5;2m<<diff added token::b>>[24m[39m<<diff::>>
+    [38;5;3m<<diff header::Removed regular file Makefile:>><<diff header language:: (Makefile)>><<diff header::>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff::     : >>[4m[38;5;1m<<diff removed token::a>>[24m[39m
+    [38;5;3m<<diff header::Modified regular file lib.rs:>><<diff header language:: (Rust)>><<diff header::>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[4m[38;5;1m<<diff removed token::a>>[38;5;2m<<diff added token::b>>[24m[39m<<diff::>>
+    [38;5;3m<<diff header::Modified regular file unknown.ext:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: >>[4m[38;5;1m<<diff removed token::a>>[38;5;2m<<diff added token::b>>[24m[39m<<diff::>>
+    ");
+}
+
+#[test]
+fn test_diff_missing_newline() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "foo").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\nbar").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "foo\nbar").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1:
+       1    1: foo
+            2: bar
+    Modified regular file file2:
+       1    1: foo
+       2     : bar
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file1 b/file1
+    index 1910281566..a907ec3f43 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,1 +1,2 @@
+    -foo
+    \ No newline at end of file
+    +foo
+    +bar
+    \ No newline at end of file
+    diff --git a/file2 b/file2
+    index a907ec3f43..1910281566 100644
+    --- a/file2
+    +++ b/file2
+    @@ -1,2 +1,1 @@
+    -foo
+    -bar
+    \ No newline at end of file
+    +foo
+    \ No newline at end of file
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file1 | 3 ++-
+    file2 | 3 +--
+    2 files changed, 3 insertions(+), 3 deletions(-)
+    "###);
+}
+
+#[test]
+fn test_color_words_diff_missing_newline() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Empty"]);
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\nf\ng\nh\ni").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Add no newline"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\ne\nf\ng\nh\ni").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Modify first line"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\nE\nf\ng\nh\ni").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Modify middle line"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\nE\nf\ng\nh\nI").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Modify last line"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\nE\nf\ng\nh\nI\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Append newline"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\nE\nf\ng\nh\nI").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Remove newline"]);
+    std::fs::write(repo_path.join("file1"), "").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Empty"]);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "-Tdescription",
+            "-pr::@-",
+            "--no-graph",
+            "--reversed",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    === Empty
+    Added regular file file1:
+        (empty)
+    === Add no newline
+    Modified regular file file1:
+            1: a
+            2: b
+            3: c
+            4: d
+            5: e
+            6: f
+            7: g
+            8: h
+            9: i
+    === Modify first line
+    Modified regular file file1:
+       1    1: aA
+       2    2: b
+       3    3: c
+       4    4: d
+        ... (5 lines)
+    === Modify middle line
+    Modified regular file file1:
+       1    1: A
+       2    2: b
+       3    3: c
+       4    4: d
+       5    5: eE
+       6    6: f
+       7    7: g
+       8    8: h
+       9    9: i
+    === Modify last line
+    Modified regular file file1:
+        ... (5 lines)
+       6    6: f
+       7    7: g
+       8    8: h
+       9    9: iI
+    === Append newline
+    Modified regular file file1:
+        ... (5 lines)
+       6    6: f
+       7    7: g
+       8    8: h
+       9    9: I
+        (newline at end of file added)
+    === Remove newline
+    Modified regular file file1:
+        ... (5 lines)
+       6    6: f
+       7    7: g
+       8    8: h
+       9    9: I
+        (newline at end of file removed)
+    === Empty
+    Modified regular file file1:
+       1     : A
+       2     : b
+       3     : c
+       4     : d
+       5     : E
+       6     : f
+       7     : g
+       8     : h
+       9     : I
+    "###);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "--config=diff.color-words.max-inline-alternation=0",
+            "-Tdescription",
+            "-pr::@-",
+            "--no-graph",
+            "--reversed",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    === Empty
+    Added regular file file1:
+        (empty)
+    === Add no newline
+    Modified regular file file1:
+            1: a
+            2: b
+            3: c
+            4: d
+            5: e
+            6: f
+            7: g
+            8: h
+            9: i
+    === Modify first line
+    Modified regular file file1:
+       1     : a
+            1: A
+       2    2: b
+       3    3: c
+       4    4: d
+        ... (5 lines)
+    === Modify middle line
+    Modified regular file file1:
+       1    1: A
+       2    2: b
+       3    3: c
+       4    4: d
+       5     : e
+            5: E
+       6    6: f
+       7    7: g
+       8    8: h
+       9    9: i
+    === Modify last line
+    Modified regular file file1:
+        ... (5 lines)
+       6    6: f
+       7    7: g
+       8    8: h
+       9     : i
+            9: I
+    === Append newline
+    Modified regular file file1:
+        ... (5 lines)
+       6    6: f
+       7    7: g
+       8    8: h
+       9     : I
+            9: I
+        (newline at end of file added)
+    === Remove newline
+    Modified regular file file1:
+        ... (5 lines)
+       6    6: f
+       7    7: g
+       8    8: h
+       9     : I
+            9: I
+        (newline at end of file removed)
+    === Empty
+    Modified regular file file1:
+       1     : A
+       2     : b
+       3     : c
+       4     : d
+       5     : E
+       6     : f
+       7     : g
+       8     : h
+       9     : I
+    "###);
+}
+
+#[test]
+fn test_diff_ignore_whitespace() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(
+        repo_path.join("file1"),
+        indoc! {"
+            foo {
+                bar;
+            }
+            baz {}
+        "},
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-mindent + whitespace insertion"]);
+    std::fs::write(
+        repo_path.join("file1"),
+        indoc! {"
+            {
+                foo {
+                    bar;
+                }
+            }
+            baz {  }
+        "},
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["status"]);
+
+    // Git diff as reference output
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--ignore-all-space"]);
+    insta::assert_snapshot!(stdout, @r#"
+    diff --git a/file1 b/file1
+    index f532aa68ad..033c4a6168 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,4 +1,6 @@
+    +{
+         foo {
+             bar;
+         }
+    +}
+     baz {  }
+    "#);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--ignore-space-change"]);
+    insta::assert_snapshot!(stdout, @r#"
+    diff --git a/file1 b/file1
+    index f532aa68ad..033c4a6168 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,4 +1,6 @@
+    -foo {
+    +{
+    +    foo {
+             bar;
+    +    }
+     }
+    -baz {}
+    +baz {  }
+    "#);
+
+    // Diff-stat should respects the whitespace options
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--ignore-all-space"]);
+    insta::assert_snapshot!(stdout, @r#"
+    file1 | 2 ++
+    1 file changed, 2 insertions(+), 0 deletions(-)
+    "#);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--ignore-space-change"]);
+    insta::assert_snapshot!(stdout, @r#"
+    file1 | 6 ++++--
+    1 file changed, 4 insertions(+), 2 deletions(-)
+    "#);
+
+    // Word-level changes are still highlighted
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--color=always", "--ignore-all-space"],
+    );
+    insta::assert_snapshot!(stdout, @r#"
+    [38;5;3mModified regular file file1:[39m
+         [38;5;2m   1[39m: [4m[38;5;2m{[24m[39m
+    [38;5;1m   1[39m [38;5;2m   2[39m: [4m[38;5;2m    [24m[39mfoo {
+    [38;5;1m   2[39m [38;5;2m   3[39m:     [4m[38;5;2m    [24m[39mbar;
+    [38;5;1m   3[39m [38;5;2m   4[39m: [4m[38;5;2m    [24m[39m}
+         [38;5;2m   5[39m: [4m[38;5;2m}[24m[39m
+    [38;5;1m   4[39m [38;5;2m   6[39m: baz {[4m[38;5;2m  [24m[39m}
+    "#);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--color=always", "--ignore-space-change"],
+    );
+    insta::assert_snapshot!(stdout, @r#"
+    [38;5;3mModified regular file file1:[39m
+         [38;5;2m   1[39m: [4m[38;5;2m{[24m[39m
+    [38;5;1m   1[39m [38;5;2m   2[39m: [4m[38;5;2m    [24m[39mfoo {
+    [38;5;1m   2[39m [38;5;2m   3[39m:     [4m[38;5;2m    [24m[39mbar;
+         [38;5;2m   4[39m: [4m[38;5;2m    }[24m[39m
+    [38;5;1m   3[39m [38;5;2m   5[39m: }
+    [38;5;1m   4[39m [38;5;2m   6[39m: baz {[4m[38;5;2m  [24m[39m}
+    "#);
+}
+
+#[test]
+fn test_diff_ignore_cr_at_eol() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "a\r\nB\r\nc\r\nd\r\r\n").unwrap();
+
+    // Only the genuine changes are shown
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--ignore-cr-at-eol"]);
+    insta::assert_snapshot!(stdout.replace('\r', "<CR>"), @r"
+    diff --git a/file1 b/file1
+    index d68dd4031d..63235377d4 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,4 +1,4 @@
+     a<CR>
+    -b
+    +B<CR>
+     c<CR>
+    -d
+    +d<CR><CR>
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--ignore-cr-at-eol"]);
+    insta::assert_snapshot!(stdout, @r"
+    file1 | 4 ++--
+    1 file changed, 2 insertions(+), 2 deletions(-)
+    ");
+
+    // Word-level changes are still highlighted
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--color=always", "--ignore-cr-at-eol"],
+    );
+    insta::assert_snapshot!(stdout.replace('\r', "<CR>"), @r"
+    [38;5;3mModified regular file file1:[39m
+    [38;5;1m   1[39m [38;5;2m   1[39m: a[4m[38;5;2m<CR>[24m[39m
+    [38;5;1m   2[39m [38;5;2m   2[39m: [4m[38;5;1mb[38;5;2mB<CR>[24m[39m
+    [38;5;1m   3[39m [38;5;2m   3[39m: c[4m[38;5;2m<CR>[24m[39m
+    [38;5;1m   4[39m [38;5;2m   4[39m: d[4m[38;5;2m<CR><CR>[24m[39m
+    ");
+}
+
+#[test]
+fn test_diff_whitespace_only_note() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "foo {\n    bar;\n}\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "baz\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "foo  {\n\tbar;\n}\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "qux\n").unwrap();
+
+    // Without whitespace-ignoring options, the changes are shown as usual
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file1:
+       1    1: foo  {
+       2    2:     	bar;
+       3    3: }
+    Modified regular file file2:
+       1    1: bazqux
+    ");
+
+    // The note is shown only for the file whose changes were all hidden
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--ignore-all-space"]);
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file1:
+        (only whitespace changes, hidden)
+    Modified regular file file2:
+       1    1: bazqux
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--color=always", "--ignore-space-change"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    [38;5;3mModified regular file file1:[39m
+    [38;5;6m    (only whitespace changes, hidden)[39m
+    [38;5;3mModified regular file file2:[39m
+    [38;5;1m   1[39m [38;5;2m   1[39m: [4m[38;5;1mbaz[38;5;2mqux[24m[39m
+    ");
+
+    // The note is configurable
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--ignore-all-space",
+            "--config=diff.color-words.whitespace-only-note='(whitespace only)'",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file1:
+        (whitespace only)
+    Modified regular file file2:
+       1    1: bazqux
+    ");
+}
+
+#[test]
+fn test_diff_skipped_context() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\nf\ng\nh\ni\nj").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "=== Left side of diffs"]);
+
+    test_env.jj_cmd_ok(&repo_path, &["new", "@", "-m", "=== Must skip 2 lines"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\ne\nf\ng\nh\ni\nJ").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== Don't skip 1 line"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\ne\nf\ng\nh\nI\nj").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== No gap to skip"]);
+    std::fs::write(repo_path.join("file1"), "a\nB\nc\nd\ne\nf\ng\nh\nI\nj").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== No gap to skip"]);
+    std::fs::write(repo_path.join("file1"), "a\nb\nC\nd\ne\nf\ng\nh\nI\nj").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== 1 line at start"]);
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\nE\nf\ng\nh\ni\nj").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== 1 line at end"]);
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\nF\ng\nh\ni\nj").unwrap();
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "-Tdescription", "-p", "--no-graph", "--reversed"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    === Left side of diffs
+    Added regular file file1:
+            1: a
+            2: b
+            3: c
+            4: d
+            5: e
+            6: f
+            7: g
+            8: h
+            9: i
+           10: j
+    === Must skip 2 lines
+    Modified regular file file1:
+       1    1: aA
+       2    2: b
+       3    3: c
+       4    4: d
+        ... (2 lines)
+       7    7: g
+       8    8: h
+       9    9: i
+      10   10: jJ
+    === Don't skip 1 line
+    Modified regular file file1:
+       1    1: aA
+       2    2: b
+       3    3: c
+       4    4: d
+       5    5: e
+       6    6: f
+       7    7: g
+       8    8: h
+       9    9: iI
+      10   10: j
+    === No gap to skip
+    Modified regular file file1:
+       1    1: a
+       2    2: bB
+       3    3: c
+       4    4: d
+       5    5: e
+       6    6: f
+       7    7: g
+       8    8: h
+       9    9: iI
+      10   10: j
+    === No gap to skip
+    Modified regular file file1:
+       1    1: a
+       2    2: b
+       3    3: cC
+       4    4: d
+       5    5: e
+       6    6: f
+       7    7: g
+       8    8: h
+       9    9: iI
+      10   10: j
+    === 1 line at start
+    Modified regular file file1:
+       1    1: a
+       2    2: b
+       3    3: c
+       4    4: d
+       5    5: eE
+       6    6: f
+       7    7: g
+       8    8: h
+        ... (2 lines)
+    === 1 line at end
+    Modified regular file file1:
+        ... (2 lines)
+       3    3: c
+       4    4: d
+       5    5: e
+       6    6: fF
+       7    7: g
+       8    8: h
+       9    9: i
+      10   10: j
+    "###);
+}
+
+#[test]
+fn test_diff_skipped_context_from_settings_color_words() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.add_config(
+        r#"
+[diff.color-words]
+context = 0
+        "#,
+    );
+
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "=== First commit"]);
+
+    test_env.jj_cmd_ok(&repo_path, &["new", "@", "-m", "=== Must show 0 context"]);
+    std::fs::write(repo_path.join("file1"), "a\nb\nC\nd\ne").unwrap();
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "-Tdescription", "-p", "--no-graph", "--reversed"],
+    );
+    insta::assert_snapshot!(stdout, @r#"
+    === First commit
+    Added regular file file1:
+            1: a
+            2: b
+            3: c
+            4: d
+            5: e
+    === Must show 0 context
+    Modified regular file file1:
+        ... (2 lines)
+       3    3: cC
+        ... (2 lines)
+    "#);
+}
+
+#[test]
+fn test_diff_skipped_context_from_settings_git() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.add_config(
+        r#"
+[diff.git]
+context = 0
+        "#,
+    );
+
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "=== First commit"]);
+
+    test_env.jj_cmd_ok(&repo_path, &["new", "@", "-m", "=== Must show 0 context"]);
+    std::fs::write(repo_path.join("file1"), "a\nb\nC\nd\ne").unwrap();
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "-Tdescription",
+            "-p",
+            "--git",
+            "--no-graph",
+            "--reversed",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    === First commit
+    diff --git a/file1 b/file1
+    new file mode 100644
+    index 0000000000..0fec236860
+    --- /dev/null
+    +++ b/file1
+    @@ -0,0 +1,5 @@
+    +a
+    +b
+    +c
+    +d
+    +e
+    \ No newline at end of file
+    === Must show 0 context
+    diff --git a/file1 b/file1
+    index 0fec236860..b7615dae52 100644
+    --- a/file1
+    +++ b/file1
+    @@ -3,1 +3,1 @@
+    -c
+    +C
+    ");
+}
+
+#[test]
+fn test_diff_context_gap() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\ne\nf\nG\nh\nI\nj\n").unwrap();
+    test_env.add_config(
+        r#"
+[diff.context-gap]
+enabled = true
+lines = 1
+threshold = 2
+        "#,
+    );
+
+    // The region between "A" and "G" is shortened, but the one between "G" and
+    // "I" is too short to hide enough lines
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--context=5"]);
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file1:
+       1    1: aA
+       2    2: b
+        ... (3 lines)
+       6    6: f
+       7    7: gG
+       8    8: h
+       9    9: iI
+      10   10: j
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=5"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file1
+    index 92dfa21641..0cd71b2292 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,2 +1,2 @@
+    -a
+    +A
+     b
+    @@ -6,5 +6,5 @@
+     f
+    -g
+    +G
+     h
+    -i
+    +I
+     j
+    ");
+
+    // Regions longer than the context lines on both ends aren't affected
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=2"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file1
+    index 92dfa21641..0cd71b2292 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,3 +1,3 @@
+    -a
+    +A
+     b
+     c
+    @@ -5,6 +5,6 @@
+     e
+     f
+    -g
+    +G
+     h
+    -i
+    +I
+     j
+    ");
+}
+
+#[test]
+fn test_diff_skipped_context_nondefault() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "=== Left side of diffs"]);
+
+    test_env.jj_cmd_ok(&repo_path, &["new", "@", "-m", "=== Must skip 2 lines"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nc\nD").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== Don't skip 1 line"]);
+    std::fs::write(repo_path.join("file1"), "A\nb\nC\nd").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== No gap to skip"]);
+    std::fs::write(repo_path.join("file1"), "a\nB\nC\nd").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== 1 line at start"]);
+    std::fs::write(repo_path.join("file1"), "a\nB\nc\nd").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== 1 line at end"]);
+    std::fs::write(repo_path.join("file1"), "a\nb\nC\nd").unwrap();
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "log",
+            "-Tdescription",
+            "-p",
+            "--no-graph",
+            "--reversed",
+            "--context=0",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    === Left side of diffs
+    Added regular file file1:
+            1: a
+            2: b
+            3: c
+            4: d
+    === Must skip 2 lines
+    Modified regular file file1:
+       1    1: aA
+        ... (2 lines)
+       4    4: dD
+    === Don't skip 1 line
+    Modified regular file file1:
+       1    1: aA
+       2    2: b
+       3    3: cC
+       4    4: d
+    === No gap to skip
+    Modified regular file file1:
+       1    1: a
+       2    2: bB
+       3    3: cC
+       4    4: d
+    === 1 line at start
+    Modified regular file file1:
+       1    1: a
+       2    2: bB
+        ... (2 lines)
+    === 1 line at end
+    Modified regular file file1:
+        ... (2 lines)
+       3    3: cC
+       4    4: d
+    "###);
+}
+
+#[test]
+fn test_diff_leading_trailing_context() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    // N=5 context lines at start/end of the file
+    std::fs::write(
+        repo_path.join("file1"),
+        "1\n2\n3\n4\n5\nL\n6\n7\n8\n9\n10\n11\n",
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(
+        repo_path.join("file1"),
+        "1\n2\n3\n4\n5\n6\nR\n7\n8\n9\n10\n11\n",
+    )
+    .unwrap();
+
+    // N=5 <= num_context_lines + 1: No room to skip.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--context=4"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1:
+       1    1: 1
+       2    2: 2
+       3    3: 3
+       4    4: 4
+       5    5: 5
+       6     : L
+       7    6: 6
+            7: R
+       8    8: 7
+       9    9: 8
+      10   10: 9
+      11   11: 10
+      12   12: 11
+    "###);
+
+    // N=5 <= 2 * num_context_lines + 1: The last hunk wouldn't be split if
+    // trailing diff existed.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--context=3"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1:
+        ... (2 lines)
+       3    3: 3
+       4    4: 4
+       5    5: 5
+       6     : L
+       7    6: 6
+            7: R
+       8    8: 7
+       9    9: 8
+      10   10: 9
+        ... (2 lines)
+    "###);
+
+    // N=5 > 2 * num_context_lines + 1: The last hunk should be split no matter
+    // if trailing diff existed.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--context=1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified regular file file1:
+        ... (4 lines)
+       5    5: 5
+       6     : L
+       7    6: 6
+            7: R
+       8    8: 7
+        ... (4 lines)
+    "###);
+
+    // N=5 <= num_context_lines: No room to skip.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=5"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file1 b/file1
+    index 1bf57dee4a..69b3e1865c 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,12 +1,12 @@
+     1
+     2
+     3
+     4
+     5
+    -L
+     6
+    +R
+     7
+     8
+     9
+     10
+     11
+    "###);
+
+    // N=5 <= 2 * num_context_lines: The last hunk wouldn't be split if
+    // trailing diff existed.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=3"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file1 b/file1
+    index 1bf57dee4a..69b3e1865c 100644
+    --- a/file1
+    +++ b/file1
+    @@ -3,8 +3,8 @@
+     3
+     4
+     5
+    -L
+     6
+    +R
+     7
+     8
+     9
+    "###);
+
+    // N=5 > 2 * num_context_lines: The last hunk should be split no matter
+    // if trailing diff existed.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=2"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file1 b/file1
+    index 1bf57dee4a..69b3e1865c 100644
+    --- a/file1
+    +++ b/file1
+    @@ -4,6 +4,6 @@
+     4
+     5
+    -L
+     6
+    +R
+     7
+     8
+    "###);
+}
+
+#[test]
+fn test_diff_trailing_newline() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("no-newline"), "a\nb").unwrap();
+    std::fs::write(repo_path.join("deleted"), "c\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("no-newline"), "a\nc").unwrap();
+    std::fs::remove_file(repo_path.join("deleted")).unwrap();
+    std::fs::write(repo_path.join("binary"), b"\0binary").unwrap();
+
+    // Every format ends with exactly one newline
+    for args in [
+        &["--summary"][..],
+        &["--stat"],
+        &["--shortstat"],
+        &["--types"],
+        &["--name-only"],
+        &["--git"],
+        &["--color-words"],
+        &["--json"],
+        &["--stat", "--git"],
+        &["--summary", "--color-words"],
+    ] {
+        let stdout = test_env.jj_cmd_success(&repo_path, &[&["diff"], args].concat());
+        assert!(
+            stdout.ends_with('\n') && !stdout.ends_with("\n\n"),
+            "{args:?}: {stdout:?}"
+        );
+    }
+
+    // Unless the diff is empty
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "-r", "root()"]);
+    assert_eq!(stdout, "");
+
+    // A newline is added to the output of external tools if missing
+    if cfg!(unix) {
+        let stdout = test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "diff",
+                "--tool=printf",
+                "--config=merge-tools.printf.diff-args=['tool output']",
+            ],
+        );
+        assert_eq!(stdout, "tool output\n");
+    }
+}
+
+#[test]
+fn test_diff_external_tool() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(repo_path.join("file2"), "foo\nbar\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "foo\n").unwrap();
+
+    let edit_script = test_env.set_up_fake_diff_editor();
+
+    // nonzero exit codes should print a warning
+    std::fs::write(&edit_script, "fail").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["diff", "--config=ui.diff.tool=fake-diff-editor"],
+    );
+    let mut insta_settings = insta::Settings::clone_current();
+    insta_settings.add_filter("exit (status|code)", "<exit status>");
+    insta_settings.bind(|| {
+        insta::assert_snapshot!(stdout, @r"");
+        insta::assert_snapshot!(stderr, @r#"
+        Warning: Tool exited with <exit status>: 1 (run with --debug to see the exact invocation)
+        "#);
+    });
+
+    // nonzero exit codes should not print a warning if it's an expected exit code
+    std::fs::write(&edit_script, "fail").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "diff",
+            "--tool",
+            "fake-diff-editor",
+            "--config=merge-tools.fake-diff-editor.diff-expected-exit-codes=[1]",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"");
+    insta::assert_snapshot!(stderr, @r"");
+
+    std::fs::write(
+        &edit_script,
+        "print-files-before\0print --\0print-files-after",
+    )
+    .unwrap();
+
+    // diff without file patterns
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &["diff", "--tool=fake-diff-editor"]), @r###"
+    file1
+    file2
+    --
+    file2
+    file3
+    "###);
+
+    // diff with file patterns
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &["diff", "--tool=fake-diff-editor", "file1"]), @r###"
+    file1
+    --
+    "###);
+
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &["log", "-p", "--tool=fake-diff-editor"]), @r###"
+    @  rlvkpnrz test.user@example.com 2001-02-03 08:05:09 39d9055d
+    │  (no description set)
+    │  file1
+    │  file2
+    │  --
+    │  file2
+    │  file3
+    ○  qpvuntsm test.user@example.com 2001-02-03 08:05:08 0ad4ef22
+    │  (no description set)
+    │  --
+    │  file1
+    │  file2
+    ◆  zzzzzzzz root() 00000000
+       --
+    "###);
+
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &["show", "--tool=fake-diff-editor"]), @r#"
+    Commit ID: 39d9055d70873099fd924b9af218289d5663eac8
+    Change ID: rlvkpnrzqnoowoytxnquwvuryrwnrmlp
+    Author   : Test User <test.user@example.com> (2001-02-03 08:05:09)
+    Committer: Test User <test.user@example.com> (2001-02-03 08:05:09)
+
+        (no description set)
+
+    file1
+    file2
+    --
+    file2
+    file3
+    "#);
+
+    // Enabled by default, looks up the merge-tools table
+    let config = "--config=ui.diff.tool=fake-diff-editor";
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["diff", config]), @r###"
+    file1
+    file2
+    --
+    file2
+    file3
+    "###);
+
+    // Inlined command arguments
+    let command_toml = to_toml_value(fake_diff_editor_path());
+    let config = format!("--config=ui.diff.tool=[{command_toml}, '$right', '$left']");
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["diff", &config]), @r###"
+    file2
+    file3
+    --
+    file1
+    file2
+    "###);
+
+    // A summary format is shown before the tool output, with a hint
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["diff", "--summary", "--tool=fake-diff-editor"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    M file2
+    R {file1 => file3}
+    file1
+    file2
+    --
+    file2
+    file3
+    ");
+    insta::assert_snapshot!(stderr, @"Hint: The summary is shown before the output of the diff tool `fake-diff-editor`. Omit the summary flags to show only the tool output.");
+
+    // Output of external diff tool shouldn't be escaped
+    std::fs::write(&edit_script, "print \x1b[1;31mred").unwrap();
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &["diff", "--color=always", "--tool=fake-diff-editor"]),
+        @r###"
+    [1;31mred
+    "###);
+
+    // Non-zero exit code isn't an error
+    std::fs::write(&edit_script, "print diff\0fail").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["show", "--tool=fake-diff-editor"]);
+    insta::assert_snapshot!(stdout, @r#"
+    Commit ID: 39d9055d70873099fd924b9af218289d5663eac8
+    Change ID: rlvkpnrzqnoowoytxnquwvuryrwnrmlp
+    Author   : Test User <test.user@example.com> (2001-02-03 08:05:09)
+    Committer: Test User <test.user@example.com> (2001-02-03 08:05:09)
+
+        (no description set)
+
+    diff
+    "#);
+    insta::assert_snapshot!(stderr.replace("exit code:", "exit status:"), @r###"
+    Warning: Tool exited with exit status: 1 (run with --debug to see the exact invocation)
+    "###);
+
+    // --tool=:builtin shouldn't be ignored
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["diff", "--tool=:builtin"]);
+    insta::assert_snapshot!(strip_last_line(&stderr), @r###"
+    Error: Failed to generate diff
+    Caused by:
+    1: Error executing ':builtin' (run with --debug to see the exact invocation)
+    "###);
+}
+
+#[test]
+fn test_diff_external_file_by_file_tool() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("file1"), "file1\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "file2\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(repo_path.join("file2"), "file2\nfile2\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "file3\n").unwrap();
+    std::fs::write(repo_path.join("file4"), "file1\n").unwrap();
+
+    let edit_script = test_env.set_up_fake_diff_editor();
+    std::fs::write(
+        &edit_script,
+        "print ==\0print-files-before\0print --\0print-files-after",
+    )
+    .unwrap();
+
+    // Enabled by default, looks up the merge-tools table
+    let configs: &[_] = &[
+        "--config=ui.diff.tool=fake-diff-editor",
+        "--config=merge-tools.fake-diff-editor.diff-invocation-mode=file-by-file",
+    ];
+
+    // diff without file patterns
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &[&["diff"], configs].concat()), @r###"
+    ==
+    file2
+    --
+    file2
+    ==
+    file3
+    --
+    file3
+    ==
+    file1
+    --
+    file4
+    "###);
+
+    // diff with file patterns
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &[&["diff", "file1"], configs].concat()), @r###"
+    ==
+    file1
+    --
+    file1
+    "###);
+
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &[&["log", "-p"], configs].concat()), @r###"
+    @  rlvkpnrz test.user@example.com 2001-02-03 08:05:09 7b01704a
+    │  (no description set)
+    │  ==
+    │  file2
+    │  --
+    │  file2
+    │  ==
+    │  file3
+    │  --
+    │  file3
+    │  ==
+    │  file1
+    │  --
+    │  file4
+    ○  qpvuntsm test.user@example.com 2001-02-03 08:05:08 6e485984
+    │  (no description set)
+    │  ==
+    │  file1
+    │  --
+    │  file1
+    │  ==
+    │  file2
+    │  --
+    │  file2
+    ◆  zzzzzzzz root() 00000000
+    "###);
+
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &[&["show"], configs].concat()), @r#"
+    Commit ID: 7b01704a670bc77d11ed117d362855cff1d4513b
+    Change ID: rlvkpnrzqnoowoytxnquwvuryrwnrmlp
+    Author   : Test User <test.user@example.com> (2001-02-03 08:05:09)
+    Committer: Test User <test.user@example.com> (2001-02-03 08:05:09)
+
+        (no description set)
+
+    ==
+    file2
+    --
+    file2
+    ==
+    file3
+    --
+    file3
+    ==
+    file1
+    --
+    file4
+    "#);
+
+    // Display paths of the files
+    std::fs::write(&edit_script, "print-labels").unwrap();
+    let configs_with_labels: &[_] = &[
+        configs,
+        &[
+            "--config=merge-tools.fake-diff-editor.diff-args=['--label=$left_path', \
+             '--label=$right_path', '$left', '$right']",
+        ],
+    ]
+    .concat();
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &[&["diff"], configs_with_labels].concat()), @r"
+    file2
+    file2
+    file3
+    file3
+    file1
+    file4
+    ");
+
+    // Unknown variables are rejected
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "diff",
+            "--tool=fake-diff-editor",
+            "--config=merge-tools.fake-diff-editor.diff-args=['$left_path', '$left', '$right']",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Config error: Invalid type or value for merge-tools.fake-diff-editor.diff-args
+    Caused by: Unknown variable $left_path (available variables: $left, $right)
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    ");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_diff_external_tool_symlink() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let external_file_path = test_env.env_root().join("external-file");
+    std::fs::write(&external_file_path, "").unwrap();
+    let external_file_permissions = external_file_path.symlink_metadata().unwrap().permissions();
+
+    std::os::unix::fs::symlink("non-existent1", repo_path.join("dead")).unwrap();
+    std::os::unix::fs::symlink(&external_file_path, repo_path.join("file")).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("dead")).unwrap();
+    std::os::unix::fs::symlink("non-existent2", repo_path.join("dead")).unwrap();
+    std::fs::remove_file(repo_path.join("file")).unwrap();
+    std::fs::write(repo_path.join("file"), "").unwrap();
+
+    let edit_script = test_env.set_up_fake_diff_editor();
+    std::fs::write(
+        edit_script,
+        "print-files-before\0print --\0print-files-after",
+    )
+    .unwrap();
+
+    // Shouldn't try to change permission of symlinks
+    insta::assert_snapshot!(
+        test_env.jj_cmd_success(&repo_path, &["diff", "--tool=fake-diff-editor"]), @r###"
+    dead
+    file
+    --
+    dead
+    file
     "###);
+
+    // External file should be intact
+    assert_eq!(
+        external_file_path.symlink_metadata().unwrap().permissions(),
+        external_file_permissions
+    );
+}
+
+#[test]
+fn test_diff_external_tool_conflict_marker_style() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let file_path = repo_path.join("file");
+
+    // Create a conflict
+    std::fs::write(
+        &file_path,
+        indoc! {"
+        line 1
+        line 2
+        line 3
+        line 4
+        line 5
+    "},
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "base"]);
+    std::fs::write(
+        &file_path,
+        indoc! {"
+        line 1
+        line 2.1
+        line 2.2
+        line 3
+        line 4.1
+        line 5
+    "},
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "side-a"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(base)", "-m", "side-b"]);
+    std::fs::write(
+        &file_path,
+        indoc! {"
+        line 1
+        line 2.3
+        line 3
+        line 4.2
+        line 4.3
+        line 5
+    "},
+    )
+    .unwrap();
+
+    // Resolve one of the conflicts in the working copy
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["new", "description(side-a)", "description(side-b)"],
+    );
+    std::fs::write(
+        &file_path,
+        indoc! {"
+        line 1
+        line 2.1
+        line 2.2
+        line 2.3
+        line 3
+        <<<<<<<
+        %%%%%%%
+        -line 4
+        +line 4.1
+        +++++++
+        line 4.2
+        line 4.3
+        >>>>>>>
+        line 5
+    "},
+    )
+    .unwrap();
+
+    // Set up diff editor to use "snapshot" conflict markers
+    let edit_script = test_env.set_up_fake_diff_editor();
+    test_env.add_config(r#"merge-tools.fake-diff-editor.conflict-marker-style = "snapshot""#);
+
+    // We want to see whether the diff is using the correct conflict markers
+    std::fs::write(
+        &edit_script,
+        ["files-before file", "files-after file", "dump file file"].join("\0"),
+    )
+    .unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["diff", "--tool", "fake-diff-editor"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @"");
+    // Conflicts should render using "snapshot" format
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("file")).unwrap(), @r##"
+    line 1
+    line 2.1
+    line 2.2
+    line 2.3
+    line 3
+    <<<<<<< Conflict 1 of 1
+    +++++++ Contents of side #1
+    line 4.1
+    ------- Contents of base
+    line 4
+    +++++++ Contents of side #2
+    line 4.2
+    line 4.3
+    >>>>>>> Conflict 1 of 1 ends
+    line 5
+    "##);
 }
 
 #[test]
-fn test_diff_missing_newline() {
+fn test_diff_stat_with_summary() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
 
-    std::fs::write(repo_path.join("file1"), "foo").unwrap();
-    std::fs::write(repo_path.join("file2"), "foo\nbar").unwrap();
+    std::fs::write(repo_path.join("deleted"), "deleted\n").unwrap();
+    std::fs::write(repo_path.join("modified"), "a\n").unwrap();
+    std::fs::write(repo_path.join("renamed"), "a\nb\nc\n").unwrap();
+    std::fs::write(repo_path.join("type-changed"), "type\n").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::write(repo_path.join("file1"), "foo\nbar").unwrap();
-    std::fs::write(repo_path.join("file2"), "foo").unwrap();
+    std::fs::remove_file(repo_path.join("deleted")).unwrap();
+    std::fs::write(repo_path.join("modified"), "b\n").unwrap();
+    std::fs::rename(repo_path.join("renamed"), repo_path.join("renamed2")).unwrap();
+    std::fs::write(repo_path.join("created"), "created\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "x", "type-changed"]);
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Modified regular file file1:
-       1    1: foo
-            2: bar
-    Modified regular file file2:
-       1    1: foo
-       2     : bar
-    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--summary"]);
+    insta::assert_snapshot!(stdout, @r"
+    created               | 1 +
+    deleted               | 1 -
+    modified              | 2 +-
+    {renamed => renamed2} | 0
+    type-changed          | 0
+    5 files changed, 2 insertions(+), 2 deletions(-)
+    create regular file created
+    delete regular file deleted
+    rename {renamed => renamed2}
+    change regular file -> executable file type-changed
+    ");
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
-    insta::assert_snapshot!(stdout, @r###"
-    diff --git a/file1 b/file1
-    index 1910281566..a907ec3f43 100644
-    --- a/file1
-    +++ b/file1
-    @@ -1,1 +1,2 @@
-    -foo
-    \ No newline at end of file
-    +foo
-    +bar
-    \ No newline at end of file
-    diff --git a/file2 b/file2
-    index a907ec3f43..1910281566 100644
-    --- a/file2
-    +++ b/file2
-    @@ -1,2 +1,1 @@
-    -foo
-    -bar
-    \ No newline at end of file
-    +foo
-    \ No newline at end of file
-    "###);
+    // --summary can't be combined with other short formats
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--summary", "--types"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the argument '--summary' cannot be used with '--types'
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
-    insta::assert_snapshot!(stdout, @r###"
-    file1 | 3 ++-
-    file2 | 3 +--
-    2 files changed, 3 insertions(+), 3 deletions(-)
-    "###);
+    Usage: jj diff --summary [FILESETS]...
+
+    For more information, try '--help'.
+    ");
 }
 
 #[test]
-fn test_color_words_diff_missing_newline() {
+fn test_diff_stat() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
 
-    std::fs::write(repo_path.join("file1"), "").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Empty"]);
-    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\nf\ng\nh\ni").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Add no newline"]);
-    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\ne\nf\ng\nh\ni").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Modify first line"]);
-    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\nE\nf\ng\nh\ni").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Modify middle line"]);
-    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\nE\nf\ng\nh\nI").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Modify last line"]);
-    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\nE\nf\ng\nh\nI\n").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Append newline"]);
-    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\nE\nf\ng\nh\nI").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Remove newline"]);
-    std::fs::write(repo_path.join("file1"), "").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "=== Empty"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file1 | 1 +
+    1 file changed, 1 insertion(+), 0 deletions(-)
+    "###);
 
-    let stdout = test_env.jj_cmd_success(
-        &repo_path,
-        &[
-            "log",
-            "-Tdescription",
-            "-pr::@-",
-            "--no-graph",
-            "--reversed",
-        ],
-    );
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
+    insta::assert_snapshot!(stdout, @"0 files changed, 0 insertions(+), 0 deletions(-)");
+
+    std::fs::write(repo_path.join("file1"), "foo\nbar\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "bar\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
     insta::assert_snapshot!(stdout, @r###"
-    === Empty
-    Added regular file file1:
-        (empty)
-    === Add no newline
-    Modified regular file file1:
-            1: a
-            2: b
-            3: c
-            4: d
-            5: e
-            6: f
-            7: g
-            8: h
-            9: i
-    === Modify first line
-    Modified regular file file1:
-       1    1: aA
-       2    2: b
-       3    3: c
-       4    4: d
-        ...
-    === Modify middle line
-    Modified regular file file1:
-       1    1: A
-       2    2: b
-       3    3: c
-       4    4: d
-       5    5: eE
-       6    6: f
-       7    7: g
-       8    8: h
-       9    9: i
-    === Modify last line
-    Modified regular file file1:
-        ...
-       6    6: f
-       7    7: g
-       8    8: h
-       9    9: iI
-    === Append newline
-    Modified regular file file1:
-        ...
-       6    6: f
-       7    7: g
-       8    8: h
-       9    9: I
-    === Remove newline
-    Modified regular file file1:
-        ...
-       6    6: f
-       7    7: g
-       8    8: h
-       9    9: I
-    === Empty
-    Modified regular file file1:
-       1     : A
-       2     : b
-       3     : c
-       4     : d
-       5     : E
-       6     : f
-       7     : g
-       8     : h
-       9     : I
+    file1 | 1 -
+    1 file changed, 0 insertions(+), 1 deletion(-)
     "###);
 
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--shortstat"]);
+    insta::assert_snapshot!(stdout, @"1 file changed, 0 insertions(+), 1 deletion(-)");
     let stdout = test_env.jj_cmd_success(
         &repo_path,
-        &[
-            "log",
-            "--config=diff.color-words.max-inline-alternation=0",
-            "-Tdescription",
-            "-pr::@-",
-            "--no-graph",
-            "--reversed",
-        ],
+        &["diff", "--config=ui.diff.format=shortstat", "--color=debug"],
     );
-    insta::assert_snapshot!(stdout, @r###"
-    === Empty
-    Added regular file file1:
-        (empty)
-    === Add no newline
-    Modified regular file file1:
-            1: a
-            2: b
-            3: c
-            4: d
-            5: e
-            6: f
-            7: g
-            8: h
-            9: i
-    === Modify first line
-    Modified regular file file1:
-       1     : a
-            1: A
-       2    2: b
-       3    3: c
-       4    4: d
-        ...
-    === Modify middle line
-    Modified regular file file1:
-       1    1: A
-       2    2: b
-       3    3: c
-       4    4: d
-       5     : e
-            5: E
-       6    6: f
-       7    7: g
-       8    8: h
-       9    9: i
-    === Modify last line
-    Modified regular file file1:
-        ...
-       6    6: f
-       7    7: g
-       8    8: h
-       9     : i
-            9: I
-    === Append newline
-    Modified regular file file1:
-        ...
-       6    6: f
-       7    7: g
-       8    8: h
-       9     : I
-            9: I
-    === Remove newline
-    Modified regular file file1:
-        ...
-       6    6: f
-       7    7: g
-       8    8: h
-       9     : I
-            9: I
-    === Empty
-    Modified regular file file1:
-       1     : A
-       2     : b
-       3     : c
-       4     : d
-       5     : E
-       6     : f
-       7     : g
-       8     : h
-       9     : I
-    "###);
+    insta::assert_snapshot!(stdout, @"<<diff stat-summary::1 file changed, 0 insertions(+), 1 deletion(-)>>");
 }
 
 #[test]
-fn test_diff_ignore_whitespace() {
+fn test_diff_stat_group_digits() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
+    let lines = (0..1234).map(|n| format!("{n}\n")).collect::<String>();
+    std::fs::write(repo_path.join("file"), lines).unwrap();
+    std::fs::write(repo_path.join("binary"), vec![0; 2048]).unwrap();
 
-    std::fs::write(
-        repo_path.join("file1"),
-        indoc! {"
-            foo {
-                bar;
-            }
-            baz {}
-        "},
-    )
-    .unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "-mindent + whitespace insertion"]);
-    std::fs::write(
-        repo_path.join("file1"),
-        indoc! {"
-            {
-                foo {
-                    bar;
-                }
-            }
-            baz {  }
-        "},
-    )
-    .unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["status"]);
-
-    // Git diff as reference output
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--ignore-all-space"]);
-    insta::assert_snapshot!(stdout, @r#"
-    diff --git a/file1 b/file1
-    index f532aa68ad..033c4a6168 100644
-    --- a/file1
-    +++ b/file1
-    @@ -1,4 +1,6 @@
-    +{
-         foo {
-             bar;
-         }
-    +}
-     baz {  }
-    "#);
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--ignore-space-change"]);
-    insta::assert_snapshot!(stdout, @r#"
-    diff --git a/file1 b/file1
-    index f532aa68ad..033c4a6168 100644
-    --- a/file1
-    +++ b/file1
-    @@ -1,4 +1,6 @@
-    -foo {
-    +{
-    +    foo {
-             bar;
-    +    }
-     }
-    -baz {}
-    +baz {  }
-    "#);
-
-    // Diff-stat should respects the whitespace options
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--ignore-all-space"]);
-    insta::assert_snapshot!(stdout, @r#"
-    file1 | 2 ++
-    1 file changed, 2 insertions(+), 0 deletions(-)
-    "#);
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--ignore-space-change"]);
-    insta::assert_snapshot!(stdout, @r#"
-    file1 | 6 ++++--
-    1 file changed, 4 insertions(+), 2 deletions(-)
-    "#);
+    // Numbers are ungrouped by default
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
+    insta::assert_snapshot!(stdout, @r"
+    binary | Bin 0 -> 2048 bytes
+    file   | 1234 +++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+    2 files changed, 1234 insertions(+), 0 deletions(-)
+    ");
 
-    // Word-level changes are still highlighted
     let stdout = test_env.jj_cmd_success(
         &repo_path,
-        &["diff", "--color=always", "--ignore-all-space"],
+        &["diff", "--stat", "--config=diff.stat.group-digits=true"],
     );
-    insta::assert_snapshot!(stdout, @r#"
-    [38;5;3mModified regular file file1:[39m
-         [38;5;2m   1[39m: [4m[38;5;2m{[24m[39m
-    [38;5;1m   1[39m [38;5;2m   2[39m: [4m[38;5;2m    [24m[39mfoo {
-    [38;5;1m   2[39m [38;5;2m   3[39m:     [4m[38;5;2m    [24m[39mbar;
-    [38;5;1m   3[39m [38;5;2m   4[39m: [4m[38;5;2m    [24m[39m}
-         [38;5;2m   5[39m: [4m[38;5;2m}[24m[39m
-    [38;5;1m   4[39m [38;5;2m   6[39m: baz {[4m[38;5;2m  [24m[39m}
-    "#);
+    insta::assert_snapshot!(stdout, @r"
+    binary | Bin 0 -> 2,048 bytes
+    file   | 1,234 ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+    2 files changed, 1,234 insertions(+), 0 deletions(-)
+    ");
     let stdout = test_env.jj_cmd_success(
         &repo_path,
-        &["diff", "--color=always", "--ignore-space-change"],
+        &[
+            "diff",
+            "--shortstat",
+            "--config=diff.stat.group-digits=true",
+        ],
     );
-    insta::assert_snapshot!(stdout, @r#"
-    [38;5;3mModified regular file file1:[39m
-         [38;5;2m   1[39m: [4m[38;5;2m{[24m[39m
-    [38;5;1m   1[39m [38;5;2m   2[39m: [4m[38;5;2m    [24m[39mfoo {
-    [38;5;1m   2[39m [38;5;2m   3[39m:     [4m[38;5;2m    [24m[39mbar;
-         [38;5;2m   4[39m: [4m[38;5;2m    }[24m[39m
-    [38;5;1m   3[39m [38;5;2m   5[39m: }
-    [38;5;1m   4[39m [38;5;2m   6[39m: baz {[4m[38;5;2m  [24m[39m}
-    "#);
+    insta::assert_snapshot!(stdout, @"2 files changed, 1,234 insertions(+), 0 deletions(-)");
 }
 
 #[test]
-fn test_diff_skipped_context() {
+fn test_diff_normalize() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("case.txt"), "hello\nworld\n").unwrap();
+    std::fs::write(repo_path.join("other"), "hello\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("case.txt"), "HELLO\nthere\n").unwrap();
+    std::fs::write(repo_path.join("other"), "HELLO\n").unwrap();
 
-    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\nf\ng\nh\ni\nj").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "=== Left side of diffs"]);
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r#"diff.normalize."*.txt" = [{formatter}, "--uppercase"]"#
+    ));
 
-    test_env.jj_cmd_ok(&repo_path, &["new", "@", "-m", "=== Must skip 2 lines"]);
-    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\ne\nf\ng\nh\ni\nJ").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== Don't skip 1 line"]);
-    std::fs::write(repo_path.join("file1"), "A\nb\nc\nd\ne\nf\ng\nh\nI\nj").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== No gap to skip"]);
-    std::fs::write(repo_path.join("file1"), "a\nB\nc\nd\ne\nf\ng\nh\nI\nj").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== No gap to skip"]);
-    std::fs::write(repo_path.join("file1"), "a\nb\nC\nd\ne\nf\ng\nh\nI\nj").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== 1 line at start"]);
-    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\nE\nf\ng\nh\ni\nj").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== 1 line at end"]);
-    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\nF\ng\nh\ni\nj").unwrap();
+    // Only the matching file is normalized, and the normalized content is shown
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/case.txt b/case.txt
+    index 94954abda4..e5927c1196 100644
+    --- a/case.txt
+    +++ b/case.txt
+    @@ -1,2 +1,2 @@
+     HELLO
+    -WORLD
+    +THERE
+    diff --git a/other b/other
+    index ce01362503..e427984d4a 100644
+    --- a/other
+    +++ b/other
+    @@ -1,1 +1,1 @@
+    -hello
+    +HELLO
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
+    insta::assert_snapshot!(stdout, @r"
+    case.txt | 2 +-
+    other    | 2 +-
+    2 files changed, 2 insertions(+), 2 deletions(-)
+    ");
 
-    let stdout = test_env.jj_cmd_success(
-        &repo_path,
-        &["log", "-Tdescription", "-p", "--no-graph", "--reversed"],
-    );
-    insta::assert_snapshot!(stdout, @r###"
-    === Left side of diffs
-    Added regular file file1:
-            1: a
-            2: b
-            3: c
-            4: d
-            5: e
-            6: f
-            7: g
-            8: h
-            9: i
-           10: j
-    === Must skip 2 lines
-    Modified regular file file1:
-       1    1: aA
-       2    2: b
-       3    3: c
-       4    4: d
-        ...
-       7    7: g
-       8    8: h
-       9    9: i
-      10   10: jJ
-    === Don't skip 1 line
-    Modified regular file file1:
-       1    1: aA
-       2    2: b
-       3    3: c
-       4    4: d
-       5    5: e
-       6    6: f
-       7    7: g
-       8    8: h
-       9    9: iI
-      10   10: j
-    === No gap to skip
-    Modified regular file file1:
-       1    1: a
-       2    2: bB
-       3    3: c
-       4    4: d
-       5    5: e
-       6    6: f
-       7    7: g
-       8    8: h
-       9    9: iI
-      10   10: j
-    === No gap to skip
-    Modified regular file file1:
-       1    1: a
-       2    2: b
-       3    3: cC
-       4    4: d
-       5    5: e
-       6    6: f
-       7    7: g
-       8    8: h
-       9    9: iI
-      10   10: j
-    === 1 line at start
-    Modified regular file file1:
-       1    1: a
-       2    2: b
-       3    3: c
-       4    4: d
-       5    5: eE
-       6    6: f
-       7    7: g
-       8    8: h
-        ...
-    === 1 line at end
-    Modified regular file file1:
-        ...
-       3    3: c
-       4    4: d
-       5    5: e
-       6    6: fF
-       7    7: g
-       8    8: h
-       9    9: i
-      10   10: j
-    "###);
+    // The file is still reported as modified, but no lines differ
+    std::fs::write(repo_path.join("case.txt"), "HELLO\nWORLD\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "case.txt"]);
+    insta::assert_snapshot!(stdout, @"M case.txt");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--color-words", "case.txt"]);
+    insta::assert_snapshot!(stdout, @"Modified regular file case.txt:");
+
+    // Failing command
+    test_env.add_config(format!(
+        r#"diff.normalize."*.txt" = [{formatter}, "--fail"]"#
+    ));
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["diff", "--git"]);
+    let stderr = stderr.replace(formatter_path.to_str().unwrap(), "$FORMATTER");
+    insta::assert_snapshot!(stderr.lines().next().unwrap(), @"Error: Failed to normalize case.txt with `$FORMATTER --fail`");
 }
 
 #[test]
-fn test_diff_skipped_context_from_settings_color_words() {
+fn test_diff_git_compact_single_line() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
+    std::fs::write(
+        repo_path.join("file"),
+        "let x = 1;\nkeep\nfoo bar\n\nmany\nlines\n\none\n\nlast",
+    )
+    .unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(
+        repo_path.join("file"),
+        "let y = 2;\nkeep\nfoo baz bar\n\nMANY\nLINES\n\nnow\ntwo\n\nLAST",
+    )
+    .unwrap();
+    test_env.add_config("diff.git.compact-single-line = true");
 
-    test_env.add_config(
-        r#"
-[diff.color-words]
-context = 0
-        "#,
+    // Only lines replaced by a single line are compacted
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=0"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index a902fa6821..b43e0d4b55 100644
+    --- a/file
+    +++ b/file
+    @@ -1,1 +1,1 @@
+    ~let [-x-]{+y+} = [-1-]{+2+};
+    @@ -3,1 +3,1 @@
+    ~foo {+baz +}bar
+    @@ -5,2 +5,2 @@
+    -many
+    -lines
+    +MANY
+    +LINES
+    @@ -8,1 +8,2 @@
+    -one
+    +now
+    +two
+    @@ -10,1 +11,1 @@
+    -last
+    \ No newline at end of file
+    +LAST
+    \ No newline at end of file
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--context=0", "--color=always"],
     );
+    insta::assert_snapshot!(stdout.lines().take(6).join("\n"), @r"
+    [1mdiff --git a/file b/file[0m
+    [1mindex a902fa6821..b43e0d4b55 100644[0m
+    [1m--- a/file[0m
+    [1m+++ b/file[0m
+    [38;5;6m@@ -1,1 +1,1 @@[39m
+    ~let [4m[38;5;1m[-x-][38;5;2m{+y+}[24m[39m = [4m[38;5;1m[-1-][38;5;2m{+2+}[24m[39m;
+    ");
+}
 
-    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "=== First commit"]);
+#[test]
+fn test_diff_dim_whitespace_changes() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "if x {\nfoo();\n}\nbar();\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "if x {\n    foo();\n}\nbaz();\n").unwrap();
 
-    test_env.jj_cmd_ok(&repo_path, &["new", "@", "-m", "=== Must show 0 context"]);
-    std::fs::write(repo_path.join("file1"), "a\nb\nC\nd\ne").unwrap();
+    // Disabled by default
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--color=debug"]);
+    assert!(!stdout.contains("whitespace_change"));
 
-    let stdout = test_env.jj_cmd_success(
-        &repo_path,
-        &["log", "-Tdescription", "-p", "--no-graph", "--reversed"],
-    );
-    insta::assert_snapshot!(stdout, @r#"
-    === First commit
-    Added regular file file1:
-            1: a
-            2: b
-            3: c
-            4: d
-            5: e
-    === Must show 0 context
-    Modified regular file file1:
-        ...
-       3    3: cC
-        ...
-    "#);
+    test_env.add_config("diff.dim-whitespace-changes = true");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--color=debug"]);
+    insta::assert_snapshot!(stdout, @r"
+    [1m<<diff file_header::diff --git a/file b/file>>[0m
+    [1m<<diff file_header::index 9e91797c2b..ef63bfa836 100644>>[0m
+    [1m<<diff file_header::--- a/file>>[0m
+    [1m<<diff file_header::+++ b/file>>[0m
+    [38;5;6m<<diff hunk_header::@@ -1,4 +1,4 @@>>[39m
+    <<diff context:: if x {>>
+    [38;5;8m<<diff whitespace_change removed::-foo();>>[39m
+    [38;5;8m<<diff whitespace_change added::+>>[24m<<diff whitespace_change added token::    >>[24m<<diff whitespace_change added::foo();>>[39m
+    <<diff context:: }>>
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::bar>>[24m<<diff removed::();>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::baz>>[24m<<diff added::();>>[39m
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--color-words", "--color=debug"]);
+    insta::assert_snapshot!(stdout, @r"
+    [38;5;3m<<diff header::Modified regular file file:>>[39m
+    [38;5;1m<<diff removed line_number::   1>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   1>>[39m<<diff::: if x {>>
+    [38;5;8m<<diff whitespace_change removed line_number::   2>>[39m<<diff whitespace_change:: >>[38;5;8m<<diff whitespace_change added line_number::   2>>[39m<<diff whitespace_change::: >>[24m[38;5;8m<<diff whitespace_change added token::    >>[24m[39m<<diff whitespace_change::foo();>>
+    [38;5;1m<<diff removed line_number::   3>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   3>>[39m<<diff::: }>>
+    [38;5;1m<<diff removed line_number::   4>>[39m<<diff:: >>[38;5;2m<<diff added line_number::   4>>[39m<<diff::: >>[4m[38;5;1m<<diff removed token::bar>>[38;5;2m<<diff added token::baz>>[24m[39m<<diff::();>>
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--color=always"]);
+    insta::assert_snapshot!(stdout, @r"
+    [1mdiff --git a/file b/file[0m
+    [1mindex 9e91797c2b..ef63bfa836 100644[0m
+    [1m--- a/file[0m
+    [1m+++ b/file[0m
+    [38;5;6m@@ -1,4 +1,4 @@[39m
+     if x {
+    [38;5;8m-foo();[39m
+    [38;5;8m+[24m    [24mfoo();[39m
+     }
+    [38;5;1m-[4mbar[24m();[39m
+    [38;5;2m+[4mbaz[24m();[39m
+    ");
 }
 
 #[test]
-fn test_diff_skipped_context_from_settings_git() {
+fn test_diff_introduced_by() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne\nf\ng\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "x\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=introduce"]);
+    std::fs::write(
+        repo_path.join("file1"),
+        "a\nb\nc\nd\ne\nf\ng\nnew\n\nother\n",
+    )
+    .unwrap();
+    std::fs::write(repo_path.join("file2"), "x\ny\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(
+        repo_path.join("file1"),
+        "A\nb\nc\nd\ne\nf\ng\nnew\n\nOTHER\n",
+    )
+    .unwrap();
+    std::fs::write(repo_path.join("file2"), "x\nY\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "new\n").unwrap();
 
-    test_env.add_config(
-        r#"
-[diff.git]
-context = 0
-        "#,
-    );
-
-    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd\ne").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "=== First commit"]);
-
-    test_env.jj_cmd_ok(&repo_path, &["new", "@", "-m", "=== Must show 0 context"]);
-    std::fs::write(repo_path.join("file1"), "a\nb\nC\nd\ne").unwrap();
-
+    // Only hunks still adding lines introduced by the revision are shown. The
+    // modified line in file2 no longer matches, and file3 isn't touched by the
+    // revision.
     let stdout = test_env.jj_cmd_success(
         &repo_path,
         &[
-            "log",
-            "-Tdescription",
-            "-p",
+            "diff",
             "--git",
-            "--no-graph",
-            "--reversed",
+            "--context=0",
+            "--from=description(introduce)-",
+            "--introduced-by=description(introduce)",
         ],
     );
     insta::assert_snapshot!(stdout, @r"
-    === First commit
-    diff --git a/file1 b/file1
-    new file mode 100644
-    index 0000000000..0fec236860
-    --- /dev/null
-    +++ b/file1
-    @@ -0,0 +1,5 @@
-    +a
-    +b
-    +c
-    +d
-    +e
-    \ No newline at end of file
-    === Must show 0 context
     diff --git a/file1 b/file1
-    index 0fec236860..b7615dae52 100644
+    index f9d9a0195c..b8d2372fda 100644
     --- a/file1
     +++ b/file1
-    @@ -3,1 +3,1 @@
-    -c
-    +C
+    @@ -7,0 +8,3 @@
+    +new
+    +
+    +OTHER
     ");
+
+    // Other formats can't be used
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["diff", "--stat", "--introduced-by=description(introduce)"],
+    );
+    insta::assert_snapshot!(stderr, @"Error: Only the git format can be used to show hunks with introduced lines");
 }
 
 #[test]
-fn test_diff_skipped_context_nondefault() {
+fn test_diff_git_line_numbers() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(
+        repo_path.join("file"),
+        "a\nB\nc\nd\ne\nf\ng\nh\nnew\ni\nJ\n",
+    )
+    .unwrap();
 
-    std::fs::write(repo_path.join("file1"), "a\nb\nc\nd").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "=== Left side of diffs"]);
-
-    test_env.jj_cmd_ok(&repo_path, &["new", "@", "-m", "=== Must skip 2 lines"]);
-    std::fs::write(repo_path.join("file1"), "A\nb\nc\nD").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== Don't skip 1 line"]);
-    std::fs::write(repo_path.join("file1"), "A\nb\nC\nd").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== No gap to skip"]);
-    std::fs::write(repo_path.join("file1"), "a\nB\nC\nd").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== 1 line at start"]);
-    std::fs::write(repo_path.join("file1"), "a\nB\nc\nd").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "=== 1 line at end"]);
-    std::fs::write(repo_path.join("file1"), "a\nb\nC\nd").unwrap();
-
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--context=1", "--line-numbers"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index 92dfa21641..cee2869851 100644
+    --- a/file
+    +++ b/file
+    @@ -1,3 +1,3 @@
+       1:  a
+       2: -b
+       2: +B
+       3:  c
+    @@ -8,3 +8,4 @@
+       8:  h
+       9: +new
+      10:  i
+      10: -j
+      11: +J
+    ");
     let stdout = test_env.jj_cmd_success(
         &repo_path,
         &[
-            "log",
-            "-Tdescription",
-            "-p",
-            "--no-graph",
-            "--reversed",
+            "diff",
+            "--git",
             "--context=0",
+            "--line-numbers",
+            "--color=debug",
+            "--config=diff.git.compact-single-line=true",
         ],
     );
-    insta::assert_snapshot!(stdout, @r###"
-    === Left side of diffs
-    Added regular file file1:
-            1: a
-            2: b
-            3: c
-            4: d
-    === Must skip 2 lines
-    Modified regular file file1:
-       1    1: aA
-        ...
-       4    4: dD
-    === Don't skip 1 line
-    Modified regular file file1:
-       1    1: aA
-       2    2: b
-       3    3: cC
-       4    4: d
-    === No gap to skip
-    Modified regular file file1:
-       1    1: a
-       2    2: bB
-       3    3: cC
-       4    4: d
-    === 1 line at start
-    Modified regular file file1:
-       1    1: a
-       2    2: bB
-        ...
-    === 1 line at end
-    Modified regular file file1:
-        ...
-       3    3: cC
-       4    4: d
-    "###);
+    insta::assert_snapshot!(stdout, @r"
+    [1m<<diff file_header::diff --git a/file b/file>>[0m
+    [1m<<diff file_header::index 92dfa21641..cee2869851 100644>>[0m
+    [1m<<diff file_header::--- a/file>>[0m
+    [1m<<diff file_header::+++ b/file>>[0m
+    [38;5;6m<<diff hunk_header::@@ -2,1 +2,1 @@>>[39m
+    <<diff line_number::   2>><<diff::: ~>>[4m[38;5;1m<<diff removed token::[-b-]>>[38;5;2m<<diff added token::{+B+}>>[24m[39m<<diff::>>
+    [38;5;6m<<diff hunk_header::@@ -8,0 +9,1 @@>>[39m
+    [38;5;2m<<diff added line_number::   9>><<diff added::: +>>[4m<<diff added token::new>>[24m[39m
+    [38;5;6m<<diff hunk_header::@@ -10,1 +11,1 @@>>[39m
+    <<diff line_number::  11>><<diff::: ~>>[4m[38;5;1m<<diff removed token::[-j-]>>[38;5;2m<<diff added token::{+J+}>>[24m[39m<<diff::>>
+    ");
 }
 
 #[test]
-fn test_diff_leading_trailing_context() {
+fn test_diff_git_hunk_line_counts() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "a\nb\nc\nd\ne\nf\ng\nh\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "a\nB\nnew1\nnew2\nc\nd\ne\nf\ng\n").unwrap();
 
-    // N=5 context lines at start/end of the file
-    std::fs::write(
-        repo_path.join("file1"),
-        "1\n2\n3\n4\n5\nL\n6\n7\n8\n9\n10\n11\n",
-    )
-    .unwrap();
+    // Disabled by default
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=1"]);
+    assert!(!stdout.contains("(+"));
+
+    test_env.add_config("diff.git.hunk-line-counts = true");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=1"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index 71ac1b5791..fc03c4319e 100644
+    --- a/file
+    +++ b/file
+    @@ -1,3 +1,5 @@ (+3 -1)
+     a
+    -b
+    +B
+    +new1
+    +new2
+     c
+    @@ -7,2 +9,1 @@ (+0 -1)
+     g
+    -h
+    ");
+}
+
+#[test]
+fn test_diff_git_show_function_context() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let old_content = indoc! {"
+        fn foo() {
+            let a = 1;
+            let b = 2;
+            let c = 3;
+        }
+
+        fn bar() {
+            let x = 1;
+            let y = 2;
+        }
+    "};
+    std::fs::write(repo_path.join("file"), old_content).unwrap();
     test_env.jj_cmd_ok(&repo_path, &["new"]);
     std::fs::write(
-        repo_path.join("file1"),
-        "1\n2\n3\n4\n5\n6\nR\n7\n8\n9\n10\n11\n",
+        repo_path.join("file"),
+        old_content
+            .replace("let a = 1;", "let a = 10;")
+            .replace("let y = 2;", "let y = 20;"),
     )
     .unwrap();
 
-    // N=5 <= num_context_lines + 1: No room to skip.
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--context=4"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Modified regular file file1:
-       1    1: 1
-       2    2: 2
-       3    3: 3
-       4    4: 4
-       5    5: 5
-       6     : L
-       7    6: 6
-            7: R
-       8    8: 7
-       9    9: 8
-      10   10: 9
-      11   11: 10
-      12   12: 11
-    "###);
-
-    // N=5 <= 2 * num_context_lines + 1: The last hunk wouldn't be split if
-    // trailing diff existed.
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--context=3"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Modified regular file file1:
-        ...
-       3    3: 3
-       4    4: 4
-       5    5: 5
-       6     : L
-       7    6: 6
-            7: R
-       8    8: 7
-       9    9: 8
-      10   10: 9
-        ...
-    "###);
-
-    // N=5 > 2 * num_context_lines + 1: The last hunk should be split no matter
-    // if trailing diff existed.
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--context=1"]);
-    insta::assert_snapshot!(stdout, @r###"
-    Modified regular file file1:
-        ...
-       5    5: 5
-       6     : L
-       7    6: 6
-            7: R
-       8    8: 7
-        ...
-    "###);
-
-    // N=5 <= num_context_lines: No room to skip.
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=5"]);
-    insta::assert_snapshot!(stdout, @r###"
-    diff --git a/file1 b/file1
-    index 1bf57dee4a..69b3e1865c 100644
-    --- a/file1
-    +++ b/file1
-    @@ -1,12 +1,12 @@
-     1
-     2
-     3
-     4
-     5
-    -L
-     6
-    +R
-     7
-     8
-     9
-     10
-     11
-    "###);
-
-    // N=5 <= 2 * num_context_lines: The last hunk wouldn't be split if
-    // trailing diff existed.
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=3"]);
-    insta::assert_snapshot!(stdout, @r###"
-    diff --git a/file1 b/file1
-    index 1bf57dee4a..69b3e1865c 100644
-    --- a/file1
-    +++ b/file1
-    @@ -3,8 +3,8 @@
-     3
-     4
-     5
-    -L
-     6
-    +R
-     7
-     8
-     9
-    "###);
+    // The first hunk has no function line before it
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--context=1", "--show-function-context"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index 65d88f57e4..ad4279f6a6 100644
+    --- a/file
+    +++ b/file
+    @@ -1,3 +1,3 @@
+     fn foo() {
+    -    let a = 1;
+    +    let a = 10;
+         let b = 2;
+    @@ -8,3 +8,3 @@ fn bar() {
+         let x = 1;
+    -    let y = 2;
+    +    let y = 20;
+     }
+    ");
 
-    // N=5 > 2 * num_context_lines: The last hunk should be split no matter
-    // if trailing diff existed.
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=2"]);
-    insta::assert_snapshot!(stdout, @r###"
-    diff --git a/file1 b/file1
-    index 1bf57dee4a..69b3e1865c 100644
-    --- a/file1
-    +++ b/file1
-    @@ -4,6 +4,6 @@
-     4
-     5
-    -L
-     6
-    +R
-     7
-     8
-    "###);
+    // The pattern can be configured
+    test_env.add_config("diff.git.show-function-context = true");
+    test_env.add_config("diff.git.function-pattern = '^\\s+let b'");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=1"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index 65d88f57e4..ad4279f6a6 100644
+    --- a/file
+    +++ b/file
+    @@ -1,3 +1,3 @@
+     fn foo() {
+    -    let a = 1;
+    +    let a = 10;
+         let b = 2;
+    @@ -8,3 +8,3 @@     let b = 2;
+         let x = 1;
+    -    let y = 2;
+    +    let y = 20;
+     }
+    ");
+
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["diff", "--git", "--config=diff.git.function-pattern='('"],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Config error: Invalid type or value for diff.git.function-pattern
+    Caused by: regex parse error:
+        (
+        ^
+    error: unclosed group
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    ");
 }
 
 #[test]
-fn test_diff_external_tool() {
-    let mut test_env = TestEnvironment::default();
+fn test_diff_git_word_highlight() {
+    let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
-
-    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
-    std::fs::write(repo_path.join("file2"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("file"), "let x = 1;\nfoo\nlet y = 2;\n").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::remove_file(repo_path.join("file1")).unwrap();
-    std::fs::write(repo_path.join("file2"), "foo\nbar\n").unwrap();
-    std::fs::write(repo_path.join("file3"), "foo\n").unwrap();
+    std::fs::write(
+        repo_path.join("file"),
+        "let x = 10;\nfoo\nnew\nlet y = 2;\n",
+    )
+    .unwrap();
 
-    let edit_script = test_env.set_up_fake_diff_editor();
+    // Only the changed words of modified lines are colored as removed/added
+    test_env.add_config("diff.git.word-highlight = true");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--color=debug"]);
+    insta::assert_snapshot!(stdout, @r"
+    [1m<<diff file_header::diff --git a/file b/file>>[0m
+    [1m<<diff file_header::index 22477fbf50..5a678f61bf 100644>>[0m
+    [1m<<diff file_header::--- a/file>>[0m
+    [1m<<diff file_header::+++ b/file>>[0m
+    [38;5;6m<<diff hunk_header::@@ -1,3 +1,4 @@>>[39m
+    [38;5;1m<<diff removed::->>[39m<<diff context::let x = >>[4m[38;5;1m<<diff removed token::1>>[24m[39m<<diff context::;>>
+    [38;5;2m<<diff added::+>>[39m<<diff context::let x = >>[4m[38;5;2m<<diff added token::10>>[24m[39m<<diff context::;>>
+    <<diff context:: foo>>
+    [38;5;2m<<diff added::+>>[4m<<diff added token::new>>[24m[39m
+    <<diff context:: let y = 2;>>
+    ");
 
-    // nonzero exit codes should print a warning
-    std::fs::write(&edit_script, "fail").unwrap();
-    let (stdout, stderr) = test_env.jj_cmd_ok(
-        &repo_path,
-        &["diff", "--config=ui.diff.tool=fake-diff-editor"],
-    );
-    let mut insta_settings = insta::Settings::clone_current();
-    insta_settings.add_filter("exit (status|code)", "<exit status>");
-    insta_settings.bind(|| {
-        insta::assert_snapshot!(stdout, @r"");
-        insta::assert_snapshot!(stderr, @r#"
-        Warning: Tool exited with <exit status>: 1 (run with --debug to see the exact invocation)
-        "#);
-    });
+    // The output is the same as without the option once colors are stripped
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index 22477fbf50..5a678f61bf 100644
+    --- a/file
+    +++ b/file
+    @@ -1,3 +1,4 @@
+    -let x = 1;
+    +let x = 10;
+     foo
+    +new
+     let y = 2;
+    ");
+}
 
-    // nonzero exit codes should not print a warning if it's an expected exit code
-    std::fs::write(&edit_script, "fail").unwrap();
-    let (stdout, stderr) = test_env.jj_cmd_ok(
+#[test]
+fn test_diff_git_color_moved() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "moved 1\nmoved 2\n\na\nb\nc\nd\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "a\nB\nc\nd\n\nmoved 1\nmoved 2\n").unwrap();
+
+    // Moved lines are labeled, but blank lines and lines changed in place
+    // aren't
+    let stdout = test_env.jj_cmd_success(
         &repo_path,
         &[
             "diff",
-            "--tool",
-            "fake-diff-editor",
-            "--config=merge-tools.fake-diff-editor.diff-expected-exit-codes=[1]",
+            "--git",
+            "--context=0",
+            "--color=debug",
+            "--color-moved",
         ],
     );
-    insta::assert_snapshot!(stdout, @r"");
-    insta::assert_snapshot!(stderr, @r"");
+    insta::assert_snapshot!(stdout, @r"
+    [1m<<diff file_header::diff --git a/file b/file>>[0m
+    [1m<<diff file_header::index ab08dd4266..9f50cb7ce6 100644>>[0m
+    [1m<<diff file_header::--- a/file>>[0m
+    [1m<<diff file_header::+++ b/file>>[0m
+    [38;5;6m<<diff hunk_header::@@ -1,3 +0,0 @@>>[39m
+    [38;5;5m<<diff moved removed::->>[4m<<diff moved removed token::moved 1>>[24m[39m
+    [38;5;5m<<diff moved removed::->>[4m<<diff moved removed token::moved 2>>[24m[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::>>[24m[39m
+    [38;5;6m<<diff hunk_header::@@ -5,1 +2,1 @@>>[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::b>>[24m<<diff removed::>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::B>>[24m<<diff added::>>[39m
+    [38;5;6m<<diff hunk_header::@@ -7,0 +5,3 @@>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::>>[24m[39m
+    [38;5;6m<<diff moved added::+>>[4m<<diff moved added token::moved 1>>[24m[39m
+    [38;5;6m<<diff moved added::+>>[4m<<diff moved added token::moved 2>>[24m[39m
+    ");
 
-    std::fs::write(
-        &edit_script,
-        "print-files-before\0print --\0print-files-after",
-    )
-    .unwrap();
+    // Moved lines aren't detected by default
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--context=0", "--color=debug"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    [1m<<diff file_header::diff --git a/file b/file>>[0m
+    [1m<<diff file_header::index ab08dd4266..9f50cb7ce6 100644>>[0m
+    [1m<<diff file_header::--- a/file>>[0m
+    [1m<<diff file_header::+++ b/file>>[0m
+    [38;5;6m<<diff hunk_header::@@ -1,3 +0,0 @@>>[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::moved 1>>[24m[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::moved 2>>[24m[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::>>[24m[39m
+    [38;5;6m<<diff hunk_header::@@ -5,1 +2,1 @@>>[39m
+    [38;5;1m<<diff removed::->>[4m<<diff removed token::b>>[24m<<diff removed::>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::B>>[24m<<diff added::>>[39m
+    [38;5;6m<<diff hunk_header::@@ -7,0 +5,3 @@>>[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::>>[24m[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::moved 1>>[24m[39m
+    [38;5;2m<<diff added::+>>[4m<<diff added token::moved 2>>[24m[39m
+    ");
+}
 
-    // diff without file patterns
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &["diff", "--tool=fake-diff-editor"]), @r###"
-    file1
-    file2
-    --
-    file2
-    file3
-    "###);
+#[test]
+fn test_diff_git_full_index() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("deleted"), "deleted\n").unwrap();
+    std::fs::write(repo_path.join("modified"), "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("deleted")).unwrap();
+    std::fs::write(repo_path.join("modified"), "b\n").unwrap();
+    std::fs::write(repo_path.join("added"), "added\n").unwrap();
 
-    // diff with file patterns
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &["diff", "--tool=fake-diff-editor", "file1"]), @r###"
-    file1
-    --
-    "###);
+    // Missing objects are shown as zeros of the same length
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--full-index"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/added b/added
+    new file mode 100644
+    index 0000000000000000000000000000000000000000..d5f7fc3f74f7dec08280f370a975b112e8f60818
+    --- /dev/null
+    +++ b/added
+    @@ -0,0 +1,1 @@
+    +added
+    diff --git a/deleted b/deleted
+    deleted file mode 100644
+    index 71779d2cab258b810b2f567c9a619f6e0105f44e..0000000000000000000000000000000000000000
+    --- a/deleted
+    +++ /dev/null
+    @@ -1,1 +0,0 @@
+    -deleted
+    diff --git a/modified b/modified
+    index 78981922613b2afb6025042ff6bd878ac1994e85..61780798228d17af2d34fce4cfbdf35556832472 100644
+    --- a/modified
+    +++ b/modified
+    @@ -1,1 +1,1 @@
+    -a
+    +b
+    ");
+}
 
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &["log", "-p", "--tool=fake-diff-editor"]), @r###"
-    @  rlvkpnrz test.user@example.com 2001-02-03 08:05:09 39d9055d
-    │  (no description set)
-    │  file1
-    │  file2
-    │  --
-    │  file2
-    │  file3
-    ○  qpvuntsm test.user@example.com 2001-02-03 08:05:08 0ad4ef22
-    │  (no description set)
-    │  --
-    │  file1
-    │  file2
-    ◆  zzzzzzzz root() 00000000
-       --
-    "###);
+#[test]
+fn test_diff_git_minimal_headers() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("deleted"), "deleted\n").unwrap();
+    std::fs::write(repo_path.join("modified"), "a\n").unwrap();
+    std::fs::write(repo_path.join("mode-changed"), "x\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("deleted")).unwrap();
+    std::fs::write(repo_path.join("modified"), "b\n").unwrap();
+    std::fs::write(repo_path.join("added"), "added\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "x", "mode-changed"]);
 
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &["show", "--tool=fake-diff-editor"]), @r#"
-    Commit ID: 39d9055d70873099fd924b9af218289d5663eac8
-    Change ID: rlvkpnrzqnoowoytxnquwvuryrwnrmlp
-    Author   : Test User <test.user@example.com> (2001-02-03 08:05:09)
-    Committer: Test User <test.user@example.com> (2001-02-03 08:05:09)
+    // Only the lines needed to create and delete files are kept, and files
+    // without content changes are omitted
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--minimal-headers"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/added b/added
+    new file mode 100644
+    --- /dev/null
+    +++ b/added
+    @@ -0,0 +1,1 @@
+    +added
+    diff --git a/deleted b/deleted
+    deleted file mode 100644
+    --- a/deleted
+    +++ /dev/null
+    @@ -1,1 +0,0 @@
+    -deleted
+    diff --git a/modified b/modified
+    --- a/modified
+    +++ b/modified
+    @@ -1,1 +1,1 @@
+    -a
+    +b
+    ");
 
-        (no description set)
+    // Creating and deleting empty files is still shown
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("empty"), "").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("empty")).unwrap();
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--minimal-headers", "-r=@-"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/empty b/empty
+    new file mode 100644
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--minimal-headers"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/empty b/empty
+    deleted file mode 100644
+    ");
+}
 
-    file1
-    file2
-    --
-    file2
-    file3
-    "#);
+#[test]
+fn test_diff_git_preview() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "A\nb\nC\nd\nE\nf\nG\nh\nI\nj\n").unwrap();
 
-    // Enabled by default, looks up the merge-tools table
-    let config = "--config=ui.diff.tool=fake-diff-editor";
-    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["diff", config]), @r###"
-    file1
-    file2
-    --
-    file2
-    file3
-    "###);
+    // Only the first and last 3 lines of the diff are shown
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=0", "--preview=3"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index 92dfa21641..100fc49c1f 100644
+    --- a/file
+    +++ b/file
+    @@ -1,1 +1,1 @@
+    -a
+    +A
+    @@ -3,1 +2,0 @@
+    -c
+    ... (large file, preview only)
+    @@ -7,0 +7,1 @@
+    +G
+    @@ -9,1 +9,1 @@
+    -i
+    +I
+    ");
 
-    // Inlined command arguments
-    let command_toml = to_toml_value(fake_diff_editor_path());
-    let config = format!("--config=ui.diff.tool=[{command_toml}, '$right', '$left']");
-    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["diff", &config]), @r###"
-    file2
-    file3
-    --
-    file1
-    file2
-    "###);
+    // Short diffs are shown in full
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--context=0", "--preview=5"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file b/file
+    index 92dfa21641..100fc49c1f 100644
+    --- a/file
+    +++ b/file
+    @@ -1,1 +1,1 @@
+    -a
+    +A
+    @@ -3,1 +3,1 @@
+    -c
+    +C
+    @@ -5,1 +5,1 @@
+    -e
+    +E
+    @@ -7,1 +7,1 @@
+    -g
+    +G
+    @@ -9,1 +9,1 @@
+    -i
+    +I
+    ");
+}
 
-    // Output of external diff tool shouldn't be escaped
-    std::fs::write(&edit_script, "print \x1b[1;31mred").unwrap();
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &["diff", "--color=always", "--tool=fake-diff-editor"]),
-        @r###"
-    [1;31mred
-    "###);
+#[test]
+fn test_diff_metadata_only() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join("run.sh"), "echo\n").unwrap();
+    std::fs::write(repo_path.join("modified"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("deleted"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    test_env.jj_cmd_ok(&repo_path, &["file", "chmod", "x", "run.sh"]);
+    std::fs::write(repo_path.join("modified"), "bar\n").unwrap();
+    std::fs::remove_file(repo_path.join("deleted")).unwrap();
+    std::fs::write(repo_path.join("added"), "baz\n").unwrap();
+
+    // Content-only changes are omitted
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--metadata-only"]);
+    insta::assert_snapshot!(stdout, @r"
+    added: type - -> F, mode 000000 -> 100644
+    deleted: type F -> -, mode 100644 -> 000000
+    run.sh: mode 100644 -> 100755
+    ");
+
+    // Can be combined with a long format
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["diff", "--metadata-only", "--git", "run.sh"]);
+    insta::assert_snapshot!(stdout, @r"
+    run.sh: mode 100644 -> 100755
+    diff --git a/run.sh b/run.sh
+    old mode 100644
+    new mode 100755
+    ");
+}
+
+#[test]
+fn test_diff_git_find_renames() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file1"), "1\n2\n3\n4\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    std::fs::write(repo_path.join("file2"), "1\n2\n3\n5\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file2
+    similarity index 75%
+    rename from file1
+    rename to file2
+    index 94ebaf9001..e0d13b09c8 100644
+    --- a/file1
+    +++ b/file2
+    @@ -1,4 +1,4 @@
+     1
+     2
+     3
+    -4
+    +5
+    ");
+
+    // The rename is shown as a deleted and an added file below the threshold
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--find-renames=80"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file1
+    deleted file mode 100644
+    index 94ebaf9001..0000000000
+    --- a/file1
+    +++ /dev/null
+    @@ -1,4 +0,0 @@
+    -1
+    -2
+    -3
+    -4
+    diff --git a/file2 b/file2
+    new file mode 100644
+    index 0000000000..e0d13b09c8
+    --- /dev/null
+    +++ b/file2
+    @@ -0,0 +1,4 @@
+    +1
+    +2
+    +3
+    +5
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--no-renames"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file1
+    deleted file mode 100644
+    index 94ebaf9001..0000000000
+    --- a/file1
+    +++ /dev/null
+    @@ -1,4 +0,0 @@
+    -1
+    -2
+    -3
+    -4
+    diff --git a/file2 b/file2
+    new file mode 100644
+    index 0000000000..e0d13b09c8
+    --- /dev/null
+    +++ b/file2
+    @@ -0,0 +1,4 @@
+    +1
+    +2
+    +3
+    +5
+    ");
 
-    // Non-zero exit code isn't an error
-    std::fs::write(&edit_script, "print diff\0fail").unwrap();
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["show", "--tool=fake-diff-editor"]);
-    insta::assert_snapshot!(stdout, @r#"
-    Commit ID: 39d9055d70873099fd924b9af218289d5663eac8
-    Change ID: rlvkpnrzqnoowoytxnquwvuryrwnrmlp
-    Author   : Test User <test.user@example.com> (2001-02-03 08:05:09)
-    Committer: Test User <test.user@example.com> (2001-02-03 08:05:09)
+    // Other formats are unaffected
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "--no-renames"]);
+    insta::assert_snapshot!(stdout, @"R {file1 => file2}");
+}
 
-        (no description set)
+#[test]
+fn test_diff_text() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(repo_path.join("file"), b"foo\0\nbar\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), b"foo\0\nbaz\n").unwrap();
 
-    diff
-    "#);
-    insta::assert_snapshot!(stderr.replace("exit code:", "exit status:"), @r###"
-    Warning: Tool exited with exit status: 1 (run with --debug to see the exact invocation)
-    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--stat"]);
+    insta::assert_snapshot!(stdout, @r"
+    file | Bin 9 -> 9 bytes
+    1 file changed, 0 insertions(+), 0 deletions(-)
+    diff --git a/file b/file
+    index c77645686a..600437a38b 100644
+    Binary files a/file and b/file differ
+    ");
 
-    // --tool=:builtin shouldn't be ignored
-    let stderr = test_env.jj_cmd_failure(&repo_path, &["diff", "--tool=:builtin"]);
-    insta::assert_snapshot!(strip_last_line(&stderr), @r###"
-    Error: Failed to generate diff
-    Caused by:
-    1: Error executing ':builtin' (run with --debug to see the exact invocation)
-    "###);
+    // Binary files are diffed as text
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "--stat", "--text"]);
+    insta::assert_snapshot!(stdout.replace('\0', "<NUL>"), @r"
+    file | 2 +-
+    1 file changed, 1 insertion(+), 1 deletion(-)
+    diff --git a/file b/file
+    index c77645686a..600437a38b 100644
+    --- a/file
+    +++ b/file
+    @@ -1,2 +1,2 @@
+     foo<NUL>
+    -bar
+    +baz
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--color-words", "--text"]);
+    insta::assert_snapshot!(stdout.replace('\0', "<NUL>"), @r"
+    Modified regular file file:
+       1    1: foo<NUL>
+       2    2: barbaz
+    ");
 }
 
 #[test]
-fn test_diff_external_file_by_file_tool() {
-    let mut test_env = TestEnvironment::default();
+fn test_diff_ignore_blank_lines() {
+    let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
-
-    std::fs::write(repo_path.join("file1"), "file1\n").unwrap();
-    std::fs::write(repo_path.join("file2"), "file2\n").unwrap();
+    std::fs::write(repo_path.join("file1"), "a\nb\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "a\nb\nc\nd\ne\nf\ng\n").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::remove_file(repo_path.join("file1")).unwrap();
-    std::fs::write(repo_path.join("file2"), "file2\nfile2\n").unwrap();
-    std::fs::write(repo_path.join("file3"), "file3\n").unwrap();
-    std::fs::write(repo_path.join("file4"), "file1\n").unwrap();
+    std::fs::write(repo_path.join("file1"), "a\n\n  \nb\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "a\n\nb\nc\nd\ne\nf\nG\n").unwrap();
 
-    let edit_script = test_env.set_up_fake_diff_editor();
-    std::fs::write(
-        edit_script,
-        "print ==\0print-files-before\0print --\0print-files-after",
-    )
-    .unwrap();
+    // Changes of blank lines are shown only along with other changes
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--ignore-blank-lines", "--context=1"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file1
+    index 422c2b7ab3..a1f186b16c 100644
+    --- a/file1
+    +++ b/file1
+    diff --git a/file2 b/file2
+    index f9d9a0195c..5eb102f38b 100644
+    --- a/file2
+    +++ b/file2
+    @@ -6,2 +7,2 @@
+     f
+    -g
+    +G
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--ignore-blank-lines", "--context=5"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file1 b/file1
+    index 422c2b7ab3..a1f186b16c 100644
+    --- a/file1
+    +++ b/file1
+    diff --git a/file2 b/file2
+    index f9d9a0195c..5eb102f38b 100644
+    --- a/file2
+    +++ b/file2
+    @@ -1,7 +1,8 @@
+     a
+    +
+     b
+     c
+     d
+     e
+     f
+    -g
+    +G
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--color-words",
+            "--ignore-blank-lines",
+            "--context=1",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file1:
+        (only whitespace changes, hidden)
+    Modified regular file file2:
+        ... (6 lines)
+       6    7: f
+       7    8: gG
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--ignore-blank-lines"]);
+    insta::assert_snapshot!(stdout, @r"
+    file1 | 0
+    file2 | 2 +-
+    2 files changed, 1 insertion(+), 1 deletion(-)
+    ");
+}
 
-    // Enabled by default, looks up the merge-tools table
-    let configs: &[_] = &[
-        "--config=ui.diff.tool=fake-diff-editor",
-        "--config=merge-tools.fake-diff-editor.diff-invocation-mode=file-by-file",
-    ];
+#[test]
+fn test_diff_exclude() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"ui.diff.exclude = ["Cargo.lock", "glob:**/*.pb.go"]"#);
 
-    // diff without file patterns
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &[&["diff"], configs].concat()), @r###"
-    ==
-    file2
-    --
-    file2
-    ==
-    file3
-    --
-    file3
-    ==
-    file1
-    --
-    file4
-    "###);
+    std::fs::create_dir(repo_path.join("src")).unwrap();
+    std::fs::write(repo_path.join("Cargo.lock"), "lock\n").unwrap();
+    std::fs::write(repo_path.join("src").join("api.pb.go"), "generated\n").unwrap();
+    std::fs::write(repo_path.join("src").join("main.go"), "main\n").unwrap();
 
-    // diff with file patterns
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &[&["diff", "file1"], configs].concat()), @r###"
-    ==
-    file1
-    --
-    file1
-    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @"A src/main.go");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["show", "--summary", "-T", "''"]);
+    insta::assert_snapshot!(stdout, @"A src/main.go");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["log", "--summary", "-r", "@", "--no-graph", "-T", "''"],
+    );
+    insta::assert_snapshot!(stdout, @"A src/main.go");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["evolog", "--summary", "-r", "@", "--no-graph", "-T", "''"],
+    );
+    insta::assert_snapshot!(stdout, @"A src/main.go");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["interdiff", "--summary", "--from", "root()", "--to", "@"],
+    );
+    insta::assert_snapshot!(stdout, @"A src/main.go");
 
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &[&["log", "-p"], configs].concat()), @r###"
-    @  rlvkpnrz test.user@example.com 2001-02-03 08:05:09 7b01704a
-    │  (no description set)
-    │  ==
-    │  file2
-    │  --
-    │  file2
-    │  ==
-    │  file3
-    │  --
-    │  file3
-    │  ==
-    │  file1
-    │  --
-    │  file4
-    ○  qpvuntsm test.user@example.com 2001-02-03 08:05:08 6e485984
-    │  (no description set)
-    │  ==
-    │  file1
-    │  --
-    │  file1
-    │  ==
-    │  file2
-    │  --
-    │  file2
-    ◆  zzzzzzzz root() 00000000
-    "###);
+    // Excluded files aren't shown even if requested explicitly
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "Cargo.lock"]);
+    insta::assert_snapshot!(stdout, @"");
 
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &[&["show"], configs].concat()), @r#"
-    Commit ID: 7b01704a670bc77d11ed117d362855cff1d4513b
-    Change ID: rlvkpnrzqnoowoytxnquwvuryrwnrmlp
-    Author   : Test User <test.user@example.com> (2001-02-03 08:05:09)
-    Committer: Test User <test.user@example.com> (2001-02-03 08:05:09)
+    // Exclusion can be disabled for one invocation
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "--no-exclude"]);
+    insta::assert_snapshot!(stdout, @r"
+    A Cargo.lock
+    A src/api.pb.go
+    A src/main.go
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["show", "--summary", "-T", "''", "--no-exclude"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    A Cargo.lock
+    A src/api.pb.go
+    A src/main.go
+    ");
 
-        (no description set)
+    // Invalid fileset
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["diff", "--config=ui.diff.exclude=['foo(']"]);
+    insta::assert_snapshot!(stderr, @r"
+    Error: Failed to parse fileset: Syntax error
+    Caused by:  --> 1:5
+      |
+    1 | foo(
+      |     ^---
+      |
+      = expected <expression>
+    Hint: See https://jj-vcs.github.io/jj/latest/filesets/ for filesets syntax, or for how to match file paths.
+    ");
+}
 
-    ==
-    file2
-    --
-    file2
-    ==
-    file3
-    --
-    file3
-    ==
-    file1
-    --
-    file4
-    "#);
+#[test]
+fn test_diff_contents() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::write(test_env.env_root().join("left.txt"), "foo\nbar\n").unwrap();
+    std::fs::write(test_env.env_root().join("right.txt"), "foo\nbaz\n").unwrap();
+    std::fs::write(test_env.env_root().join("binary"), b"\0binary").unwrap();
+
+    // Files outside of the workspace
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--file", "../left.txt", "../right.txt"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/../left.txt b/../right.txt
+    --- a/../left.txt
+    +++ b/../right.txt
+    @@ -1,2 +1,2 @@
+     foo
+    -bar
+    +baz
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--color-words",
+            "--file",
+            "../left.txt",
+            "../right.txt",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file ../right.txt (../left.txt => ../right.txt):
+       1    1: foo
+       2    2: barbaz
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--file", "../left.txt", "../binary"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/../left.txt b/../binary
+    Binary files a/../left.txt and b/../binary differ
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--git",
+            "--text",
+            "--file",
+            "../left.txt",
+            "../binary",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/../left.txt b/../binary
+    --- a/../left.txt
+    +++ b/../binary
+    @@ -1,2 +1,1 @@
+    -foo
+    -bar
+    + binary
+    \ No newline at end of file
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--color-words",
+            "--text",
+            "--file",
+            "../left.txt",
+            "../binary",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file ../binary (../left.txt => ../binary):
+       1     : foo
+       2     : bar
+            1:  binary
+        (newline at end of file removed)
+    ");
+    let absolute_path = test_env.env_root().join("right.txt");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--git",
+            "--file",
+            "../left.txt",
+            absolute_path.to_str().unwrap(),
+        ],
+    );
+    // Like `git diff --no-index`, the absolute path isn't prefixed with "b//"
+    // ($TEST_ENV includes the leading "/")
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/../left.txt b$TEST_ENV/right.txt
+    --- a/../left.txt
+    +++ b$TEST_ENV/right.txt
+    @@ -1,2 +1,2 @@
+     foo
+    -bar
+    +baz
+    ");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--file", "../left.txt", "../left.txt"],
+    );
+    insta::assert_snapshot!(stdout, @"");
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["diff", "--file", "../left.txt", "../missing"]);
+    insta::assert_snapshot!(stderr.lines().next().unwrap(), @"Error: Failed to read ../missing");
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["diff", "--stat", "--file", "../left.txt", "../right.txt"],
+    );
+    insta::assert_snapshot!(stderr, @"Error: Only the git and color-words formats can be used to compare contents directly");
+
+    // Blobs by id
+    std::fs::write(repo_path.join("file"), "foo\nbar\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "foo\nbaz\n").unwrap();
+    let file_id = |rev: &str| {
+        let stdout = test_env.jj_cmd_success(&repo_path, &["debug", "tree", "-r", rev]);
+        let (_, rest) = stdout.split_once("FileId(\"").unwrap();
+        rest[..40].to_owned()
+    };
+    let (left_id, right_id) = (file_id("@-"), file_id("@"));
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "--blob", &left_id, &right_id],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/3bd1f0e29744a1f32b08d5650e62e2e62afb177c b/0c071e1d07528f124e31f1b6c71348ec13f21a7a
+    --- a/3bd1f0e29744a1f32b08d5650e62e2e62afb177c
+    +++ b/0c071e1d07528f124e31f1b6c71348ec13f21a7a
+    @@ -1,2 +1,2 @@
+     foo
+    -bar
+    +baz
+    ");
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["diff", "--blob", "xyz", &right_id]);
+    insta::assert_snapshot!(stderr, @"Error: Invalid blob id: xyz");
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["diff", "--blob", &"0".repeat(40), &right_id]);
+    insta::assert_snapshot!(stderr.lines().next().unwrap(), @"Error: Failed to read blob 0000000000000000000000000000000000000000");
 }
 
-#[cfg(unix)]
 #[test]
-fn test_diff_external_tool_symlink() {
-    let mut test_env = TestEnvironment::default();
+fn test_diff_multiple_revisions() {
+    let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
-
-    let external_file_path = test_env.env_root().join("external-file");
-    std::fs::write(&external_file_path, "").unwrap();
-    let external_file_permissions = external_file_path.symlink_metadata().unwrap().permissions();
-
-    std::os::unix::fs::symlink("non-existent1", repo_path.join("dead")).unwrap();
-    std::os::unix::fs::symlink(&external_file_path, repo_path.join("file")).unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::remove_file(repo_path.join("dead")).unwrap();
-    std::os::unix::fs::symlink("non-existent2", repo_path.join("dead")).unwrap();
+    std::fs::write(repo_path.join("file"), "a\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m=first"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=second"]);
+    std::fs::write(repo_path.join("file"), "b\n").unwrap();
+    std::fs::write(repo_path.join("other"), "other\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m=third"]);
     std::fs::remove_file(repo_path.join("file")).unwrap();
-    std::fs::write(repo_path.join("file"), "").unwrap();
-
-    let edit_script = test_env.set_up_fake_diff_editor();
-    std::fs::write(
-        edit_script,
-        "print-files-before\0print --\0print-files-after",
-    )
-    .unwrap();
 
-    // Shouldn't try to change permission of symlinks
-    insta::assert_snapshot!(
-        test_env.jj_cmd_success(&repo_path, &["diff", "--tool=fake-diff-editor"]), @r###"
-    dead
-    file
-    --
-    dead
-    file
-    "###);
+    // Changes from each revision to the next
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "diff",
+            "--summary",
+            "-r=description(first)",
+            "-r=@-",
+            "-r=@",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    From: qpvuntsm 2d4fa21a first
+    To:   kkmpptxz 54bc1988 second
+    M file
+    A other
+
+    From: kkmpptxz 54bc1988 second
+    To:   zsuskuln 9649238e third
+    D file
+    ");
 
-    // External file should be intact
-    assert_eq!(
-        external_file_path.symlink_metadata().unwrap().permissions(),
-        external_file_permissions
+    // The revisions don't have to be in order
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["diff", "--git", "-r=@", "-r=description(first)", "file"],
     );
+    insta::assert_snapshot!(stdout, @r"
+    From: zsuskuln 9649238e third
+    To:   qpvuntsm 2d4fa21a first
+    diff --git a/file b/file
+    new file mode 100644
+    index 0000000000..7898192261
+    --- /dev/null
+    +++ b/file
+    @@ -0,0 +1,1 @@
+    +a
+    ");
+
+    // A single revision is compared to its parents
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "-r=@-"]);
+    insta::assert_snapshot!(stdout, @r"
+    M file
+    A other
+    ");
+
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["diff", "--header", "-r=@-", "-r=@"]);
+    insta::assert_snapshot!(stderr, @"Error: --header can only be used when showing a single revision");
 }
 
 #[test]
-fn test_diff_external_tool_conflict_marker_style() {
-    let mut test_env = TestEnvironment::default();
+fn test_diff_fixable() {
+    let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
-    let file_path = repo_path.join("file");
+    std::fs::write(repo_path.join("a.rs"), "a\n").unwrap();
+    std::fs::write(repo_path.join("b.txt"), "b\n").unwrap();
+    std::fs::write(repo_path.join("c.md"), "c\n").unwrap();
+    std::fs::write(repo_path.join("d"), "d\n").unwrap();
+
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r#"
+        [fix.tools.upper]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["glob:*.rs"]
+
+        [fix.tools.lower]
+        command = [{formatter}, "--lowercase"]
+        patterns = ["glob:*.txt"]
+
+        [fix.tools.disabled]
+        command = [{formatter}, "--reverse"]
+        patterns = ["c.md"]
+        enabled = false
+        "#
+    ));
+
+    // Files matching any enabled tool
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "--fixable"]);
+    insta::assert_snapshot!(stdout, @r"
+    A a.rs
+    A b.txt
+    ");
 
-    // Create a conflict
-    std::fs::write(
-        &file_path,
-        indoc! {"
-        line 1
-        line 2
-        line 3
-        line 4
-        line 5
-    "},
-    )
-    .unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["commit", "-m", "base"]);
-    std::fs::write(
-        &file_path,
-        indoc! {"
-        line 1
-        line 2.1
-        line 2.2
-        line 3
-        line 4.1
-        line 5
-    "},
-    )
-    .unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "side-a"]);
-    test_env.jj_cmd_ok(&repo_path, &["new", "description(base)", "-m", "side-b"]);
-    std::fs::write(
-        &file_path,
-        indoc! {"
-        line 1
-        line 2.3
-        line 3
-        line 4.2
-        line 4.3
-        line 5
-    "},
-    )
-    .unwrap();
+    // Files matching the named tool
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary", "--fixable=lower"]);
+    insta::assert_snapshot!(stdout, @"A b.txt");
 
-    // Resolve one of the conflicts in the working copy
-    test_env.jj_cmd_ok(
+    // Combined with paths
+    let stdout = test_env.jj_cmd_success(
         &repo_path,
-        &["new", "description(side-a)", "description(side-b)"],
+        &["diff", "--summary", "--fixable", "a.rs", "c.md"],
     );
-    std::fs::write(
-        &file_path,
-        indoc! {"
-        line 1
-        line 2.1
-        line 2.2
-        line 2.3
-        line 3
-        <<<<<<<
-        %%%%%%%
-        -line 4
-        +line 4.1
-        +++++++
-        line 4.2
-        line 4.3
-        >>>>>>>
-        line 5
-    "},
-    )
-    .unwrap();
-
-    // Set up diff editor to use "snapshot" conflict markers
-    let edit_script = test_env.set_up_fake_diff_editor();
-    test_env.add_config(r#"merge-tools.fake-diff-editor.conflict-marker-style = "snapshot""#);
+    insta::assert_snapshot!(stdout, @"A a.rs");
 
-    // We want to see whether the diff is using the correct conflict markers
-    std::fs::write(
-        &edit_script,
-        ["files-before file", "files-after file", "dump file file"].join("\0"),
-    )
-    .unwrap();
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["diff", "--tool", "fake-diff-editor"]);
-    insta::assert_snapshot!(stdout, @"");
-    insta::assert_snapshot!(stderr, @"");
-    // Conflicts should render using "snapshot" format
-    insta::assert_snapshot!(
-        std::fs::read_to_string(test_env.env_root().join("file")).unwrap(), @r##"
-    line 1
-    line 2.1
-    line 2.2
-    line 2.3
-    line 3
-    <<<<<<< Conflict 1 of 1
-    +++++++ Contents of side #1
-    line 4.1
-    ------- Contents of base
-    line 4
-    +++++++ Contents of side #2
-    line 4.2
-    line 4.3
-    >>>>>>> Conflict 1 of 1 ends
-    line 5
-    "##);
+    // Unknown or disabled tool
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["diff", "--fixable=nonexistent"]);
+    insta::assert_snapshot!(stderr, @r"
+    Error: No enabled tool named `nonexistent` in `fix.tools`
+    Hint: The enabled tools are: lower, upper
+    ");
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["diff", "--fixable=disabled"]);
+    insta::assert_snapshot!(stderr, @r"
+    Error: No enabled tool named `disabled` in `fix.tools`
+    Hint: The enabled tools are: lower, upper
+    ");
 }
 
 #[test]
-fn test_diff_stat() {
+fn test_diff_stat_fraction() {
     let test_env = TestEnvironment::default();
     test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
     let repo_path = test_env.env_root().join("repo");
-    std::fs::write(repo_path.join("file1"), "foo\n").unwrap();
-
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
-    insta::assert_snapshot!(stdout, @r###"
-    file1 | 1 +
-    1 file changed, 1 insertion(+), 0 deletions(-)
-    "###);
-
+    std::fs::write(
+        repo_path.join("modified"),
+        "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n",
+    )
+    .unwrap();
+    std::fs::write(repo_path.join("deleted"), "a\nb\nc\n").unwrap();
+    std::fs::write(repo_path.join("unchanged"), "a\n").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(
+        repo_path.join("modified"),
+        "1\n2\n3\n4\n5\n6\n7\n8\nnine\n10\n",
+    )
+    .unwrap();
+    std::fs::remove_file(repo_path.join("deleted")).unwrap();
+    std::fs::write(repo_path.join("added"), "x\ny\n").unwrap();
+    std::fs::write(repo_path.join("binary"), b"\0").unwrap();
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
-    insta::assert_snapshot!(stdout, @"0 files changed, 0 insertions(+), 0 deletions(-)");
+    // The line modified in place is 10% of the file
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--fraction"]);
+    insta::assert_snapshot!(stdout, @r"
+    added    | 2 (100% of file) ++
+    binary   | Bin 0 -> 1 bytes
+    deleted  | 3 (100% of file) ---
+    modified | 2 ( 10% of file) +-
+    4 files changed, 3 insertions(+), 4 deletions(-)
+    ");
 
-    std::fs::write(repo_path.join("file1"), "foo\nbar\n").unwrap();
-    test_env.jj_cmd_ok(&repo_path, &["new"]);
-    std::fs::write(repo_path.join("file1"), "bar\n").unwrap();
+    // Requires --stat
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--fraction"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the following required arguments were not provided:
+      --stat
 
-    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
-    insta::assert_snapshot!(stdout, @r###"
-    file1 | 1 -
-    1 file changed, 0 insertions(+), 1 deletion(-)
-    "###);
+    Usage: jj diff --stat --fraction [FILESETS]...
+
+    For more information, try '--help'.
+    ");
 }
 
 #[test]
@@ -2511,13 +5600,13 @@ fn test_diff_stat_long_name_or_stat() {
     2 files changed, 2 insertions(+), 0 deletions(-)
     "###);
     insta::assert_snapshot!(get_stat(&test_env, 10, 10), @r###"
-    1234567890     | 10 +++++++
-    ...六七八九十  | 10 +++++++
+    1234567890    | 10 ++++++++
+    ...六七八九十 | 10 ++++++++
     2 files changed, 20 insertions(+), 0 deletions(-)
     "###);
     insta::assert_snapshot!(get_stat(&test_env, 10, 100), @r###"
-    1234567890     | 100 ++++++
-    ...六七八九十  | 100 ++++++
+    1234567890    | 100 ++++++++
+    ...六七八九十 | 100 ++++++++
     2 files changed, 200 insertions(+), 0 deletions(-)
     "###);
     insta::assert_snapshot!(get_stat(&test_env, 50, 1), @r###"
@@ -2538,8 +5627,8 @@ fn test_diff_stat_long_name_or_stat() {
 
     // Lengths around where we introduce the ellipsis
     insta::assert_snapshot!(get_stat(&test_env, 13, 100), @r###"
-    1234567890123  | 100 ++++++
-    ...九十一二三  | 100 ++++++
+    1234567890123 | 100 ++++++++
+    ...九十一二三 | 100 ++++++++
     2 files changed, 200 insertions(+), 0 deletions(-)
     "###);
     insta::assert_snapshot!(get_stat(&test_env, 14, 100), @r###"
@@ -2583,6 +5672,74 @@ fn test_diff_stat_long_name_or_stat() {
     "###);
 }
 
+#[test]
+fn test_diff_stat_width() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir/file"), "line\n".repeat(100)).unwrap();
+
+    // Very narrow widths still leave room for the path and the bar
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--stat-width=2"]);
+    insta::assert_snapshot!(stdout, @r"
+    ... | 100 ++
+    1 file changed, 100 insertions(+), 0 deletions(-)
+    ");
+
+    // Very wide widths show the bar unscaled
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--stat-width=200"]);
+    insta::assert_snapshot!(stdout, @r"
+    dir/file | 100 ++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+    1 file changed, 100 insertions(+), 0 deletions(-)
+    ");
+
+    // The bar can be limited to a maximum length
+    test_env.add_config("diff.stat.graph-width = 10");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--stat-width=200"]);
+    insta::assert_snapshot!(stdout, @r"
+    dir/file | 100 ++++++++++
+    1 file changed, 100 insertions(+), 0 deletions(-)
+    ");
+
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["diff", "--stat-width=10"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the following required arguments were not provided:
+      --stat
+
+    Usage: jj diff --stat --stat-width <N> [FILESETS]...
+
+    For more information, try '--help'.
+    ");
+}
+
+#[test]
+fn test_diff_stat_long_path() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let dir = repo_path.join("first").join("second").join("third");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("file.rs"), "line\n".repeat(3)).unwrap();
+    std::fs::write(repo_path.join("top.rs"), "line\n").unwrap();
+
+    // Long paths are cut at a path separator, keeping the last components
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--stat-width=40"]);
+    insta::assert_snapshot!(stdout.replace('\\', "/"), @r"
+    .../third/file.rs | 3 +++
+    top.rs            | 1 +
+    2 files changed, 4 insertions(+), 0 deletions(-)
+    ");
+
+    // The last component is cut if it doesn't fit by itself
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat", "--stat-width=20"]);
+    insta::assert_snapshot!(stdout.replace('\\', "/"), @r"
+    ...le.rs | 3 +++
+    top.rs   | 1 +
+    2 files changed, 4 insertions(+), 0 deletions(-)
+    ");
+}
+
 #[test]
 fn test_diff_binary() {
     let test_env = TestEnvironment::default();
@@ -2631,10 +5788,38 @@ fn test_diff_binary() {
 
     let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
     insta::assert_snapshot!(stdout, @r###"
-    file1.png | 3 ---
-    file2.png | 5 ++---
-    file3.png | 3 +++
-    file4.png | 1 +
-    4 files changed, 6 insertions(+), 6 deletions(-)
+    file1.png | Bin 16 -> 0 bytes
+    file2.png | Bin 16 -> 8 bytes
+    file3.png | Bin 0 -> 12 bytes
+    file4.png | Bin 0 -> 3 bytes
+    4 files changed, 0 insertions(+), 0 deletions(-)
     "###);
+
+    // Binary content larger than the peek size should be measured in full
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file3.png"), b"\0".repeat(10000)).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--stat"]);
+    insta::assert_snapshot!(stdout, @r"
+    file3.png | Bin 12 -> 10000 bytes
+    1 file changed, 0 insertions(+), 0 deletions(-)
+    ");
+
+    // Binary files that differ only past the peek size are still different
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(
+        repo_path.join("file3.png"),
+        [b"\0".repeat(10000), b"tail".to_vec()].concat(),
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r"
+    Modified regular file file3.png:
+        (binary)
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/file3.png b/file3.png
+    index e64c723ad5..5047830750 100644
+    Binary files a/file3.png and b/file3.png differ
+    ");
 }
@@ -256,6 +256,59 @@ fn test_config_tables_overlapping_patterns() {
     "###);
 }
 
+#[test]
+fn test_config_tools_priority() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.tool-1]
+        command = [{formatter}, "--append", "tool-1"]
+        patterns = ["foo"]
+        priority = 1
+
+        [fix.tools.tool-2]
+        command = [{formatter}, "--append", "tool-2"]
+        patterns = ["foo"]
+
+        [fix.tools.tool-3]
+        command = [{formatter}, "--append", "tool-3"]
+        patterns = ["foo"]
+        priority = -1
+        "###,
+    ));
+
+    std::fs::write(repo_path.join("foo"), "foo\n").unwrap();
+
+    // Tools with lower priority run first, and the default priority is 0
+    let (_stdout, _stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "foo", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    foo
+    tool-3
+    tool-2
+    tool-1
+    ");
+
+    // Tools with the same priority run in the order of their names
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("foo"), "foo\n").unwrap();
+    let (_stdout, _stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["fix", "--config=fix.tools.tool-3.priority=0"]);
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "foo", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    foo
+    tool-2
+    tool-3
+    tool-1
+    ");
+}
+
 #[test]
 fn test_config_tables_all_commands_missing() {
     let test_env = TestEnvironment::default();
@@ -341,9 +394,10 @@ fn test_config_tables_empty_patterns_list() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
-      Fixed 0 commits of 1 checked.
-      Nothing changed.
-      "###);
+    Fixed 0 commits of 1 checked.
+    Fixed 0 files of 0 checked, skipped 0.
+    Nothing changed.
+    "###);
 
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "foo", "-r", "@"]);
     insta::assert_snapshot!(content, @"foo\n");
@@ -387,6 +441,259 @@ fn test_config_filesets() {
     insta::assert_snapshot!(content, @"2b\n");
 }
 
+#[test]
+fn test_config_repo_tools_file() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.tool-1]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["foo", "bar"]
+
+        [fix.tools.tool-2]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["baz"]
+        "###,
+    ));
+    // Fields of tracked tools override the user's fields of the same tool, and
+    // the other fields are kept.
+    std::fs::write(
+        repo_path.join(".jj-fix-tools.toml"),
+        format!(
+            r###"
+            [tool-1]
+            command = [{formatter}, "--lowercase"]
+
+            [tool-3]
+            command = [{formatter}, "--uppercase"]
+            patterns = ["qux"]
+
+            [tool-4]
+            command = [{formatter}, "--uppercase"]
+            patterns = ["quux"]
+            "###,
+        ),
+    )
+    .unwrap();
+
+    std::fs::write(repo_path.join("foo"), "Foo\n").unwrap();
+    std::fs::write(repo_path.join("bar"), "Bar\n").unwrap();
+    std::fs::write(repo_path.join("baz"), "Baz\n").unwrap();
+    std::fs::write(repo_path.join("qux"), "Qux\n").unwrap();
+    std::fs::write(repo_path.join("quux"), "Quux\n").unwrap();
+
+    // The file is ignored unless the user trusts it
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r"
+    Hint: Ignoring the tools in `.jj-fix-tools.toml`. Set `fix.trust-repo-tools = true` to run them.
+    Fixed 1 commits of 1 checked.
+    Fixed 3 files of 3 checked, skipped 0.
+    Files changed by each tool: tool-1: 2 files, tool-2: 1 files
+    Working copy now at: qpvuntsm f1a245ef (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 3 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "foo", "-r", "@"]);
+    insta::assert_snapshot!(content, @"FOO");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "qux", "-r", "@"]);
+    insta::assert_snapshot!(content, @"Qux");
+
+    test_env.add_config(
+        r#"
+        fix.trust-repo-tools = true
+        fix.tools.tool-4.enabled = false
+        "#,
+    );
+    let (_stdout, _stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "foo", "-r", "@"]);
+    insta::assert_snapshot!(content, @"foo");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "bar", "-r", "@"]);
+    insta::assert_snapshot!(content, @"bar");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "baz", "-r", "@"]);
+    insta::assert_snapshot!(content, @"BAZ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "qux", "-r", "@"]);
+    insta::assert_snapshot!(content, @"QUX");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "quux", "-r", "@"]);
+    insta::assert_snapshot!(content, @"Quux");
+
+    // The file is read from the working-copy commit, not the workspace's .jj
+    // directory.
+    let output = test_env.jj_cmd_success(&repo_path, &["file", "list"]);
+    assert!(output.contains(".jj-fix-tools.toml"));
+
+    // Invalid tool definitions are reported with the file path.
+    std::fs::write(
+        repo_path.join(".jj-fix-tools.toml"),
+        "[tool-0]\npatterns = ['foo']\n",
+    )
+    .unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r"
+    Config error: Invalid type or value for fix.tools.tool-0
+    Caused by: missing field `command`
+
+    Hint: Check the config file: $TEST_ENV/repo/.jj-fix-tools.toml
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    ");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_shell_command() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = format!("'{}'", formatter_path.to_str().unwrap());
+    let shell = format!("{formatter} --uppercase | {formatter} --tee $path-copy");
+    test_env.add_config(formatdoc! {"
+        [fix.tools.shell-tool]
+        command = {{ shell = {shell} }}
+        patterns = ['all()']
+        ",
+        shell = to_toml_value(shell),
+    });
+
+    std::fs::write(repo_path.join("foo bar"), "Foo\n").unwrap();
+    let (_stdout, _stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "foo bar", "-r", "@"]);
+    insta::assert_snapshot!(content, @"FOO");
+    // $path is passed to the tool as a single argument
+    let copy_content = std::fs::read_to_string(repo_path.join("foo bar-copy")).unwrap();
+    insta::assert_snapshot!(copy_content, @"FOO");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_config_shell_command_with_variables() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(
+        r#"
+        [fix.tools.shell-tool]
+        command = { shell = "type $path" }
+        patterns = ['all()']
+        "#,
+    );
+    std::fs::write(repo_path.join("foo"), "Foo\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r"
+    Config error: In `fix.tools.shell-tool`: Variables can't be used in shell scripts on Windows
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    ");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_max_concurrency() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = format!("'{}'", formatter_path.to_str().unwrap());
+    // The tool fails if another invocation holds the lock
+    let shell =
+        format!("mkdir ../lock || exit 1; sleep 0.01; {formatter} --uppercase; rmdir ../lock");
+    test_env.add_config(formatdoc! {"
+        [fix.tools.locking-tool]
+        command = {{ shell = {shell} }}
+        patterns = ['all()']
+        max-concurrency = 1
+        ",
+        shell = to_toml_value(shell),
+    });
+
+    for i in 0..10 {
+        std::fs::write(repo_path.join(format!("file{i}")), "foo\n").unwrap();
+    }
+    let (_stdout, _stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    for i in 0..10 {
+        let content = test_env.jj_cmd_success(
+            &repo_path,
+            &["file", "show", &format!("file{i}"), "-r", "@"],
+        );
+        assert_eq!(content, "FOO\n");
+    }
+
+    // The limit must be positive
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["fix", "--config=fix.tools.locking-tool.max-concurrency=0"],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Config error: Invalid type or value for fix.tools.locking-tool
+    Caused by: invalid value: integer `0`, expected a nonzero usize
+    in `max-concurrency`
+
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    ");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_max_concurrency_with_unlimited_tool() {
+    let mut test_env = TestEnvironment::default();
+    // Few enough threads that they would all wait for the limited tool if
+    // waiting blocked them
+    test_env.add_env_var("RAYON_NUM_THREADS", "2");
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = format!("'{}'", formatter_path.to_str().unwrap());
+    // The limited tool only succeeds once the unlimited tool has run
+    let limited_shell = format!(
+        "for i in $(seq 100); do [ -e ../marker ] && break; sleep 0.1; done; [ -e ../marker ] \
+         || exit 1; {formatter} --uppercase"
+    );
+    let unlimited_shell = format!("touch ../marker; {formatter} --lowercase");
+    test_env.add_config(formatdoc! {"
+        [fix.tools.limited-tool]
+        command = {{ shell = {limited_shell} }}
+        patterns = ['glob:\"limited*\"']
+        max-concurrency = 1
+
+        [fix.tools.unlimited-tool]
+        command = {{ shell = {unlimited_shell} }}
+        patterns = ['unlimited']
+        ",
+        limited_shell = to_toml_value(limited_shell),
+        unlimited_shell = to_toml_value(unlimited_shell),
+    });
+
+    for i in 0..8 {
+        std::fs::write(repo_path.join(format!("limited{i}")), "Foo\n").unwrap();
+    }
+    std::fs::write(repo_path.join("unlimited"), "Foo\n").unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 9 files of 9 checked, skipped 0.
+    Files changed by each tool: limited-tool: 8 files, unlimited-tool: 1 files
+    Working copy now at: qpvuntsm 88046a1c (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 9 files, removed 0 files
+    ");
+    for i in 0..8 {
+        let content = test_env.jj_cmd_success(
+            &repo_path,
+            &["file", "show", &format!("limited{i}"), "-r", "@"],
+        );
+        assert_eq!(content, "FOO\n");
+    }
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "unlimited", "-r", "@"]);
+    assert_eq!(content, "foo\n");
+}
+
 #[test]
 fn test_relative_paths() {
     let test_env = TestEnvironment::default();
@@ -446,6 +753,7 @@ fn test_fix_empty_commit() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 0 commits of 1 checked.
+    Fixed 0 files of 0 checked, skipped 0.
     Nothing changed.
     ");
 }
@@ -461,6 +769,8 @@ fn test_fix_leaf_commit() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
     Working copy now at: rlvkpnrz 85ce8924 (no description set)
     Parent commit      : qpvuntsm b2ca2bc5 (no description set)
     Added 0 files, modified 1 files, removed 0 files
@@ -488,6 +798,8 @@ fn test_fix_parent_commit() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 3 commits of 3 checked.
+    Fixed 3 files of 3 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 3 files
     Working copy now at: mzvwutvl d30c8ae2 child2 | (no description set)
     Parent commit      : qpvuntsm 70a4dae2 parent | (no description set)
     Added 0 files, modified 1 files, removed 0 files
@@ -514,7 +826,11 @@ fn test_fix_sibling_commit() {
 
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "child1"]);
     insta::assert_snapshot!(stdout, @"");
-    insta::assert_snapshot!(stderr, @"Fixed 1 commits of 1 checked.");
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    ");
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "parent"]);
     insta::assert_snapshot!(content, @"parent");
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "child1"]);
@@ -553,6 +869,8 @@ fn test_default_revset() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 3 commits of 3 checked.
+    Fixed 3 files of 3 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 3 files
     Working copy now at: yostqsxw dabc47b2 bar2 | (no description set)
     Parent commit      : yqosqzyt 984b5924 bar1 | (no description set)
     Added 0 files, modified 1 files, removed 0 files
@@ -588,7 +906,11 @@ fn test_custom_default_revset() {
 
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
     insta::assert_snapshot!(stdout, @"");
-    insta::assert_snapshot!(stderr, @"Fixed 1 commits of 1 checked.");
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    ");
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "foo"]);
     insta::assert_snapshot!(content, @"foo");
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "bar"]);
@@ -626,6 +948,7 @@ fn test_fix_empty_file() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 0 commits of 1 checked.
+    Fixed 0 files of 1 checked, skipped 0.
     Nothing changed.
     ");
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
@@ -633,63 +956,336 @@ fn test_fix_empty_file() {
 }
 
 #[test]
-fn test_fix_some_paths() {
+fn test_fix_max_file_size() {
     let (test_env, repo_path) = init_with_fake_formatter(&["--uppercase"]);
-    std::fs::write(repo_path.join("file1"), "foo").unwrap();
-    std::fs::write(repo_path.join("file2"), "bar").unwrap();
+    std::fs::write(repo_path.join("small"), "small\n").unwrap();
+    std::fs::write(repo_path.join("large"), "large file\n").unwrap();
 
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@", "file1"]);
+    test_env.add_config("fix.max-file-size = 6");
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
+    Warning: Skipping large: File is larger than 6.0B
     Fixed 1 commits of 1 checked.
-    Working copy now at: qpvuntsm 54a90d2b (no description set)
+    Fixed 1 files of 2 checked, skipped 1.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: qpvuntsm d52c24da (no description set)
     Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
     Added 0 files, modified 1 files, removed 0 files
     ");
-    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file1"]);
-    insta::assert_snapshot!(content, @r###"
-    FOO
-    "###);
-    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file2"]);
-    insta::assert_snapshot!(content, @"bar");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "small", "-r", "@"]);
+    insta::assert_snapshot!(content, @"SMALL");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "large", "-r", "@"]);
+    insta::assert_snapshot!(content, @"large file");
+
+    // The flag overrides the config, and 0 means no limit
+    let (_stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@", "--max-file-size=0"]);
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 2 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: qpvuntsm bf674c27 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "large", "-r", "@"]);
+    insta::assert_snapshot!(content, @"LARGE FILE");
 }
 
 #[test]
-fn test_fix_cyclic() {
-    let (test_env, repo_path) = init_with_fake_formatter(&["--reverse"]);
-    std::fs::write(repo_path.join("file"), "content\n").unwrap();
+fn test_fix_empty_output() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.tool-1]
+        command = [{formatter}, "--stdout="]
+        patterns = ["foo", "empty"]
 
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+        [fix.tools.tool-2]
+        command = [{formatter}, "--append=appended"]
+        patterns = ["all()"]
+        "###,
+    ));
+    std::fs::write(repo_path.join("foo"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("bar"), "bar\n").unwrap();
+    std::fs::write(repo_path.join("empty"), "").unwrap();
+
+    // The file emptied by the first tool isn't passed to the second tool, and
+    // is left unchanged. An already empty file isn't suspicious.
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
+    Warning: Skipping foo: Tool `tool-1` produced empty output
     Fixed 1 commits of 1 checked.
-    Working copy now at: qpvuntsm bf5e6a5a (no description set)
+    Fixed 2 files of 3 checked, skipped 1.
+    Files changed by each tool: tool-2: 2 files
+    Working copy now at: qpvuntsm d783c467 (no description set)
     Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
-    Added 0 files, modified 1 files, removed 0 files
+    Added 0 files, modified 2 files, removed 0 files
     ");
-    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
-    insta::assert_snapshot!(content, @"tnetnoc\n");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "foo", "-r", "@"]);
+    insta::assert_snapshot!(content, @"foo");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "bar", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    bar
+    appended
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "empty", "-r", "@"]);
+    insta::assert_snapshot!(content, @"appended");
+    test_env.jj_cmd_ok(&repo_path, &["undo"]);
 
-    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    // The command can fail instead
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["fix", "-s", "@", "--config=fix.empty-output=abort"],
+    );
+    insta::assert_snapshot!(stderr, @r#"
+    Error: Tool `tool-1` produced empty output for foo
+    Hint: Set `fix.empty-output = "allow"` if the tool is expected to empty files.
+    "#);
+
+    // The empty output can be accepted
+    let (_stdout, _stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["fix", "-s", "@", "--config=fix.empty-output=allow"],
+    );
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "foo", "-r", "@"]);
+    insta::assert_snapshot!(content, @"appended");
+}
+
+#[test]
+fn test_fix_file_counts() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        fix.max-file-size = "10B"
+
+        [fix.tools.upper]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["glob:*.txt"]
+
+        [fix.tools.empty]
+        command = [{formatter}, "--stdout="]
+        patterns = ["emptied.txt"]
+        "###,
+    ));
+    std::fs::write(repo_path.join("fixed.txt"), "fixed\n").unwrap();
+    std::fs::write(repo_path.join("unchanged.txt"), "UNCHANGED\n").unwrap();
+    std::fs::write(repo_path.join("large.txt"), "too large to fix\n").unwrap();
+    std::fs::write(repo_path.join("emptied.txt"), "emptied\n").unwrap();
+    std::fs::write(repo_path.join("unmatched"), "unmatched\n").unwrap();
+
+    // Files not matching any tool aren't checked
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
+    Warning: Skipping large.txt: File is larger than 10.0B
+    Warning: Skipping emptied.txt: Tool `empty` produced empty output
     Fixed 1 commits of 1 checked.
-    Working copy now at: qpvuntsm 0e2d20d6 (no description set)
+    Fixed 1 files of 4 checked, skipped 2.
+    Files changed by each tool: upper: 1 files
+    Working copy now at: qpvuntsm 4bc4dc33 (no description set)
     Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
     Added 0 files, modified 1 files, removed 0 files
     ");
-    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
-    insta::assert_snapshot!(content, @"content\n");
+
+    // Files with the same content are counted once, even if they appear in
+    // several commits
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("fixed.txt"), "again\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@--"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Skipping large.txt: File is larger than 10.0B
+    Warning: Skipping emptied.txt: Tool `empty` produced empty output
+    Fixed 2 commits of 3 checked.
+    Fixed 1 files of 5 checked, skipped 2.
+    Files changed by each tool: upper: 1 files
+    Working copy now at: zsuskuln ce80b1db (empty) (no description set)
+    Parent commit      : kkmpptxz ea30ba97 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
 }
 
 #[test]
-fn test_deduplication() {
-    // Append all fixed content to a log file. This assumes we're running the tool
-    // in the root directory of the repo, which is worth reconsidering if we
-    // establish a contract regarding cwd.
-    let (test_env, repo_path) = init_with_fake_formatter(&["--uppercase", "--tee", "$path-fixlog"]);
+fn test_fix_verify_command() {
+    let (test_env, repo_path) = init_with_fake_formatter(&["--uppercase"]);
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    std::fs::write(repo_path.join("file"), "content\n").unwrap();
 
-    // There are at least two interesting cases: the content is repeated immediately
+    // The verification command is run after fixing
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "fix",
+            "--config",
+            &format!("fix.verify-command=[{formatter}]"),
+        ],
+    );
+    insta::assert_snapshot!(stderr.replace(formatter_path.to_str().unwrap(), "$FORMATTER"), @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: qpvuntsm 35630029 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    Running verification command: $FORMATTER
+    Verification succeeded.
+    ");
+
+    // Nothing to verify if nothing changed
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "fix",
+            "--config",
+            &format!("fix.verify-command=[{formatter}, '--fail']"),
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 0 commits of 1 checked.
+    Fixed 0 files of 1 checked, skipped 0.
+    Nothing changed.
+    ");
+
+    // The fixes are kept if the verification command fails by default
+    std::fs::write(repo_path.join("file"), "changed\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "fix",
+            "--config",
+            &format!("fix.verify-command=[{formatter}, '--fail']"),
+        ],
+    );
+    insta::assert_snapshot!(stderr.replace(formatter_path.to_str().unwrap(), "$FORMATTER"), @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: qpvuntsm f3793813 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    Running verification command: $FORMATTER --fail
+    Error: Verification command exited with exit status: 1
+    Hint: Use `jj undo` to undo the fixes.
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
+    insta::assert_snapshot!(content, @"CHANGED");
+
+    // The fixes can be undone if the verification command fails
+    std::fs::write(repo_path.join("file"), "changed again\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "fix",
+            "--config",
+            &format!("fix.verify-command=[{formatter}, '--fail']"),
+            "--config=fix.undo-on-verify-failure=true",
+        ],
+    );
+    insta::assert_snapshot!(stderr.replace(formatter_path.to_str().unwrap(), "$FORMATTER"), @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: qpvuntsm c57e09ac (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    Running verification command: $FORMATTER --fail
+    Working copy now at: qpvuntsm efec8932 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    Error: Verification command exited with exit status: 1
+    Hint: The fixes were undone because `fix.undo-on-verify-failure` is set.
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
+    insta::assert_snapshot!(content, @"changed again");
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["op", "log", "-n2", "--no-graph", "-Tdescription ++ '\n'"],
+    );
+    insta::assert_snapshot!(stdout, @r"
+    undo operation 69efe8b2d2d3b258984b4407b5d715d29ad336e1842e55a5e2c9f5b9af7ad9dc9b0d0daea1a720a16c18468921f89de66112129074a0a4e6e044910c7fbdc2e0
+    fixed 1 commits
+    ");
+}
+
+#[test]
+fn test_fix_some_paths() {
+    let (test_env, repo_path) = init_with_fake_formatter(&["--uppercase"]);
+    std::fs::write(repo_path.join("file1"), "foo").unwrap();
+    std::fs::write(repo_path.join("file2"), "bar").unwrap();
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@", "file1"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: qpvuntsm 54a90d2b (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file1"]);
+    insta::assert_snapshot!(content, @r###"
+    FOO
+    "###);
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file2"]);
+    insta::assert_snapshot!(content, @"bar");
+}
+
+#[test]
+fn test_fix_cyclic() {
+    let (test_env, repo_path) = init_with_fake_formatter(&["--reverse"]);
+    std::fs::write(repo_path.join("file"), "content\n").unwrap();
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: qpvuntsm bf5e6a5a (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
+    insta::assert_snapshot!(content, @"tnetnoc\n");
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: qpvuntsm 0e2d20d6 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
+    insta::assert_snapshot!(content, @"content\n");
+}
+
+#[test]
+fn test_deduplication() {
+    // Append all fixed content to a log file. This assumes we're running the tool
+    // in the root directory of the repo, which is worth reconsidering if we
+    // establish a contract regarding cwd.
+    let (test_env, repo_path) = init_with_fake_formatter(&["--uppercase", "--tee", "$path-fixlog"]);
+
+    // There are at least two interesting cases: the content is repeated immediately
     // in the child commit, or later in another descendant.
     std::fs::write(repo_path.join("file"), "foo\n").unwrap();
     test_env.jj_cmd_ok(&repo_path, &["bookmark", "create", "-r@", "a"]);
@@ -707,6 +1303,8 @@ fn test_deduplication() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 4 commits of 4 checked.
+    Fixed 2 files of 2 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 2 files
     Working copy now at: yqosqzyt cf770245 d | (no description set)
     Parent commit      : mzvwutvl 370615a5 c | (empty) (no description set)
     Added 0 files, modified 1 files, removed 0 files
@@ -747,6 +1345,7 @@ fn test_executed_but_nothing_changed() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 0 commits of 1 checked.
+    Fixed 0 files of 1 checked, skipped 0.
     Nothing changed.
     ");
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
@@ -763,7 +1362,9 @@ fn test_failure() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
+    Warning: Tool `fake-formatter` failed on file (changed in ac0155d86e95) with exit status: 1:
     Fixed 0 commits of 1 checked.
+    Fixed 0 files of 1 checked, skipped 0.
     Nothing changed.
     ");
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
@@ -782,6 +1383,8 @@ fn test_stderr_success() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     errorFixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
     Working copy now at: qpvuntsm 487808ba (no description set)
     Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
     Added 0 files, modified 1 files, removed 0 files
@@ -799,7 +1402,9 @@ fn test_stderr_failure() {
     let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@"]);
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
+    Warning: Tool `fake-formatter` failed on file (changed in f53648c046cb) with exit status: 1:
     errorFixed 0 commits of 1 checked.
+    Fixed 0 files of 1 checked, skipped 0.
     Nothing changed.
     ");
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
@@ -823,8 +1428,24 @@ fn test_missing_command() {
     // executions succeed.
     insta::assert_snapshot!(stderr, @r"
     Fixed 0 commits of 1 checked.
+    Fixed 0 files of 0 checked, skipped 0.
     Nothing changed.
     ");
+
+    // The error is reported for each file the tool was run on. The error
+    // message depends on the platform.
+    std::fs::write(repo_path.join("file"), "content").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s", "@"]);
+    insta::assert_snapshot!(stdout, @"");
+    if cfg!(unix) {
+        insta::assert_snapshot!(stderr, @r"
+        Warning: Tool `bad-tool` failed on file (changed in 23cf567afae2):
+        Failed to run tool: No such file or directory (os error 2)
+        Fixed 0 commits of 1 checked.
+        Fixed 0 files of 1 checked, skipped 0.
+        Nothing changed.
+        ");
+    }
 }
 
 #[test]
@@ -838,6 +1459,8 @@ fn test_fix_file_types() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
     Working copy now at: qpvuntsm 6836a9e4 (no description set)
     Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
     Added 0 files, modified 1 files, removed 0 files
@@ -860,6 +1483,8 @@ fn test_fix_executable() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
     Working copy now at: qpvuntsm fee78e99 (no description set)
     Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
     Added 0 files, modified 1 files, removed 0 files
@@ -888,6 +1513,7 @@ fn test_fix_trivial_merge_commit() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 0 commits of 1 checked.
+    Fixed 0 files of 0 checked, skipped 0.
     Nothing changed.
     ");
     let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file_a", "-r", "@"]);
@@ -920,6 +1546,8 @@ fn test_fix_adding_merge_commit() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 1 commits of 1 checked.
+    Fixed 4 files of 4 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 4 files
     Working copy now at: mzvwutvl f93eb5a9 (no description set)
     Parent commit      : qpvuntsm 6e64e7a7 a | (no description set)
     Parent commit      : kkmpptxz c536f264 b | (no description set)
@@ -951,6 +1579,8 @@ fn test_fix_both_sides_of_conflict() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 3 commits of 3 checked.
+    Fixed 2 files of 2 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 2 files
     Working copy now at: mzvwutvl a55c6ec2 (conflict) (empty) (no description set)
     Parent commit      : qpvuntsm 8e8aad69 a | (no description set)
     Parent commit      : kkmpptxz 91f9b284 b | (no description set)
@@ -995,6 +1625,8 @@ fn test_fix_resolve_conflict() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r"
     Fixed 3 commits of 3 checked.
+    Fixed 2 files of 2 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 2 files
     Working copy now at: mzvwutvl 50fd048d (empty) (no description set)
     Parent commit      : qpvuntsm dd2721f1 a | (no description set)
     Parent commit      : kkmpptxz 07c27a8e b | (no description set)
@@ -1060,6 +1692,8 @@ fn test_all_files() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Fixed 2 commits of 2 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: tool: 1 files
     Working copy now at: rlvkpnrz c098d165 child
     Parent commit      : qpvuntsm 0bb31627 parent
     Added 0 files, modified 1 files, removed 0 files
@@ -1106,6 +1740,8 @@ fn test_all_files() {
     insta::assert_snapshot!(stdout, @"");
     insta::assert_snapshot!(stderr, @r###"
     Fixed 2 commits of 2 checked.
+    Fixed 3 files of 3 checked, skipped 0.
+    Files changed by each tool: tool: 3 files
     Working copy now at: rlvkpnrz c5d0aa1d child
     Parent commit      : qpvuntsm b4d02ca9 parent
     Added 0 files, modified 2 files, removed 0 files
@@ -1151,3 +1787,919 @@ fn test_all_files() {
     child ddd
     "###);
 }
+
+#[test]
+fn test_fix_include_unchanged_files_limit() {
+    let (test_env, repo_path) = init_with_fake_formatter(&["--uppercase"]);
+    test_env.add_config("fix.max-unchanged-files = 2");
+    std::fs::write(repo_path.join("a"), "a\n").unwrap();
+    std::fs::write(repo_path.join("b"), "b\n").unwrap();
+    std::fs::write(repo_path.join("c"), "c\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+
+    // Too many files without confirmation
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["fix", "--include-unchanged-files"]);
+    insta::assert_snapshot!(stderr, @r"
+    Fix 3 files? (yN): N
+    Error: Refusing to fix 3 files, which is more than `fix.max-unchanged-files` (2)
+    Hint: Restrict the files to fix with a fileset, or pass `--yes` to fix them anyway.
+    ");
+
+    // Few enough files after restricting the paths
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["fix", "--include-unchanged-files", "a", "b"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 2 commits of 2 checked.
+    Fixed 2 files of 2 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 2 files
+    Working copy now at: rlvkpnrz 451a4774 (empty) (no description set)
+    Parent commit      : qpvuntsm 90aadf17 (no description set)
+    Added 0 files, modified 2 files, removed 0 files
+    ");
+
+    // Confirmation can be skipped
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["fix", "--include-unchanged-files", "--yes"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 2 commits of 2 checked.
+    Fixed 1 files of 3 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: rlvkpnrz 1cacbdb1 (empty) (no description set)
+    Parent commit      : qpvuntsm c5fc95b5 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "c"]);
+    insta::assert_snapshot!(content, @"C");
+}
+
+#[test]
+fn test_emit_commands() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(indoc! {r#"
+        [fix.tools.tool-1]
+        command = ["tool-1", "--stdin-filename=$path"]
+        patterns = ["glob:'**/*.rs'"]
+
+        [fix.tools.tool-2]
+        command = { command = ["tool 2", "it's"], env = { MODE = "a b" } }
+        patterns = ["glob:'a/*'"]
+        "#});
+
+    std::fs::create_dir(repo_path.join("a")).unwrap();
+    std::fs::write(repo_path.join("a/main.rs"), "main\n").unwrap();
+    std::fs::write(repo_path.join("a/my file.txt"), "text\n").unwrap();
+    std::fs::write(repo_path.join("lib.rs"), "lib\n").unwrap();
+    std::fs::write(repo_path.join("other"), "other\n").unwrap();
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "--emit-commands"]);
+    insta::assert_snapshot!(stdout, @r"
+    tool-1 --stdin-filename=a/main.rs | MODE='a b' 'tool 2' 'it'\''s' < a/main.rs
+    MODE='a b' 'tool 2' 'it'\''s' < 'a/my file.txt'
+    tool-1 --stdin-filename=lib.rs < lib.rs
+    ");
+    insta::assert_snapshot!(stderr, @"");
+
+    // Nothing was run, and nothing was changed
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["op", "log", "-n1", "--no-graph", "-Tdescription"],
+    );
+    insta::assert_snapshot!(stdout, @"snapshot working copy");
+}
+
+#[test]
+fn test_fix_lint_mode() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.upper]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["glob:*.txt"]
+
+        [fix.tools.lint-ok]
+        command = [{formatter}, "--stdout=ignored"]
+        patterns = ["all()"]
+        mode = "lint"
+
+        [fix.tools.lint-bad]
+        command = [{formatter}, "--stdout=bad line\n", "--stderr=more details\n", "--fail"]
+        patterns = ["glob:bad*"]
+        mode = "lint"
+        "###,
+    ));
+    std::fs::write(repo_path.join("good.txt"), "good\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["commit", "-m=first"]);
+    std::fs::write(repo_path.join("bad.txt"), "bad\n").unwrap();
+    std::fs::write(repo_path.join("bad"), "bad\n").unwrap();
+
+    // Lint tools don't modify files, and the problems they find are reported
+    // after fixing the files
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["fix", "-s=root()+"]);
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Tool `lint-bad` found problems in bad (changed in 3a7f8965ed75):
+    bad line
+    more details
+    Warning: Tool `lint-bad` found problems in bad.txt (changed in 3a7f8965ed75):
+    bad line
+    more details
+    Fixed 2 commits of 2 checked.
+    Fixed 2 files of 3 checked, skipped 0.
+    Files changed by each tool: upper: 2 files
+    Working copy now at: rlvkpnrz 6fb0baf3 (no description set)
+    Parent commit      : qpvuntsm 66c2b4fb first
+    Added 0 files, modified 2 files, removed 0 files
+    Error: Lint tools found problems in 2 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "bad.txt", "-r", "@"]);
+    insta::assert_snapshot!(content, @"BAD");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "bad", "-r", "@"]);
+    insta::assert_snapshot!(content, @"bad");
+
+    // Lint tools aren't included in the emitted commands
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "--emit-commands"]);
+    insta::assert_snapshot!(stdout.replace(formatter_path.to_str().unwrap(), "$FORMATTER"), @r"
+    $FORMATTER --uppercase < bad.txt
+    $FORMATTER --uppercase < good.txt
+    ");
+    insta::assert_snapshot!(stderr, @"Warning: Left out tools that don't fix standard input: lint-bad, lint-ok");
+}
+
+#[test]
+fn test_fix_stabilize() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.stable]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["glob:*.txt"]
+        stabilize = true
+
+        [fix.tools.unstable]
+        command = [{formatter}, "--append=more"]
+        patterns = ["glob:unstable*"]
+        stabilize = true
+        "###,
+    ));
+    std::fs::write(repo_path.join("stable.txt"), "stable\n").unwrap();
+    std::fs::write(repo_path.join("unstable.txt"), "unstable\n").unwrap();
+
+    // The output of a tool that keeps changing its own output isn't used
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Skipping tool `unstable` for unstable.txt: Output didn't stabilize after 5 runs
+    Fixed 1 commits of 1 checked.
+    Fixed 2 files of 2 checked, skipped 0.
+    Files changed by each tool: stable: 2 files
+    Working copy now at: qpvuntsm 1e3ad503 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 2 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "stable.txt", "-r", "@"]);
+    insta::assert_snapshot!(content, @"STABLE");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "unstable.txt", "-r", "@"]);
+    insta::assert_snapshot!(content, @"UNSTABLE");
+}
+
+#[test]
+fn test_fix_here() {
+    let (test_env, repo_path) = init_with_fake_formatter(&["--uppercase"]);
+    std::fs::write(repo_path.join("unchanged"), "unchanged\n").unwrap();
+    std::fs::write(repo_path.join("conflict"), "base\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "base"]);
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "side"]);
+    std::fs::write(repo_path.join("conflict"), "side\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "description(base)", "-m", "wc"]);
+    std::fs::write(repo_path.join("conflict"), "wc\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["rebase", "-r", "@", "-d", "description(side)"],
+    );
+    std::fs::write(repo_path.join("changed"), "changed\n").unwrap();
+
+    // Only the changed file without conflicts is fixed, and only in @
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "--here"]);
+    insta::assert_snapshot!(stdout, @"");
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Skipping conflict: File has conflicts
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: fake-formatter: 1 files
+    Working copy now at: zsuskuln e7b85336 (conflict) wc
+    Parent commit      : kkmpptxz c660517f side
+    Added 0 files, modified 1 files, removed 0 files
+    There are unresolved conflicts at these paths:
+    conflict    2-sided conflict
+    ");
+    let content = std::fs::read_to_string(repo_path.join("changed")).unwrap();
+    insta::assert_snapshot!(content, @"CHANGED");
+    let content = std::fs::read_to_string(repo_path.join("unchanged")).unwrap();
+    insta::assert_snapshot!(content, @"unchanged");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "conflict", "-r", "@-"]);
+    insta::assert_snapshot!(content, @"side");
+
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["fix", "--here", "-s", "@"]);
+    insta::assert_snapshot!(stderr, @r"
+    error: the argument '--here' cannot be used with '--source <REVSETS>'
+
+    Usage: jj fix --here [FILESETS]...
+
+    For more information, try '--help'.
+    ");
+}
+
+#[test]
+fn test_fix_dry_run() {
+    let (test_env, repo_path) = init_with_fake_formatter(&["--uppercase"]);
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "B\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file1"), "b\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "c\n").unwrap();
+
+    // The files that would be changed are listed without changing anything
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "--dry-run"]);
+    insta::assert_snapshot!(stdout, @r"
+    file1 (changed in 327608c8a0a0)
+    file1 (changed in 6798610aa1cb)
+    file3 (changed in 327608c8a0a0)
+    ");
+    insta::assert_snapshot!(stderr, @r"
+    Would fix 3 files of 4 checked, skipped 0.
+    Files that would be changed by each tool: fake-formatter: 3 files
+    ");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["op", "log", "-n1", "-T", "description"]);
+    insta::assert_snapshot!(stdout, @"@  snapshot working copy");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file1", "-r", "@"]);
+    insta::assert_snapshot!(content, @"b");
+}
+
+#[test]
+fn test_fix_in_place_tool() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.in-place]
+        command = [{formatter}, "--uppercase", "--in-place", "$path"]
+        patterns = ["all()"]
+        mode = "in-place"
+
+        [fix.tools.stdin]
+        command = [{formatter}, "--append", "stdin"]
+        patterns = ["dir/file"]
+        priority = 1
+        "###,
+    ));
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir/file"), "foo\n").unwrap();
+
+    // The tool rewrites a temporary file, and the next tool reads its result on
+    // standard input
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: in-place: 1 files, stdin: 1 files
+    Working copy now at: qpvuntsm 714ef67c (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "dir/file", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    FOO
+    stdin
+    ");
+
+    // A failing tool leaves the file unchanged
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("dir/file"), "bar\n").unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "fix",
+            "-s=@",
+            &format!(
+                "--config=fix.tools.in-place.command=[{formatter}, '--uppercase', '--in-place', '$path', '--fail']"
+            ),
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Tool `in-place` failed on dir/file (changed in 4ab59b11642d) with exit status: 1:
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: stdin: 1 files
+    Working copy now at: zsuskuln 450f901a (no description set)
+    Parent commit      : qpvuntsm 714ef67c (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "dir/file", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    bar
+    stdin
+    ");
+}
+
+#[test]
+fn test_fix_tool_without_stdin() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.file]
+        command = [{formatter}, "--uppercase", "--file", "$path"]
+        patterns = ["all()"]
+        stdin = false
+
+        [fix.tools.stdin]
+        command = [{formatter}, "--append", "stdin"]
+        patterns = ["dir/file"]
+        priority = 1
+        "###,
+    ));
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir/file"), "foo\n").unwrap();
+
+    // The tool reads a temporary file and prints the result, and the next tool
+    // reads that on standard input
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: file: 1 files, stdin: 1 files
+    Working copy now at: qpvuntsm 714ef67c (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "dir/file", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    FOO
+    stdin
+    ");
+
+    // Such tools aren't part of the emitted commands
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "--emit-commands"]);
+    insta::assert_snapshot!(stdout.replace(formatter_path.to_str().unwrap(), "$FORMATTER"), @"$FORMATTER --append stdin < dir/file");
+    insta::assert_snapshot!(stderr, @"Warning: Left out tools that don't fix standard input: file");
+
+    // Lint tools can read a temporary file too
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "fix",
+            "--config=fix.tools.stdin.enabled=false",
+            "--config=fix.tools.file.mode='lint'",
+            &format!("--config=fix.tools.file.command=[{formatter}, '--file', '$path', '--fail']"),
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Tool `file` found problems in dir/file (changed in 714ef67c67f4):
+    FOO
+    stdin
+    Fixed 0 commits of 1 checked.
+    Fixed 0 files of 1 checked, skipped 0.
+    Nothing changed.
+    Error: Lint tools found problems in 1 files
+    ");
+}
+
+#[test]
+fn test_fix_tool_temp_extension() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.file]
+        command = [{formatter}, "--uppercase", "--file", "$path", "--stderr", "file tool: $path"]
+        patterns = ["all()"]
+        stdin = false
+        temp-extension = "js"
+
+        [fix.tools.in-place]
+        command = [{formatter}, "--append", "in-place", "--in-place", "$path", "--stderr", "in-place tool: $path"]
+        patterns = ["all()"]
+        mode = "in-place"
+        temp-extension = "txt"
+        priority = 1
+        "###,
+    ));
+    std::fs::write(repo_path.join("file.mjs"), "foo\n").unwrap();
+
+    // Both tools get a temporary file with the configured extension
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    assert!(stderr.contains(&format!("{}file.js", std::path::MAIN_SEPARATOR)));
+    assert!(stderr.contains(&format!("{}file.txt", std::path::MAIN_SEPARATOR)));
+    assert!(!stderr.contains("file.mjs"));
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file.mjs", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    FOO
+    in-place
+    ");
+}
+
+#[test]
+fn test_fix_commit_scoped_tool() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.commit]
+        command = [{formatter}, "--uppercase", "--in-place-dir", "$root", "--stderr", "ran\n"]
+        patterns = ["glob:'**/*.txt'"]
+        mode = "in-place"
+        scope = "commit"
+
+        [fix.tools.file]
+        command = [{formatter}, "--append", "file"]
+        patterns = ["dir/a.txt"]
+        "###,
+    ));
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir/a.txt"), "a\n").unwrap();
+    std::fs::write(repo_path.join("b.txt"), "b\n").unwrap();
+    std::fs::write(repo_path.join("c.py"), "c\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("b.txt"), "b2\n").unwrap();
+
+    // The tool runs once per commit on all of its files, after the file-scoped
+    // tools
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s=@-"]);
+    insta::assert_snapshot!(stderr, @r"
+    ran
+    ran
+    Fixed 2 commits of 2 checked.
+    Fixed 3 files of 3 checked, skipped 0.
+    Files changed by each tool: commit: 3 files, file: 1 files
+    Working copy now at: rlvkpnrz 316c0c13 (no description set)
+    Parent commit      : qpvuntsm 0c323d25 (no description set)
+    Added 0 files, modified 2 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "dir/a.txt", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    A
+    FILE
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "b.txt", "-r", "@-"]);
+    insta::assert_snapshot!(content, @"B");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "b.txt", "-r", "@"]);
+    insta::assert_snapshot!(content, @"B2");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "c.py", "-r", "@"]);
+    insta::assert_snapshot!(content, @"c");
+
+    // A failing tool leaves the files unchanged
+    std::fs::write(repo_path.join("b.txt"), "b3\n").unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "fix",
+            &format!(
+                "--config=fix.tools.commit.command=[{formatter}, '--in-place-dir', '$root', '--fail']"
+            ),
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Tool `commit` failed on commit 0c323d257839 with exit status: 1:
+    Warning: Tool `commit` failed on commit 95063e7c442b with exit status: 1:
+    Fixed 2 commits of 2 checked.
+    Fixed 1 files of 3 checked, skipped 0.
+    Files changed by each tool: file: 1 files
+    Working copy now at: rlvkpnrz 6d1154b5 (no description set)
+    Parent commit      : qpvuntsm fc6de4d0 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+
+    // Commit-scoped tools must rewrite the files in place
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["fix", "--config=fix.tools.commit.mode='fix'"]);
+    insta::assert_snapshot!(stderr, @r#"
+    Config error: In `fix.tools.commit`: `scope = "commit"` requires `mode = "in-place"`
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    "#);
+
+    // Options that apply to single files are rejected
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["fix", "--config=fix.tools.commit.content-pattern='b'"],
+    );
+    insta::assert_snapshot!(stderr, @r#"
+    Config error: In `fix.tools.commit`: `content-pattern` can't be used with `scope = "commit"`
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    "#);
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["fix", "--config=fix.tools.commit.stabilize=true"],
+    );
+    insta::assert_snapshot!(stderr, @r#"
+    Config error: In `fix.tools.commit`: `stabilize` can't be used with `scope = "commit"`
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    "#);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fix_commit_scoped_tool_file_checks() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(
+        r#"
+        [fix.tools.commit]
+        command = { shell = ": > a.txt; : > big.txt" }
+        patterns = ["glob:'*.txt'"]
+        mode = "in-place"
+        scope = "commit"
+        "#,
+    );
+    std::fs::write(repo_path.join("a.txt"), "a\n").unwrap();
+    std::fs::write(repo_path.join("b.txt"), "b\n").unwrap();
+    std::fs::write(repo_path.join("big.txt"), "0123456789\n").unwrap();
+
+    // Large files aren't passed to the tool, and emptied files are left
+    // unchanged
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "--max-file-size=5"]);
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Skipping big.txt: File is larger than 5.0B
+    Warning: Skipping a.txt: Tool `commit` produced empty output
+    Fixed 0 commits of 1 checked.
+    Fixed 0 files of 3 checked, skipped 2.
+    Nothing changed.
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "a.txt", "-r", "@"]);
+    insta::assert_snapshot!(content, @"a");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "big.txt", "-r", "@"]);
+    insta::assert_snapshot!(content, @"0123456789");
+}
+
+#[test]
+fn test_fix_root_and_commit_variables() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.change-id]
+        command = [{formatter}, "--append", "$change_id"]
+        patterns = ["file"]
+
+        [fix.tools.root]
+        command = [{formatter}, "--tee", "$root/.jj/root-output"]
+        patterns = ["file"]
+        "###,
+    ));
+    // The same content in two commits is fixed once for each commit
+    std::fs::write(repo_path.join("file"), "foo\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "bar\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file"), "foo\n").unwrap();
+
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "-s=@--"]);
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 3 commits of 3 checked.
+    Fixed 3 files of 3 checked, skipped 0.
+    Files changed by each tool: change-id: 3 files, root: 3 files
+    Working copy now at: kkmpptxz cbffefa7 (no description set)
+    Parent commit      : rlvkpnrz fa6e6b91 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@--"]);
+    insta::assert_snapshot!(content, @r"
+    foo
+    qpvuntsmwlqtpsluzzsnyyzlmlwvmlnu
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    foo
+    kkmpptxzrspxrzommnulwmwkkqwworpl
+    ");
+    // `$root` is the workspace root
+    assert!(repo_path.join(".jj/root-output").is_file());
+
+    // Failures are reported for the commit the tool ran for
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &[
+            "fix",
+            "-s=@",
+            &format!("--config=fix.tools.change-id.command=[{formatter}, '--fail', '--stderr', '$commit_id']"),
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Tool `change-id` failed on file (changed in cbffefa7ac08) with exit status: 1:
+    cbffefa7ac0811f1a792585de492ce794ceddf25Fixed 0 commits of 1 checked.
+    Fixed 0 files of 1 checked, skipped 0.
+    Nothing changed.
+    ");
+}
+
+#[test]
+fn test_fix_changed_files_per_tool() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.upper]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["all()"]
+
+        [fix.tools.noop]
+        command = [{formatter}]
+        patterns = ["all()"]
+
+        [fix.tools.reverse]
+        command = [{formatter}, "--reverse"]
+        patterns = ["b"]
+        "###,
+    ));
+    std::fs::write(repo_path.join("a"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("b"), "bar\n").unwrap();
+    std::fs::write(repo_path.join("c"), "BAZ\n").unwrap();
+
+    // Tools that didn't change any file are not listed
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "--dry-run"]);
+    insta::assert_snapshot!(stderr, @r"
+    Would fix 2 files of 3 checked, skipped 0.
+    Files that would be changed by each tool: reverse: 1 files, upper: 2 files
+    ");
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 2 files of 3 checked, skipped 0.
+    Files changed by each tool: reverse: 1 files, upper: 2 files
+    Working copy now at: qpvuntsm 9df3245f (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 2 files, removed 0 files
+    ");
+}
+
+#[test]
+fn test_fix_error_on_failure() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.upper]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["all()"]
+
+        [fix.tools.broken]
+        command = [{formatter}, "--fail"]
+        patterns = ["b", "c"]
+        "###,
+    ));
+    std::fs::write(repo_path.join("a"), "foo\n").unwrap();
+    std::fs::write(repo_path.join("b"), "bar\n").unwrap();
+    std::fs::write(repo_path.join("c"), "baz\n").unwrap();
+
+    // The other fixes are still applied
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["fix", "--error-on-failure"]);
+    insta::assert_snapshot!(stderr, @r"
+    Warning: Tool `broken` failed on b (changed in 25f515fbba38) with exit status: 1:
+    Warning: Tool `broken` failed on c (changed in 25f515fbba38) with exit status: 1:
+    Fixed 1 commits of 1 checked.
+    Fixed 3 files of 3 checked, skipped 0.
+    Files changed by each tool: upper: 3 files
+    Working copy now at: qpvuntsm f324ae1b (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 3 files, removed 0 files
+    Warning: Tools failed on these files:
+      b: broken
+      c: broken
+    Error: Fix tools failed on 2 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "a", "b", "c"]);
+    insta::assert_snapshot!(content, @r"
+    FOO
+    BAR
+    BAZ
+    ");
+
+    // The fixes are verified before the failures are reported
+    std::fs::write(repo_path.join("a"), "foo2\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "fix",
+            "--error-on-failure",
+            "--config",
+            &format!("fix.verify-command=[{formatter}]"),
+        ],
+    );
+    insta::assert_snapshot!(stderr.replace(formatter_path.to_str().unwrap(), "$FORMATTER"), @r"
+    Warning: Tool `broken` failed on b (changed in 8b33ee1fb83a) with exit status: 1:
+    Warning: Tool `broken` failed on c (changed in 8b33ee1fb83a) with exit status: 1:
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 3 checked, skipped 0.
+    Files changed by each tool: upper: 1 files
+    Working copy now at: qpvuntsm c5b4de79 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    Running verification command: $FORMATTER
+    Verification succeeded.
+    Warning: Tools failed on these files:
+      b: broken
+      c: broken
+    Error: Fix tools failed on 2 files
+    ");
+
+    // The fixes are undone if the verification fails
+    std::fs::write(repo_path.join("a"), "foo3\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "fix",
+            "--error-on-failure",
+            "--config",
+            &format!("fix.verify-command=[{formatter}, '--fail']"),
+            "--config=fix.undo-on-verify-failure=true",
+        ],
+    );
+    insta::assert_snapshot!(stderr.replace(formatter_path.to_str().unwrap(), "$FORMATTER"), @r"
+    Warning: Tool `broken` failed on b (changed in 2cc9fb53232e) with exit status: 1:
+    Warning: Tool `broken` failed on c (changed in 2cc9fb53232e) with exit status: 1:
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 3 checked, skipped 0.
+    Files changed by each tool: upper: 1 files
+    Working copy now at: qpvuntsm 4ec6c683 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    Running verification command: $FORMATTER --fail
+    Working copy now at: qpvuntsm 2cc9fb53 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    Error: Verification command exited with exit status: 1
+    Hint: The fixes were undone because `fix.undo-on-verify-failure` is set.
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "a"]);
+    insta::assert_snapshot!(content, @"foo3");
+}
+
+#[test]
+fn test_fix_selected_tools() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.upper]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["all()"]
+
+        [fix.tools.reverse]
+        command = [{formatter}, "--reverse"]
+        patterns = ["all()"]
+
+        [fix.tools.tail]
+        command = [{formatter}, "--append", "end"]
+        patterns = ["all()"]
+
+        [fix.tools.disabled]
+        command = [{formatter}, "--lowercase"]
+        patterns = ["all()"]
+        enabled = false
+        "###,
+    ));
+    std::fs::write(repo_path.join("file"), "foo\n").unwrap();
+
+    // Only the named tools are run, in the configured order
+    let (_stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["fix", "--dry-run", "--tool=upper", "--tool=reverse"],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Would fix 1 files of 1 checked, skipped 0.
+    Files that would be changed by each tool: reverse: 1 files, upper: 1 files
+    ");
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "--tool=tail"]);
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 1 checked, skipped 0.
+    Files changed by each tool: tail: 1 files
+    Working copy now at: qpvuntsm 2d26f836 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "file"]);
+    insta::assert_snapshot!(content, @r"
+    foo
+    end
+    ");
+
+    // Unknown or disabled tool
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["fix", "--tool=upper", "--tool=nonexistent"]);
+    insta::assert_snapshot!(stderr, @r"
+    Error: No enabled tool named `nonexistent` in `fix.tools`
+    Hint: The enabled tools are: reverse, tail, upper
+    ");
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["fix", "--tool=disabled"]);
+    insta::assert_snapshot!(stderr, @r"
+    Error: No enabled tool named `disabled` in `fix.tools`
+    Hint: The enabled tools are: reverse, tail, upper
+    ");
+}
+
+#[test]
+fn test_fix_content_pattern() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.scripts]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["all()"]
+        content-pattern = '^#!.*sh'
+
+        [fix.tools.tail]
+        command = [{formatter}, "--append", "end"]
+        patterns = ["all()"]
+        content-pattern = 'ECHO'
+        "###,
+    ));
+    std::fs::write(repo_path.join("script"), "#!/bin/sh\necho foo\n").unwrap();
+    std::fs::write(repo_path.join("text"), "sh\necho foo\n").unwrap();
+
+    // Only matching content is passed to the tools, and the second tool sees the
+    // output of the first one
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r"
+    Fixed 1 commits of 1 checked.
+    Fixed 1 files of 2 checked, skipped 0.
+    Files changed by each tool: scripts: 1 files, tail: 1 files
+    Working copy now at: qpvuntsm f054b39d (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "script", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    #!/BIN/SH
+    ECHO FOO
+    end
+    ");
+    let content = test_env.jj_cmd_success(&repo_path, &["file", "show", "text", "-r", "@"]);
+    insta::assert_snapshot!(content, @r"
+    sh
+    echo foo
+    ");
+
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["fix", "--config=fix.tools.scripts.content-pattern='('"],
+    );
+    insta::assert_snapshot!(stderr, @r"
+    Config error: In `fix.tools.scripts`: Invalid `content-pattern`
+    Caused by: regex parse error:
+        (
+        ^
+    error: unclosed group
+    For help, see https://jj-vcs.github.io/jj/latest/config/.
+    ");
+}
@@ -64,3 +64,52 @@ fn test_gitsubmodule_print_gitmodules() {
 	path:new
     "###);
 }
+
+#[test]
+fn test_gitsubmodule_diff() {
+    let test_env = TestEnvironment::default();
+    let workspace_root = test_env.env_root().join("repo");
+    let git_repo = git::init(&workspace_root);
+    let write_tree = |submodule_id: &str| {
+        let mut editor = git_repo
+            .edit_tree(gix::ObjectId::empty_tree(gix::hash::Kind::default()))
+            .unwrap();
+        editor
+            .upsert(
+                "sub",
+                gix::object::tree::EntryKind::Commit,
+                gix::ObjectId::from_hex(submodule_id.as_bytes()).unwrap(),
+            )
+            .unwrap();
+        editor.write().unwrap().detach()
+    };
+    let before = git::write_commit(
+        &git_repo,
+        "refs/heads/before",
+        write_tree("1111111111111111111111111111111111111111"),
+        "before",
+        &[],
+    );
+    git::write_commit(
+        &git_repo,
+        "refs/heads/after",
+        write_tree("2222222222222222222222222222222222222222"),
+        "after",
+        &[before],
+    );
+    test_env.jj_cmd_ok(&workspace_root, &["git", "init", "--git-repo", "."]);
+
+    // The checked out commits are compared
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["diff", "-r", "after"]);
+    insta::assert_snapshot!(stdout, @"Submodule sub moved from 111111111111 to 222222222222");
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["diff", "-r", "after", "--git"]);
+    insta::assert_snapshot!(stdout, @r"
+    diff --git a/sub b/sub
+    index 1111111111..2222222222 040000
+    --- a/sub
+    +++ b/sub
+    @@ -1,1 +1,1 @@
+    -Subproject commit 1111111111111111111111111111111111111111
+    +Subproject commit 2222222222222222222222222222222222222222
+    ");
+}
@@ -193,6 +193,7 @@ fn test_restore_conflicted_merge() {
        6     : b
        7     : >>>>>>> Conflict 1 of 1 ends
             1: resolution
+        (newline at end of file removed)
     "###);
 
     // ...and restore it back again.
@@ -234,6 +235,7 @@ fn test_restore_conflicted_merge() {
        6     : b
        7     : >>>>>>> Conflict 1 of 1 ends
             1: resolution
+        (newline at end of file removed)
     "###);
 
     // ... and restore it back again.
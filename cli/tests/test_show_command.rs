@@ -140,6 +140,7 @@ fn test_show_basic() {
     +bar
     +baz quux
     diff --git a/file1 b/file3
+    similarity index 100%
     rename from file1
     rename to file3
     "#);
@@ -162,6 +163,7 @@ fn test_show_basic() {
     +bar
     +baz quux
     diff --git a/file1 b/file3
+    similarity index 100%
     rename from file1
     rename to file3
     "#);
@@ -185,6 +187,7 @@ fn test_show_basic() {
     [38;5;2m<<diff added::+>>[4m<<diff added token::bar>>[24m[39m
     [38;5;2m<<diff added::+baz >>[4m<<diff added token::quux>>[24m<<diff added::>>[39m
     [1m<<diff file_header::diff --git a/file1 b/file3>>[0m
+    [1m<<diff file_header::similarity index 100%>>[0m
     [1m<<diff file_header::rename from file1>>[0m
     [1m<<diff file_header::rename to file3>>[0m
     "#);
@@ -210,6 +213,7 @@ fn test_show_basic() {
     +bar
     +baz quux
     diff --git a/file1 b/file3
+    similarity index 100%
     rename from file1
     rename to file3
     "#);
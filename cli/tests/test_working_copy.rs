@@ -375,7 +375,7 @@ fn test_conflict_marker_length_stored_in_working_copy() {
     let stdout = test_env.jj_cmd_success(&repo_path, &["st"]);
     insta::assert_snapshot!(stdout, @r#"
     Working copy changes:
-    M file
+    U file
     There are unresolved conflicts at these paths:
     file    2-sided conflict
     Working copy : mzvwutvl 3a981880 (conflict) (no description set)
@@ -423,6 +423,12 @@ pub trait Backend: Send + Sync + Debug {
 
     async fn read_file(&self, path: &RepoPath, id: &FileId) -> BackendResult<Box<dyn Read>>;
 
+    /// Returns the size of the file in bytes, or `None` if the backend can't
+    /// tell it without reading the file content.
+    async fn file_size(&self, _path: &RepoPath, _id: &FileId) -> BackendResult<Option<u64>> {
+        Ok(None)
+    }
+
     async fn write_file(
         &self,
         path: &RepoPath,
@@ -21,7 +21,6 @@ use std::hash::Hasher;
 use std::hash::RandomState;
 use std::iter;
 use std::ops::Range;
-use std::slice;
 
 use bstr::BStr;
 use hashbrown::HashTable;
@@ -183,6 +182,30 @@ impl CompareBytes for CompareBytesIgnoreWhitespaceAmount {
     }
 }
 
+/// Compares byte sequences ignoring a carriage return at the end of line.
+#[derive(Clone, Debug, Default)]
+pub struct CompareBytesIgnoreCrAtEol;
+
+impl CompareBytes for CompareBytesIgnoreCrAtEol {
+    fn eq(&self, left: &[u8], right: &[u8]) -> bool {
+        split_cr_at_eol(left) == split_cr_at_eol(right)
+    }
+
+    fn hash<H: Hasher>(&self, text: &[u8], state: &mut H) {
+        split_cr_at_eol(text).hash(state);
+    }
+}
+
+/// Splits `text` into the line content without the trailing `\r`, and the
+/// line terminator.
+fn split_cr_at_eol(text: &[u8]) -> (&[u8], &[u8]) {
+    let (content, terminator) = match text.strip_suffix(b"\n") {
+        Some(content) => (content, &b"\n"[..]),
+        None => (text, &b""[..]),
+    };
+    (content.strip_suffix(b"\r").unwrap_or(content), terminator)
+}
+
 // Not implementing Eq because the text should be compared by WordComparator.
 #[derive(Clone, Copy, Debug)]
 struct HashedWord<'input> {
@@ -773,11 +796,44 @@ impl<'input> Diff<'input> {
         DiffHunkIterator { diff: self, ranges }
     }
 
+    /// Returns iterator over matching and different texts, which owns the
+    /// diff so that it can be stored along with the iterator state.
+    pub fn into_hunks(self) -> DiffHunkIntoIter<'input> {
+        let position = DiffHunkPosition::new(&self.unchanged_regions);
+        DiffHunkIntoIter {
+            diff: self,
+            position,
+        }
+    }
+
     /// Returns iterator over matching and different ranges in bytes.
     pub fn hunk_ranges(&self) -> DiffHunkRangeIterator<'_> {
         DiffHunkRangeIterator::new(self)
     }
 
+    /// Returns the matching hunk at the unchanged `range`.
+    fn matching_hunk(&self, range: &UnchangedRange) -> DiffHunk<'input> {
+        let contents = self.hunk_at(range).collect();
+        let kind = DiffHunkKind::Matching;
+        DiffHunk { kind, contents }
+    }
+
+    /// Returns the different hunk between the `previous` and `current`
+    /// unchanged ranges.
+    fn different_hunk(
+        &self,
+        previous: &UnchangedRange,
+        current: &UnchangedRange,
+    ) -> DiffHunk<'input> {
+        let contents: DiffHunkContentVec = self.hunk_between(previous, current).collect();
+        debug_assert!(
+            contents.iter().any(|content| !content.is_empty()),
+            "unchanged regions should have been compacted"
+        );
+        let kind = DiffHunkKind::Different;
+        DiffHunk { kind, contents }
+    }
+
     /// Returns contents at the unchanged `range`.
     fn hunk_at<'a>(&'a self, range: &'a UnchangedRange) -> impl Iterator<Item = &'input BStr> + 'a {
         itertools::chain(
@@ -908,21 +964,28 @@ impl<'input> Iterator for DiffHunkIterator<'_, 'input> {
 
     fn next(&mut self) -> Option<Self::Item> {
         self.ranges.next_with(
-            |previous| {
-                let contents = self.diff.hunk_at(previous).collect();
-                let kind = DiffHunkKind::Matching;
-                DiffHunk { kind, contents }
-            },
-            |previous, current| {
-                let contents: DiffHunkContentVec =
-                    self.diff.hunk_between(previous, current).collect();
-                debug_assert!(
-                    contents.iter().any(|content| !content.is_empty()),
-                    "unchanged regions should have been compacted"
-                );
-                let kind = DiffHunkKind::Different;
-                DiffHunk { kind, contents }
-            },
+            |previous| self.diff.matching_hunk(previous),
+            |previous, current| self.diff.different_hunk(previous, current),
+        )
+    }
+}
+
+/// Iterator over matching and different texts, which owns the diff.
+#[derive(Clone, Debug)]
+pub struct DiffHunkIntoIter<'input> {
+    diff: Diff<'input>,
+    position: DiffHunkPosition,
+}
+
+impl<'input> Iterator for DiffHunkIntoIter<'input> {
+    type Item = DiffHunk<'input>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let diff = &self.diff;
+        self.position.next_with(
+            &diff.unchanged_regions,
+            |previous| diff.matching_hunk(previous),
+            |previous, current| diff.different_hunk(previous, current),
         )
     }
 }
@@ -940,35 +1003,61 @@ pub type DiffHunkRangeVec = SmallVec<[Range<usize>; 2]>;
 /// Iterator over matching and different ranges in bytes.
 #[derive(Clone, Debug)]
 pub struct DiffHunkRangeIterator<'diff> {
-    previous: &'diff UnchangedRange,
-    unchanged_emitted: bool,
-    unchanged_iter: slice::Iter<'diff, UnchangedRange>,
+    unchanged_regions: &'diff [UnchangedRange],
+    position: DiffHunkPosition,
 }
 
 impl<'diff> DiffHunkRangeIterator<'diff> {
     fn new(diff: &'diff Diff) -> Self {
-        let mut unchanged_iter = diff.unchanged_regions.iter();
-        let previous = unchanged_iter.next().unwrap();
         DiffHunkRangeIterator {
-            previous,
-            unchanged_emitted: previous.is_all_empty(),
-            unchanged_iter,
+            unchanged_regions: &diff.unchanged_regions,
+            position: DiffHunkPosition::new(&diff.unchanged_regions),
+        }
+    }
+
+    fn next_with<T>(
+        &mut self,
+        hunk_at: impl FnOnce(&UnchangedRange) -> T,
+        hunk_between: impl FnOnce(&UnchangedRange, &UnchangedRange) -> T,
+    ) -> Option<T> {
+        self.position
+            .next_with(self.unchanged_regions, hunk_at, hunk_between)
+    }
+}
+
+/// Position of a hunk iterator in the unchanged regions of a diff.
+///
+/// This doesn't borrow the regions so that it can be stored along with the
+/// diff that owns them.
+#[derive(Clone, Debug)]
+struct DiffHunkPosition {
+    previous_index: usize,
+    unchanged_emitted: bool,
+}
+
+impl DiffHunkPosition {
+    fn new(unchanged_regions: &[UnchangedRange]) -> Self {
+        DiffHunkPosition {
+            previous_index: 0,
+            unchanged_emitted: unchanged_regions[0].is_all_empty(),
         }
     }
 
     fn next_with<T>(
         &mut self,
+        unchanged_regions: &[UnchangedRange],
         hunk_at: impl FnOnce(&UnchangedRange) -> T,
         hunk_between: impl FnOnce(&UnchangedRange, &UnchangedRange) -> T,
     ) -> Option<T> {
+        let previous = &unchanged_regions[self.previous_index];
         if !self.unchanged_emitted {
             self.unchanged_emitted = true;
-            return Some(hunk_at(self.previous));
+            return Some(hunk_at(previous));
         }
-        let current = self.unchanged_iter.next()?;
-        let hunk = hunk_between(self.previous, current);
-        self.previous = current;
-        self.unchanged_emitted = self.previous.is_all_empty();
+        let current = unchanged_regions.get(self.previous_index + 1)?;
+        let hunk = hunk_between(previous, current);
+        self.previous_index += 1;
+        self.unchanged_emitted = current.is_all_empty();
         Some(hunk)
     }
 }
@@ -1019,6 +1108,35 @@ pub fn diff<'a, T: AsRef<[u8]> + ?Sized + 'a>(
     diff.hunks().collect()
 }
 
+/// Counts the lines added and removed between `left` and `right`.
+///
+/// Lines are compared exactly. Returns `(added, removed)`.
+pub fn count_diff_lines(left: &[u8], right: &[u8]) -> (usize, usize) {
+    count_changed_lines(Diff::by_line([left, right]).hunks())
+}
+
+/// Counts the lines added and removed in the given two-sided diff `hunks`.
+///
+/// Returns `(added, removed)`. The caller can filter out hunks to be ignored
+/// before counting.
+pub fn count_changed_lines<'input>(
+    hunks: impl IntoIterator<Item = DiffHunk<'input>>,
+) -> (usize, usize) {
+    let mut added = 0;
+    let mut removed = 0;
+    for hunk in hunks {
+        match hunk.kind {
+            DiffHunkKind::Matching => {}
+            DiffHunkKind::Different => {
+                let [left, right] = hunk.contents[..].try_into().unwrap();
+                removed += left.split_inclusive(|b| *b == b'\n').count();
+                added += right.split_inclusive(|b| *b == b'\n').count();
+            }
+        }
+    }
+    (added, removed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1189,6 +1307,25 @@ mod tests {
         assert!(!comp.eq(b"ab", b"a b"));
     }
 
+    #[test]
+    fn test_compare_bytes_ignore_cr_at_eol() {
+        let comp = WordComparator::new(CompareBytesIgnoreCrAtEol);
+        let hash = |data: &[u8]| comp.hash_one(data);
+
+        assert!(comp.eq(b"", b""));
+        assert!(comp.eq(b"", b"\r"));
+        assert!(comp.eq(b"a\n", b"a\r\n"));
+        assert!(comp.eq(b"a", b"a\r"));
+        assert_eq!(hash(b""), hash(b"\r"));
+        assert_eq!(hash(b"a\n"), hash(b"a\r\n"));
+
+        assert!(!comp.eq(b"a", b"a\n"));
+        assert!(!comp.eq(b"a\r", b"a\n"));
+        assert!(!comp.eq(b"a\rb\n", b"ab\n"));
+        assert!(!comp.eq(b"a\r\r\n", b"a\n"));
+        assert!(!comp.eq(b"a \n", b"a\n"));
+    }
+
     fn unchanged_ranges(
         (left_text, left_ranges): (&[u8], &[Range<usize>]),
         (right_text, right_ranges): (&[u8], &[Range<usize>]),
@@ -1332,6 +1469,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_count_diff_lines() {
+        assert_eq!(count_diff_lines(b"", b""), (0, 0));
+        assert_eq!(count_diff_lines(b"a\nb\n", b"a\nb\n"), (0, 0));
+        assert_eq!(count_diff_lines(b"", b"a\nb\n"), (2, 0));
+        assert_eq!(count_diff_lines(b"a\nb\n", b""), (0, 2));
+        assert_eq!(count_diff_lines(b"a\nb\nc\n", b"a\nB\nc\nd"), (2, 1));
+    }
+
     #[test]
     fn test_diff_single_input() {
         assert_eq!(diff(["abc"]), vec![DiffHunk::matching(["abc"])]);
@@ -1582,6 +1728,21 @@ mod tests {
                 },
             ]
         );
+        assert_eq!(
+            diff.clone().into_hunks().collect_vec(),
+            diff.hunks().collect_vec()
+        );
+
+        // The first unchanged region is empty
+        let diff = Diff::by_word(["a", "b"]);
+        assert_eq!(
+            diff.clone().into_hunks().collect_vec(),
+            vec![DiffHunk::different(["a", "b"])]
+        );
+        assert_eq!(
+            diff.clone().into_hunks().collect_vec(),
+            diff.hunks().collect_vec()
+        );
     }
 
     #[test]
@@ -949,6 +949,16 @@ impl Backend for GitBackend {
         self.read_file_sync(id)
     }
 
+    async fn file_size(&self, _path: &RepoPath, id: &FileId) -> BackendResult<Option<u64>> {
+        let git_blob_id = validate_git_object_id(id)?;
+        let locked_repo = self.lock_git_repo();
+        let header = locked_repo
+            .find_header(git_blob_id)
+            .map_err(|err| map_not_found_err(err, id))?;
+        // Let read_file() report the error if the object isn't a blob.
+        Ok((header.kind() == gix::object::Kind::Blob).then(|| header.size()))
+    }
+
     async fn write_file(
         &self,
         _path: &RepoPath,
@@ -1789,6 +1799,31 @@ mod tests {
         assert_eq!(std::str::from_utf8(&sig.data).unwrap(), commit_buf);
     }
 
+    #[test]
+    fn read_file_size() {
+        let settings = user_settings();
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let git_repo_path = temp_dir.path().join("git");
+        let git_repo = git2::Repository::init(git_repo_path).unwrap();
+        let blob_id = git_repo.blob(b"content\n").unwrap();
+
+        let backend = GitBackend::init_external(&settings, store_path, git_repo.path()).unwrap();
+        let file_id = FileId::from_bytes(blob_id.as_bytes());
+        assert_eq!(
+            backend
+                .file_size(RepoPath::root(), &file_id)
+                .block_on()
+                .unwrap(),
+            Some(8)
+        );
+        let missing_id = FileId::from_bytes(&[0xff; 20]);
+        assert_matches!(
+            backend.file_size(RepoPath::root(), &missing_id).block_on(),
+            Err(BackendError::ObjectNotFound { .. })
+        );
+    }
+
     #[test]
     fn read_empty_string_placeholder() {
         let git_signature1 = gix::actor::SignatureRef {
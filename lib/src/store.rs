@@ -242,6 +242,20 @@ impl Store {
         self.backend.read_file(path, id).await
     }
 
+    /// Returns the size of the file in bytes if the backend can tell it
+    /// without reading the file content.
+    pub fn file_size(&self, path: &RepoPath, id: &FileId) -> BackendResult<Option<u64>> {
+        self.file_size_async(path, id).block_on()
+    }
+
+    pub async fn file_size_async(
+        &self,
+        path: &RepoPath,
+        id: &FileId,
+    ) -> BackendResult<Option<u64>> {
+        self.backend.file_size(path, id).await
+    }
+
     pub async fn write_file(
         &self,
         path: &RepoPath,